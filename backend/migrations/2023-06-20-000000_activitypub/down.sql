@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE activitypub_deliveries;
+DROP TABLE activitypub_inbox_activities;
+DROP TABLE activitypub_remote_followers;
+DROP TABLE activitypub_actor_keys;
@@ -0,0 +1,47 @@
+-- ACTIVITYPUB MODELS
+-- Keypairs used to sign outbound deliveries. Public keys are published in actor documents.
+CREATE TABLE activitypub_actor_keys (
+  id BIGSERIAL PRIMARY KEY,
+  user_id BIGINT NOT NULL UNIQUE REFERENCES users ON DELETE CASCADE,
+  public_key_pem TEXT NOT NULL,
+  private_key_pem TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Remote (i.e. Mastodon) actors following a local user.
+CREATE TABLE activitypub_remote_followers (
+  id BIGSERIAL PRIMARY KEY,
+  user_id BIGINT NOT NULL REFERENCES users ON DELETE CASCADE,
+  actor_uri VARCHAR NOT NULL,
+  inbox_uri VARCHAR NOT NULL,
+  shared_inbox_uri VARCHAR NULL DEFAULT NULL,
+  follow_activity_uri VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX idx_activitypub_remote_follower ON activitypub_remote_followers(user_id, actor_uri);
+
+-- Likes and replies (Creates) received from remote actors for local posts.
+CREATE TABLE activitypub_inbox_activities (
+  id BIGSERIAL PRIMARY KEY,
+  activity_uri VARCHAR NOT NULL UNIQUE,
+  activity_type VARCHAR NOT NULL,
+  actor_uri VARCHAR NOT NULL,
+  post_id BIGINT NULL DEFAULT NULL REFERENCES posts ON DELETE CASCADE,
+  activity JSONB NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX idx_activitypub_inbox_post ON activitypub_inbox_activities(post_id, activity_type);
+
+-- Outbound activities, delivered (with retries) by the background delivery worker.
+CREATE TABLE activitypub_deliveries (
+  id BIGSERIAL PRIMARY KEY,
+  user_id BIGINT NOT NULL REFERENCES users ON DELETE CASCADE,
+  inbox_uri VARCHAR NOT NULL,
+  activity JSONB NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  last_error TEXT NULL DEFAULT NULL,
+  next_attempt_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  delivered_at TIMESTAMP NULL DEFAULT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX idx_activitypub_pending_deliveries ON activitypub_deliveries(delivered_at, next_attempt_at);
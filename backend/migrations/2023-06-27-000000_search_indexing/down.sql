@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE server_configurations DROP COLUMN disallow_search_indexing;
@@ -0,0 +1 @@
+ALTER TABLE server_configurations ADD COLUMN disallow_search_indexing BOOLEAN NOT NULL DEFAULT FALSE;
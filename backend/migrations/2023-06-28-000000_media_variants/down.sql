@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_unprocessed_media;
+DROP TABLE media_variants;
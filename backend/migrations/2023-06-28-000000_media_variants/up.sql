@@ -0,0 +1,13 @@
+-- Resized (and metadata-stripped) copies of image media, generated by the media processing worker.
+CREATE TABLE media_variants (
+  id BIGSERIAL PRIMARY KEY,
+  media_id BIGINT NOT NULL REFERENCES media ON DELETE CASCADE,
+  size VARCHAR NOT NULL,
+  minio_path VARCHAR NOT NULL,
+  content_type VARCHAR NOT NULL,
+  width INTEGER NOT NULL,
+  height INTEGER NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX idx_media_variant ON media_variants(media_id, size);
+CREATE INDEX idx_unprocessed_media ON media(created_at) WHERE processed = FALSE;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE media_upload_parts;
+DROP TABLE media_uploads;
@@ -0,0 +1,23 @@
+-- Resumable upload sessions, backed by S3 multipart uploads. The `media` row is only created on finalize.
+CREATE TABLE media_uploads (
+  id BIGSERIAL PRIMARY KEY,
+  user_id BIGINT NOT NULL REFERENCES users ON DELETE CASCADE,
+  minio_path VARCHAR NOT NULL,
+  s3_upload_id VARCHAR NOT NULL,
+  content_type VARCHAR NOT NULL,
+  name VARCHAR NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  expires_at TIMESTAMP NOT NULL
+);
+CREATE INDEX idx_media_upload_expiry ON media_uploads(expires_at);
+
+CREATE TABLE media_upload_parts (
+  id BIGSERIAL PRIMARY KEY,
+  media_upload_id BIGINT NOT NULL REFERENCES media_uploads ON DELETE CASCADE,
+  part_number INTEGER NOT NULL,
+  etag VARCHAR NOT NULL,
+  sha256 VARCHAR NOT NULL,
+  size BIGINT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX idx_media_upload_part ON media_upload_parts(media_upload_id, part_number);
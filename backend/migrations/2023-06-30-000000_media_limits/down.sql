@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX idx_media_user;
+ALTER TABLE media DROP COLUMN size;
+ALTER TABLE server_configurations DROP COLUMN media_settings;
@@ -0,0 +1,6 @@
+-- Media settings (including upload limits) were previously hardcoded. Existing servers keep unlimited uploads.
+ALTER TABLE server_configurations ADD COLUMN media_settings JSONB NOT NULL DEFAULT
+  '{"visible": true, "default_moderation": 1, "default_visibility": 4, "custom_title": null, "limits": []}'::jsonb;
+-- Used to track users' storage usage. Media uploaded before this migration counts as 0 bytes.
+ALTER TABLE media ADD COLUMN size BIGINT NOT NULL DEFAULT 0;
+CREATE INDEX idx_media_user ON media(user_id);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE media_album_items;
+DROP TABLE media_albums;
+ALTER TABLE media DROP COLUMN alt_text;
@@ -0,0 +1,24 @@
+ALTER TABLE media ADD COLUMN alt_text VARCHAR NULL;
+
+CREATE TABLE media_albums (
+  id BIGSERIAL PRIMARY KEY,
+  user_id BIGINT NOT NULL REFERENCES users ON DELETE CASCADE,
+  name VARCHAR NOT NULL,
+  description TEXT NULL,
+  cover_media_id BIGINT NULL REFERENCES media ON DELETE SET NULL,
+  visibility VARCHAR NOT NULL DEFAULT 'GLOBAL_PUBLIC',
+  moderation VARCHAR NOT NULL DEFAULT 'UNMODERATED',
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX idx_media_album_user ON media_albums(user_id);
+
+CREATE TABLE media_album_items (
+  id BIGSERIAL PRIMARY KEY,
+  media_album_id BIGINT NOT NULL REFERENCES media_albums ON DELETE CASCADE,
+  media_id BIGINT NOT NULL REFERENCES media ON DELETE CASCADE,
+  position INTEGER NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX idx_media_album_item ON media_album_items(media_album_id, media_id);
+CREATE INDEX idx_media_album_item_media ON media_album_items(media_id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE media DROP COLUMN blob_id;
+DROP TABLE media_blobs;
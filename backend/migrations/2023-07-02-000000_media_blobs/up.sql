@@ -0,0 +1,14 @@
+-- Uploaded media is stored once per distinct content (by SHA-256), shared by all `media` referencing it.
+CREATE TABLE media_blobs (
+  id BIGSERIAL PRIMARY KEY,
+  sha256 VARCHAR NOT NULL,
+  minio_path VARCHAR NOT NULL,
+  size BIGINT NOT NULL,
+  ref_count INTEGER NOT NULL DEFAULT 0,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX idx_media_blob_sha256 ON media_blobs(sha256);
+
+-- Media uploaded before blobs (and server-generated media) has no blob, and owns its object outright.
+ALTER TABLE media ADD COLUMN blob_id BIGINT NULL REFERENCES media_blobs ON DELETE SET NULL;
+CREATE INDEX idx_media_blob ON media(blob_id);
@@ -0,0 +1,8 @@
+-- Secrets generated by the server itself (i.e. the key signing media URLs), shared by all replicas.
+CREATE TABLE server_secrets (
+  id BIGSERIAL PRIMARY KEY,
+  name VARCHAR NOT NULL,
+  value TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX idx_server_secret_name ON server_secrets(name);
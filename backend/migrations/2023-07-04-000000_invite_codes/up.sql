@@ -0,0 +1,14 @@
+-- Codes admins hand out so people can register when the server is invite-only.
+CREATE TABLE invite_codes (
+  id BIGSERIAL PRIMARY KEY,
+  code VARCHAR NOT NULL,
+  created_by_user_id BIGINT NULL REFERENCES users ON DELETE SET NULL,
+  -- NULL means unlimited uses.
+  max_uses INTEGER NULL,
+  use_count INTEGER NOT NULL DEFAULT 0,
+  -- Permissions for accounts created with the code. NULL means the server's default_user_permissions.
+  permissions JSONB NULL,
+  expires_at TIMESTAMP NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX idx_invite_code ON invite_codes(code);
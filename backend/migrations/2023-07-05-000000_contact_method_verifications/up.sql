@@ -0,0 +1,20 @@
+-- Codes sent to users' email/phone to verify them. At most one pending per user and contact method.
+CREATE TABLE contact_method_verifications (
+  id BIGSERIAL PRIMARY KEY,
+  user_id BIGINT NOT NULL REFERENCES users ON DELETE CASCADE,
+  -- `EMAIL` or `PHONE`.
+  contact_method VARCHAR NOT NULL,
+  -- The address the code was sent to; the code only verifies this address.
+  value VARCHAR NOT NULL,
+  code_hash VARCHAR NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  expires_at TIMESTAMP NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX idx_contact_method_verification ON contact_method_verifications(user_id, contact_method);
+
+-- Existing addresses were never verified, whatever their `verified` flags say.
+UPDATE users SET email = jsonb_set(email, '{verified}', 'false')
+  WHERE email IS NOT NULL AND jsonb_typeof(email) = 'object';
+UPDATE users SET phone = jsonb_set(phone, '{verified}', 'false')
+  WHERE phone IS NOT NULL AND jsonb_typeof(phone) = 'object';
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE users DROP COLUMN password_reset_required;
+DROP TABLE password_reset_tokens;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE two_factor_challenges;
+DROP TABLE user_recovery_codes;
+DROP TABLE user_totps;
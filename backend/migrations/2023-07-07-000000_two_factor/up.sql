@@ -0,0 +1,34 @@
+-- TOTP (RFC 6238) enrollments. Unconfirmed until the user enters a code from their authenticator.
+CREATE TABLE user_totps (
+  id BIGSERIAL PRIMARY KEY,
+  user_id BIGINT NOT NULL REFERENCES users ON DELETE CASCADE,
+  -- Base32, as given to authenticator apps.
+  secret VARCHAR NOT NULL,
+  confirmed_at TIMESTAMP NULL,
+  -- The time step of the last accepted code, so codes can't be replayed.
+  last_used_step BIGINT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX idx_user_totp ON user_totps(user_id);
+
+-- Single-use codes for logging in without an authenticator. Only hashes are stored.
+CREATE TABLE user_recovery_codes (
+  id BIGSERIAL PRIMARY KEY,
+  user_id BIGINT NOT NULL REFERENCES users ON DELETE CASCADE,
+  code_hash VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX idx_user_recovery_codes ON user_recovery_codes(user_id);
+
+-- Partial logins awaiting a second factor.
+CREATE TABLE two_factor_challenges (
+  id BIGSERIAL PRIMARY KEY,
+  user_id BIGINT NOT NULL REFERENCES users ON DELETE CASCADE,
+  token_hash VARCHAR NOT NULL,
+  -- The refresh token expiry requested at login.
+  requested_expires_at TIMESTAMP NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  expires_at TIMESTAMP NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE UNIQUE INDEX idx_two_factor_challenge_token ON two_factor_challenges(token_hash);
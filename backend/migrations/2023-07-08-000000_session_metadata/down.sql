@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE two_factor_challenges DROP COLUMN device_name;
+DROP INDEX idx_refresh_tokens_user;
+ALTER TABLE user_refresh_tokens
+  DROP COLUMN device_id,
+  DROP COLUMN user_agent,
+  DROP COLUMN ip_address,
+  DROP COLUMN last_used_at;
@@ -0,0 +1,10 @@
+-- Where each session (refresh token) came from and when it was last used, for GetSessions.
+ALTER TABLE user_refresh_tokens
+  ADD COLUMN device_id BIGINT NULL REFERENCES user_devices ON DELETE SET NULL,
+  ADD COLUMN user_agent VARCHAR NULL,
+  ADD COLUMN ip_address VARCHAR NULL,
+  ADD COLUMN last_used_at TIMESTAMP NULL;
+CREATE INDEX idx_refresh_tokens_user ON user_refresh_tokens(user_id);
+
+-- The device name from Login, for the session created once the second factor is verified.
+ALTER TABLE two_factor_challenges ADD COLUMN device_name VARCHAR NULL;
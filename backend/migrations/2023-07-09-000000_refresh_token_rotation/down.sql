@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE server_configurations DROP COLUMN authentication_settings;
+ALTER TABLE user_refresh_tokens ALTER COLUMN expires_at DROP NOT NULL;
+DROP INDEX idx_refresh_tokens_family;
+ALTER TABLE user_refresh_tokens
+  DROP COLUMN family_id,
+  DROP COLUMN rotated_at,
+  DROP COLUMN session_expires_at;
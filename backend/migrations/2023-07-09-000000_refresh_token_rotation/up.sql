@@ -0,0 +1,16 @@
+-- Refresh tokens are rotated on every AccessToken call. Tokens rotated from one another form a
+-- family (i.e. a session), identified by its first token's ID. Rotated tokens are kept (with
+-- `rotated_at` set) until they expire, so presenting one again can revoke the whole family.
+ALTER TABLE user_refresh_tokens
+  ADD COLUMN family_id BIGINT NULL,
+  ADD COLUMN rotated_at TIMESTAMP NULL,
+  ADD COLUMN session_expires_at TIMESTAMP NULL;
+UPDATE user_refresh_tokens SET family_id = id;
+ALTER TABLE user_refresh_tokens ALTER COLUMN family_id SET NOT NULL;
+CREATE INDEX idx_refresh_tokens_family ON user_refresh_tokens(family_id);
+
+-- Refresh tokens no longer last forever. Existing non-expiring ones get the default lifetime.
+UPDATE user_refresh_tokens SET expires_at = NOW() + INTERVAL '30 days' WHERE expires_at IS NULL;
+ALTER TABLE user_refresh_tokens ALTER COLUMN expires_at SET NOT NULL;
+
+ALTER TABLE server_configurations ADD COLUMN authentication_settings JSONB NOT NULL DEFAULT '{}'::jsonb;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE oidc_login_completions;
+DROP TABLE oidc_login_states;
+DROP TABLE linked_identities;
+UPDATE server_configurations SET authentication_settings = authentication_settings - 'oidc_providers';
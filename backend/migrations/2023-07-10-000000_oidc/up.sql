@@ -0,0 +1,40 @@
+-- External (OpenID Connect) identities linked to local users.
+CREATE TABLE linked_identities (
+  id BIGSERIAL PRIMARY KEY,
+  user_id BIGINT NOT NULL REFERENCES users ON DELETE CASCADE,
+  provider_id VARCHAR NOT NULL,
+  subject VARCHAR NOT NULL,
+  email VARCHAR NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  last_login_at TIMESTAMP NULL
+);
+CREATE UNIQUE INDEX idx_linked_identity ON linked_identities(provider_id, subject);
+CREATE INDEX idx_linked_identities_user ON linked_identities(user_id);
+
+-- OIDC logins awaiting the provider's callback, keyed by the `state` sent to it.
+-- `link_user_id` is set when linking an identity to a logged-in user instead of logging in.
+CREATE TABLE oidc_login_states (
+  id BIGSERIAL PRIMARY KEY,
+  state VARCHAR NOT NULL UNIQUE,
+  provider_id VARCHAR NOT NULL,
+  code_verifier VARCHAR NOT NULL,
+  nonce VARCHAR NOT NULL,
+  redirect_path VARCHAR NOT NULL,
+  link_user_id BIGINT NULL REFERENCES users ON DELETE CASCADE,
+  expires_at TIMESTAMP NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Single-use codes handing completed OIDC logins to clients (see CompleteOidcLogin).
+CREATE TABLE oidc_login_completions (
+  id BIGSERIAL PRIMARY KEY,
+  user_id BIGINT NOT NULL REFERENCES users ON DELETE CASCADE,
+  code_hash VARCHAR NOT NULL UNIQUE,
+  expires_at TIMESTAMP NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Stored settings are deserialized strictly, so existing ones need the new (empty) provider list.
+UPDATE server_configurations
+SET authentication_settings = authentication_settings || '{"oidc_providers": []}'::jsonb
+WHERE NOT authentication_settings ? 'oidc_providers';
@@ -0,0 +1,8 @@
+-- Consecutive failed logins per user, and temporary lockouts after too many (see GetAccountLockouts).
+CREATE TABLE account_lockouts (
+  user_id BIGINT PRIMARY KEY REFERENCES users ON DELETE CASCADE,
+  failed_attempts INTEGER NOT NULL DEFAULT 0,
+  locked_until TIMESTAMP NULL,
+  last_failed_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  last_failed_ip VARCHAR NULL
+);
@@ -0,0 +1,13 @@
+-- Personal API tokens for bots (see CreateApiToken). Only a hash of each token is stored.
+-- `scopes` is a list of `{"permission": "CREATE_POSTS", "group_id": 123}`, `group_id` being optional.
+CREATE TABLE user_api_tokens (
+  id BIGSERIAL PRIMARY KEY,
+  user_id BIGINT NOT NULL REFERENCES users ON DELETE CASCADE,
+  token_hash VARCHAR NOT NULL UNIQUE,
+  label VARCHAR NOT NULL,
+  scopes JSONB NOT NULL DEFAULT '[]'::JSONB,
+  expires_at TIMESTAMP NULL,
+  last_used_at TIMESTAMP NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX idx_user_api_tokens_user ON user_api_tokens(user_id);
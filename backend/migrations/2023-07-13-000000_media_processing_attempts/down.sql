@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE media DROP COLUMN processing_attempts;
+ALTER TABLE media DROP COLUMN processing_error;
+ALTER TABLE media DROP COLUMN next_processing_at;
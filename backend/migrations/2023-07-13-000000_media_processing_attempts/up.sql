@@ -0,0 +1,4 @@
+-- Failed image processing is retried with backoff (see media_processing::worker) before giving up.
+ALTER TABLE media ADD COLUMN processing_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE media ADD COLUMN processing_error TEXT NULL;
+ALTER TABLE media ADD COLUMN next_processing_at TIMESTAMP NULL;
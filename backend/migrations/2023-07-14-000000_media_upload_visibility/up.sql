@@ -0,0 +1,2 @@
+-- The visibility finalized uploads' Media get, from the `Media-Visibility` header (as POST /media defaults to GLOBAL_PUBLIC).
+ALTER TABLE media_uploads ADD COLUMN visibility VARCHAR NOT NULL DEFAULT 'GLOBAL_PUBLIC';
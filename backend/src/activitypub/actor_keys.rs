@@ -0,0 +1,55 @@
+use diesel::*;
+use rsa::pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+
+use crate::db_connection::PgPooledConnection;
+use crate::models;
+use crate::schema::activitypub_actor_keys;
+
+const ACTOR_KEY_BITS: usize = 2048;
+
+/// Loads the user's actor keypair, generating (and storing) one the first time it's needed.
+/// Generation is CPU-bound, so it runs on the blocking thread pool.
+pub async fn get_or_create_actor_key(
+    user_id: i64,
+    conn: &mut PgPooledConnection,
+) -> anyhow::Result<models::ActivityPubActorKey> {
+    let existing = activitypub_actor_keys::table
+        .filter(activitypub_actor_keys::user_id.eq(user_id))
+        .first::<models::ActivityPubActorKey>(conn)
+        .optional()?;
+    if let Some(key) = existing {
+        return Ok(key);
+    }
+
+    log::info!("Generating ActivityPub actor key for user_id={}", user_id);
+    let (public_key_pem, private_key_pem) =
+        tokio::task::spawn_blocking(generate_actor_keypair).await??;
+    let key = insert_into(activitypub_actor_keys::table)
+        .values(&models::NewActivityPubActorKey {
+            user_id,
+            public_key_pem,
+            private_key_pem,
+        })
+        .on_conflict(activitypub_actor_keys::user_id)
+        .do_nothing()
+        .get_result::<models::ActivityPubActorKey>(conn)
+        .optional()?;
+    match key {
+        Some(key) => Ok(key),
+        // Another request generated a key concurrently.
+        None => Ok(activitypub_actor_keys::table
+            .filter(activitypub_actor_keys::user_id.eq(user_id))
+            .first::<models::ActivityPubActorKey>(conn)?),
+    }
+}
+
+/// A new RSA keypair, as (public key, PKCS#8 private key) PEMs.
+pub fn generate_actor_keypair() -> anyhow::Result<(String, String)> {
+    let private_key = RsaPrivateKey::new(&mut rand::thread_rng(), ACTOR_KEY_BITS)?;
+    let public_key = RsaPublicKey::from(&private_key);
+    Ok((
+        public_key.to_public_key_pem(LineEnding::LF)?,
+        private_key.to_pkcs8_pem(LineEnding::LF)?.to_string(),
+    ))
+}
@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+use std::ops::Add;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use diesel::*;
+use tokio::task::JoinHandle;
+
+use super::*;
+use crate::db_connection::{PgPool, PgPooledConnection};
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::{PostContext, Visibility};
+use crate::schema::{activitypub_deliveries, activitypub_remote_followers, users};
+
+const DELIVERY_INTERVAL: Duration = Duration::from_secs(10);
+const DELIVERY_BATCH_SIZE: i64 = 50;
+/// With exponential backoff from 1 minute, the last retry happens ~4 days after the first attempt.
+pub const MAX_DELIVERY_ATTEMPTS: i32 = 13;
+
+pub fn enqueue_delivery(
+    user_id: i64,
+    inbox_uri: &str,
+    activity: serde_json::Value,
+    conn: &mut PgPooledConnection,
+) -> anyhow::Result<()> {
+    insert_into(activitypub_deliveries::table)
+        .values(&models::NewActivityPubDelivery {
+            user_id,
+            inbox_uri: inbox_uri.to_string(),
+            activity,
+        })
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Queues a `Create` for a new top-level `GLOBAL_PUBLIC` post to each of the author's remote
+/// followers (once per shared inbox). A no-op unless `ACTIVITYPUB_DOMAIN` is configured.
+pub fn enqueue_post_create(
+    post: &models::Post,
+    author: &models::User,
+    conn: &mut PgPooledConnection,
+) -> anyhow::Result<()> {
+    let domain = match federation_domain() {
+        Some(domain) => domain,
+        None => return Ok(()),
+    };
+    if post.visibility != Visibility::GlobalPublic.to_string_visibility()
+        || post.context != PostContext::Post.as_str_name()
+        || author.visibility != Visibility::GlobalPublic.to_string_visibility()
+    {
+        return Ok(());
+    }
+
+    let followers = activitypub_remote_followers::table
+        .filter(activitypub_remote_followers::user_id.eq(author.id))
+        .load::<models::ActivityPubRemoteFollower>(conn)?;
+    let inboxes = followers
+        .iter()
+        .map(|f| f.shared_inbox_uri.to_owned().unwrap_or(f.inbox_uri.to_owned()))
+        .collect::<HashSet<String>>();
+    let activity = post.to_activitypub_create(&domain, &author.username);
+    for inbox in inboxes.iter() {
+        enqueue_delivery(author.id, inbox, activity.to_owned(), conn)?;
+    }
+    log::info!(
+        "Queued ActivityPub delivery of post_id={} to {} inboxes",
+        post.id,
+        inboxes.len()
+    );
+    Ok(())
+}
+
+/// Starts the background task that delivers queued activities to remote inboxes, retrying
+/// failures with exponential backoff.
+pub fn start_delivery_worker(pool: Arc<PgPool>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(DELIVERY_INTERVAL);
+        loop {
+            interval.tick().await;
+            let mut conn = match pool.get() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    log::warn!("ActivityPub delivery worker failed to get connection: {:?}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = deliver_pending(&mut conn).await {
+                log::warn!("ActivityPub delivery worker error: {:?}", e);
+            }
+        }
+    })
+}
+
+async fn deliver_pending(conn: &mut PgPooledConnection) -> anyhow::Result<()> {
+    let pending = activitypub_deliveries::table
+        .filter(activitypub_deliveries::delivered_at.is_null())
+        .filter(activitypub_deliveries::attempts.lt(MAX_DELIVERY_ATTEMPTS))
+        .filter(activitypub_deliveries::next_attempt_at.le(diesel::dsl::now))
+        .order(activitypub_deliveries::next_attempt_at.asc())
+        .limit(DELIVERY_BATCH_SIZE)
+        .load::<models::ActivityPubDelivery>(conn)?;
+
+    for delivery in pending {
+        match deliver(&delivery, conn).await {
+            Ok(_) => {
+                log::info!("Delivered activity to {}", delivery.inbox_uri);
+                update(activitypub_deliveries::table.find(delivery.id))
+                    .set((
+                        activitypub_deliveries::attempts.eq(delivery.attempts + 1),
+                        activitypub_deliveries::delivered_at.eq(SystemTime::now()),
+                        activitypub_deliveries::last_error.eq(None::<String>),
+                    ))
+                    .execute(conn)?;
+            }
+            Err(e) => {
+                let backoff = Duration::from_secs(60 * 2u64.pow(delivery.attempts as u32));
+                log::warn!(
+                    "Failed to deliver activity to {} (attempt {}): {:?}",
+                    delivery.inbox_uri,
+                    delivery.attempts + 1,
+                    e
+                );
+                update(activitypub_deliveries::table.find(delivery.id))
+                    .set((
+                        activitypub_deliveries::attempts.eq(delivery.attempts + 1),
+                        activitypub_deliveries::next_attempt_at.eq(SystemTime::now().add(backoff)),
+                        activitypub_deliveries::last_error.eq(Some(e.to_string())),
+                    ))
+                    .execute(conn)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn deliver(
+    delivery: &models::ActivityPubDelivery,
+    conn: &mut PgPooledConnection,
+) -> anyhow::Result<()> {
+    let domain = federation_domain().ok_or_else(|| anyhow::anyhow!("no_activitypub_domain"))?;
+    let username = users::table
+        .select(users::username)
+        .find(delivery.user_id)
+        .first::<String>(conn)?;
+    let key = get_or_create_actor_key(delivery.user_id, conn).await?;
+
+    let url = reqwest::Url::parse(&delivery.inbox_uri)?;
+    let body = serde_json::to_vec(&delivery.activity)?;
+    let mut request = FEDERATION_CLIENT
+        .post(url.to_owned())
+        .header("Content-Type", ACTIVITY_JSON);
+    for (name, value) in sign_post(&key_id(&domain, &username), &key.private_key_pem, &url, &body)? {
+        request = request.header(name, value);
+    }
+    request.body(body).send().await?.error_for_status()?;
+    Ok(())
+}
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, bail};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use ring::digest;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs1v15::{Signature, SigningKey, VerifyingKey};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use rsa::signature::{SignatureEncoding, Signer, Verifier};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use sha2::Sha256;
+
+/// Maximum allowed clock skew for the `Date` header of signed requests.
+const MAX_DATE_SKEW: Duration = Duration::from_secs(60 * 60 * 12);
+
+/// Headers signed on outbound deliveries, and required to be signed on inbound ones. This is the
+/// set Mastodon requires for POSTs. Signing `date` (checked against `MAX_DATE_SKEW`) and `host`
+/// keeps captured requests from being replayed later or to other servers.
+const SIGNED_HEADERS: [&str; 4] = ["(request-target)", "host", "date", "digest"];
+
+/// A parsed (draft-cavage) HTTP `Signature` header.
+#[derive(Debug, Clone)]
+pub struct SignatureParams {
+    pub key_id: String,
+    pub algorithm: Option<String>,
+    pub headers: Vec<String>,
+    pub signature: Vec<u8>,
+}
+
+pub fn parse_signature_header(header: &str) -> anyhow::Result<SignatureParams> {
+    let mut params: HashMap<String, String> = HashMap::new();
+    let mut in_quotes = false;
+    let mut current = String::new();
+    for c in header.chars().chain(std::iter::once(',')) {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                if let Some((key, value)) = current.split_once('=') {
+                    params.insert(key.trim().to_lowercase(), value.trim().to_string());
+                }
+                current.clear();
+            }
+            c => current.push(c),
+        }
+    }
+
+    let key_id = params
+        .remove("keyid")
+        .ok_or_else(|| anyhow!("signature_missing_key_id"))?;
+    let signature = BASE64.decode(
+        params
+            .remove("signature")
+            .ok_or_else(|| anyhow!("signature_missing_signature"))?,
+    )?;
+    let headers = params
+        .remove("headers")
+        .unwrap_or_else(|| "date".to_string())
+        .split_whitespace()
+        .map(|h| h.to_lowercase())
+        .collect();
+    Ok(SignatureParams {
+        key_id,
+        algorithm: params.remove("algorithm"),
+        headers,
+        signature,
+    })
+}
+
+/// Fails unless the signature covers all of `SIGNED_HEADERS`.
+pub fn verify_signed_headers(params: &SignatureParams) -> anyhow::Result<()> {
+    for required in SIGNED_HEADERS {
+        if !params.headers.iter().any(|h| h == required) {
+            bail!("signature_must_cover_{}", required);
+        }
+    }
+    Ok(())
+}
+
+/// Builds the string that is signed for the given request. `headers` must have lowercase keys.
+pub fn signing_string(
+    method: &str,
+    path_and_query: &str,
+    headers: &HashMap<String, String>,
+    signed_headers: &[String],
+) -> anyhow::Result<String> {
+    let mut lines = vec![];
+    for name in signed_headers {
+        let line = match name.as_str() {
+            "(request-target)" => format!(
+                "(request-target): {} {}",
+                method.to_lowercase(),
+                path_and_query
+            ),
+            name => format!(
+                "{}: {}",
+                name,
+                headers
+                    .get(name)
+                    .ok_or_else(|| anyhow!("signed_header_missing_{}", name))?
+            ),
+        };
+        lines.push(line);
+    }
+    Ok(lines.join("\n"))
+}
+
+pub fn verify_signature(
+    params: &SignatureParams,
+    signing_string: &str,
+    public_key_pem: &str,
+) -> anyhow::Result<()> {
+    match params.algorithm.as_deref() {
+        None | Some("rsa-sha256") | Some("hs2019") => {}
+        Some(algorithm) => bail!("unsupported_signature_algorithm_{}", algorithm),
+    }
+    let public_key = RsaPublicKey::from_public_key_pem(public_key_pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(public_key_pem))?;
+    let signature = Signature::try_from(params.signature.as_slice())?;
+    VerifyingKey::<Sha256>::new(public_key)
+        .verify(signing_string.as_bytes(), &signature)
+        .map_err(|_| anyhow!("invalid_signature"))
+}
+
+pub fn digest_header(body: &[u8]) -> String {
+    format!("SHA-256={}", sha256_base64(body))
+}
+
+fn sha256_base64(body: &[u8]) -> String {
+    BASE64.encode(digest::digest(&digest::SHA256, body).as_ref())
+}
+
+pub fn verify_digest(digest: &str, body: &[u8]) -> anyhow::Result<()> {
+    let expected = sha256_base64(body);
+    let matches = digest
+        .split(',')
+        .filter_map(|d| d.trim().split_once('='))
+        .any(|(algorithm, value)| algorithm.eq_ignore_ascii_case("SHA-256") && value == expected);
+    if !matches {
+        bail!("digest_mismatch");
+    }
+    Ok(())
+}
+
+pub fn verify_date(date: &str) -> anyhow::Result<()> {
+    let date = httpdate::parse_http_date(date)?;
+    let now = SystemTime::now();
+    let skew = match date.duration_since(now) {
+        Ok(ahead) => ahead,
+        Err(behind) => behind.duration(),
+    };
+    if skew > MAX_DATE_SKEW {
+        bail!("date_out_of_range");
+    }
+    Ok(())
+}
+
+/// Produces the `Host`, `Date`, `Digest` and `Signature` headers for a signed POST to `url`.
+pub fn sign_post(
+    key_id: &str,
+    private_key_pem: &str,
+    url: &reqwest::Url,
+    body: &[u8],
+) -> anyhow::Result<Vec<(&'static str, String)>> {
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let path_and_query = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    let mut headers = HashMap::new();
+    headers.insert("host".to_string(), host);
+    headers.insert("date".to_string(), httpdate::fmt_http_date(SystemTime::now()));
+    headers.insert("digest".to_string(), digest_header(body));
+
+    let signed_headers = SIGNED_HEADERS
+        .iter()
+        .map(|h| h.to_string())
+        .collect::<Vec<String>>();
+    let signing_string = signing_string("post", &path_and_query, &headers, &signed_headers)?;
+    let private_key = RsaPrivateKey::from_pkcs8_pem(private_key_pem)?;
+    let signature = SigningKey::<Sha256>::new(private_key).sign(signing_string.as_bytes());
+
+    Ok(vec![
+        ("Host", headers.remove("host").unwrap()),
+        ("Date", headers.remove("date").unwrap()),
+        ("Digest", headers.remove("digest").unwrap()),
+        (
+            "Signature",
+            format!(
+                "keyId=\"{}\",algorithm=\"rsa-sha256\",headers=\"{}\",signature=\"{}\"",
+                key_id,
+                signed_headers.join(" "),
+                BASE64.encode(signature.to_bytes())
+            ),
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::activitypub::generate_actor_keypair;
+
+    /// Verifies signed headers as `inbox` does for a POST to `path`.
+    fn verify_signed_post(
+        signed: &[(&'static str, String)],
+        path: &str,
+        body: &[u8],
+        public_key_pem: &str,
+    ) -> anyhow::Result<()> {
+        let headers = signed
+            .iter()
+            .map(|(name, value)| (name.to_lowercase(), value.to_owned()))
+            .collect::<HashMap<String, String>>();
+        let params = parse_signature_header(&headers["signature"])?;
+        verify_signed_headers(&params)?;
+        verify_digest(&headers["digest"], body)?;
+        verify_date(&headers["date"])?;
+        let signing_string = signing_string("POST", path, &headers, &params.headers)?;
+        verify_signature(&params, &signing_string, public_key_pem)
+    }
+
+    #[test]
+    fn signed_posts_verify_unless_tampered() {
+        let (public_key_pem, private_key_pem) = generate_actor_keypair().unwrap();
+        let url = reqwest::Url::parse("https://remote.example/users/alice/inbox").unwrap();
+        let body = br#"{"type":"Create"}"#;
+        let signed = sign_post(
+            "https://jonline.example/ap/users/bob#main-key",
+            &private_key_pem,
+            &url,
+            body,
+        )
+        .unwrap();
+        verify_signed_post(&signed, url.path(), body, &public_key_pem).unwrap();
+
+        // A different body no longer matches the digest.
+        let tampered_body = br#"{"type":"Delete"}"#;
+        assert!(verify_signed_post(&signed, url.path(), tampered_body, &public_key_pem).is_err());
+
+        // Nor does a digest recomputed for it match the signature.
+        let tampered_digest = signed
+            .iter()
+            .map(|(name, value)| match *name {
+                "Digest" => (*name, digest_header(tampered_body)),
+                _ => (*name, value.to_owned()),
+            })
+            .collect::<Vec<(&'static str, String)>>();
+        let error = verify_signed_post(&tampered_digest, url.path(), tampered_body, &public_key_pem)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "invalid_signature");
+    }
+
+    #[test]
+    fn signatures_must_cover_date_and_host() {
+        let signature = |headers: &str| {
+            parse_signature_header(&format!(
+                "keyId=\"https://remote.example/users/alice#main-key\",headers=\"{}\",signature=\"AAAA\"",
+                headers
+            ))
+            .unwrap()
+        };
+        verify_signed_headers(&signature("(request-target) host date digest")).unwrap();
+        assert_eq!(
+            verify_signed_headers(&signature("(request-target) host digest"))
+                .unwrap_err()
+                .to_string(),
+            "signature_must_cover_date"
+        );
+        assert_eq!(
+            verify_signed_headers(&signature("(request-target) date digest"))
+                .unwrap_err()
+                .to_string(),
+            "signature_must_cover_host"
+        );
+    }
+}
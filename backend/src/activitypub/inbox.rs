@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail};
+use diesel::*;
+use serde_json::json;
+
+use super::*;
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::Visibility;
+use crate::schema::{activitypub_inbox_activities, activitypub_remote_followers, posts, users};
+
+/// The parts of an inbound HTTP request needed to verify its signature.
+#[derive(Debug, Clone)]
+pub struct SignedRequest {
+    pub method: String,
+    pub path_and_query: String,
+    /// Header values keyed by lowercase header name.
+    pub headers: HashMap<String, String>,
+}
+
+/// Verifies the `Digest`, `Date` and `Signature` headers of an inbox POST, returning the
+/// remote actor that signed it. The signature must cover the request target, `Host`, `Date` and
+/// `Digest`, and the signer must be the activity's `actor`.
+pub async fn verify_inbox_request(
+    request: &SignedRequest,
+    body: &[u8],
+    activity: &serde_json::Value,
+) -> anyhow::Result<RemoteActor> {
+    let digest = request
+        .headers
+        .get("digest")
+        .ok_or_else(|| anyhow!("missing_digest"))?;
+    verify_digest(digest, body)?;
+    verify_date(
+        request
+            .headers
+            .get("date")
+            .ok_or_else(|| anyhow!("missing_date"))?,
+    )?;
+
+    let params = parse_signature_header(
+        request
+            .headers
+            .get("signature")
+            .ok_or_else(|| anyhow!("missing_signature"))?,
+    )?;
+    verify_signed_headers(&params)?;
+    let remote_actor = fetch_remote_actor_for_key(&params.key_id).await?;
+    if remote_actor.public_key_id != params.key_id {
+        bail!("signature_key_mismatch");
+    }
+    let signing_string = signing_string(
+        &request.method,
+        &request.path_and_query,
+        &request.headers,
+        &params.headers,
+    )?;
+    verify_signature(&params, &signing_string, &remote_actor.public_key_pem)?;
+
+    let activity_actor = activity.get("actor").and_then(object_id);
+    if activity_actor.as_deref() != Some(remote_actor.id.as_str()) {
+        bail!("actor_does_not_match_signature");
+    }
+    Ok(remote_actor)
+}
+
+/// Applies a verified activity. Supports Follow, Undo (of Follow and Like), Create and Like;
+/// anything else is accepted and ignored.
+pub fn process_inbox_activity(
+    activity: &serde_json::Value,
+    remote_actor: &RemoteActor,
+    domain: &str,
+    conn: &mut PgPooledConnection,
+) -> anyhow::Result<()> {
+    let activity_type = activity.get("type").and_then(|t| t.as_str()).unwrap_or("");
+    let activity_id = activity
+        .get("id")
+        .and_then(|i| i.as_str())
+        .ok_or_else(|| anyhow!("activity_missing_id"))?;
+    let object = activity.get("object").unwrap_or(&serde_json::Value::Null);
+    log::info!(
+        "ActivityPub inbox: {} {} from {}",
+        activity_type,
+        activity_id,
+        remote_actor.id
+    );
+    match activity_type {
+        "Follow" => {
+            let target = object_id(object).ok_or_else(|| anyhow!("follow_missing_object"))?;
+            let user = local_user_for_actor(domain, &target, conn)?;
+            insert_into(activitypub_remote_followers::table)
+                .values(&models::NewActivityPubRemoteFollower {
+                    user_id: user.id,
+                    actor_uri: remote_actor.id.to_owned(),
+                    inbox_uri: remote_actor.inbox.to_owned(),
+                    shared_inbox_uri: remote_actor.shared_inbox.to_owned(),
+                    follow_activity_uri: activity_id.to_string(),
+                })
+                .on_conflict((
+                    activitypub_remote_followers::user_id,
+                    activitypub_remote_followers::actor_uri,
+                ))
+                .do_update()
+                .set((
+                    activitypub_remote_followers::inbox_uri.eq(&remote_actor.inbox),
+                    activitypub_remote_followers::shared_inbox_uri.eq(&remote_actor.shared_inbox),
+                    activitypub_remote_followers::follow_activity_uri.eq(activity_id),
+                ))
+                .execute(conn)?;
+            let accept = json!({
+                "@context": ACTIVITYSTREAMS_CONTEXT,
+                "id": format!("{}#accepts/{}", actor_uri(domain, &user.username), uuid::Uuid::new_v4()),
+                "type": "Accept",
+                "actor": actor_uri(domain, &user.username),
+                "object": activity,
+            });
+            enqueue_delivery(user.id, &remote_actor.inbox, accept, conn)?;
+        }
+        "Undo" => {
+            let undone_id = object_id(object).ok_or_else(|| anyhow!("undo_missing_object"))?;
+            delete(
+                activitypub_remote_followers::table
+                    .filter(activitypub_remote_followers::actor_uri.eq(&remote_actor.id))
+                    .filter(activitypub_remote_followers::follow_activity_uri.eq(&undone_id)),
+            )
+            .execute(conn)?;
+            delete(
+                activitypub_inbox_activities::table
+                    .filter(activitypub_inbox_activities::actor_uri.eq(&remote_actor.id))
+                    .filter(activitypub_inbox_activities::activity_uri.eq(&undone_id)),
+            )
+            .execute(conn)?;
+        }
+        "Like" => {
+            let target = object_id(object).ok_or_else(|| anyhow!("like_missing_object"))?;
+            let post_id = local_post_for_note(domain, &target, conn)?;
+            record_inbox_activity(activity_id, activity_type, remote_actor, Some(post_id), activity, conn)?;
+        }
+        "Create" => {
+            let reply_to = object
+                .get("inReplyTo")
+                .and_then(object_id)
+                .map(|uri| local_post_for_note(domain, &uri, conn).ok())
+                .flatten();
+            match reply_to {
+                Some(post_id) => record_inbox_activity(
+                    activity_id,
+                    activity_type,
+                    remote_actor,
+                    Some(post_id),
+                    activity,
+                    conn,
+                )?,
+                None => log::info!("Ignoring Create not addressed to a local post: {}", activity_id),
+            }
+        }
+        _ => log::info!("Ignoring unsupported ActivityPub activity type: {}", activity_type),
+    };
+    Ok(())
+}
+
+fn record_inbox_activity(
+    activity_id: &str,
+    activity_type: &str,
+    remote_actor: &RemoteActor,
+    post_id: Option<i64>,
+    activity: &serde_json::Value,
+    conn: &mut PgPooledConnection,
+) -> anyhow::Result<()> {
+    insert_into(activitypub_inbox_activities::table)
+        .values(&models::NewActivityPubInboxActivity {
+            activity_uri: activity_id.to_string(),
+            activity_type: activity_type.to_string(),
+            actor_uri: remote_actor.id.to_owned(),
+            post_id,
+            activity: activity.to_owned(),
+        })
+        .on_conflict(activitypub_inbox_activities::activity_uri)
+        .do_nothing()
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Finds the local, `GLOBAL_PUBLIC` user for one of our actor IRIs.
+pub fn local_user_for_actor(
+    domain: &str,
+    uri: &str,
+    conn: &mut PgPooledConnection,
+) -> anyhow::Result<models::User> {
+    let username = uri
+        .strip_prefix(&actor_uri(domain, ""))
+        .filter(|u| !u.is_empty() && !u.contains('/'))
+        .ok_or_else(|| anyhow!("not_a_local_actor"))?;
+    users::table
+        .filter(users::username.eq(username))
+        .filter(users::visibility.eq(Visibility::GlobalPublic.to_string_visibility()))
+        .first::<models::User>(conn)
+        .map_err(|_| anyhow!("user_not_found"))
+}
+
+/// Finds the local, `GLOBAL_PUBLIC` post for one of our Note IRIs (or Tamagui post URLs).
+pub fn local_post_for_note(
+    domain: &str,
+    uri: &str,
+    conn: &mut PgPooledConnection,
+) -> anyhow::Result<i64> {
+    let proto_id = uri
+        .strip_prefix(&format!("https://{}/ap/posts/", domain))
+        .or_else(|| uri.strip_prefix(&format!("https://{}/post/", domain)))
+        .ok_or_else(|| anyhow!("not_a_local_post"))?;
+    let post_id = proto_id
+        .to_string()
+        .to_db_id()
+        .map_err(|_| anyhow!("invalid_post_id"))?;
+    posts::table
+        .select(posts::id)
+        .filter(posts::id.eq(post_id))
+        .filter(posts::visibility.eq(Visibility::GlobalPublic.to_string_visibility()))
+        .first::<i64>(conn)
+        .map_err(|_| anyhow!("post_not_found"))
+}
@@ -0,0 +1,59 @@
+// ActivityPub federation for `GLOBAL_PUBLIC` Users and Posts, so that they can be followed from
+// Mastodon and other Fediverse servers. Rocket routes live in `crate::web::activitypub`.
+
+mod http_signatures;
+pub use http_signatures::*;
+
+mod actor_keys;
+pub use actor_keys::*;
+
+mod remote_objects;
+pub use remote_objects::*;
+
+mod inbox;
+pub use inbox::*;
+
+mod delivery;
+pub use delivery::*;
+
+use crate::env_var;
+
+pub const ACTIVITYSTREAMS_CONTEXT: &str = "https://www.w3.org/ns/activitystreams";
+pub const SECURITY_CONTEXT: &str = "https://w3id.org/security/v1";
+pub const ACTIVITYSTREAMS_PUBLIC: &str = "https://www.w3.org/ns/activitystreams#Public";
+pub const ACTIVITY_JSON: &str = "application/activity+json";
+
+/// The domain used for ActivityPub IDs, from `ACTIVITYPUB_DOMAIN`. Required for outbound delivery,
+/// since background jobs have no request `Host` to fall back on.
+pub fn federation_domain() -> Option<String> {
+    env_var("ACTIVITYPUB_DOMAIN")
+}
+
+pub fn actor_uri(domain: &str, username: &str) -> String {
+    format!("https://{}/ap/users/{}", domain, username)
+}
+
+pub fn key_id(domain: &str, username: &str) -> String {
+    format!("{}#main-key", actor_uri(domain, username))
+}
+
+pub fn note_uri(domain: &str, post_id: i64) -> String {
+    use crate::marshaling::ToProtoId;
+    format!("https://{}/ap/posts/{}", domain, post_id.to_proto_id())
+}
+
+pub fn shared_inbox_uri(domain: &str) -> String {
+    format!("https://{}/ap/inbox", domain)
+}
+
+/// ActivityPub references may be either a bare IRI or an embedded object with an `id`.
+pub fn object_id(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(id) => Some(id.to_owned()),
+        serde_json::Value::Object(object) => object
+            .get("id")
+            .and_then(|id| id.as_str())
+            .map(|id| id.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+
+use super::ACTIVITY_JSON;
+
+lazy_static! {
+    pub static ref FEDERATION_CLIENT: reqwest::Client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(15))
+        .user_agent(format!("Jonline/{}", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("Failed to build federation HTTP client");
+}
+
+/// Fetches a remote ActivityPub object (an actor, a key, a note) by its IRI.
+pub async fn fetch_remote_object(uri: &str) -> anyhow::Result<serde_json::Value> {
+    let response = FEDERATION_CLIENT
+        .get(uri)
+        .header("Accept", ACTIVITY_JSON)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.json::<serde_json::Value>().await?)
+}
+
+/// A remote actor's inboxes and public key, as needed to verify and deliver activities.
+#[derive(Debug, Clone)]
+pub struct RemoteActor {
+    pub id: String,
+    pub inbox: String,
+    pub shared_inbox: Option<String>,
+    pub public_key_id: String,
+    pub public_key_pem: String,
+}
+
+/// Resolves the actor that owns `key_id`. Mastodon serves the actor document at the key's
+/// `#main-key` IRI; other servers serve a standalone key with an `owner`.
+pub async fn fetch_remote_actor_for_key(key_id: &str) -> anyhow::Result<RemoteActor> {
+    let key_document = fetch_remote_object(key_id).await?;
+    let actor = match key_document.get("inbox") {
+        Some(_) => key_document,
+        None => {
+            let owner = key_document
+                .get("owner")
+                .and_then(|o| o.as_str())
+                .ok_or_else(|| anyhow!("key_missing_owner"))?;
+            fetch_remote_object(owner).await?
+        }
+    };
+    parse_remote_actor(&actor)
+}
+
+pub fn parse_remote_actor(actor: &serde_json::Value) -> anyhow::Result<RemoteActor> {
+    let str_field = |value: &serde_json::Value, name: &str| {
+        value
+            .get(name)
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string())
+            .ok_or_else(|| anyhow!("actor_missing_{}", name))
+    };
+    let public_key = actor
+        .get("publicKey")
+        .ok_or_else(|| anyhow!("actor_missing_public_key"))?;
+    Ok(RemoteActor {
+        id: str_field(actor, "id")?,
+        inbox: str_field(actor, "inbox")?,
+        shared_inbox: actor
+            .get("endpoints")
+            .and_then(|e| e.get("sharedInbox"))
+            .and_then(|i| i.as_str())
+            .map(|i| i.to_string()),
+        public_key_id: str_field(public_key, "id")?,
+        public_key_pem: str_field(public_key, "publicKeyPem")?,
+    })
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long a validated access token is trusted without rechecking the database. Revocations
+/// through `auth::revoke_*` evict tokens immediately; this bounds anything else (i.e. manual deletes).
+pub const ACCESS_TOKEN_CACHE_TTL: Duration = Duration::from_secs(60);
+/// Beyond this many cached tokens, stale ones are evicted (or, failing that, all of them).
+const MAX_CACHED_ACCESS_TOKENS: usize = 10_000;
+
+/// A validated access token's session.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AuthSession {
+    pub user_id: i64,
+    pub access_token_id: i64,
+    pub refresh_token_id: i64,
+    pub family_id: i64,
+    pub expires_at: SystemTime,
+}
+
+/// Briefly caches validated access tokens, so authenticated RPCs don't each look up their token.
+pub struct AccessTokenCache {
+    ttl: Duration,
+    sessions: Mutex<HashMap<String, (AuthSession, Instant)>>,
+}
+
+impl AccessTokenCache {
+    pub fn new(ttl: Duration) -> AccessTokenCache {
+        AccessTokenCache {
+            ttl,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, access_token: &str) -> Option<AuthSession> {
+        self.get_at(access_token, Instant::now(), SystemTime::now())
+    }
+
+    pub fn insert(&self, access_token: &str, session: AuthSession) {
+        self.insert_at(access_token, session, Instant::now())
+    }
+
+    /// Evicts sessions matching `predicate`, i.e. when they're revoked.
+    pub fn invalidate(&self, predicate: impl Fn(&AuthSession) -> bool) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, (session, _)| !predicate(session));
+    }
+
+    fn get_at(&self, access_token: &str, now: Instant, system_now: SystemTime) -> Option<AuthSession> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(access_token) {
+            Some((session, cached_at))
+                if now.duration_since(*cached_at) < self.ttl && session.expires_at > system_now =>
+            {
+                Some(*session)
+            }
+            Some(_) => {
+                sessions.remove(access_token);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert_at(&self, access_token: &str, session: AuthSession, now: Instant) {
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.len() >= MAX_CACHED_ACCESS_TOKENS {
+            let ttl = self.ttl;
+            sessions.retain(|_, (_, cached_at)| now.duration_since(*cached_at) < ttl);
+            if sessions.len() >= MAX_CACHED_ACCESS_TOKENS {
+                sessions.clear();
+            }
+        }
+        sessions.insert(access_token.to_string(), (session, now));
+    }
+}
+
+lazy_static! {
+    pub static ref ACCESS_TOKEN_CACHE: AccessTokenCache = AccessTokenCache::new(ACCESS_TOKEN_CACHE_TTL);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn access_token_cache_expires_and_invalidates() {
+        let cache = AccessTokenCache::new(Duration::from_secs(60));
+        let now = Instant::now();
+        let system_now = SystemTime::now();
+        let session = AuthSession {
+            user_id: 1,
+            access_token_id: 2,
+            refresh_token_id: 3,
+            family_id: 3,
+            expires_at: system_now + Duration::from_secs(3600),
+        };
+        cache.insert_at("a", session, now);
+        cache.insert_at("b", AuthSession { user_id: 4, family_id: 5, ..session }, now);
+        assert_eq!(cache.get_at("a", now, system_now), Some(session));
+        assert_eq!(cache.get_at("a", now + Duration::from_secs(60), system_now), None);
+
+        cache.insert_at("a", session, now);
+        // Tokens expiring within the TTL aren't served past their expiry.
+        assert_eq!(cache.get_at("a", now, system_now + Duration::from_secs(3600)), None);
+
+        cache.insert_at("a", session, now);
+        cache.invalidate(|session| session.family_id == 3);
+        assert_eq!(cache.get_at("a", now, system_now), None);
+        assert!(cache.get_at("b", now, system_now).is_some());
+    }
+}
@@ -0,0 +1,242 @@
+use std::time::{Duration, SystemTime};
+
+use diesel::*;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tonic::{Code, Status};
+
+use super::{AuthContext, AuthCredential};
+use crate::db_connection::*;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::Permission;
+use crate::protos::Permission::*;
+use crate::schema::user_api_tokens;
+use crate::schema::users;
+
+/// Distinguishes API tokens from access tokens in `authorization` headers.
+pub const API_TOKEN_PREFIX: &str = "jlbot_";
+const API_TOKEN_BYTES: usize = 32;
+/// How stale a token's `last_used_at` may get before it's updated, as with sessions.
+const LAST_USED_RESOLUTION: Duration = Duration::from_secs(300);
+
+/// Permissions API tokens can't be granted, as they'd let bots act for (or on) other users.
+pub const UNSCOPABLE_PERMISSIONS: [Permission; 6] = [
+    Unknown,
+    ModerateUsers,
+    GrantBasicPermissions,
+    RunBots,
+    Admin,
+    ViewPrivateContactMethods,
+];
+
+/// A stored `ApiTokenScope`: a permission (by name), optionally limited to a group (by DB ID).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiTokenScope {
+    pub permission: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_id: Option<i64>,
+}
+
+impl ApiTokenScope {
+    pub fn proto_permission(&self) -> Option<Permission> {
+        Permission::from_str_name(&self.permission)
+    }
+}
+
+/// A new random API token, to be shown to its owner once and stored only as `hash_api_token`.
+pub fn generate_api_token() -> String {
+    let mut randoms = [0u8; API_TOKEN_BYTES];
+    SystemRandom::new()
+        .fill(&mut randoms)
+        .expect("Failed to generate API token");
+    let token: String = randoms.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", API_TOKEN_PREFIX, token)
+}
+
+pub fn is_api_token(token: &str) -> bool {
+    token.starts_with(API_TOKEN_PREFIX)
+}
+
+pub fn hash_api_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.trim().as_bytes()))
+}
+
+/// The permissions a token with `scopes` has: those of its owner's that it's globally scoped to,
+/// plus `RUN_BOTS` so it's still shown as a bot. Group-limited scopes only apply where RPCs
+/// check them (see `api_token_scopes_allow`).
+pub fn api_token_permissions(
+    user_permissions: &[Permission],
+    scopes: &[ApiTokenScope],
+) -> Vec<Permission> {
+    user_permissions
+        .iter()
+        .filter(|permission| {
+            **permission == RunBots
+                || scopes.iter().any(|scope| {
+                    scope.group_id.is_none() && scope.proto_permission() == Some(**permission)
+                })
+        })
+        .copied()
+        .collect()
+}
+
+/// Whether `scopes` grant `permission` in the group (by DB ID), i.e. globally or for that
+/// group, or for `None`, globally.
+pub fn api_token_scopes_allow(
+    scopes: &[ApiTokenScope],
+    permission: Permission,
+    group_id: Option<i64>,
+) -> bool {
+    scopes.iter().any(|scope| {
+        scope.proto_permission() == Some(permission)
+            && (scope.group_id.is_none() || scope.group_id == group_id)
+    })
+}
+
+/// Validates an API token, returning its owner with their permissions narrowed to its scopes.
+/// Owners must still have `RUN_BOTS`.
+pub fn authenticate_api_token(
+    token: &str,
+    conn: &mut PgPooledConnection,
+) -> Result<AuthContext, Status> {
+    let not_authorized = || Status::new(Code::Unauthenticated, "not_authorized");
+    let data_error = |e: diesel::result::Error| {
+        log::error!("Error validating API token: {:?}", e);
+        Status::new(Code::Internal, "data_error")
+    };
+    let (api_token, mut user) = user_api_tokens::table
+        .inner_join(users::table)
+        .select((user_api_tokens::all_columns, users::all_columns))
+        .filter(user_api_tokens::token_hash.eq(hash_api_token(token)))
+        .filter(
+            user_api_tokens::expires_at
+                .is_null()
+                .or(user_api_tokens::expires_at.gt(diesel::dsl::now.nullable())),
+        )
+        .first::<(models::UserApiToken, models::User)>(conn)
+        .optional()
+        .map_err(data_error)?
+        .ok_or_else(not_authorized)?;
+    let user_permissions = user.permissions.to_proto_permissions();
+    if !user_permissions.contains(&RunBots) {
+        return Err(Status::new(Code::PermissionDenied, "permission_RUN_BOTS_required"));
+    }
+    let scopes: Vec<ApiTokenScope> =
+        serde_json::from_value(api_token.scopes.to_owned()).unwrap_or_default();
+    let permissions = api_token_permissions(&user_permissions, &scopes);
+    user.permissions = permissions.to_json_permissions();
+
+    let now = SystemTime::now();
+    if api_token
+        .last_used_at
+        .map_or(true, |last_used_at| last_used_at < now - LAST_USED_RESOLUTION)
+    {
+        update(user_api_tokens::table.find(api_token.id))
+            .set(user_api_tokens::last_used_at.eq(now))
+            .execute(conn)
+            .unwrap_or(0);
+    }
+    Ok(AuthContext {
+        user,
+        permissions,
+        credential: AuthCredential::ApiToken {
+            api_token_id: api_token.id,
+            scopes,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bot_user() -> models::User {
+        models::User {
+            id: 1,
+            username: "bot".to_string(),
+            password_salted_hash: String::new(),
+            real_name: String::new(),
+            email: None,
+            phone: None,
+            permissions: vec![CreatePosts, RunBots].to_json_permissions(),
+            avatar_media_id: None,
+            bio: String::new(),
+            visibility: "GLOBAL_PUBLIC".to_string(),
+            moderation: "UNMODERATED".to_string(),
+            default_follow_moderation: "UNMODERATED".to_string(),
+            follower_count: 0,
+            following_count: 0,
+            group_count: 0,
+            post_count: 0,
+            event_count: 0,
+            response_count: 0,
+            created_at: SystemTime::now(),
+            updated_at: SystemTime::now(),
+            password_reset_required: false,
+        }
+    }
+
+    #[test]
+    fn api_token_scopes_narrow_permissions() {
+        let scopes = vec![
+            ApiTokenScope { permission: "VIEW_POSTS".to_string(), group_id: None },
+            ApiTokenScope { permission: "CREATE_POSTS".to_string(), group_id: Some(7) },
+            ApiTokenScope { permission: "CREATE_EVENTS".to_string(), group_id: None },
+        ];
+        let user_permissions = vec![ViewPosts, CreatePosts, ModeratePosts, RunBots, Admin];
+        // Group-limited scopes don't grant permissions globally.
+        assert_eq!(
+            api_token_permissions(&user_permissions, &scopes),
+            vec![ViewPosts, RunBots]
+        );
+
+        assert!(api_token_scopes_allow(&scopes, ViewPosts, None));
+        assert!(api_token_scopes_allow(&scopes, ViewPosts, Some(3)));
+        assert!(api_token_scopes_allow(&scopes, CreatePosts, Some(7)));
+        assert!(!api_token_scopes_allow(&scopes, ModeratePosts, Some(7)));
+
+        let token = generate_api_token();
+        assert!(is_api_token(&token));
+        assert_eq!(token.len(), API_TOKEN_PREFIX.len() + API_TOKEN_BYTES * 2);
+        assert_ne!(hash_api_token(&token), token);
+    }
+
+    #[test]
+    fn group_scoped_api_tokens_are_denied_elsewhere() {
+        let scopes = vec![ApiTokenScope {
+            permission: "CREATE_POSTS".to_string(),
+            group_id: Some(7),
+        }];
+        let context = AuthContext {
+            user: bot_user(),
+            permissions: api_token_permissions(&[CreatePosts, RunBots], &scopes),
+            credential: AuthCredential::ApiToken {
+                api_token_id: 1,
+                scopes: scopes.clone(),
+            },
+        };
+
+        // Only in group 7...
+        assert!(context.validate_api_token_scope(&[CreatePosts], Some(7)).is_ok());
+        assert!(!api_token_scopes_allow(&scopes, CreatePosts, Some(3)));
+        assert!(context.validate_api_token_scope(&[CreatePosts], Some(3)).is_err());
+        // ...not for RPCs needing the permission globally, like CreatePost and CreateEvent...
+        assert!(!context.has_permission(CreatePosts));
+        assert!(context.validate_api_token_scope(&[CreatePosts], None).is_err());
+        // ...nor for anything bots aren't explicitly allowed to do, like managing groups.
+        assert!(context.validate_api_token_scope(&[ModerateGroups], Some(7)).is_err());
+        assert!(context.validate_session().is_err());
+
+        let session = AuthContext {
+            credential: AuthCredential::Session {
+                access_token_id: 1,
+                refresh_token_id: 1,
+            },
+            ..context
+        };
+        assert!(session.validate_api_token_scope(&[ModerateGroups], Some(7)).is_ok());
+        assert!(session.validate_session().is_ok());
+    }
+}
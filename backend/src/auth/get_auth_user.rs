@@ -1,56 +1,221 @@
+use std::time::{Duration, SystemTime};
+
 use diesel::*;
 use tonic::{Code, Request, Status};
 
+use super::access_token_cache::{AuthSession, ACCESS_TOKEN_CACHE};
+use super::api_tokens::{api_token_scopes_allow, authenticate_api_token, is_api_token, ApiTokenScope};
 use crate::db_connection::*;
+use crate::marshaling::*;
 use crate::models;
+use crate::protos::Permission;
 use crate::schema;
 use crate::schema::user_refresh_tokens::dsl as user_refresh_tokens;
 use crate::schema::user_access_tokens::dsl as user_access_tokens;
 use crate::schema::users::dsl as users;
 
-pub fn get_auth_user_id<T>(
-    request: &Request<T>,
+/// How stale a session's `last_used_at` may get before it's updated, to avoid a write per request.
+const LAST_USED_RESOLUTION: Duration = Duration::from_secs(300);
+
+/// The authenticated user and session for a request, attached to its extensions by
+/// `AuthInterceptor` (or resolved from an access token directly, as for media requests).
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    /// For API tokens, with `permissions` narrowed to the token's scopes.
+    pub user: models::User,
+    pub permissions: Vec<Permission>,
+    pub credential: AuthCredential,
+}
+
+/// What a request was authenticated with.
+#[derive(Debug, Clone)]
+pub enum AuthCredential {
+    /// A logged-in session's access token. `refresh_token_id` identifies the session (see `GetSessions`).
+    Session {
+        access_token_id: i64,
+        refresh_token_id: i64,
+    },
+    /// A bot's `ApiToken`.
+    ApiToken {
+        api_token_id: i64,
+        scopes: Vec<ApiTokenScope>,
+    },
+}
+
+impl AuthContext {
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.permissions.contains(&permission)
+    }
+
+    /// Sessions always pass. API tokens need a scope for one of `permissions`: one limited to
+    /// `group_id` (a DB ID) or, for `None`, a global one.
+    pub fn validate_api_token_scope(
+        &self,
+        permissions: &[Permission],
+        group_id: Option<i64>,
+    ) -> Result<(), Status> {
+        match &self.credential {
+            AuthCredential::Session { .. } => Ok(()),
+            AuthCredential::ApiToken { scopes, .. } => match permissions
+                .iter()
+                .any(|permission| api_token_scopes_allow(scopes, *permission, group_id))
+            {
+                true => Ok(()),
+                false => Err(Status::new(Code::PermissionDenied, "api_token_scope_required")),
+            },
+        }
+    }
+
+    /// Fails for API tokens, for everything bots haven't explicitly been allowed to do.
+    pub fn validate_session(&self) -> Result<(), Status> {
+        match self.credential {
+            AuthCredential::Session { .. } => Ok(()),
+            AuthCredential::ApiToken { .. } => Err(api_token_not_allowed()),
+        }
+    }
+}
+
+/// Validates an access token (or API token), from `ACCESS_TOKEN_CACHE` if it was validated
+/// recently. The user is always loaded fresh, so permission changes apply immediately.
+pub fn authenticate_access_token(
+    access_token: &str,
     conn: &mut PgPooledConnection,
-) -> Result<i64, Status> {
-    let access_token = request
-        .metadata()
-        .get("authorization")
-        .ok_or(Status::new(Code::Unauthenticated, "No authentication header."))?
-        .to_str()
-        .unwrap()
-        .to_owned();
-
-    delete(
-        user_access_tokens::user_access_tokens
-            .filter(user_access_tokens::token.eq(access_token.to_owned()))
-            .filter(user_access_tokens::expires_at.lt(diesel::dsl::now)),
+) -> Result<AuthContext, Status> {
+    if is_api_token(access_token) {
+        return authenticate_api_token(access_token, conn);
+    }
+    let not_authorized = || Status::new(Code::Unauthenticated, "not_authorized");
+    let session = match ACCESS_TOKEN_CACHE.get(access_token) {
+        Some(session) => session,
+        None => {
+            let session = schema::user_access_tokens::table
+                .inner_join(schema::user_refresh_tokens::table)
+                .select((
+                    user_refresh_tokens::user_id,
+                    user_access_tokens::id,
+                    user_refresh_tokens::id,
+                    user_refresh_tokens::family_id,
+                    user_access_tokens::expires_at,
+                ))
+                .filter(user_access_tokens::token.eq(access_token))
+                .filter(user_access_tokens::expires_at.gt(diesel::dsl::now))
+                .first::<(i64, i64, i64, i64, SystemTime)>(conn)
+                .optional()
+                .map_err(|e| {
+                    log::error!("Error validating access token: {:?}", e);
+                    Status::new(Code::Internal, "data_error")
+                })?
+                .map(
+                    |(user_id, access_token_id, refresh_token_id, family_id, expires_at)| AuthSession {
+                        user_id,
+                        access_token_id,
+                        refresh_token_id,
+                        family_id,
+                        expires_at,
+                    },
+                )
+                .ok_or_else(not_authorized)?;
+            // Cache misses happen at most once per TTL per token, which is plenty for `last_used_at`.
+            touch_session(session.refresh_token_id, conn);
+            ACCESS_TOKEN_CACHE.insert(access_token, session);
+            session
+        }
+    };
+    let user = users::users
+        .find(session.user_id)
+        .first::<models::User>(conn)
+        .optional()
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?
+        .ok_or_else(not_authorized)?;
+    Ok(AuthContext {
+        permissions: user.permissions.to_proto_permissions(),
+        user,
+        credential: AuthCredential::Session {
+            access_token_id: session.access_token_id,
+            refresh_token_id: session.refresh_token_id,
+        },
+    })
+}
+
+/// Marks the session (refresh token) as used now, at `LAST_USED_RESOLUTION`.
+fn touch_session(refresh_token_id: i64, conn: &mut PgPooledConnection) {
+    let now = SystemTime::now();
+    update(
+        user_refresh_tokens::user_refresh_tokens
+            .filter(user_refresh_tokens::id.eq(refresh_token_id))
+            .filter(
+                user_refresh_tokens::last_used_at
+                    .is_null()
+                    .or(user_refresh_tokens::last_used_at.lt(now - LAST_USED_RESOLUTION)),
+            ),
     )
+    .set(user_refresh_tokens::last_used_at.eq(now))
     .execute(conn)
     .unwrap_or(0);
+}
 
-    let user_id: Result<i64, _> = schema::user_access_tokens::table
-        .inner_join(schema::user_refresh_tokens::table)
-        .select(user_refresh_tokens::user_id)
-        .filter(user_access_tokens::token.eq(access_token))
-        .first::<i64>(conn);
-
-    match user_id {
-        Ok(user_id) => Ok(user_id),
-        Err(_) => Err(Status::new(Code::Unauthenticated, "not_authorized")),
+/// The request's `AuthContext`, as attached by `AuthInterceptor`.
+pub fn get_auth_context<T>(request: &Request<T>) -> Result<&AuthContext, Status> {
+    match request.extensions().get::<AuthContext>() {
+        Some(context) => Ok(context),
+        None if request.metadata().contains_key("authorization") => {
+            Err(Status::new(Code::Unauthenticated, "not_authorized"))
+        }
+        None => Err(Status::new(Code::Unauthenticated, "No authentication header.")),
     }
 }
 
-pub fn get_auth_user<T>(
+/// The request's user, unless it was authenticated with an API token. API tokens are denied
+/// by default: RPCs bots may use get their user with `get_viewing_auth_user` (if read-only),
+/// `get_scoped_auth_user` or `get_group_scoped_auth_user` instead.
+pub fn get_auth_user<T>(request: &Request<T>) -> Result<models::User, Status> {
+    let context = get_auth_context(request)?;
+    context.validate_session()?;
+    Ok(context.user.clone())
+}
+
+/// The request's user, including bots (with their permissions narrowed to their token's
+/// scopes). Only for read-only RPCs.
+pub fn get_viewing_auth_user<T>(request: &Request<T>) -> Result<models::User, Status> {
+    get_auth_context(request).map(|context| context.user.clone())
+}
+
+/// The request's user, if any API token it was authenticated with has a global scope for one
+/// of `permissions`.
+pub fn get_scoped_auth_user<T>(
     request: &Request<T>,
-    conn: &mut PgPooledConnection,
+    permissions: &[Permission],
 ) -> Result<models::User, Status> {
-    let user_id = get_auth_user_id(request, conn);
-    let user: models::User = match user_id {
-        Err(status) => return Err(status),
-        Ok(user_id) => schema::users::table
-            .filter(users::id.eq(user_id))
-            .first::<models::User>(conn)
-            .unwrap(),
-    };
-    Ok(user)
+    let context = get_auth_context(request)?;
+    context.validate_api_token_scope(permissions, None)?;
+    Ok(context.user.clone())
+}
+
+/// For RPCs acting on a group (by proto ID): the request's user, if any API token it was
+/// authenticated with is scoped to the group (or globally) for one of `permissions`.
+pub fn get_group_scoped_auth_user<T>(
+    request: &Request<T>,
+    permissions: &[Permission],
+    group_id: &str,
+) -> Result<models::User, Status> {
+    let context = get_auth_context(request)?;
+    if let AuthCredential::ApiToken { .. } = context.credential {
+        let group_id = group_id.to_string().to_db_id_or_err("group_id")?;
+        context.validate_api_token_scope(permissions, Some(group_id))?;
+    }
+    Ok(context.user.clone())
+}
+
+/// The ID of the refresh token (i.e. the session) authenticating the request.
+pub fn get_auth_refresh_token_id<T>(request: &Request<T>) -> Result<i64, Status> {
+    match &get_auth_context(request)?.credential {
+        AuthCredential::Session {
+            refresh_token_id, ..
+        } => Ok(*refresh_token_id),
+        AuthCredential::ApiToken { .. } => Err(api_token_not_allowed()),
+    }
+}
+
+fn api_token_not_allowed() -> Status {
+    Status::new(Code::PermissionDenied, "api_token_not_allowed")
 }
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use tonic::service::Interceptor;
+use tonic::{Code, Request, Status};
+
+use super::authenticate_access_token;
+use crate::db_connection::PgPool;
+
+/// Authenticates each gRPC request's `authorization` access token once, attaching an
+/// `AuthContext` for `auth::get_auth_user` and friends. Requests without a valid token pass
+/// through unauthenticated, so publicly accessible RPCs still work; RPCs requiring auth fail
+/// when they look for the context.
+#[derive(Clone)]
+pub struct AuthInterceptor {
+    pub pool: Arc<PgPool>,
+}
+
+impl Interceptor for AuthInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let access_token = match request.metadata().get("authorization") {
+            Some(value) => match value.to_str() {
+                Ok(value) => value.trim().trim_start_matches("Bearer ").to_string(),
+                // Not a token we could have issued.
+                Err(_) => return Ok(request),
+            },
+            None => return Ok(request),
+        };
+        if access_token.is_empty() {
+            return Ok(request);
+        }
+        let mut conn = self
+            .pool
+            .get()
+            .map_err(|_| Status::new(Code::DataLoss, "database_connection_failure"))?;
+        match authenticate_access_token(&access_token, &mut conn) {
+            Ok(context) => {
+                request.extensions_mut().insert(context);
+            }
+            Err(status) if status.code() == Code::Unauthenticated => {}
+            Err(status) => return Err(status),
+        }
+        Ok(request)
+    }
+}
@@ -1,6 +1,41 @@
 mod token_generation;
 pub use token_generation::generate_refresh_and_access_token;
 pub use token_generation::generate_access_token;
+pub use token_generation::rotate_refresh_token;
+pub use token_generation::TokenLifetimes;
+
+mod session_metadata;
+pub use session_metadata::SessionMetadata;
+
+mod token_revocation;
+pub use token_revocation::revoke_user_tokens;
+pub use token_revocation::revoke_token_family;
+
+mod token_cleanup;
+pub use token_cleanup::delete_expired_tokens;
+pub use token_cleanup::start_expired_token_cleanup_worker;
+
+mod access_token_cache;
+pub use access_token_cache::AuthSession;
+pub use access_token_cache::ACCESS_TOKEN_CACHE;
 
 mod get_auth_user;
-pub use get_auth_user::get_auth_user;
\ No newline at end of file
+pub use get_auth_user::authenticate_access_token;
+pub use get_auth_user::get_auth_context;
+pub use get_auth_user::get_auth_user;
+pub use get_auth_user::get_viewing_auth_user;
+pub use get_auth_user::get_scoped_auth_user;
+pub use get_auth_user::get_group_scoped_auth_user;
+pub use get_auth_user::get_auth_refresh_token_id;
+pub use get_auth_user::AuthContext;
+pub use get_auth_user::AuthCredential;
+
+mod api_tokens;
+pub use api_tokens::authenticate_api_token;
+pub use api_tokens::generate_api_token;
+pub use api_tokens::hash_api_token;
+pub use api_tokens::ApiTokenScope;
+pub use api_tokens::UNSCOPABLE_PERMISSIONS;
+
+mod interceptor;
+pub use interceptor::AuthInterceptor;
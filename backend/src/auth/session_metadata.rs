@@ -0,0 +1,130 @@
+use std::net::IpAddr;
+
+use tonic::Request;
+
+use crate::protos::ServerConfiguration;
+use crate::web::secure_media::{effective_client_ip, parse_ip_ranges};
+
+/// Longest user agent we'll store for a session.
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+/// Where a session (i.e. refresh token) was created from, as shown by `GetSessions`.
+#[derive(Debug, Default, Clone)]
+pub struct SessionMetadata {
+    pub device_name: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    /// The connecting peer and the forwarding headers it sent, for `client_ip`.
+    pub peer_address: Option<IpAddr>,
+    pub forwarded_for: Option<String>,
+    pub cf_connecting_ip: Option<String>,
+}
+
+impl SessionMetadata {
+    /// Reads the user agent and IP address from the request's headers. The IP is the first
+    /// `X-Forwarded-For` address if present (so it's meaningful behind a proxy or CDN), and
+    /// is informational only: clients can set it to anything.
+    pub fn from_request<T>(request: &Request<T>) -> SessionMetadata {
+        let header = |name: &str| {
+            request
+                .metadata()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let user_agent = header("user-agent")
+            .or_else(|| header("x-user-agent"))
+            .map(|user_agent| user_agent.chars().take(MAX_USER_AGENT_LENGTH).collect());
+        let forwarded_for = header("x-forwarded-for");
+        let peer_address = request.remote_addr().map(|addr| addr.ip());
+        let ip_address = forwarded_for
+            .as_ref()
+            .and_then(|forwarded| {
+                forwarded
+                    .split(',')
+                    .next()
+                    .map(|ip| ip.trim().to_string())
+                    .filter(|ip| ip.parse::<std::net::IpAddr>().is_ok())
+            })
+            .or_else(|| peer_address.map(|ip| ip.to_string()));
+        SessionMetadata {
+            device_name: None,
+            user_agent,
+            ip_address,
+            peer_address,
+            forwarded_for,
+            cf_connecting_ip: header("cf-connecting-ip"),
+        }
+    }
+
+    /// The client's IP, for rate limiting. Unlike `ip_address`, this is the peer's address
+    /// unless the peer is one of `ExternalCdnConfig.trusted_proxies`, whose forwarding headers
+    /// are then followed as for secure media (see `effective_client_ip`).
+    pub fn client_ip(&self, configuration: &ServerConfiguration) -> Option<String> {
+        let trusted_proxies = parse_ip_ranges(
+            configuration
+                .external_cdn_config
+                .as_ref()
+                .and_then(|config| config.trusted_proxies.as_deref())
+                .unwrap_or(""),
+        );
+        self.peer_address.map(|peer| {
+            effective_client_ip(
+                peer,
+                self.forwarded_for.as_deref(),
+                self.cf_connecting_ip.as_deref(),
+                &trusted_proxies,
+            )
+            .to_string()
+        })
+    }
+
+    pub fn with_device_name(self, device_name: Option<String>) -> SessionMetadata {
+        SessionMetadata {
+            device_name: device_name
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty()),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::ExternalCdnConfig;
+
+    fn metadata(peer: &str, forwarded_for: Option<&str>) -> SessionMetadata {
+        SessionMetadata {
+            peer_address: peer.parse().ok(),
+            forwarded_for: forwarded_for.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn client_ips_only_trust_configured_proxies() {
+        let mut configuration = ServerConfiguration::default();
+        // Without trusted proxies, forwarding headers are ignored.
+        assert_eq!(
+            metadata("8.8.8.8", Some("1.2.3.4")).client_ip(&configuration),
+            Some("8.8.8.8".to_string())
+        );
+
+        configuration.external_cdn_config = Some(ExternalCdnConfig {
+            trusted_proxies: Some("10.0.0.0/8".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            metadata("10.0.0.2", Some("1.2.3.4, 10.0.0.1")).client_ip(&configuration),
+            Some("1.2.3.4".to_string())
+        );
+        // Untrusted peers still can't spoof their address.
+        assert_eq!(
+            metadata("8.8.8.8", Some("1.2.3.4")).client_ip(&configuration),
+            Some("8.8.8.8".to_string())
+        );
+        assert_eq!(metadata("", Some("1.2.3.4")).client_ip(&configuration), None);
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use diesel::pg::PgConnection;
+use diesel::*;
+use tokio::task::JoinHandle;
+
+use crate::db_connection::PgPool;
+use crate::schema::user_access_tokens::dsl as user_access_tokens;
+use crate::schema::user_refresh_tokens::dsl as user_refresh_tokens;
+
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Deletes expired access and refresh tokens (including rotated refresh tokens kept for reuse
+/// detection, once they'd have expired anyway). Returns how many of each were deleted.
+pub fn delete_expired_tokens(conn: &mut PgConnection) -> Result<(usize, usize), diesel::result::Error> {
+    let access_tokens = delete(
+        user_access_tokens::user_access_tokens
+            .filter(user_access_tokens::expires_at.lt(diesel::dsl::now)),
+    )
+    .execute(conn)?;
+    let refresh_tokens = delete(
+        user_refresh_tokens::user_refresh_tokens
+            .filter(user_refresh_tokens::expires_at.lt(diesel::dsl::now)),
+    )
+    .execute(conn)?;
+    Ok((access_tokens, refresh_tokens))
+}
+
+/// Periodically deletes expired tokens (see `delete_expired_tokens`), which validation ignores.
+pub fn start_expired_token_cleanup_worker(pool: Arc<PgPool>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let mut conn = match pool.get() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    log::warn!("Token cleanup worker failed to get connection: {:?}", e);
+                    continue;
+                }
+            };
+            match delete_expired_tokens(&mut conn) {
+                Ok((0, 0)) => {}
+                Ok((access_tokens, refresh_tokens)) => log::info!(
+                    "Deleted {} expired access tokens and {} expired refresh tokens",
+                    access_tokens,
+                    refresh_tokens
+                ),
+                Err(e) => log::warn!("Token cleanup worker error: {:?}", e),
+            }
+        }
+    })
+}
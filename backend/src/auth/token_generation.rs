@@ -1,15 +1,22 @@
 use std::option::Option;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use diesel::*;
 use prost_wkt_types::*;
 use ring::rand::*;
+use tonic::{Code, Status};
 
+use super::{SessionMetadata, ACCESS_TOKEN_CACHE};
 use crate::db_connection::*;
+use crate::models;
 use crate::protos::*;
+use crate::schema::user_devices;
 use crate::schema::user_refresh_tokens::dsl as user_refresh_tokens;
 use crate::schema::user_access_tokens::dsl as user_access_tokens;
 
+pub const DEFAULT_REFRESH_TOKEN_LIFETIME: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+pub const DEFAULT_ACCESS_TOKEN_LIFETIME: Duration = Duration::from_secs(8 * 60 * 60);
+
 /// Generate a secure random token of the given length.
 macro_rules! generate_token {
     ($length_u8:expr) => {{
@@ -24,56 +31,290 @@ macro_rules! generate_token {
     }};
 }
 
-/// Generate and store a refresh token and access token for the given user.
+/// Token and session lifetimes, from the server's `AuthenticationSettings`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenLifetimes {
+    pub refresh_token: Duration,
+    pub access_token: Duration,
+    pub session: Option<Duration>,
+}
+
+impl TokenLifetimes {
+    pub fn from_configuration(configuration: &ServerConfiguration) -> TokenLifetimes {
+        let settings = configuration
+            .authentication_settings
+            .to_owned()
+            .unwrap_or_default();
+        let seconds = |seconds: Option<u64>| {
+            seconds
+                .filter(|seconds| *seconds > 0)
+                .map(Duration::from_secs)
+        };
+        TokenLifetimes {
+            refresh_token: seconds(settings.refresh_token_lifetime_seconds)
+                .unwrap_or(DEFAULT_REFRESH_TOKEN_LIFETIME),
+            access_token: seconds(settings.access_token_lifetime_seconds)
+                .unwrap_or(DEFAULT_ACCESS_TOKEN_LIFETIME),
+            session: seconds(settings.session_lifetime_seconds),
+        }
+    }
+
+    /// When a session starting `now` ends, given the expiry its client requested (if any).
+    pub fn session_expiry(&self, now: SystemTime, requested: Option<SystemTime>) -> Option<SystemTime> {
+        match (requested, self.session.map(|lifetime| now + lifetime)) {
+            (Some(requested), Some(limit)) => Some(requested.min(limit)),
+            (requested, limit) => requested.or(limit),
+        }
+    }
+
+    /// When a refresh token issued `now` expires, in a session ending at `session_expires_at`.
+    pub fn refresh_token_expiry(
+        &self,
+        now: SystemTime,
+        session_expires_at: Option<SystemTime>,
+    ) -> SystemTime {
+        let expiry = now + self.refresh_token;
+        session_expires_at.map_or(expiry, |session_expires_at| session_expires_at.min(expiry))
+    }
+
+    /// When an access token issued `now` expires, for a refresh token expiring at `refresh_token_expires_at`.
+    pub fn access_token_expiry(&self, now: SystemTime, refresh_token_expires_at: SystemTime) -> SystemTime {
+        (now + self.access_token).min(refresh_token_expires_at)
+    }
+}
+
+/// Generate and store a refresh token (starting a new token family, i.e. session) and access token
+/// for the given user, recording the session's metadata (and its device, if named).
 pub fn generate_refresh_and_access_token(
     user_id: i64,
     conn: &mut PgPooledConnection,
     expires_at: Option<Timestamp>,
-) -> RefreshTokenResponse {
+    metadata: &SessionMetadata,
+    lifetimes: &TokenLifetimes,
+) -> Result<RefreshTokenResponse, Status> {
+    let data_error = |e: diesel::result::Error| {
+        log::error!("Error generating refresh token for user_id={}: {:?}", user_id, e);
+        Status::new(Code::Internal, "data_error")
+    };
     let refresh_token = generate_token!(512);
+    let device_id = metadata
+        .device_name
+        .as_ref()
+        .map(|device_name| upsert_device(user_id, device_name, conn))
+        .transpose()
+        .map_err(data_error)?;
 
+    let now = SystemTime::now();
     let requested_expiration: Option<SystemTime> = expires_at
         .map(SystemTime::try_from)
         .map(|x| x.ok())
         .flatten();
-    let (refresh_token_id, expires_at): (i64, Option<SystemTime>) =
-        insert_into(user_refresh_tokens::user_refresh_tokens)
-            .values((
-                user_refresh_tokens::user_id.eq(user_id),
-                user_refresh_tokens::token.eq(refresh_token.to_owned()),
-                user_refresh_tokens::expires_at.eq(requested_expiration),
-            ))
-            .returning((user_refresh_tokens::id, user_refresh_tokens::expires_at))
-            .get_result::<(i64, Option<SystemTime>)>(&mut *conn)
-            .unwrap();
+    let session_expires_at = lifetimes.session_expiry(now, requested_expiration);
+    let expires_at = lifetimes.refresh_token_expiry(now, session_expires_at);
+    // The family is identified by its first token's ID, so reserve it up front.
+    let refresh_token_id = diesel::select(diesel::dsl::sql::<diesel::sql_types::BigInt>(
+        "nextval('user_refresh_tokens_id_seq')",
+    ))
+    .get_result::<i64>(conn)
+    .map_err(data_error)?;
+    insert_into(user_refresh_tokens::user_refresh_tokens)
+        .values(&models::NewUserRefreshToken {
+            id: Some(refresh_token_id),
+            user_id,
+            token: refresh_token.to_owned(),
+            created_at: now,
+            expires_at,
+            device_id,
+            user_agent: metadata.user_agent.to_owned(),
+            ip_address: metadata.ip_address.to_owned(),
+            last_used_at: Some(now),
+            family_id: refresh_token_id,
+            session_expires_at,
+        })
+        .execute(conn)
+        .map_err(data_error)?;
     let auth_exp_token = ExpirableToken {
         token: refresh_token.to_owned(),
-        expires_at: expires_at.map(Timestamp::from),
+        expires_at: Some(Timestamp::from(expires_at)),
     };
     log::info!("Generated refresh token for user_id={}", user_id);
 
-    let refresh_exp_token = generate_access_token(refresh_token_id, conn);
-    RefreshTokenResponse {
+    let refresh_exp_token = generate_access_token(
+        refresh_token_id,
+        lifetimes.access_token_expiry(now, expires_at),
+        conn,
+    )
+    .map_err(data_error)?;
+    Ok(RefreshTokenResponse {
         refresh_token: Some(auth_exp_token),
         access_token: Some(refresh_exp_token),
         user: None,
-    }
+        two_factor_challenge: None,
+    })
+}
+
+/// Replaces a refresh token with a new one in the same family, moving its access tokens to the new
+/// one and issuing another. The old token is kept, marked rotated, so its reuse can be detected.
+/// Returns `None` if it had already been rotated (i.e. by a concurrent call), which callers
+/// should treat as reuse.
+pub fn rotate_refresh_token(
+    refresh_token: &models::UserRefreshToken,
+    conn: &mut PgPooledConnection,
+    metadata: &SessionMetadata,
+    lifetimes: &TokenLifetimes,
+) -> Result<Option<AccessTokenResponse>, diesel::result::Error> {
+    let now = SystemTime::now();
+    let new_token = generate_token!(512);
+    let expires_at = lifetimes.refresh_token_expiry(now, refresh_token.session_expires_at);
+    let new_token_id = conn.transaction::<Option<i64>, diesel::result::Error, _>(|conn| {
+        let rotated = update(
+            user_refresh_tokens::user_refresh_tokens
+                .filter(user_refresh_tokens::id.eq(refresh_token.id))
+                .filter(user_refresh_tokens::rotated_at.is_null()),
+        )
+        .set(user_refresh_tokens::rotated_at.eq(now))
+        .execute(conn)?;
+        if rotated == 0 {
+            return Ok(None);
+        }
+        let new_token_id = insert_into(user_refresh_tokens::user_refresh_tokens)
+            .values(&models::NewUserRefreshToken {
+                id: None,
+                user_id: refresh_token.user_id,
+                token: new_token.to_owned(),
+                created_at: refresh_token.created_at,
+                expires_at,
+                device_id: refresh_token.device_id,
+                user_agent: metadata
+                    .user_agent
+                    .to_owned()
+                    .or(refresh_token.user_agent.to_owned()),
+                ip_address: metadata
+                    .ip_address
+                    .to_owned()
+                    .or(refresh_token.ip_address.to_owned()),
+                last_used_at: Some(now),
+                family_id: refresh_token.family_id,
+                session_expires_at: refresh_token.session_expires_at,
+            })
+            .returning(user_refresh_tokens::id)
+            .get_result::<i64>(conn)?;
+        update(
+            user_access_tokens::user_access_tokens
+                .filter(user_access_tokens::refresh_token_id.eq(refresh_token.id)),
+        )
+        .set(user_access_tokens::refresh_token_id.eq(new_token_id))
+        .execute(conn)?;
+        Ok(Some(new_token_id))
+    })?;
+    let new_token_id = match new_token_id {
+        Some(new_token_id) => new_token_id,
+        None => return Ok(None),
+    };
+    // Cached sessions still point at the old token.
+    ACCESS_TOKEN_CACHE.invalidate(|session| session.family_id == refresh_token.family_id);
+    log::info!(
+        "Rotated refresh token for user_id={}, family_id={}",
+        refresh_token.user_id,
+        refresh_token.family_id
+    );
+
+    let access_token = generate_access_token(
+        new_token_id,
+        lifetimes.access_token_expiry(now, expires_at),
+        conn,
+    )?;
+    Ok(Some(AccessTokenResponse {
+        refresh_token: Some(ExpirableToken {
+            token: new_token,
+            expires_at: Some(Timestamp::from(expires_at)),
+        }),
+        access_token: Some(access_token),
+    }))
+}
+
+/// The ID of the user's device named `device_name`, creating it if needed.
+fn upsert_device(
+    user_id: i64,
+    device_name: &str,
+    conn: &mut PgPooledConnection,
+) -> Result<i64, diesel::result::Error> {
+    insert_into(user_devices::table)
+        .values(&models::NewUserDevice {
+            user_id,
+            device_name: device_name.to_string(),
+        })
+        .on_conflict((user_devices::user_id, user_devices::device_name))
+        .do_update()
+        // A no-op update, so the existing row's ID is returned.
+        .set(user_devices::device_name.eq(diesel::upsert::excluded(user_devices::device_name)))
+        .returning(user_devices::id)
+        .get_result::<i64>(conn)
 }
 
 /// Generate and store an access token for the given refresh token.
-pub fn generate_access_token(refresh_token_id: i64, conn: &mut PgPooledConnection) -> ExpirableToken {
+pub fn generate_access_token(
+    refresh_token_id: i64,
+    expires_at: SystemTime,
+    conn: &mut PgPooledConnection,
+) -> Result<ExpirableToken, diesel::result::Error> {
     let access_token = generate_token!(128);
-    let expires_at: SystemTime = insert_into(user_access_tokens::user_access_tokens)
+    insert_into(user_access_tokens::user_access_tokens)
         .values((
             user_access_tokens::refresh_token_id.eq(refresh_token_id),
             user_access_tokens::token.eq(access_token.to_owned()),
+            user_access_tokens::expires_at.eq(expires_at),
         ))
-        .returning(user_access_tokens::expires_at)
-        .get_result::<SystemTime>(conn)
-        .unwrap();
+        .execute(conn)?;
     log::info!("Generated access token for refresh_token_id={}, expires_at={:#?}", refresh_token_id, expires_at);
-    ExpirableToken {
+    Ok(ExpirableToken {
         token: access_token.to_owned(),
         expires_at: Some(Timestamp::from(expires_at)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_lifetimes_respect_session_expiry() {
+        let minute = Duration::from_secs(60);
+        let hour = minute * 60;
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let lifetimes = TokenLifetimes {
+            refresh_token: hour * 24,
+            access_token: hour,
+            session: Some(hour * 72),
+        };
+
+        assert_eq!(lifetimes.session_expiry(now, None), Some(now + hour * 72));
+        assert_eq!(lifetimes.session_expiry(now, Some(now + hour * 2)), Some(now + hour * 2));
+        assert_eq!(lifetimes.session_expiry(now, Some(now + hour * 100)), Some(now + hour * 72));
+        let unlimited = TokenLifetimes { session: None, ..lifetimes };
+        assert_eq!(unlimited.session_expiry(now, None), None);
+
+        assert_eq!(lifetimes.refresh_token_expiry(now, None), now + hour * 24);
+        assert_eq!(lifetimes.refresh_token_expiry(now, Some(now + hour * 2)), now + hour * 2);
+        assert_eq!(lifetimes.access_token_expiry(now, now + hour * 24), now + hour);
+        assert_eq!(lifetimes.access_token_expiry(now, now + minute), now + minute);
+
+        let configured = TokenLifetimes::from_configuration(&ServerConfiguration {
+            authentication_settings: Some(AuthenticationSettings {
+                refresh_token_lifetime_seconds: Some(3600),
+                access_token_lifetime_seconds: Some(0),
+                session_lifetime_seconds: None,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert_eq!(
+            configured,
+            TokenLifetimes {
+                refresh_token: hour,
+                access_token: DEFAULT_ACCESS_TOKEN_LIFETIME,
+                session: None,
+            }
+        );
     }
 }
@@ -0,0 +1,54 @@
+use diesel::*;
+
+use super::ACCESS_TOKEN_CACHE;
+use crate::db_connection::*;
+use crate::schema::user_refresh_tokens::dsl as user_refresh_tokens;
+
+/// Deletes the user's refresh tokens (and, by cascade, their access tokens), logging out
+/// all of their sessions except the one `except_refresh_token_id` belongs to, if given.
+pub fn revoke_user_tokens(
+    user_id: i64,
+    except_refresh_token_id: Option<i64>,
+    conn: &mut PgPooledConnection,
+) -> Result<usize, diesel::result::Error> {
+    let except_family_id = match except_refresh_token_id {
+        Some(except_id) => Some(
+            user_refresh_tokens::user_refresh_tokens
+                .find(except_id)
+                .select(user_refresh_tokens::family_id)
+                .first::<i64>(conn)?,
+        ),
+        None => None,
+    };
+    let revoked = match except_family_id {
+        Some(except_family_id) => delete(
+            user_refresh_tokens::user_refresh_tokens
+                .filter(user_refresh_tokens::user_id.eq(user_id))
+                .filter(user_refresh_tokens::family_id.ne(except_family_id)),
+        )
+        .execute(conn)?,
+        None => delete(
+            user_refresh_tokens::user_refresh_tokens.filter(user_refresh_tokens::user_id.eq(user_id)),
+        )
+        .execute(conn)?,
+    };
+    ACCESS_TOKEN_CACHE.invalidate(|session| {
+        session.user_id == user_id && Some(session.family_id) != except_family_id
+    });
+    log::info!("Revoked {} refresh tokens for user_id={}", revoked, user_id);
+    Ok(revoked)
+}
+
+/// Deletes a whole family of rotated refresh tokens (i.e. a session), and their access tokens.
+pub fn revoke_token_family(
+    family_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<usize, diesel::result::Error> {
+    let revoked = delete(
+        user_refresh_tokens::user_refresh_tokens.filter(user_refresh_tokens::family_id.eq(family_id)),
+    )
+    .execute(conn)?;
+    ACCESS_TOKEN_CACHE.invalidate(|session| session.family_id == family_id);
+    log::info!("Revoked {} refresh tokens in family_id={}", revoked, family_id);
+    Ok(revoked)
+}
@@ -0,0 +1,57 @@
+extern crate jonline;
+extern crate rocket;
+
+use jonline::{env_var, init_bin_logging};
+use rocket::data::ToByteUnit;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{routes, Data, Request};
+
+/// A local stand-in for a remote (i.e. Mastodon) inbox, for testing ActivityPub delivery.
+/// Logs every activity POSTed to `/inbox` along with its signature headers.
+///
+/// Usage: `STUB_INBOX_PORT=9999 cargo run --bin activitypub_stub_inbox`, then insert an
+/// `activitypub_remote_followers` row with `inbox_uri` `http://localhost:9999/inbox`.
+#[rocket::main]
+async fn main() {
+    init_bin_logging();
+    let port = env_var("STUB_INBOX_PORT")
+        .and_then(|p| p.parse::<u16>().ok())
+        .unwrap_or(9999);
+    log::info!("Starting stub ActivityPub inbox on port {}...", port);
+    let figment = rocket::Config::figment()
+        .merge(("port", port))
+        .merge(("address", "0.0.0.0"));
+    let _ = rocket::custom(figment)
+        .mount("/", routes![inbox])
+        .launch()
+        .await;
+}
+
+struct SignatureHeaders(Vec<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SignatureHeaders {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(SignatureHeaders(
+            ["Host", "Date", "Digest", "Signature"]
+                .iter()
+                .map(|name| format!("{}: {:?}", name, req.headers().get_one(name)))
+                .collect(),
+        ))
+    }
+}
+
+#[rocket::post("/inbox", data = "<body>")]
+async fn inbox(body: Data<'_>, headers: SignatureHeaders) -> Status {
+    let body = body
+        .open(1.mebibytes())
+        .into_string()
+        .await
+        .map(|b| b.into_inner())
+        .unwrap_or_default();
+    log::info!("Received activity:\n{}\n{}", headers.0.join("\n"), body);
+    Status::Accepted
+}
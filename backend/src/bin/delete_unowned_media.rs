@@ -1,16 +1,17 @@
 extern crate diesel;
 extern crate jonline;
 use diesel::*;
-use jonline::{db_connection, minio_connection, init_bin_logging};
+use jonline::media_store::{get_media_store, MediaStore};
+use jonline::{db_connection, media_processing, init_bin_logging};
 use jonline::schema::{media, posts};
 
 #[tokio::main]
 async fn main() {
     init_bin_logging();
     log::info!("Deleting Unowned Media...");
-    log::info!("Connecting to DB and MinIO...");
+    log::info!("Connecting to DB and media store...");
     let mut conn = db_connection::establish_connection();
-    let bucket = minio_connection::get_and_test_bucket().await.expect("Failed to connect to MinIO");
+    let media_store = get_media_store().await.expect("Failed to connect to media store");
 
     let mut unowned_media = media::table
         .filter(media::user_id.is_null()).load::<jonline::models::Media>(&mut conn)
@@ -32,14 +33,49 @@ async fn main() {
                 .expect("Failed to update Post");
         }
 
-        match bucket.delete_object(&media.minio_path).await {
-            Ok(_) => { 
-                delete(media::table.find(media.id)).execute(&mut conn)
-                    .expect("Failed to delete Media");
-                log::info!("Deleted Media: {:?}", media);
-             },
+        let derived_paths = media_processing::derived_object_paths(media, &mut conn);
+        let blob_id = match media.blob_id {
+            Some(blob_id) => blob_id,
+            None => {
+                // Media from before content-addressed blobs owns its objects outright.
+                if delete_objects(media_store.as_ref(), &derived_paths, &media.minio_path).await {
+                    delete(media::table.find(media.id)).execute(&mut conn)
+                        .expect("Failed to delete Media");
+                    log::info!("Deleted Media: {:?}", media);
+                }
+                continue;
+            }
+        };
+
+        // Other Media may share the blob; only delete its objects along with its last reference.
+        let released = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            delete(media::table.find(media.id)).execute(conn)?;
+            media_processing::release_blob(blob_id, conn)
+        });
+        match released {
+            Ok(Some(blob)) => {
+                delete_objects(media_store.as_ref(), &derived_paths, &blob.minio_path).await;
+                log::info!("Deleted Media: {:?} and its blob {}", media, blob.sha256);
+            }
+            Ok(None) => log::info!("Deleted Media: {:?} (its blob is still referenced)", media),
             Err(e) => log::error!("Failed to delete Media: {:?} with error: {:?}. Proceeding through remaining media.", media, e),
         }
     }
     log::info!("Done Deleting Unowned Media.");
 }
+
+/// Deletes a Media object and its derived objects, returning whether the former was deleted.
+async fn delete_objects(media_store: &dyn MediaStore, derived_paths: &[String], minio_path: &str) -> bool {
+    for path in derived_paths {
+        if let Err(e) = media_store.delete(path).await {
+            log::warn!("Failed to delete processed Media object {}: {:?}", path, e);
+        }
+    }
+    match media_store.delete(minio_path).await {
+        Ok(_) => true,
+        Err(e) => {
+            log::error!("Failed to delete Media object {}: {:?}", minio_path, e);
+            false
+        }
+    }
+}
@@ -15,22 +15,22 @@ use jonline::marshaling::*;
 use jonline::models;
 use jonline::models::{get_user, Post};
 use jonline::protos::Visibility;
-use jonline::{db_connection, init_bin_logging, minio_connection};
+use jonline::media_store::{get_media_store, MediaStore};
+use jonline::{db_connection, init_bin_logging};
 // use jonline::schema::posts::dsl::*;
 use jonline::schema::{media, posts};
-use s3::Bucket;
 use uuid::Uuid;
 
 #[tokio::main]
 async fn main() {
     init_bin_logging();
     log::info!("Generating preview images...");
-    log::info!("Connecting to DB and MinIO...");
+    log::info!("Connecting to DB and media store...");
     let pool = db_connection::establish_pool();
     let mut conn = pool.get().expect("Failed to get DB connection");
-    let bucket = minio_connection::get_and_test_bucket()
+    let media_store = get_media_store()
         .await
-        .expect("Failed to connect to MinIO");
+        .expect("Failed to connect to media store");
 
     log::info!("Starting browser...");
     let browser = start_browser().expect("Failed to start browser");
@@ -44,7 +44,7 @@ async fn main() {
     log::info!("Got {} posts to update.", posts_to_update.len());
 
     for post in posts_to_update {
-        update_post(&post, &browser, &mut conn, &bucket).await;
+        update_post(&post, &browser, &mut conn, media_store.as_ref()).await;
     }
 
     log::info!("Done generating preview images.");
@@ -54,7 +54,7 @@ async fn update_post(
     post: &Post,
     browser: &Browser,
     conn: &mut PgPooledConnection,
-    bucket: &Bucket,
+    media_store: &dyn MediaStore,
 ) {
     if post.user_id.is_none() {
         log::warn!("Post {} has no user_id, skipping.", post.id);
@@ -83,8 +83,8 @@ async fn update_post(
                         uuid,
                         filename
                     );
-                    let upload_status = bucket
-                        .put_object(&minio_path, screenshot.as_slice())
+                    let upload_status = media_store
+                        .put(&minio_path, screenshot.as_slice(), "image/png")
                         .await
                         .map_err(|e| {
                             log::warn!("Failed to upload screenshot for link {}: {}", url, e);
@@ -102,6 +102,8 @@ async fn update_post(
                             description: None,
                             generated: true,
                             visibility: Visibility::GlobalPublic.to_string_visibility(),
+                            size: screenshot.len() as i64,
+                            blob_id: None,
                         })
                         .get_result::<models::Media>(conn)
                         .unwrap();
@@ -0,0 +1,209 @@
+extern crate jonline;
+extern crate rocket;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine as _};
+use jonline::oidc::{generate_oidc_token, oidc_code_challenge};
+use jonline::{env_var, init_bin_logging};
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use rocket::form::{Form, FromForm};
+use rocket::http::{ContentType, Status};
+use rocket::response::Redirect;
+use rocket::{routes, State};
+use serde_json::json;
+
+/// A local OpenID Connect issuer for testing `OidcProvider` logins. Every authorization request is
+/// approved immediately, as the user named by its `login_hint` (or `mock-user`), whose verified
+/// email is `{user}@example.com`. PKCE and redirect URIs are checked; client secrets aren't.
+///
+/// Usage: `MOCK_OIDC_PORT=9998 cargo run --bin mock_oidc_issuer`, then configure a provider with
+/// issuer `http://localhost:9998` and any `client_id`, and run the server with
+/// `OIDC_REDIRECT_BASE_URL=http://localhost:8000`. Set `MOCK_OIDC_ISSUER` if it's reachable at
+/// another URL.
+#[rocket::main]
+async fn main() {
+    init_bin_logging();
+    let port = env_var("MOCK_OIDC_PORT")
+        .and_then(|p| p.parse::<u16>().ok())
+        .unwrap_or(9998);
+    let issuer = env_var("MOCK_OIDC_ISSUER").unwrap_or_else(|| format!("http://localhost:{}", port));
+    log::info!("Starting mock OIDC issuer {} on port {}...", issuer, port);
+
+    let rng = SystemRandom::new();
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+        .expect("Failed to generate signing key");
+    let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref())
+        .expect("Failed to load signing key");
+    let figment = rocket::Config::figment()
+        .merge(("port", port))
+        .merge(("address", "0.0.0.0"));
+    let _ = rocket::custom(figment)
+        .manage(MockIssuer {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            key_pair,
+            codes: Mutex::new(HashMap::new()),
+        })
+        .mount("/", routes![configuration, jwks, authorize, token])
+        .launch()
+        .await;
+}
+
+const KEY_ID: &str = "mock-key";
+
+struct MockIssuer {
+    issuer: String,
+    key_pair: EcdsaKeyPair,
+    codes: Mutex<HashMap<String, PendingCode>>,
+}
+
+struct PendingCode {
+    subject: String,
+    client_id: String,
+    redirect_uri: String,
+    nonce: Option<String>,
+    code_challenge: String,
+}
+
+fn json_response(value: serde_json::Value) -> (ContentType, String) {
+    (ContentType::JSON, value.to_string())
+}
+
+#[rocket::get("/.well-known/openid-configuration")]
+fn configuration(mock: &State<MockIssuer>) -> (ContentType, String) {
+    json_response(json!({
+        "issuer": mock.issuer,
+        "authorization_endpoint": format!("{}/authorize", mock.issuer),
+        "token_endpoint": format!("{}/token", mock.issuer),
+        "jwks_uri": format!("{}/jwks", mock.issuer),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["ES256"],
+        "code_challenge_methods_supported": ["S256"],
+    }))
+}
+
+#[rocket::get("/jwks")]
+fn jwks(mock: &State<MockIssuer>) -> (ContentType, String) {
+    // An uncompressed P-256 point: 0x04, x, y.
+    let point = mock.key_pair.public_key().as_ref();
+    json_response(json!({"keys": [{
+        "kty": "EC",
+        "crv": "P-256",
+        "kid": KEY_ID,
+        "use": "sig",
+        "alg": "ES256",
+        "x": BASE64_URL.encode(&point[1..33]),
+        "y": BASE64_URL.encode(&point[33..65]),
+    }]}))
+}
+
+#[rocket::get(
+    "/authorize?<client_id>&<redirect_uri>&<state>&<nonce>&<code_challenge>&<code_challenge_method>&<login_hint>"
+)]
+fn authorize(
+    client_id: String,
+    redirect_uri: String,
+    state: Option<String>,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+    login_hint: Option<String>,
+    mock: &State<MockIssuer>,
+) -> Result<Redirect, Status> {
+    let code_challenge = match (code_challenge, code_challenge_method.as_deref()) {
+        (Some(code_challenge), Some("S256")) => code_challenge,
+        _ => return Err(Status::BadRequest),
+    };
+    let code = generate_oidc_token();
+    let subject = login_hint
+        .filter(|hint| !hint.is_empty())
+        .unwrap_or_else(|| "mock-user".to_string());
+    log::info!("Authorizing {} for client {}", subject, client_id);
+    mock.codes.lock().unwrap().insert(
+        code.to_owned(),
+        PendingCode {
+            subject,
+            client_id,
+            redirect_uri: redirect_uri.to_owned(),
+            nonce,
+            code_challenge,
+        },
+    );
+    let mut params = vec![("code", code)];
+    if let Some(state) = state {
+        params.push(("state", state));
+    }
+    let url = reqwest::Url::parse_with_params(&redirect_uri, &params).map_err(|_| Status::BadRequest)?;
+    Ok(Redirect::to(url.to_string()))
+}
+
+#[derive(FromForm)]
+struct TokenRequest {
+    grant_type: String,
+    code: String,
+    redirect_uri: String,
+    client_id: String,
+    code_verifier: String,
+}
+
+#[rocket::post("/token", data = "<request>")]
+fn token(request: Form<TokenRequest>, mock: &State<MockIssuer>) -> (Status, (ContentType, String)) {
+    let invalid_grant = |description: &str| {
+        log::warn!("Rejected token request: {}", description);
+        (
+            Status::BadRequest,
+            json_response(json!({"error": "invalid_grant", "error_description": description})),
+        )
+    };
+    let pending = match mock.codes.lock().unwrap().remove(&request.code) {
+        Some(pending) => pending,
+        None => return invalid_grant("unknown or used code"),
+    };
+    if request.grant_type != "authorization_code" {
+        return invalid_grant("unsupported grant_type");
+    }
+    if request.client_id != pending.client_id || request.redirect_uri != pending.redirect_uri {
+        return invalid_grant("client_id or redirect_uri mismatch");
+    }
+    if oidc_code_challenge(&request.code_verifier) != pending.code_challenge {
+        return invalid_grant("code_verifier mismatch");
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let header = json!({"alg": "ES256", "typ": "JWT", "kid": KEY_ID});
+    let claims = json!({
+        "iss": mock.issuer,
+        "sub": pending.subject,
+        "aud": pending.client_id,
+        "iat": now,
+        "exp": now + 300,
+        "nonce": pending.nonce,
+        "email": format!("{}@example.com", pending.subject),
+        "email_verified": true,
+        "preferred_username": pending.subject,
+        "name": pending.subject,
+    });
+    let message = format!(
+        "{}.{}",
+        BASE64_URL.encode(header.to_string()),
+        BASE64_URL.encode(claims.to_string())
+    );
+    let signature = mock
+        .key_pair
+        .sign(&SystemRandom::new(), message.as_bytes())
+        .expect("Failed to sign ID token");
+    let id_token = format!("{}.{}", message, BASE64_URL.encode(signature.as_ref()));
+    (
+        Status::Ok,
+        json_response(json!({
+            "access_token": generate_oidc_token(),
+            "token_type": "Bearer",
+            "expires_in": 300,
+            "id_token": id_token,
+        })),
+    )
+}
@@ -6,6 +6,8 @@ use tonic::{Code, Request, Response, Status};
 
 use crate::auth;
 use crate::db_connection::*;
+use crate::media_store::MediaStore;
+use crate::messaging::Messenger;
 use crate::rpcs;
 
 use futures::Stream;
@@ -15,14 +17,16 @@ use tokio_stream::{wrappers::ReceiverStream, StreamExt};
 
 pub struct JonLineImpl {
     pub pool: Arc<PgPool>,
-    pub bucket: Arc<s3::Bucket>,
+    pub media_store: Arc<dyn MediaStore>,
+    pub messenger: Arc<dyn Messenger>,
 }
 
 impl Clone for JonLineImpl {
     fn clone(&self) -> Self {
         JonLineImpl {
             pool: self.pool.clone(),
-            bucket: self.bucket.clone(),
+            media_store: self.media_store.clone(),
+            messenger: self.messenger.clone(),
         }
     }
 }
@@ -44,7 +48,9 @@ impl Jonline for JonLineImpl {
         request: Request<CreateAccountRequest>,
     ) -> Result<Response<RefreshTokenResponse>, Status> {
         let mut conn = get_connection(&self.pool)?;
-        rpcs::create_account(request.into_inner(), &mut conn).map(Response::new)
+        let metadata = auth::SessionMetadata::from_request(&request);
+        rpcs::create_account(request.into_inner(), metadata, self.messenger.as_ref(), &mut conn)
+            .map(Response::new)
     }
 
     async fn login(
@@ -63,47 +69,223 @@ impl Jonline for JonLineImpl {
         rpcs::access_token(request, &mut conn)
     }
 
-    async fn get_current_user(&self, request: Request<()>) -> Result<Response<User>, Status> {
+    async fn get_sessions(&self, request: Request<()>) -> Result<Response<GetSessionsResponse>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let refresh_token_id = auth::get_auth_refresh_token_id(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::get_sessions(user, refresh_token_id, &mut conn).map(Response::new)
+    }
+
+    async fn revoke_session(&self, request: Request<Session>) -> Result<Response<()>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::revoke_session(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn revoke_other_sessions(&self, request: Request<()>) -> Result<Response<()>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let refresh_token_id = auth::get_auth_refresh_token_id(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::revoke_other_sessions(user, refresh_token_id, &mut conn).map(Response::new)
+    }
+
+    async fn change_password(
+        &self,
+        request: Request<ChangePasswordRequest>,
+    ) -> Result<Response<()>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let refresh_token_id = auth::get_auth_refresh_token_id(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::change_password(request.into_inner(), user, refresh_token_id, &mut conn)
+            .map(Response::new)
+    }
+
+    async fn request_password_reset(
+        &self,
+        request: Request<RequestPasswordResetRequest>,
+    ) -> Result<Response<()>, Status> {
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::request_password_reset(request.into_inner(), self.messenger.clone(), &mut conn)
+            .map(Response::new)
+    }
+
+    async fn reset_password(
+        &self,
+        request: Request<ResetPasswordRequest>,
+    ) -> Result<Response<()>, Status> {
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::reset_password(request.into_inner(), &mut conn).map(Response::new)
+    }
+
+    async fn force_password_reset(&self, request: Request<User>) -> Result<Response<()>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::force_password_reset(request.into_inner(), user, self.messenger.clone(), &mut conn)
+            .map(Response::new)
+    }
+
+    async fn get_account_lockouts(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<GetAccountLockoutsResponse>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::get_account_lockouts(user, &mut conn).map(Response::new)
+    }
+
+    async fn unlock_account(&self, request: Request<User>) -> Result<Response<()>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::unlock_account(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn complete_oidc_login(
+        &self,
+        request: Request<CompleteOidcLoginRequest>,
+    ) -> Result<Response<RefreshTokenResponse>, Status> {
+        let mut conn = get_connection(&self.pool)?;
+        let metadata = auth::SessionMetadata::from_request(&request);
+        rpcs::complete_oidc_login(request.into_inner(), metadata, &mut conn).map(Response::new)
+    }
+
+    async fn get_linked_identities(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<GetLinkedIdentitiesResponse>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::get_linked_identities(user, &mut conn).map(Response::new)
+    }
+
+    async fn delete_linked_identity(
+        &self,
+        request: Request<LinkedIdentity>,
+    ) -> Result<Response<()>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::delete_linked_identity(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn verify_two_factor(
+        &self,
+        request: Request<VerifyTwoFactorRequest>,
+    ) -> Result<Response<RefreshTokenResponse>, Status> {
         let mut conn = get_connection(&self.pool)?;
-        match auth::get_auth_user(&request, &mut conn) {
+        let metadata = auth::SessionMetadata::from_request(&request);
+        rpcs::verify_two_factor(request.into_inner(), metadata, &mut conn).map(Response::new)
+    }
+
+    async fn enroll_totp(
+        &self,
+        request: Request<EnrollTotpRequest>,
+    ) -> Result<Response<TotpEnrollment>, Status> {
+        let user = auth::get_auth_user(&request).ok();
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::enroll_totp(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn confirm_totp(
+        &self,
+        request: Request<ConfirmTotpRequest>,
+    ) -> Result<Response<TotpConfirmation>, Status> {
+        let user = auth::get_auth_user(&request).ok();
+        let mut conn = get_connection(&self.pool)?;
+        let metadata = auth::SessionMetadata::from_request(&request);
+        rpcs::confirm_totp(request.into_inner(), user, metadata, &mut conn).map(Response::new)
+    }
+
+    async fn disable_totp(&self, request: Request<DisableTotpRequest>) -> Result<Response<()>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::disable_totp(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn create_api_token(&self, request: Request<ApiToken>) -> Result<Response<ApiToken>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::create_api_token(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn get_api_tokens(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<GetApiTokensResponse>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::get_api_tokens(user, &mut conn).map(Response::new)
+    }
+
+    async fn delete_api_token(&self, request: Request<ApiToken>) -> Result<Response<()>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::delete_api_token(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn get_current_user(&self, request: Request<()>) -> Result<Response<User>, Status> {
+        match auth::get_viewing_auth_user(&request) {
             Err(e) => Err(e),
             Ok(user) => rpcs::get_current_user(user),
         }
     }
 
     async fn update_user(&self, request: Request<User>) -> Result<Response<User>, Status> {
+        let user = auth::get_auth_user(&request)?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
-        rpcs::update_user(request.into_inner(), user, &mut conn).map(Response::new)
+        rpcs::update_user(request.into_inner(), user, self.messenger.as_ref(), &mut conn).map(Response::new)
+    }
+
+    async fn send_contact_method_verification(
+        &self,
+        request: Request<SendContactMethodVerificationRequest>,
+    ) -> Result<Response<ContactMethodVerification>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::send_contact_method_verification(
+            request.into_inner(),
+            user,
+            self.messenger.as_ref(),
+            &mut conn,
+        )
+        .await
+        .map(Response::new)
+    }
+
+    async fn verify_contact_method(
+        &self,
+        request: Request<VerifyContactMethodRequest>,
+    ) -> Result<Response<User>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::verify_contact_method(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
     async fn delete_user(&self, request: Request<User>) -> Result<Response<()>, Status> {
+        let user = auth::get_auth_user(&request)?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::delete_user(request.into_inner(), user, &mut conn).map(Response::new)
     }
     async fn get_users(
         &self,
         request: Request<GetUsersRequest>,
     ) -> Result<Response<GetUsersResponse>, Status> {
+        let user: Option<models::User> = auth::get_viewing_auth_user(&request).ok();
         let mut conn = get_connection(&self.pool)?;
-        let user: Option<models::User> = auth::get_auth_user(&request, &mut conn).ok();
         rpcs::get_users(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
     async fn create_follow(&self, request: Request<Follow>) -> Result<Response<Follow>, Status> {
+        let user = auth::get_scoped_auth_user(&request, &[Permission::FollowUsers])?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::create_follow(request.into_inner(), user, &mut conn).map(Response::new)
     }
     async fn update_follow(&self, request: Request<Follow>) -> Result<Response<Follow>, Status> {
+        let user = auth::get_scoped_auth_user(&request, &[Permission::FollowUsers])?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::update_follow(request.into_inner(), user, &mut conn).map(Response::new)
     }
     async fn delete_follow(&self, request: Request<Follow>) -> Result<Response<()>, Status> {
+        let user = auth::get_scoped_auth_user(&request, &[Permission::FollowUsers])?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::delete_follow(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
@@ -111,40 +293,102 @@ impl Jonline for JonLineImpl {
         &self,
         request: Request<GetMediaRequest>,
     ) -> Result<Response<GetMediaResponse>, Status> {
+        let user = auth::get_viewing_auth_user(&request).ok();
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn).ok();
         rpcs::get_media(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
     async fn delete_media(&self, request: Request<Media>) -> Result<Response<()>, Status> {
+        let user = auth::get_scoped_auth_user(&request, &[Permission::CreateMedia])?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::delete_media(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
+    async fn update_media(&self, request: Request<Media>) -> Result<Response<Media>, Status> {
+        let user = auth::get_scoped_auth_user(&request, &[Permission::CreateMedia])?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::update_media(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn get_media_albums(
+        &self,
+        request: Request<GetMediaAlbumsRequest>,
+    ) -> Result<Response<GetMediaAlbumsResponse>, Status> {
+        let user = auth::get_viewing_auth_user(&request).ok();
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::get_media_albums(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn create_media_album(
+        &self,
+        request: Request<MediaAlbum>,
+    ) -> Result<Response<MediaAlbum>, Status> {
+        let user = auth::get_scoped_auth_user(&request, &[Permission::CreateMedia])?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::create_media_album(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn update_media_album(
+        &self,
+        request: Request<MediaAlbum>,
+    ) -> Result<Response<MediaAlbum>, Status> {
+        let user = auth::get_scoped_auth_user(&request, &[Permission::CreateMedia])?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::update_media_album(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn delete_media_album(&self, request: Request<MediaAlbum>) -> Result<Response<()>, Status> {
+        let user = auth::get_scoped_auth_user(&request, &[Permission::CreateMedia])?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::delete_media_album(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn get_media_quota(&self, request: Request<()>) -> Result<Response<MediaQuota>, Status> {
+        let user = auth::get_viewing_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::get_media_quota(user, &mut conn).map(Response::new)
+    }
+
+    async fn get_media_url(
+        &self,
+        request: Request<GetMediaUrlRequest>,
+    ) -> Result<Response<MediaUrl>, Status> {
+        let user = auth::get_viewing_auth_user(&request).ok();
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::get_media_url(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
     async fn get_groups(
         &self,
         request: Request<GetGroupsRequest>,
     ) -> Result<Response<GetGroupsResponse>, Status> {
+        let user: Option<models::User> = auth::get_viewing_auth_user(&request).ok();
         let mut conn = get_connection(&self.pool)?;
-        let user: Option<models::User> = auth::get_auth_user(&request, &mut conn).ok();
         rpcs::get_groups(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
     async fn create_group(&self, request: Request<Group>) -> Result<Response<Group>, Status> {
+        let user = auth::get_scoped_auth_user(&request, &[Permission::CreateGroups])?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::create_group(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
     async fn update_group(&self, request: Request<Group>) -> Result<Response<Group>, Status> {
+        let user = auth::get_group_scoped_auth_user(
+            &request,
+            &[Permission::ModerateGroups],
+            &request.get_ref().id,
+        )?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::update_group(request.into_inner(), user, &mut conn).map(Response::new)
     }
     async fn delete_group(&self, request: Request<Group>) -> Result<Response<()>, Status> {
+        let user = auth::get_group_scoped_auth_user(
+            &request,
+            &[Permission::ModerateGroups],
+            &request.get_ref().id,
+        )?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::delete_group(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
@@ -152,32 +396,44 @@ impl Jonline for JonLineImpl {
         &self,
         request: Request<Membership>,
     ) -> Result<Response<Membership>, Status> {
+        let user = auth::get_group_scoped_auth_user(
+            &request,
+            &[Permission::JoinGroups],
+            &request.get_ref().group_id,
+        )?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::create_membership(request.into_inner(), user, &mut conn).map(Response::new)
     }
     async fn update_membership(
         &self,
         request: Request<Membership>,
     ) -> Result<Response<Membership>, Status> {
+        let user = auth::get_group_scoped_auth_user(
+            &request,
+            &[Permission::ModerateGroups],
+            &request.get_ref().group_id,
+        )?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::update_membership(request.into_inner(), user, &mut conn).map(Response::new)
     }
     async fn delete_membership(
         &self,
         request: Request<Membership>,
     ) -> Result<Response<()>, Status> {
+        let user = auth::get_group_scoped_auth_user(
+            &request,
+            &[Permission::JoinGroups],
+            &request.get_ref().group_id,
+        )?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::delete_membership(request.into_inner(), user, &mut conn).map(Response::new)
     }
     async fn get_members(
         &self,
         request: Request<GetMembersRequest>,
     ) -> Result<Response<GetMembersResponse>, Status> {
+        let user = auth::get_viewing_auth_user(&request)?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::get_members(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
@@ -185,8 +441,8 @@ impl Jonline for JonLineImpl {
         &self,
         request: Request<Post>,
     ) -> Result<Response<Post>, Status> {
+        let user = auth::get_scoped_auth_user(&request, &[Permission::CreatePosts])?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::create_post(request, user, &mut conn)
     }
     async fn update_post(&self, _request: Request<Post>) -> Result<Response<Post>, Status> {
@@ -206,21 +462,33 @@ impl Jonline for JonLineImpl {
         &self,
         request: Request<GroupPost>,
     ) -> Result<Response<GroupPost>, Status> {
+        let user = auth::get_group_scoped_auth_user(
+            &request,
+            &[Permission::CreatePosts],
+            &request.get_ref().group_id,
+        )?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::create_group_post(request.into_inner(), user, &mut conn).map(Response::new)
     }
     async fn update_group_post(
         &self,
         request: Request<GroupPost>,
     ) -> Result<Response<GroupPost>, Status> {
+        let user = auth::get_group_scoped_auth_user(
+            &request,
+            &[Permission::ModeratePosts],
+            &request.get_ref().group_id,
+        )?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::update_group_post(request.into_inner(), user, &mut conn).map(Response::new)
     }
     async fn delete_group_post(&self, request: Request<GroupPost>) -> Result<Response<()>, Status> {
+        let user = auth::get_group_scoped_auth_user(
+            &request,
+            &[Permission::CreatePosts, Permission::ModeratePosts],
+            &request.get_ref().group_id,
+        )?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::delete_group_post(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
@@ -228,8 +496,8 @@ impl Jonline for JonLineImpl {
         &self,
         request: Request<GetGroupPostsRequest>,
     ) -> Result<Response<GetGroupPostsResponse>, Status> {
+        let user: Option<models::User> = auth::get_viewing_auth_user(&request).ok();
         let mut conn = get_connection(&self.pool)?;
-        let user: Option<models::User> = auth::get_auth_user(&request, &mut conn).ok();
         rpcs::get_group_posts(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
@@ -237,8 +505,8 @@ impl Jonline for JonLineImpl {
         &self,
         request: Request<GetPostsRequest>,
     ) -> Result<Response<GetPostsResponse>, Status> {
+        let user: Option<models::User> = auth::get_viewing_auth_user(&request).ok();
         let mut conn = get_connection(&self.pool)?;
-        let user: Option<models::User> = auth::get_auth_user(&request, &mut conn).ok();
         rpcs::get_posts(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
@@ -282,8 +550,8 @@ impl Jonline for JonLineImpl {
     }
 
     async fn create_event(&self, request: Request<Event>) -> Result<Response<Event>, Status> {
+        let user = auth::get_scoped_auth_user(&request, &[Permission::CreateEvents])?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::create_event(request, user, &mut conn)
     }
 
@@ -291,8 +559,8 @@ impl Jonline for JonLineImpl {
         &self,
         request: Request<GetEventsRequest>,
     ) -> Result<Response<GetEventsResponse>, Status> {
+        let user: Option<models::User> = auth::get_viewing_auth_user(&request).ok();
         let mut conn = get_connection(&self.pool)?;
-        let user: Option<models::User> = auth::get_auth_user(&request, &mut conn).ok();
         rpcs::get_events(request.into_inner(), user, &mut conn).map(Response::new)
     }
 
@@ -308,14 +576,38 @@ impl Jonline for JonLineImpl {
         &self,
         request: Request<ServerConfiguration>,
     ) -> Result<Response<ServerConfiguration>, Status> {
+        let user = auth::get_auth_user(&request)?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::configure_server(request.into_inner(), user, &mut conn)
     }
 
+    async fn create_invite_code(
+        &self,
+        request: Request<InviteCode>,
+    ) -> Result<Response<InviteCode>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::create_invite_code(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn get_invite_codes(
+        &self,
+        request: Request<GetInviteCodesRequest>,
+    ) -> Result<Response<GetInviteCodesResponse>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::get_invite_codes(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
+    async fn delete_invite_code(&self, request: Request<InviteCode>) -> Result<Response<()>, Status> {
+        let user = auth::get_auth_user(&request)?;
+        let mut conn = get_connection(&self.pool)?;
+        rpcs::delete_invite_code(request.into_inner(), user, &mut conn).map(Response::new)
+    }
+
     async fn reset_data(&self, request: Request<()>) -> Result<Response<()>, Status> {
+        let user = auth::get_auth_user(&request)?;
         let mut conn = get_connection(&self.pool)?;
-        let user = auth::get_auth_user(&request, &mut conn)?;
         rpcs::reset_data(user, &mut conn).map(Response::new)
     }
 }
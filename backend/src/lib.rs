@@ -2,6 +2,7 @@ use std::env;
 
 #[macro_use]
 extern crate diesel;
+extern crate async_trait;
 extern crate bcrypt;
 extern crate bs58;
 extern crate uuid;
@@ -18,21 +19,39 @@ extern crate lazy_static;
 extern crate env_logger;
 extern crate log;
 extern crate tokio_stream;
+extern crate tokio_util;
 extern crate s3;
 extern crate bytes;
 extern crate awscreds;
 extern crate awsregion;
 extern crate tempfile;
 extern crate percent_encoding;
+extern crate base64;
+extern crate chrono;
+extern crate httpdate;
+extern crate rand;
+extern crate reqwest;
+extern crate rsa;
+extern crate sha2;
+extern crate image;
+extern crate exif;
+extern crate infer;
+extern crate lettre;
 
+pub mod activitypub;
 pub mod auth;
 pub mod db_connection;
 pub mod minio_connection;
 pub mod jonline;
 pub mod logic;
 pub mod marshaling;
+pub mod media_processing;
+pub mod media_store;
+pub mod messaging;
 pub mod models;
+pub mod oidc;
 pub mod protos;
+pub mod rate_limiting;
 pub mod rpcs;
 pub mod schema;
 pub mod servers;
@@ -0,0 +1,98 @@
+use std::time::{Duration, SystemTime};
+
+use diesel::*;
+
+use crate::db_connection::PgPooledConnection;
+use crate::models;
+use crate::schema::account_lockouts;
+
+/// Consecutive failed logins before an account is locked.
+pub const LOCKOUT_THRESHOLD: i32 = 10;
+/// How long the first lockout lasts. Each further failure after it doubles the lockout.
+pub const LOCKOUT_DURATION: Duration = Duration::from_secs(15 * 60);
+pub const MAX_LOCKOUT_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long an account with `failed_attempts` consecutive failed logins is locked for, if at all.
+pub fn lockout_duration(failed_attempts: i32) -> Option<Duration> {
+    if failed_attempts < LOCKOUT_THRESHOLD {
+        return None;
+    }
+    let exponent = (failed_attempts - LOCKOUT_THRESHOLD).min(16) as u32;
+    Some((LOCKOUT_DURATION * 2u32.pow(exponent)).min(MAX_LOCKOUT_DURATION))
+}
+
+/// When the user's lockout ends, if they're locked out.
+pub fn account_locked_until(
+    user_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<Option<SystemTime>, diesel::result::Error> {
+    account_lockouts::table
+        .find(user_id)
+        .select(account_lockouts::locked_until)
+        .first::<Option<SystemTime>>(conn)
+        .optional()
+        .map(|locked_until| locked_until.flatten().filter(|until| *until > SystemTime::now()))
+}
+
+/// Counts a failed login for the user, locking their account after `LOCKOUT_THRESHOLD` in a row.
+/// Returns when the lockout ends, if this failure caused one.
+pub fn record_failed_login(
+    user_id: i64,
+    ip_address: Option<String>,
+    conn: &mut PgPooledConnection,
+) -> Result<Option<SystemTime>, diesel::result::Error> {
+    let now = SystemTime::now();
+    let lockout = insert_into(account_lockouts::table)
+        .values((
+            account_lockouts::user_id.eq(user_id),
+            account_lockouts::failed_attempts.eq(1),
+            account_lockouts::last_failed_at.eq(now),
+            account_lockouts::last_failed_ip.eq(&ip_address),
+        ))
+        .on_conflict(account_lockouts::user_id)
+        .do_update()
+        .set((
+            account_lockouts::failed_attempts.eq(account_lockouts::failed_attempts + 1),
+            account_lockouts::last_failed_at.eq(now),
+            account_lockouts::last_failed_ip.eq(&ip_address),
+        ))
+        .get_result::<models::AccountLockout>(conn)?;
+    let locked_until = match lockout_duration(lockout.failed_attempts) {
+        Some(duration) => now + duration,
+        None => return Ok(None),
+    };
+    update(account_lockouts::table.find(user_id))
+        .set(account_lockouts::locked_until.eq(locked_until))
+        .execute(conn)?;
+    log::warn!(
+        "Locked user_id={} for {}s after {} failed logins",
+        user_id,
+        locked_until.duration_since(now).unwrap_or_default().as_secs(),
+        lockout.failed_attempts
+    );
+    Ok(Some(locked_until))
+}
+
+/// Clears the user's failed logins and any lockout (after logging in, or an admin unlocking them).
+/// Returns whether there were any.
+pub fn clear_failed_logins(
+    user_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<bool, diesel::result::Error> {
+    delete(account_lockouts::table.find(user_id))
+        .execute(conn)
+        .map(|deleted| deleted > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lockouts_grow_exponentially() {
+        assert_eq!(lockout_duration(LOCKOUT_THRESHOLD - 1), None);
+        assert_eq!(lockout_duration(LOCKOUT_THRESHOLD), Some(LOCKOUT_DURATION));
+        assert_eq!(lockout_duration(LOCKOUT_THRESHOLD + 2), Some(LOCKOUT_DURATION * 4));
+        assert_eq!(lockout_duration(LOCKOUT_THRESHOLD + 100), Some(MAX_LOCKOUT_DURATION));
+    }
+}
@@ -0,0 +1,75 @@
+use diesel::*;
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::db_connection::PgPooledConnection;
+use crate::models;
+use crate::protos::*;
+use crate::schema::invite_codes;
+
+/// Characters in generated invite codes; no `0`/`O` or `1`/`I`, so they're easy to read aloud.
+const INVITE_CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const INVITE_CODE_LENGTH: usize = 12;
+
+/// Whether the server's `authentication_features` include `feature`.
+pub fn has_authentication_feature(
+    configuration: &ServerConfiguration,
+    feature: AuthenticationFeature,
+) -> bool {
+    configuration
+        .authentication_features
+        .contains(&(feature as i32))
+}
+
+/// A random, human-friendly invite code (60 bits of entropy).
+pub fn generate_invite_code() -> String {
+    let mut randoms = [0u8; INVITE_CODE_LENGTH];
+    SystemRandom::new()
+        .fill(&mut randoms)
+        .expect("Failed to generate invite code");
+    randoms
+        .iter()
+        .map(|b| INVITE_CODE_ALPHABET[(*b as usize) % INVITE_CODE_ALPHABET.len()] as char)
+        .collect()
+}
+
+/// Invite codes are case-insensitive, and stored in upper case.
+pub fn normalize_invite_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+/// Atomically counts a use of the invite code, if it exists, hasn't expired and isn't used up.
+/// Returns `NotFound` otherwise. Run it in the same transaction as creating the account, so
+/// failing to create the account doesn't use up the code.
+pub fn use_invite_code(
+    code: &str,
+    conn: &mut PgPooledConnection,
+) -> Result<models::InviteCode, diesel::result::Error> {
+    update(invite_codes::table)
+        .filter(invite_codes::code.eq(normalize_invite_code(code)))
+        .filter(
+            invite_codes::expires_at
+                .is_null()
+                .or(invite_codes::expires_at.gt(diesel::dsl::now.nullable())),
+        )
+        .filter(
+            invite_codes::max_uses
+                .is_null()
+                .or(invite_codes::use_count.nullable().lt(invite_codes::max_uses)),
+        )
+        .set(invite_codes::use_count.eq(invite_codes::use_count + 1))
+        .get_result::<models::InviteCode>(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_invite_codes_are_normalized() {
+        let code = generate_invite_code();
+        assert_eq!(code.len(), INVITE_CODE_LENGTH);
+        assert_eq!(normalize_invite_code(&code), code);
+        assert_ne!(generate_invite_code(), code);
+        assert_eq!(normalize_invite_code(" welcome-2023 "), "WELCOME-2023");
+    }
+}
@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use ring::constant_time::verify_slices_are_equal;
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::{Digest, Sha256};
+
+use crate::models;
+use crate::protos::*;
+
+/// How long verification codes work for.
+pub const VERIFICATION_CODE_TTL: Duration = Duration::from_secs(15 * 60);
+/// How long users must wait before sending another code to the same contact method.
+pub const VERIFICATION_RESEND_INTERVAL: Duration = Duration::from_secs(60);
+/// Wrong codes allowed before a new code must be sent.
+pub const MAX_VERIFICATION_ATTEMPTS: i32 = 5;
+
+/// A random 6-digit code.
+pub fn generate_verification_code() -> String {
+    let sr = SystemRandom::new();
+    loop {
+        let mut randoms = [0u8; 4];
+        sr.fill(&mut randoms).expect("Failed to generate verification code");
+        let value = u32::from_le_bytes(randoms);
+        // Reject the top (partial) range so all codes are equally likely.
+        if value < u32::MAX - u32::MAX % 1_000_000 {
+            return format!("{:06}", value % 1_000_000);
+        }
+    }
+}
+
+pub fn hash_verification_code(code: &str) -> String {
+    format!("{:x}", Sha256::digest(code.trim().as_bytes()))
+}
+
+pub fn verification_code_matches(code: &str, code_hash: &str) -> bool {
+    verify_slices_are_equal(hash_verification_code(code).as_bytes(), code_hash.as_bytes()).is_ok()
+}
+
+/// The address or number in a contact method's value, without any `mailto:` or `tel:` prefix.
+pub fn contact_method_address(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix("mailto:")
+        .or_else(|| value.strip_prefix("tel:"))
+        .unwrap_or(value)
+}
+
+/// The user's stored email or phone.
+pub fn get_contact_method(
+    user: &models::User,
+    contact_method: ContactMethodType,
+) -> Option<ContactMethod> {
+    let json = match contact_method {
+        ContactMethodType::Email => user.email.as_ref(),
+        ContactMethodType::Phone => user.phone.as_ref(),
+        ContactMethodType::Unknown => None,
+    };
+    json.and_then(|json| serde_json::from_value(json.to_owned()).ok())
+}
+
+/// Prepares a contact method from a request for storage. Only the server decides whether a
+/// contact method is `supported_by_server` and `verified`; verification is kept only while the
+/// value is unchanged. Empty values are removed.
+pub fn sanitize_contact_method(
+    requested: Option<ContactMethod>,
+    existing: Option<ContactMethod>,
+    supported_by_server: bool,
+) -> Option<serde_json::Value> {
+    let requested = requested?;
+    let value = requested.value.as_deref().map(str::trim).unwrap_or("");
+    if value.is_empty() {
+        return None;
+    }
+    let verified = match existing {
+        Some(existing) => existing.verified && existing.value.as_deref().map(str::trim) == Some(value),
+        None => false,
+    };
+    serde_json::to_value(ContactMethod {
+        value: Some(value.to_string()),
+        visibility: requested.visibility,
+        supported_by_server,
+        verified: verified && supported_by_server,
+    })
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact_method(value: &str, verified: bool) -> ContactMethod {
+        ContactMethod {
+            value: Some(value.to_string()),
+            visibility: Visibility::Private as i32,
+            supported_by_server: true,
+            verified,
+        }
+    }
+
+    #[test]
+    fn verification_codes_match_their_hashes() {
+        let code = generate_verification_code();
+        assert_eq!(code.len(), 6);
+        assert!(code.chars().all(|c| c.is_ascii_digit()));
+        let hash = hash_verification_code(&code);
+        assert!(verification_code_matches(&code, &hash));
+        assert!(verification_code_matches(&format!(" {} ", code), &hash));
+        assert!(!verification_code_matches("not a code", &hash));
+    }
+
+    #[test]
+    fn changing_contact_methods_resets_verification() {
+        let existing = contact_method("mailto:a@example.com", true);
+        let unchanged = sanitize_contact_method(
+            Some(contact_method("mailto:a@example.com", false)),
+            Some(existing.to_owned()),
+            true,
+        )
+        .unwrap();
+        assert_eq!(unchanged["verified"], true);
+
+        let changed = sanitize_contact_method(
+            Some(contact_method("mailto:b@example.com", true)),
+            Some(existing.to_owned()),
+            true,
+        )
+        .unwrap();
+        assert_eq!(changed["verified"], false);
+
+        assert!(sanitize_contact_method(Some(contact_method(" ", true)), Some(existing), true).is_none());
+        assert_eq!(contact_method_address("mailto:a@example.com"), "a@example.com");
+        assert_eq!(contact_method_address("tel:+15555555555"), "+15555555555");
+    }
+}
@@ -0,0 +1,133 @@
+use diesel::*;
+
+use crate::db_connection::PgPooledConnection;
+use crate::models;
+use crate::protos::*;
+use crate::schema::media;
+
+use super::HasPermission;
+
+/// The largest media item a user may upload when their `MediaLimits` don't say otherwise.
+pub const DEFAULT_MAX_MEDIA_FILE_SIZE: u64 = 250 * 1024 * 1024;
+
+/// Why an upload was refused.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MediaUploadRejection {
+    PermissionDenied,
+    FileTooLarge,
+    QuotaExceeded,
+    UnsupportedContentType,
+}
+
+/// A user's current media usage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MediaUsage {
+    pub bytes: u64,
+    pub count: u32,
+}
+
+/// The `MediaLimits` that apply to `user`: those of the last tier in `media_settings.limits`
+/// whose permission they have. If none apply, only the default file size limit applies.
+pub fn media_limits(user: &models::User, configuration: &ServerConfiguration) -> MediaLimits {
+    configuration
+        .media_settings
+        .as_ref()
+        .map(|settings| settings.limits.to_owned())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|limits| match Permission::from_i32(limits.permission) {
+            Some(Permission::Unknown) => true,
+            Some(permission) => user.has_permission(permission),
+            None => false,
+        })
+        .last()
+        .unwrap_or_default()
+}
+
+pub fn max_media_file_size(limits: &MediaLimits) -> u64 {
+    limits.max_file_size.unwrap_or(DEFAULT_MAX_MEDIA_FILE_SIZE)
+}
+
+/// Total size and count of the media `user_id` uploaded. Media deleted by the user, and media
+/// generated by the server (i.e. link previews), don't count.
+pub fn media_usage(
+    user_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<MediaUsage, diesel::result::Error> {
+    let sizes = media::table
+        .select(media::size)
+        .filter(media::user_id.eq(user_id))
+        .filter(media::generated.eq(false))
+        .load::<i64>(conn)?;
+    Ok(MediaUsage {
+        bytes: sizes.iter().map(|size| *size as u64).sum(),
+        count: sizes.len() as u32,
+    })
+}
+
+/// Checks whether `user` may upload media at all, given their current usage.
+pub fn validate_media_upload_start(
+    user: &models::User,
+    limits: &MediaLimits,
+    usage: &MediaUsage,
+) -> Result<(), MediaUploadRejection> {
+    if !user.has_permission(Permission::CreateMedia) {
+        return Err(MediaUploadRejection::PermissionDenied);
+    }
+    match limits.max_total_size {
+        Some(max_total_size) if usage.bytes >= max_total_size => {
+            Err(MediaUploadRejection::QuotaExceeded)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks the size of an upload against the user's limits and current usage.
+pub fn validate_media_upload_size(
+    size: u64,
+    limits: &MediaLimits,
+    usage: &MediaUsage,
+) -> Result<(), MediaUploadRejection> {
+    if size > max_media_file_size(limits) {
+        return Err(MediaUploadRejection::FileTooLarge);
+    }
+    match limits.max_total_size {
+        Some(max_total_size) if usage.bytes + size > max_total_size => {
+            Err(MediaUploadRejection::QuotaExceeded)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Determines the content type to store for an upload from its first bytes, falling back to
+/// the client-declared `Content-Type` only when the data isn't recognizable and the user's
+/// limits allow any content type.
+pub fn validate_media_content_type(
+    head: &[u8],
+    declared_content_type: &str,
+    limits: &MediaLimits,
+) -> Result<String, MediaUploadRejection> {
+    let content_type = match infer::get(head) {
+        Some(detected) => detected.mime_type().to_string(),
+        None if limits.allowed_content_types.is_empty() => declared_content_type.to_string(),
+        None => return Err(MediaUploadRejection::UnsupportedContentType),
+    };
+    if content_type_allowed(&content_type, &limits.allowed_content_types) {
+        Ok(content_type)
+    } else {
+        Err(MediaUploadRejection::UnsupportedContentType)
+    }
+}
+
+/// Matches a content type against patterns like `image/png` or `video/*`. Empty patterns allow anything.
+pub fn content_type_allowed(content_type: &str, allowed: &[String]) -> bool {
+    let content_type = content_type.to_ascii_lowercase();
+    allowed.is_empty()
+        || allowed.iter().any(|pattern| {
+            let pattern = pattern.trim().to_ascii_lowercase();
+            match pattern.strip_suffix("/*") {
+                Some(major) => content_type.split('/').next() == Some(major),
+                None => content_type == pattern,
+            }
+        })
+}
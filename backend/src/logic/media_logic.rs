@@ -0,0 +1,211 @@
+use diesel::*;
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::media_processing::PROCESSABLE_CONTENT_TYPES;
+use crate::models;
+use crate::protos::*;
+use crate::rpcs::validations::PASSING_MODERATIONS;
+use crate::schema::{follows, group_posts, groups, memberships, posts, users};
+
+use super::{HasPermission, Moderated};
+
+/// Whether `user` (or an anonymous viewer, for `None`) may view `media`. Owners and admins
+/// can always view their media. Otherwise the media must pass moderation and either:
+/// - be visible by its own `visibility` (as documented on `GetMediaRequest`), or
+/// - be used by a post, group avatar or user avatar that the viewer can see, and that belongs
+///   to the media's owner (an admin of the group, for group avatars).
+pub fn can_view_media(
+    media: &models::Media,
+    user: &Option<models::User>,
+    conn: &mut PgPooledConnection,
+) -> bool {
+    if let Some(user) = user {
+        if media.user_id == Some(user.id) || user.has_permission(Permission::Admin) {
+            return true;
+        }
+    }
+    if !passes(&media.moderation) {
+        return false;
+    }
+    visibility_allows(&media.visibility, media.user_id, user, conn)
+        || used_by_visible_avatar(media, user, conn)
+        || used_by_visible_group(media, user, conn)
+        || used_by_visible_post(media, user, conn)
+}
+
+/// Whether `user` (or an anonymous viewer, for `None`) may view `album`. Which of its media
+/// they can see is still up to `can_view_media`.
+pub fn can_view_media_album(
+    album: &models::MediaAlbum,
+    user: &Option<models::User>,
+    conn: &mut PgPooledConnection,
+) -> bool {
+    if let Some(user) = user {
+        if album.user_id == user.id || user.has_permission(Permission::Admin) {
+            return true;
+        }
+    }
+    passes(&album.moderation) && visibility_allows(&album.visibility, Some(album.user_id), user, conn)
+}
+
+/// Whether `media` may be served from a validly signed URL (see `media_url_logic`). The
+/// signer could see it, so only moderation still applies.
+pub fn can_view_signed_media(media: &models::Media) -> bool {
+    passes(&media.moderation)
+}
+
+/// Whether `media`'s stored data may be served to `user`. Images awaiting processing may still
+/// carry EXIF (e.g. GPS) metadata, so until it's stripped only their owners and admins get them.
+pub fn can_serve_media_data(media: &models::Media, user: &Option<models::User>) -> bool {
+    if media.processed || !PROCESSABLE_CONTENT_TYPES.contains(&media.content_type.as_str()) {
+        return true;
+    }
+    match user {
+        Some(user) => media.user_id == Some(user.id) || user.has_permission(Permission::Admin),
+        None => false,
+    }
+}
+
+fn passes(moderation: &String) -> bool {
+    moderation
+        .to_proto_moderation()
+        .map(|m| m.passes())
+        .unwrap_or(false)
+}
+
+/// Checks a post/user/media `visibility` owned by `owner_id` against the viewer.
+fn visibility_allows(
+    visibility: &String,
+    owner_id: Option<i64>,
+    user: &Option<models::User>,
+    conn: &mut PgPooledConnection,
+) -> bool {
+    match (visibility.to_proto_visibility(), user) {
+        (_, Some(user)) if owner_id == Some(user.id) => true,
+        (Some(Visibility::GlobalPublic), _) => true,
+        (Some(Visibility::ServerPublic), Some(_)) => true,
+        (Some(Visibility::Limited), Some(user)) => match owner_id {
+            Some(owner_id) => follows::table
+                .select(follows::id)
+                .filter(follows::user_id.eq(user.id))
+                .filter(follows::target_user_id.eq(owner_id))
+                .filter(follows::target_user_moderation.eq_any(PASSING_MODERATIONS))
+                .first::<i64>(conn)
+                .is_ok(),
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+fn used_by_visible_avatar(
+    media: &models::Media,
+    user: &Option<models::User>,
+    conn: &mut PgPooledConnection,
+) -> bool {
+    let owner_id = match media.user_id {
+        Some(owner_id) => owner_id,
+        None => return false,
+    };
+    users::table
+        .select(users::visibility)
+        .filter(users::id.eq(owner_id))
+        .filter(users::avatar_media_id.eq(media.id))
+        .filter(users::moderation.eq_any(PASSING_MODERATIONS))
+        .first::<String>(conn)
+        .map(|visibility| visibility_allows(&visibility, Some(owner_id), user, conn))
+        .unwrap_or(false)
+}
+
+fn used_by_visible_group(
+    media: &models::Media,
+    user: &Option<models::User>,
+    conn: &mut PgPooledConnection,
+) -> bool {
+    let owner_id = match media.user_id {
+        Some(owner_id) => owner_id,
+        None => return false,
+    };
+    let groups = groups::table
+        .select((groups::id, groups::visibility))
+        .filter(groups::avatar_media_id.eq(media.id))
+        .filter(groups::moderation.eq_any(PASSING_MODERATIONS))
+        .load::<(i64, String)>(conn)
+        .unwrap_or_default();
+    groups.iter().any(|(group_id, visibility)| {
+        is_group_admin(*group_id, owner_id, conn)
+            && (visibility_allows(visibility, None, user, conn) || is_member(*group_id, user, conn))
+    })
+}
+
+fn used_by_visible_post(
+    media: &models::Media,
+    user: &Option<models::User>,
+    conn: &mut PgPooledConnection,
+) -> bool {
+    let owner_id = match media.user_id {
+        Some(owner_id) => owner_id,
+        None => return false,
+    };
+    let posts = posts::table
+        .select((posts::id, posts::visibility))
+        .filter(posts::media.contains(vec![media.id]))
+        .filter(posts::user_id.eq(owner_id))
+        .filter(posts::moderation.eq_any(PASSING_MODERATIONS))
+        .load::<(i64, String)>(conn)
+        .unwrap_or_default();
+    posts.iter().any(|(post_id, visibility)| {
+        visibility_allows(visibility, Some(owner_id), user, conn)
+            || (visibility.to_proto_visibility() == Some(Visibility::Limited)
+                && shared_to_member_group(*post_id, user, conn))
+    })
+}
+
+/// Whether a `LIMITED` post is visible to the viewer through one of their groups.
+fn shared_to_member_group(
+    post_id: i64,
+    user: &Option<models::User>,
+    conn: &mut PgPooledConnection,
+) -> bool {
+    let user_id = match user {
+        Some(user) => user.id,
+        None => return false,
+    };
+    group_posts::table
+        .inner_join(memberships::table.on(memberships::group_id.eq(group_posts::group_id)))
+        .select(group_posts::id)
+        .filter(group_posts::post_id.eq(post_id))
+        .filter(group_posts::group_moderation.eq_any(PASSING_MODERATIONS))
+        .filter(memberships::user_id.eq(user_id))
+        .filter(memberships::group_moderation.eq_any(PASSING_MODERATIONS))
+        .filter(memberships::user_moderation.eq_any(PASSING_MODERATIONS))
+        .first::<i64>(conn)
+        .is_ok()
+}
+
+fn is_member(group_id: i64, user: &Option<models::User>, conn: &mut PgPooledConnection) -> bool {
+    let user_id = match user {
+        Some(user) => user.id,
+        None => return false,
+    };
+    memberships::table
+        .select(memberships::id)
+        .filter(memberships::group_id.eq(group_id))
+        .filter(memberships::user_id.eq(user_id))
+        .filter(memberships::group_moderation.eq_any(PASSING_MODERATIONS))
+        .filter(memberships::user_moderation.eq_any(PASSING_MODERATIONS))
+        .first::<i64>(conn)
+        .is_ok()
+}
+
+/// Whether `user_id` administers the group by its membership permissions.
+fn is_group_admin(group_id: i64, user_id: i64, conn: &mut PgPooledConnection) -> bool {
+    memberships::table
+        .select(memberships::permissions)
+        .filter(memberships::group_id.eq(group_id))
+        .filter(memberships::user_id.eq(user_id))
+        .first::<serde_json::Value>(conn)
+        .map(|permissions| permissions.to_proto_permissions().contains(&Permission::Admin))
+        .unwrap_or(false)
+}
@@ -0,0 +1,179 @@
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine as _};
+use diesel::*;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::db_connection::PgPooledConnection;
+use crate::env_var;
+use crate::marshaling::*;
+use crate::models;
+use crate::schema::server_secrets;
+
+/// How long signed media URLs are valid for by default, in seconds.
+pub const DEFAULT_MEDIA_URL_TTL: u64 = 3600;
+/// The longest a signed media URL may be valid for, in seconds.
+pub const MAX_MEDIA_URL_TTL: u64 = 7 * 24 * 3600;
+/// Expiries are rounded up to this many seconds, so repeated requests for the same media
+/// get the same URL (and hit the same cache entries).
+const MEDIA_URL_EXPIRY_GRANULARITY: u64 = 300;
+
+const MEDIA_URL_SIGNING_KEY_SECRET: &str = "media_url_signing_key";
+
+lazy_static! {
+    static ref MEDIA_URL_SIGNING_KEY: Mutex<Option<hmac::Key>> = Mutex::new(None);
+}
+
+/// Loads a secret shared by all of this server's replicas, generating (and storing) a random
+/// 256-bit one the first time it's needed.
+pub fn get_or_create_server_secret(
+    name: &str,
+    conn: &mut PgPooledConnection,
+) -> Result<String, diesel::result::Error> {
+    let existing = server_secrets::table
+        .filter(server_secrets::name.eq(name))
+        .first::<models::ServerSecret>(conn)
+        .optional()?;
+    if let Some(secret) = existing {
+        return Ok(secret.value);
+    }
+
+    log::info!("Generating server secret {}", name);
+    let mut value = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut value)
+        .expect("Failed to generate server secret");
+    insert_into(server_secrets::table)
+        .values(&models::NewServerSecret {
+            name: name.to_string(),
+            value: BASE64_URL.encode(value),
+        })
+        .on_conflict(server_secrets::name)
+        .do_nothing()
+        .execute(conn)?;
+    // Another replica may have generated it concurrently; either way, use the stored one.
+    server_secrets::table
+        .filter(server_secrets::name.eq(name))
+        .select(server_secrets::value)
+        .first::<String>(conn)
+}
+
+/// The key signing media URLs: `MEDIA_URL_SIGNING_KEY` if set, otherwise a generated server secret.
+pub fn media_url_signing_key(
+    conn: &mut PgPooledConnection,
+) -> Result<hmac::Key, diesel::result::Error> {
+    let mut key = MEDIA_URL_SIGNING_KEY.lock().unwrap();
+    if let Some(key) = key.as_ref() {
+        return Ok(key.clone());
+    }
+    let secret = match env_var("MEDIA_URL_SIGNING_KEY") {
+        Some(secret) => secret,
+        None => get_or_create_server_secret(MEDIA_URL_SIGNING_KEY_SECRET, conn)?,
+    };
+    let signing_key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    *key = Some(signing_key.clone());
+    Ok(signing_key)
+}
+
+/// When a URL requested now, valid for `ttl` seconds, should expire (in seconds since the epoch).
+pub fn media_url_expiry(now: SystemTime, ttl: u64) -> u64 {
+    let now = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let expires = now + ttl;
+    match expires % MEDIA_URL_EXPIRY_GRANULARITY {
+        0 => expires,
+        remainder => expires + MEDIA_URL_EXPIRY_GRANULARITY - remainder,
+    }
+}
+
+fn media_url_message(media_id: i64, size: Option<&str>, expires: u64) -> String {
+    format!(
+        "{}\n{}\n{}",
+        media_id.to_proto_id(),
+        size.unwrap_or("original"),
+        expires
+    )
+}
+
+/// The signature of a URL for `media_id` (at `size`, where `None` means the original) expiring at `expires`.
+pub fn media_url_signature(
+    key: &hmac::Key,
+    media_id: i64,
+    size: Option<&str>,
+    expires: u64,
+) -> String {
+    let message = media_url_message(media_id, size, expires);
+    BASE64_URL.encode(hmac::sign(key, message.as_bytes()))
+}
+
+/// A signed URL path for the media, like `/media/{id}?size=thumbnail&expires=...&signature=...`.
+pub fn signed_media_url(key: &hmac::Key, media_id: i64, size: Option<&str>, expires: u64) -> String {
+    let signature = media_url_signature(key, media_id, size, expires);
+    match size {
+        Some(size) => format!(
+            "/media/{}?size={}&expires={}&signature={}",
+            media_id.to_proto_id(),
+            size,
+            expires,
+            signature
+        ),
+        None => format!(
+            "/media/{}?expires={}&signature={}",
+            media_id.to_proto_id(),
+            expires,
+            signature
+        ),
+    }
+}
+
+/// Whether `signature` is valid for `media_id` at `size`, and hasn't expired.
+pub fn verify_media_url_signature(
+    key: &hmac::Key,
+    media_id: i64,
+    size: Option<&str>,
+    expires: u64,
+    signature: &str,
+    now: SystemTime,
+) -> bool {
+    let now = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(u64::MAX);
+    if expires < now {
+        return false;
+    }
+    let signature = match BASE64_URL.decode(signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    let message = media_url_message(media_id, size, expires);
+    hmac::verify(key, message.as_bytes(), &signature).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn media_url_signatures_verify() {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"test key");
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let expires = media_url_expiry(now, DEFAULT_MEDIA_URL_TTL);
+        assert_eq!(expires % MEDIA_URL_EXPIRY_GRANULARITY, 0);
+        assert!(expires >= 1_000_000 + DEFAULT_MEDIA_URL_TTL);
+
+        let signature = media_url_signature(&key, 10, Some("thumbnail"), expires);
+        assert!(verify_media_url_signature(&key, 10, Some("thumbnail"), expires, &signature, now));
+        // Scoped to the media, size and expiry.
+        assert!(!verify_media_url_signature(&key, 11, Some("thumbnail"), expires, &signature, now));
+        assert!(!verify_media_url_signature(&key, 10, None, expires, &signature, now));
+        assert!(!verify_media_url_signature(&key, 10, Some("thumbnail"), expires + 1, &signature, now));
+        // Expired.
+        let later = UNIX_EPOCH + Duration::from_secs(expires + 1);
+        assert!(!verify_media_url_signature(&key, 10, Some("thumbnail"), expires, &signature, later));
+
+        // The original may be requested with or without `size=original`.
+        let signature = media_url_signature(&key, 10, None, expires);
+        assert!(verify_media_url_signature(&key, 10, Some("original"), expires, &signature, now));
+    }
+}
@@ -5,4 +5,30 @@ mod moderation_logic;
 pub use moderation_logic::*;
 
 mod visibility_logic;
-pub use visibility_logic::*;
\ No newline at end of file
+pub use visibility_logic::*;
+mod media_logic;
+pub use media_logic::*;
+
+mod media_limits_logic;
+pub use media_limits_logic::*;
+
+mod media_url_logic;
+pub use media_url_logic::*;
+
+mod authentication_logic;
+pub use authentication_logic::*;
+
+mod contact_method_logic;
+pub use contact_method_logic::*;
+
+mod password_reset_logic;
+pub use password_reset_logic::*;
+
+mod two_factor_logic;
+pub use two_factor_logic::*;
+
+mod oidc_logic;
+pub use oidc_logic::*;
+
+mod account_lockout_logic;
+pub use account_lockout_logic::*;
@@ -0,0 +1,325 @@
+use std::time::{Duration, SystemTime};
+
+use bcrypt::{hash, DEFAULT_COST};
+use diesel::*;
+use sha2::{Digest, Sha256};
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::oidc::{generate_oidc_token, IdTokenClaims};
+use crate::protos::*;
+use crate::rpcs::validations::validate_username;
+use crate::schema::{linked_identities, oidc_login_completions, oidc_login_states, users};
+
+use super::{contact_method_address, get_contact_method, has_authentication_feature};
+
+/// How long users have to log in at the provider.
+pub const OIDC_LOGIN_STATE_TTL: Duration = Duration::from_secs(10 * 60);
+/// How long clients have to exchange an `oidc_code` via `CompleteOidcLogin`.
+pub const OIDC_LOGIN_COMPLETION_TTL: Duration = Duration::from_secs(5 * 60);
+
+fn data_error(e: diesel::result::Error) -> Status {
+    log::error!("OIDC data error: {:?}", e);
+    Status::new(Code::Internal, "data_error")
+}
+
+/// Stores a pending login (or link, if `link_user_id` is set) for the provider's callback.
+pub fn create_oidc_login_state(
+    login_state: models::NewOidcLoginState,
+    conn: &mut PgPooledConnection,
+) -> Result<(), diesel::result::Error> {
+    // Abandoned logins are cleaned up as new ones start.
+    delete(oidc_login_states::table.filter(oidc_login_states::expires_at.lt(SystemTime::now())))
+        .execute(conn)?;
+    insert_into(oidc_login_states::table)
+        .values(&login_state)
+        .execute(conn)
+        .map(|_| ())
+}
+
+/// Uses up the pending login for a callback's `state`, if it's for the provider and unexpired.
+pub fn take_oidc_login_state(
+    state: &str,
+    provider_id: &str,
+    conn: &mut PgPooledConnection,
+) -> Result<Option<models::OidcLoginState>, diesel::result::Error> {
+    delete(
+        oidc_login_states::table
+            .filter(oidc_login_states::state.eq(state))
+            .filter(oidc_login_states::provider_id.eq(provider_id))
+            .filter(oidc_login_states::expires_at.gt(SystemTime::now())),
+    )
+    .get_result::<models::OidcLoginState>(conn)
+    .optional()
+}
+
+/// The user a verified ID token logs in as (or is linked to, when `link_user_id` is set).
+/// Identities already linked log in as their user; otherwise, depending on the provider's settings,
+/// they're linked to the user with the same verified email or given a new account.
+pub fn resolve_oidc_user(
+    provider: &OidcProvider,
+    claims: &IdTokenClaims,
+    configuration: &ServerConfiguration,
+    link_user_id: Option<i64>,
+    conn: &mut PgPooledConnection,
+) -> Result<models::User, Status> {
+    let now = SystemTime::now();
+    let email = claims.verified_email().map(str::to_string);
+    let existing = linked_identities::table
+        .filter(linked_identities::provider_id.eq(&provider.id))
+        .filter(linked_identities::subject.eq(&claims.sub))
+        .first::<models::LinkedIdentity>(conn)
+        .optional()
+        .map_err(data_error)?;
+
+    let user_id = match (existing, link_user_id) {
+        (Some(identity), Some(link_user_id)) if identity.user_id != link_user_id => {
+            return Err(Status::new(Code::AlreadyExists, "identity_linked_to_another_user"));
+        }
+        (Some(identity), _) => {
+            update(linked_identities::table.find(identity.id))
+                .set((
+                    linked_identities::last_login_at.eq(now),
+                    linked_identities::email.eq(email.to_owned().or(identity.email)),
+                ))
+                .execute(conn)
+                .map_err(data_error)?;
+            identity.user_id
+        }
+        (None, Some(link_user_id)) => link_identity(provider, claims, link_user_id, conn)?,
+        (None, None) => {
+            let matched_user_id = match (&email, provider.link_by_verified_email) {
+                (Some(email), true) => find_user_by_verified_email(email, conn)?,
+                _ => None,
+            };
+            let user_id = match matched_user_id {
+                Some(user_id) => user_id,
+                None if provider.create_accounts => {
+                    create_oidc_user(claims, configuration, conn)?.id
+                }
+                None => return Err(Status::new(Code::NotFound, "identity_not_linked")),
+            };
+            link_identity(provider, claims, user_id, conn)?
+        }
+    };
+    users::table
+        .find(user_id)
+        .first::<models::User>(conn)
+        .map_err(data_error)
+}
+
+fn link_identity(
+    provider: &OidcProvider,
+    claims: &IdTokenClaims,
+    user_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<i64, Status> {
+    insert_into(linked_identities::table)
+        .values(&models::NewLinkedIdentity {
+            user_id,
+            provider_id: provider.id.to_owned(),
+            subject: claims.sub.to_owned(),
+            email: claims.verified_email().map(str::to_string),
+            last_login_at: Some(SystemTime::now()),
+        })
+        .execute(conn)
+        .map_err(data_error)?;
+    log::info!(
+        "Linked OIDC identity provider_id={} to user_id={}",
+        provider.id,
+        user_id
+    );
+    Ok(user_id)
+}
+
+/// The one user with `email` verified. Ambiguous matches aren't linked.
+fn find_user_by_verified_email(
+    email: &str,
+    conn: &mut PgPooledConnection,
+) -> Result<Option<i64>, Status> {
+    let address = contact_method_address(email).to_string();
+    let candidates = users::table
+        .filter(
+            diesel::dsl::sql::<diesel::sql_types::Bool>("(email->>'value') IN (")
+                .bind::<diesel::sql_types::Text, _>(address.to_owned())
+                .sql(", ")
+                .bind::<diesel::sql_types::Text, _>(format!("mailto:{}", address))
+                .sql(")"),
+        )
+        .load::<models::User>(conn)
+        .map_err(data_error)?;
+    let verified: Vec<i64> = candidates
+        .iter()
+        .filter(|user| {
+            get_contact_method(user, ContactMethodType::Email).map_or(false, |email| email.verified)
+        })
+        .map(|user| user.id)
+        .collect();
+    match verified.as_slice() {
+        [user_id] => Ok(Some(*user_id)),
+        _ => Ok(None),
+    }
+}
+
+/// A username for a new account, from the identity's `preferred_username` or email.
+pub fn oidc_username_base(claims: &IdTokenClaims) -> String {
+    let source = claims
+        .preferred_username
+        .as_deref()
+        .or(claims.email.as_deref().and_then(|email| email.split('@').next()))
+        .unwrap_or_default();
+    let username: String = source
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '.' || *c == '-')
+        .take(40)
+        .collect();
+    if username.is_empty() {
+        "user".to_string()
+    } else {
+        username
+    }
+}
+
+fn create_oidc_user(
+    claims: &IdTokenClaims,
+    configuration: &ServerConfiguration,
+    conn: &mut PgPooledConnection,
+) -> Result<models::User, Status> {
+    if !has_authentication_feature(configuration, AuthenticationFeature::CreateAccount)
+        || has_authentication_feature(configuration, AuthenticationFeature::InviteOnly)
+    {
+        return Err(Status::new(Code::PermissionDenied, "account_creation_disabled"));
+    }
+    let base = oidc_username_base(claims);
+    let mut candidate = base.to_owned();
+    let mut attempts = 0;
+    let new_username = loop {
+        let taken = validate_username(&candidate).is_err()
+            || users::table
+                .filter(users::username.eq(&candidate))
+                .select(users::id)
+                .first::<i64>(conn)
+                .optional()
+                .map_err(data_error)?
+                .is_some();
+        if !taken {
+            break candidate;
+        }
+        attempts += 1;
+        if attempts > 10 {
+            return Err(Status::new(Code::AlreadyExists, "username_already_exists"));
+        }
+        candidate = format!("{}{}", base, &generate_oidc_token()[..4].to_lowercase());
+    };
+
+    // OIDC accounts start without a (usable) password; users can set one via a password reset.
+    let password = hash(generate_oidc_token(), DEFAULT_COST).unwrap();
+    let email = claims.verified_email().and_then(|email| {
+        serde_json::to_value(ContactMethod {
+            value: Some(email.to_string()),
+            visibility: Visibility::Private as i32,
+            supported_by_server: false,
+            verified: false,
+        })
+        .ok()
+    });
+    let people_settings = configuration.people_settings.as_ref().unwrap();
+    let user = insert_into(users::table)
+        .values((
+            users::username.eq(&new_username),
+            users::password_salted_hash.eq(password),
+            users::email.eq(email),
+            users::permissions.eq(configuration.default_user_permissions.to_json_permissions()),
+            users::moderation.eq(people_settings.default_moderation.to_string_moderation()),
+            users::visibility.eq(people_settings.default_visibility.to_string_visibility()),
+        ))
+        .get_result::<models::User>(conn)
+        .map_err(data_error)?;
+    log::info!("Created user {} (user_id={}) via OIDC", new_username, user.id);
+    Ok(user)
+}
+
+fn hash_oidc_code(code: &str) -> String {
+    format!("{:x}", Sha256::digest(code.trim().as_bytes()))
+}
+
+/// A single-use code for the client to exchange for tokens via `CompleteOidcLogin`.
+pub fn create_oidc_login_completion(
+    user_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<String, diesel::result::Error> {
+    let code = generate_oidc_token();
+    insert_into(oidc_login_completions::table)
+        .values(&models::NewOidcLoginCompletion {
+            user_id,
+            code_hash: hash_oidc_code(&code),
+            expires_at: SystemTime::now() + OIDC_LOGIN_COMPLETION_TTL,
+        })
+        .execute(conn)?;
+    Ok(code)
+}
+
+/// Uses up an `oidc_code`, returning its user's ID.
+pub fn take_oidc_login_completion(code: &str, conn: &mut PgPooledConnection) -> Result<i64, Status> {
+    delete(oidc_login_completions::table.filter(oidc_login_completions::expires_at.lt(SystemTime::now())))
+        .execute(conn)
+        .map_err(data_error)?;
+    delete(
+        oidc_login_completions::table
+            .filter(oidc_login_completions::code_hash.eq(hash_oidc_code(code))),
+    )
+    .returning(oidc_login_completions::user_id)
+    .get_result::<i64>(conn)
+    .optional()
+    .map_err(data_error)?
+    .ok_or_else(|| Status::new(Code::Unauthenticated, "oidc_code_invalid"))
+}
+
+/// A local path to return to after logging in, so the login routes can't be used as open redirects.
+pub fn sanitize_redirect_path(path: Option<&str>) -> String {
+    match path.map(str::trim) {
+        Some(path)
+            if path.starts_with('/')
+                && !path.starts_with("//")
+                && !path.starts_with("/\\")
+                && !path.chars().any(|c| c.is_control()) =>
+        {
+            path.to_string()
+        }
+        _ => "/".to_string(),
+    }
+}
+
+/// `path` with a query parameter added.
+pub fn with_query_param(path: &str, name: &str, value: &str) -> String {
+    let (path, fragment) = match path.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (path, None),
+    };
+    let separator = if path.contains('?') { '&' } else { '?' };
+    let value = percent_encoding::utf8_percent_encode(value, percent_encoding::NON_ALPHANUMERIC);
+    match fragment {
+        Some(fragment) => format!("{}{}{}={}#{}", path, separator, name, value, fragment),
+        None => format!("{}{}{}={}", path, separator, name, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirect_paths_stay_local() {
+        assert_eq!(sanitize_redirect_path(Some("/posts?x=1")), "/posts?x=1");
+        assert_eq!(sanitize_redirect_path(Some("//evil.example.com")), "/");
+        assert_eq!(sanitize_redirect_path(Some("/\\evil.example.com")), "/");
+        assert_eq!(sanitize_redirect_path(Some("https://evil.example.com")), "/");
+        assert_eq!(sanitize_redirect_path(Some("/a\r\nSet-Cookie: x")), "/");
+        assert_eq!(sanitize_redirect_path(None), "/");
+
+        assert_eq!(with_query_param("/", "oidc_code", "a-b_c"), "/?oidc_code=a%2Db%5Fc");
+        assert_eq!(with_query_param("/p?x=1#top", "oidc_error", "x"), "/p?x=1&oidc_error=x#top");
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine as _};
+use diesel::*;
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::{Digest, Sha256};
+
+use crate::db_connection::PgPooledConnection;
+use crate::messaging::{Messenger, OutboundMessage};
+use crate::models;
+use crate::protos::*;
+use crate::schema::password_reset_tokens;
+
+use super::{contact_method_address, get_contact_method};
+
+/// How long password reset tokens work for.
+pub const PASSWORD_RESET_TOKEN_TTL: Duration = Duration::from_secs(3600);
+/// Reset requests for the same user within this long of the last are ignored.
+pub const PASSWORD_RESET_REQUEST_INTERVAL: Duration = Duration::from_secs(60);
+
+pub fn generate_password_reset_token() -> String {
+    let mut randoms = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut randoms)
+        .expect("Failed to generate password reset token");
+    BASE64_URL.encode(randoms)
+}
+
+pub fn hash_password_reset_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.trim().as_bytes()))
+}
+
+/// Whether `issue_password_reset` can reach the user, i.e. they have a verified email and the
+/// server can send email.
+pub fn can_issue_password_reset(user: &models::User, messenger: &Arc<dyn Messenger>) -> bool {
+    reset_email(user, messenger).is_some()
+}
+
+fn reset_email(user: &models::User, messenger: &Arc<dyn Messenger>) -> Option<ContactMethod> {
+    get_contact_method(user, ContactMethodType::Email)
+        .filter(|email| email.verified && messenger.supports(ContactMethodType::Email))
+}
+
+/// Creates a reset token for the user and emails it to them in the background, if they have a
+/// verified email the server can send to. Recently-issued tokens aren't reissued. Returns whether
+/// a token was sent; callers facing anonymous users must not reveal it.
+pub fn issue_password_reset(
+    user: &models::User,
+    server_name: &str,
+    messenger: Arc<dyn Messenger>,
+    conn: &mut PgPooledConnection,
+) -> Result<bool, diesel::result::Error> {
+    let email = match reset_email(user, &messenger) {
+        Some(email) => email,
+        None => return Ok(false),
+    };
+    let recently_issued = password_reset_tokens::table
+        .filter(password_reset_tokens::user_id.eq(user.id))
+        .filter(password_reset_tokens::created_at.gt(SystemTime::now() - PASSWORD_RESET_REQUEST_INTERVAL))
+        .select(password_reset_tokens::id)
+        .first::<i64>(conn)
+        .optional()?
+        .is_some();
+    if recently_issued {
+        return Ok(false);
+    }
+
+    let token = generate_password_reset_token();
+    insert_into(password_reset_tokens::table)
+        .values(&models::NewPasswordResetToken {
+            user_id: user.id,
+            token_hash: hash_password_reset_token(&token),
+            expires_at: SystemTime::now() + PASSWORD_RESET_TOKEN_TTL,
+        })
+        .execute(conn)?;
+
+    let message = OutboundMessage {
+        contact_method: ContactMethodType::Email,
+        to: contact_method_address(email.value.as_deref().unwrap_or_default()).to_string(),
+        subject: format!("Reset your {} password", server_name),
+        body: format!(
+            "Someone (hopefully you) asked to reset the password for {} on {}. \
+            Use this code to choose a new one within {} minutes:\n\n{}\n\n\
+            If you didn't ask for this, you can ignore this message.",
+            user.username,
+            server_name,
+            PASSWORD_RESET_TOKEN_TTL.as_secs() / 60,
+            token
+        ),
+    };
+    let user_id = user.id;
+    // Sending in the background keeps responses to anonymous requests uniformly fast.
+    tokio::spawn(async move {
+        match messenger.send(&message).await {
+            Ok(_) => log::info!("Sent password reset to user_id={}", user_id),
+            Err(e) => log::error!("Error sending password reset to user_id={}: {:?}", user_id, e),
+        }
+    });
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn password_reset_tokens_are_random_and_hashed() {
+        let token = generate_password_reset_token();
+        assert_eq!(token.len(), 43);
+        assert_ne!(generate_password_reset_token(), token);
+        assert_eq!(hash_password_reset_token(&token), hash_password_reset_token(&format!(" {}\n", token)));
+        assert_ne!(hash_password_reset_token(&token), token);
+    }
+}
@@ -0,0 +1,373 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use diesel::*;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::{Digest, Sha256};
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::{two_factor_challenges, user_recovery_codes, user_totps};
+
+use super::has_authentication_feature;
+
+/// RFC 6238 parameters, as understood by every authenticator app.
+pub const TOTP_STEP_SECONDS: u64 = 30;
+pub const TOTP_DIGITS: u32 = 6;
+/// Codes from this many steps before/after the current one are accepted, to allow for clock drift.
+const TOTP_ALLOWED_DRIFT: u64 = 1;
+const TOTP_SECRET_BYTES: usize = 20;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// How long the partial token from `login` works for.
+pub const TWO_FACTOR_CHALLENGE_TTL: Duration = Duration::from_secs(300);
+/// Wrong codes allowed per partial token.
+pub const MAX_TWO_FACTOR_ATTEMPTS: i32 = 5;
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// RFC 4648 base32, without padding (as used in `otpauth://` URIs).
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut result = String::new();
+    for chunk in data.chunks(5) {
+        let mut buffer = [0u8; 5];
+        buffer[..chunk.len()].copy_from_slice(chunk);
+        let bits = buffer.iter().fold(0u64, |bits, byte| (bits << 8) | *byte as u64);
+        let chars = (chunk.len() * 8 + 4) / 5;
+        for i in 0..chars {
+            result.push(BASE32_ALPHABET[((bits >> (35 - i * 5)) & 31) as usize] as char);
+        }
+    }
+    result
+}
+
+pub fn base32_decode(data: &str) -> Option<Vec<u8>> {
+    let mut result = Vec::new();
+    let (mut bits, mut bit_count) = (0u64, 0);
+    for c in data.trim_end_matches('=').chars() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a as char == c.to_ascii_uppercase())?;
+        bits = (bits << 5) | value as u64;
+        bit_count += 5;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            result.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+    Some(result)
+}
+
+/// A random base32 TOTP secret.
+pub fn generate_totp_secret() -> String {
+    let mut randoms = [0u8; TOTP_SECRET_BYTES];
+    SystemRandom::new()
+        .fill(&mut randoms)
+        .expect("Failed to generate TOTP secret");
+    base32_encode(&randoms)
+}
+
+pub fn totp_step(now: SystemTime) -> u64 {
+    now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0) / TOTP_STEP_SECONDS
+}
+
+/// The RFC 6238 (HMAC-SHA1) code for `secret` at time step `step`.
+pub fn totp_code(secret: &[u8], step: u64, digits: u32) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let digest = hmac::sign(&key, &step.to_be_bytes());
+    let digest = digest.as_ref();
+    let offset = (digest[digest.len() - 1] & 0xf) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!("{:0width$}", binary % 10u32.pow(digits), width = digits as usize)
+}
+
+/// The time step `code` is valid for, if it's valid for one after `last_used_step` (so codes can't be replayed).
+pub fn verify_totp_code(
+    secret: &str,
+    code: &str,
+    now: SystemTime,
+    last_used_step: Option<i64>,
+) -> Option<u64> {
+    let secret = base32_decode(secret)?;
+    let code = code.trim();
+    let current = totp_step(now);
+    (current.saturating_sub(TOTP_ALLOWED_DRIFT)..=current + TOTP_ALLOWED_DRIFT)
+        .filter(|step| last_used_step.map(|last| *step as i64 > last).unwrap_or(true))
+        .find(|step| {
+            ring::constant_time::verify_slices_are_equal(
+                totp_code(&secret, *step, TOTP_DIGITS).as_bytes(),
+                code.as_bytes(),
+            )
+            .is_ok()
+        })
+}
+
+/// An `otpauth://` URI for authenticator apps (usually shown as a QR code).
+pub fn totp_provisioning_uri(issuer: &str, account: &str, secret: &str) -> String {
+    let issuer = utf8_percent_encode(issuer, NON_ALPHANUMERIC).to_string();
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        issuer,
+        utf8_percent_encode(account, NON_ALPHANUMERIC),
+        secret,
+        issuer,
+        TOTP_DIGITS,
+        TOTP_STEP_SECONDS
+    )
+}
+
+/// Random single-use recovery codes, like `k3jd9-x8q2m`.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let sr = SystemRandom::new();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut randoms = [0u8; 7];
+            sr.fill(&mut randoms).expect("Failed to generate recovery code");
+            let code = base32_encode(&randoms)[..10].to_lowercase();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| *c != '-')
+        .collect();
+    format!("{:x}", Sha256::digest(normalized.as_bytes()))
+}
+
+/// Recovery codes are distinguishable from TOTP codes by length.
+pub fn is_recovery_code(code: &str) -> bool {
+    code.trim().len() > TOTP_DIGITS as usize
+}
+
+/// Whether the user must use two-factor auth: when the server has `REQUIRE_PRIVILEGED_TWO_FACTOR`
+/// and the user has `ADMIN` or any `MODERATE_*` permission.
+pub fn requires_two_factor(user: &models::User, configuration: &ServerConfiguration) -> bool {
+    if !has_authentication_feature(configuration, AuthenticationFeature::RequirePrivilegedTwoFactor) {
+        return false;
+    }
+    // Checked by name, so permissions this server doesn't know yet still count.
+    user.permissions
+        .as_array()
+        .map(|permissions| {
+            permissions.iter().filter_map(|p| p.as_str()).any(|p| {
+                p.eq_ignore_ascii_case(Permission::Admin.as_str_name())
+                    || p.to_uppercase().starts_with("MODERATE_")
+            })
+        })
+        .unwrap_or(false)
+}
+
+pub fn hash_two_factor_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.trim().as_bytes()))
+}
+
+/// Starts the second step of logging in, returning the partial token for it.
+/// `requested_expires_at` and `device_name` are for the eventual refresh token.
+pub fn create_two_factor_challenge(
+    user_id: i64,
+    requested_expires_at: Option<SystemTime>,
+    device_name: Option<String>,
+    enrollment_required: bool,
+    conn: &mut PgPooledConnection,
+) -> Result<TwoFactorChallenge, Status> {
+    let mut randoms = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut randoms)
+        .expect("Failed to generate two-factor token");
+    let token = randoms.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    let expires_at = SystemTime::now() + TWO_FACTOR_CHALLENGE_TTL;
+    insert_into(two_factor_challenges::table)
+        .values(&models::NewTwoFactorChallenge {
+            user_id,
+            token_hash: hash_two_factor_token(&token),
+            requested_expires_at,
+            expires_at,
+            device_name,
+        })
+        .execute(conn)
+        .map_err(|e| {
+            log::error!("Error creating two-factor challenge: {:?}", e);
+            Status::new(Code::Internal, "data_error")
+        })?;
+    Ok(TwoFactorChallenge {
+        token,
+        expires_at: Some(expires_at.to_proto()),
+        enrollment_required,
+    })
+}
+
+/// The unexpired challenge for a partial token from `login`.
+pub fn get_two_factor_challenge(
+    token: &str,
+    conn: &mut PgPooledConnection,
+) -> Result<models::TwoFactorChallenge, Status> {
+    two_factor_challenges::table
+        .filter(two_factor_challenges::token_hash.eq(hash_two_factor_token(token)))
+        .filter(two_factor_challenges::expires_at.gt(diesel::dsl::now))
+        .first::<models::TwoFactorChallenge>(conn)
+        .optional()
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?
+        .ok_or_else(|| Status::new(Code::Unauthenticated, "two_factor_token_invalid"))
+}
+
+/// The user's TOTP authenticator, if they've confirmed one.
+pub fn get_confirmed_totp(
+    user_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<Option<models::UserTotp>, Status> {
+    user_totps::table
+        .filter(user_totps::user_id.eq(user_id))
+        .filter(user_totps::confirmed_at.is_not_null())
+        .first::<models::UserTotp>(conn)
+        .optional()
+        .map_err(|_| Status::new(Code::Internal, "data_error"))
+}
+
+/// Checks a code from the user's authenticator, recording its time step so it can't be reused.
+pub fn use_totp_code(
+    totp: &models::UserTotp,
+    code: &str,
+    conn: &mut PgPooledConnection,
+) -> Result<bool, Status> {
+    let step = match verify_totp_code(&totp.secret, code, SystemTime::now(), totp.last_used_step) {
+        Some(step) => step as i64,
+        None => return Ok(false),
+    };
+    // Guards against the same code being used concurrently.
+    let updated = update(user_totps::table.find(totp.id))
+        .filter(
+            user_totps::last_used_step
+                .is_null()
+                .or(user_totps::last_used_step.lt(step)),
+        )
+        .set(user_totps::last_used_step.eq(step))
+        .execute(conn)
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?;
+    Ok(updated == 1)
+}
+
+/// Checks and uses up one of the user's recovery codes.
+pub fn use_recovery_code(
+    user_id: i64,
+    code: &str,
+    conn: &mut PgPooledConnection,
+) -> Result<bool, Status> {
+    let deleted = delete(
+        user_recovery_codes::table
+            .filter(user_recovery_codes::user_id.eq(user_id))
+            .filter(user_recovery_codes::code_hash.eq(hash_recovery_code(code))),
+    )
+    .execute(conn)
+    .map_err(|_| Status::new(Code::Internal, "data_error"))?;
+    if deleted > 0 {
+        log::info!("Used a recovery code for user_id={}", user_id);
+    }
+    Ok(deleted > 0)
+}
+
+/// Checks a TOTP code or recovery code.
+pub fn use_two_factor_code(
+    totp: &models::UserTotp,
+    code: &str,
+    conn: &mut PgPooledConnection,
+) -> Result<bool, Status> {
+    match is_recovery_code(code) {
+        true => use_recovery_code(totp.user_id, code, conn),
+        false => use_totp_code(totp, code, conn),
+    }
+}
+
+/// Counts an attempt against a partial token, returning `false` (and discarding the token)
+/// once it's out of attempts.
+pub fn count_two_factor_attempt(
+    challenge: &models::TwoFactorChallenge,
+    conn: &mut PgPooledConnection,
+) -> Result<bool, Status> {
+    let attempts = update(two_factor_challenges::table.find(challenge.id))
+        .filter(two_factor_challenges::attempts.lt(MAX_TWO_FACTOR_ATTEMPTS))
+        .set(two_factor_challenges::attempts.eq(two_factor_challenges::attempts + 1))
+        .returning(two_factor_challenges::attempts)
+        .get_result::<i32>(conn)
+        .optional()
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?;
+    if attempts.is_none() {
+        let _ = delete(two_factor_challenges::table.find(challenge.id)).execute(conn);
+    }
+    Ok(attempts.is_some())
+}
+
+/// The user enrolling in TOTP: the authenticated user, or the user logging in with a partial
+/// token (whose challenge is also returned) who hasn't enrolled yet.
+pub fn get_totp_enrollment_user(
+    two_factor_token: Option<&str>,
+    user: Option<models::User>,
+    conn: &mut PgPooledConnection,
+) -> Result<(models::User, Option<models::TwoFactorChallenge>), Status> {
+    let (user, challenge) = match (two_factor_token, user) {
+        (Some(token), _) if !token.is_empty() => {
+            let challenge = get_two_factor_challenge(token, conn)?;
+            (models::get_user(challenge.user_id, conn)?, Some(challenge))
+        }
+        (_, Some(user)) => (user, None),
+        _ => return Err(Status::new(Code::Unauthenticated, "not_authorized")),
+    };
+    if get_confirmed_totp(user.id, conn)?.is_some() {
+        return Err(Status::new(Code::FailedPrecondition, "totp_already_enabled"));
+    }
+    Ok((user, challenge))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totp_matches_rfc_6238() {
+        // Test vectors from RFC 6238, Appendix B (SHA1).
+        let secret = b"12345678901234567890";
+        assert_eq!(totp_code(secret, 59 / 30, 8), "94287082");
+        assert_eq!(totp_code(secret, 1111111109 / 30, 8), "07081804");
+        assert_eq!(totp_code(secret, 20000000000 / 30, 8), "65353130");
+        assert_eq!(totp_code(secret, 59 / 30, 6), "287082");
+    }
+
+    #[test]
+    fn totp_codes_verify_once() {
+        let secret = base32_encode(b"12345678901234567890");
+        assert_eq!(secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(base32_decode(&secret).unwrap(), b"12345678901234567890");
+        let now = UNIX_EPOCH + Duration::from_secs(59);
+        let step = verify_totp_code(&secret, "287082", now, None).unwrap();
+        assert_eq!(step, 1);
+        assert_eq!(verify_totp_code(&secret, "287082", now, Some(step as i64)), None);
+        assert_eq!(verify_totp_code(&secret, "000000", now, None), None);
+    }
+
+    #[test]
+    fn recovery_codes_hash_consistently() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes.iter().all(|code| is_recovery_code(code)));
+        assert_eq!(
+            hash_recovery_code(&codes[0]),
+            hash_recovery_code(&codes[0].to_uppercase().replace('-', ""))
+        );
+        assert_ne!(hash_recovery_code(&codes[0]), hash_recovery_code(&codes[1]));
+    }
+}
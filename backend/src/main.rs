@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate diesel;
 extern crate async_compression;
+extern crate async_trait;
 extern crate bcrypt;
 extern crate bs58;
 extern crate diesel_migrations;
@@ -26,18 +27,36 @@ extern crate awscreds;
 extern crate awsregion;
 extern crate bytes;
 extern crate percent_encoding;
+extern crate base64;
+extern crate chrono;
+extern crate httpdate;
+extern crate rand;
+extern crate reqwest;
+extern crate rsa;
+extern crate sha2;
+extern crate image;
+extern crate exif;
+extern crate infer;
+extern crate lettre;
 extern crate s3;
 extern crate tempfile;
 extern crate tokio_stream;
+extern crate tokio_util;
 
+pub mod activitypub;
 pub mod auth;
 pub mod db_connection;
 pub mod jonline;
 pub mod logic;
 pub mod marshaling;
+pub mod media_processing;
+pub mod media_store;
+pub mod messaging;
 pub mod minio_connection;
 pub mod models;
+pub mod oidc;
 pub mod protos;
+pub mod rate_limiting;
 pub mod rpcs;
 pub mod schema;
 pub mod servers;
@@ -54,16 +73,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     db_connection::migrate_database();
 
     let pool = Arc::new(db_connection::establish_pool());
-    let bucket = Arc::new(
-        minio_connection::get_and_test_bucket()
-            .await
-            .expect("Failed to connect to MinIO"),
-    );
+    let media_store = media_store::get_media_store()
+        .await
+        .expect("Failed to connect to media store (see MEDIA_STORE)");
+    let messenger = messaging::get_messenger().expect("Failed to configure messaging (see MESSAGING)");
 
     let tempdir = Arc::new(tempfile::tempdir().map_err(|e| {
         log::error!("Failed to create tempdir: {:?}", e);
         e
     })?);
+    let media_cache = Arc::new(web::MediaCache::new(tempdir));
 
     // Ideally, we should be able to restart servers and switch between HTTPS redirects.
     let mut conn = pool
@@ -78,22 +97,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let external_cdn_config = server_configuration.external_cdn_config;
 
-    let tls_configuration_successful = start_tonic_server(pool.clone(), bucket.clone())?;
+    let tls_configuration_successful = start_tonic_server(pool.clone(), media_store.clone(), messenger)?;
+    activitypub::start_delivery_worker(pool.clone());
+    media_processing::start_media_processing_worker(pool.clone(), media_store.clone());
+    media_processing::start_media_upload_expiry_worker(pool.clone(), media_store.clone());
+    auth::start_expired_token_cleanup_worker(pool.clone());
 
-    let rocket_secure = start_rocket_secure(pool.clone(), bucket.clone(), tempdir.clone());
+    let rocket_secure = start_rocket_secure(pool.clone(), media_store.clone(), media_cache.clone());
     let rocket_unsecure_80 = start_rocket_unsecured(
         80,
         pool.clone(),
-        bucket.clone(),
-        tempdir.clone(),
+        media_store.clone(),
+        media_cache.clone(),
         tls_configuration_successful,
         external_cdn_config.is_some(),
     );
     let rocket_unsecure_8000 = start_rocket_unsecured(
         8000,
         pool.clone(),
-        bucket.clone(),
-        tempdir.clone(),
+        media_store.clone(),
+        media_cache.clone(),
         tls_configuration_successful,
         external_cdn_config.is_some(),
     );
@@ -0,0 +1,106 @@
+use serde_json::json;
+
+use super::{ToEscapedHtml, ToHtmlParagraphs, ToLink, ToProtoId, ToRfc3339Time};
+use crate::activitypub::*;
+use crate::models;
+
+pub trait ToActivityPubActor {
+    fn to_activitypub_actor(&self, domain: &str, public_key_pem: &str) -> serde_json::Value;
+}
+impl ToActivityPubActor for models::User {
+    fn to_activitypub_actor(&self, domain: &str, public_key_pem: &str) -> serde_json::Value {
+        let actor_uri = actor_uri(domain, &self.username);
+        let mut actor = json!({
+            "@context": [ACTIVITYSTREAMS_CONTEXT, SECURITY_CONTEXT],
+            "id": actor_uri,
+            "type": "Person",
+            "preferredUsername": self.username,
+            "name": if self.real_name.is_empty() { &self.username } else { &self.real_name },
+            "summary": self.bio.to_html_paragraphs(),
+            "url": format!("https://{}/user/{}", domain, self.id.to_proto_id()),
+            "inbox": format!("{}/inbox", actor_uri),
+            "outbox": format!("{}/outbox", actor_uri),
+            "followers": format!("{}/followers", actor_uri),
+            "endpoints": { "sharedInbox": shared_inbox_uri(domain) },
+            "published": self.created_at.to_rfc3339(),
+            "publicKey": {
+                "id": key_id(domain, &self.username),
+                "owner": actor_uri,
+                "publicKeyPem": public_key_pem,
+            },
+        });
+        if let Some(avatar_media_id) = self.avatar_media_id {
+            actor["icon"] = json!({
+                "type": "Image",
+                "url": format!("https://{}/media/{}", domain, avatar_media_id.to_proto_id()),
+            });
+        }
+        actor
+    }
+}
+
+pub trait ToActivityPubNote {
+    fn to_activitypub_note(&self, domain: &str, username: &str) -> serde_json::Value;
+    fn to_activitypub_create(&self, domain: &str, username: &str) -> serde_json::Value;
+}
+impl ToActivityPubNote for models::Post {
+    fn to_activitypub_note(&self, domain: &str, username: &str) -> serde_json::Value {
+        let actor_uri = actor_uri(domain, username);
+        let mut content = String::new();
+        if let Some(title) = self.title.as_ref().filter(|t| !t.is_empty()) {
+            content.push_str(&format!("<p><strong>{}</strong></p>", title.to_escaped_html()));
+        }
+        if let Some(body) = self.content.as_ref() {
+            content.push_str(&body.to_html_paragraphs());
+        }
+        if let Some(link) = self.link.to_link() {
+            content.push_str(&format!(
+                "<p><a href=\"{}\">{}</a></p>",
+                link.to_escaped_html(),
+                link.to_escaped_html()
+            ));
+        }
+        let attachment = self
+            .media
+            .iter()
+            .map(|media_id| {
+                json!({
+                    "type": "Document",
+                    "url": format!("https://{}/media/{}", domain, media_id.to_proto_id()),
+                })
+            })
+            .collect::<Vec<serde_json::Value>>();
+        let mut note = json!({
+            "id": note_uri(domain, self.id),
+            "type": "Note",
+            "attributedTo": actor_uri,
+            "content": content,
+            "url": format!("https://{}/post/{}", domain, self.id.to_proto_id()),
+            "published": self.created_at.to_rfc3339(),
+            "to": [ACTIVITYSTREAMS_PUBLIC],
+            "cc": [format!("{}/followers", actor_uri)],
+            "attachment": attachment,
+        });
+        if let Some(updated_at) = self.updated_at {
+            note["updated"] = json!(updated_at.to_rfc3339());
+        }
+        if let Some(parent_post_id) = self.parent_post_id {
+            note["inReplyTo"] = json!(note_uri(domain, parent_post_id));
+        }
+        note
+    }
+
+    fn to_activitypub_create(&self, domain: &str, username: &str) -> serde_json::Value {
+        let note = self.to_activitypub_note(domain, username);
+        json!({
+            "@context": ACTIVITYSTREAMS_CONTEXT,
+            "id": format!("{}/activity", note["id"].as_str().unwrap_or_default()),
+            "type": "Create",
+            "actor": actor_uri(domain, username),
+            "published": note["published"],
+            "to": note["to"],
+            "cc": note["cc"],
+            "object": note,
+        })
+    }
+}
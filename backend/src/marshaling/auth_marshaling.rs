@@ -0,0 +1,105 @@
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+
+pub trait ToProtoInviteCode {
+    fn to_proto(&self) -> InviteCode;
+}
+
+impl ToProtoInviteCode for models::InviteCode {
+    fn to_proto(&self) -> InviteCode {
+        InviteCode {
+            id: self.id.to_proto_id(),
+            code: self.code.to_owned(),
+            created_by_user_id: self.created_by_user_id.map(|id| id.to_proto_id()),
+            max_uses: self.max_uses.map(|max_uses| max_uses as u32),
+            use_count: self.use_count as u32,
+            permissions: self
+                .permissions
+                .as_ref()
+                .map(|permissions| permissions.to_i32_permissions())
+                .unwrap_or_default(),
+            expires_at: self.expires_at.map(|expires_at| expires_at.to_proto()),
+            created_at: Some(self.created_at.to_proto()),
+        }
+    }
+}
+
+pub trait ToProtoSession {
+    fn to_proto(&self, device_name: Option<String>, current: bool) -> Session;
+}
+
+impl ToProtoSession for models::UserRefreshToken {
+    fn to_proto(&self, device_name: Option<String>, current: bool) -> Session {
+        Session {
+            id: self.id.to_proto_id(),
+            device_name,
+            user_agent: self.user_agent.to_owned(),
+            ip_address: self.ip_address.to_owned(),
+            current,
+            last_used_at: self.last_used_at.map(|last_used_at| last_used_at.to_proto()),
+            expires_at: Some(self.expires_at.to_proto()),
+            created_at: Some(self.created_at.to_proto()),
+        }
+    }
+}
+
+pub trait ToProtoLinkedIdentity {
+    fn to_proto(&self) -> LinkedIdentity;
+}
+
+impl ToProtoLinkedIdentity for models::LinkedIdentity {
+    fn to_proto(&self) -> LinkedIdentity {
+        LinkedIdentity {
+            id: self.id.to_proto_id(),
+            provider_id: self.provider_id.to_owned(),
+            subject: self.subject.to_owned(),
+            email: self.email.to_owned(),
+            last_login_at: self.last_login_at.map(|last_login_at| last_login_at.to_proto()),
+            created_at: Some(self.created_at.to_proto()),
+        }
+    }
+}
+
+pub trait ToProtoAccountLockout {
+    fn to_proto(&self, username: String) -> AccountLockout;
+}
+
+impl ToProtoAccountLockout for models::AccountLockout {
+    fn to_proto(&self, username: String) -> AccountLockout {
+        AccountLockout {
+            user_id: self.user_id.to_proto_id(),
+            username,
+            failed_attempts: self.failed_attempts.max(0) as u32,
+            locked_until: self.locked_until.map(|locked_until| locked_until.to_proto()),
+            last_failed_at: Some(self.last_failed_at.to_proto()),
+            last_failed_ip: self.last_failed_ip.to_owned(),
+        }
+    }
+}
+
+pub trait ToProtoApiToken {
+    fn to_proto(&self) -> ApiToken;
+}
+
+impl ToProtoApiToken for models::UserApiToken {
+    fn to_proto(&self) -> ApiToken {
+        let scopes: Vec<crate::auth::ApiTokenScope> =
+            serde_json::from_value(self.scopes.to_owned()).unwrap_or_default();
+        ApiToken {
+            id: self.id.to_proto_id(),
+            label: self.label.to_owned(),
+            scopes: scopes
+                .iter()
+                .map(|scope| ApiTokenScope {
+                    permission: scope.proto_permission().unwrap_or(Permission::Unknown) as i32,
+                    group_id: scope.group_id.map(|group_id| group_id.to_proto_id()),
+                })
+                .collect(),
+            token: None,
+            expires_at: self.expires_at.map(|expires_at| expires_at.to_proto()),
+            last_used_at: self.last_used_at.map(|last_used_at| last_used_at.to_proto()),
+            created_at: Some(self.created_at.to_proto()),
+        }
+    }
+}
@@ -51,10 +51,12 @@ impl ToI32PrivateUserStrategy for String {
     }
 }
 
-pub const ALL_AUTHENTICATION_FEATURES: [AuthenticationFeature; 3] = [
+pub const ALL_AUTHENTICATION_FEATURES: [AuthenticationFeature; 5] = [
     AuthenticationFeature::Unknown,
     AuthenticationFeature::CreateAccount,
     AuthenticationFeature::Login,
+    AuthenticationFeature::InviteOnly,
+    AuthenticationFeature::RequirePrivilegedTwoFactor,
 ];
 
 pub trait ToProtoAuthenticationFeature {
@@ -26,6 +26,12 @@ impl ToDbServerConfiguration for ServerConfiguration {
             authentication_features: self
                 .authentication_features
                 .to_json_authentication_features(),
+            disallow_search_indexing: self.disallow_search_indexing,
+            media_settings: serde_json::to_value(self.media_settings.to_owned()).unwrap(),
+            authentication_settings: serde_json::to_value(
+                self.authentication_settings.to_owned().unwrap_or_default(),
+            )
+            .unwrap(),
         }
     }
 }
@@ -44,6 +50,10 @@ impl ToProtoServerConfiguration for models::ServerConfiguration {
             serde_json::from_value(self.post_settings.to_owned()).unwrap();
         let event_settings: FeatureSettings =
             serde_json::from_value(self.event_settings.to_owned()).unwrap();
+        let media_settings: MediaSettings =
+            serde_json::from_value(self.media_settings.to_owned()).unwrap();
+        let authentication_settings: AuthenticationSettings =
+            serde_json::from_value(self.authentication_settings.to_owned()).unwrap_or_default();
         let external_cdn_config: Option<ExternalCdnConfig> = self
             .external_cdn_config
             .to_owned()
@@ -58,18 +68,14 @@ impl ToProtoServerConfiguration for models::ServerConfiguration {
             group_settings: Some(group_settings),
             post_settings: Some(post_settings),
             event_settings: Some(event_settings),
-            //TODO actually add media settings to the DB models...
-            media_settings: Some(FeatureSettings {
-                visible: true,
-                default_moderation: Moderation::Unmoderated as i32,
-                default_visibility: Visibility::GlobalPublic as i32,
-                custom_title: None,
-            }),
+            media_settings: Some(media_settings),
             private_user_strategy: self.private_user_strategy.to_i32_private_user_strategy(),
             authentication_features: self
                 .authentication_features
                 .to_i32_authentication_features(),
             external_cdn_config: external_cdn_config,
+            disallow_search_indexing: self.disallow_search_indexing,
+            authentication_settings: Some(authentication_settings),
             // ..Default::default()
         }
     }
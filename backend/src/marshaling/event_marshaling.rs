@@ -45,7 +45,10 @@ impl ToProtoEvent for models::Event {
         // self.to_group_proto(username, None)
         Event {
             id: self.id.to_proto_id(),
-            post: Some(post.to_proto(user.map(|u| u.username.to_owned()))),
+            post: Some(post.to_proto(
+                user.map(|u| u.username.to_owned()),
+                user.map(|u| u.permissions.to_owned()),
+            )),
             instances: instances.iter().map(|(i, p, u)| i.to_proto(p, u)).collect(),
             info: Some(EventInfo {
                 // start_time: self.start_time.map(|t| t.to_proto()),
@@ -72,7 +75,12 @@ impl ToProtoEventInstance for models::EventInstance {
         EventInstance {
             id: self.id.to_proto_id(),
             event_id: self.event_id.to_proto_id(),
-            post: post.map(|p| p.to_proto(user.map(|u| u.username.to_owned()))),
+            post: post.map(|p| {
+                p.to_proto(
+                    user.map(|u| u.username.to_owned()),
+                    user.map(|u| u.permissions.to_owned()),
+                )
+            }),
             starts_at: Some(self.starts_at.to_proto()),
             ends_at: Some(self.ends_at.to_proto()),
             info: Some(EventInstanceInfo {
@@ -0,0 +1,39 @@
+pub trait ToEscapedHtml {
+    fn to_escaped_html(&self) -> String;
+}
+impl ToEscapedHtml for str {
+    fn to_escaped_html(&self) -> String {
+        let mut escaped = String::with_capacity(self.len());
+        for c in self.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
+}
+impl ToEscapedHtml for String {
+    fn to_escaped_html(&self) -> String {
+        self.as_str().to_escaped_html()
+    }
+}
+
+/// Converts plain text (or Markdown) to simple HTML paragraphs, escaping everything.
+pub trait ToHtmlParagraphs {
+    fn to_html_paragraphs(&self) -> String;
+}
+impl ToHtmlParagraphs for str {
+    fn to_html_paragraphs(&self) -> String {
+        self.split("\n\n")
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .map(|p| format!("<p>{}</p>", p.to_escaped_html().replace('\n', "<br>")))
+            .collect::<Vec<String>>()
+            .join("")
+    }
+}
@@ -18,6 +18,29 @@ impl ToProtoMedia for models::Media {
             moderation: self.moderation.to_i32_moderation(),
             generated: self.generated,
             processed: self.processed,
+            size: self.size as u64,
+            alt_text: self.alt_text.to_owned(),
+            created_at: Some(self.created_at.to_proto()),
+            updated_at: Some(self.updated_at.to_proto()),
+        }
+    }
+}
+
+pub trait ToProtoMediaAlbum {
+    fn to_proto(&self, media_ids: &[i64]) -> MediaAlbum;
+}
+
+impl ToProtoMediaAlbum for models::MediaAlbum {
+    fn to_proto(&self, media_ids: &[i64]) -> MediaAlbum {
+        MediaAlbum {
+            id: self.id.to_proto_id(),
+            user_id: self.user_id.to_proto_id(),
+            name: self.name.to_owned(),
+            description: self.description.to_owned(),
+            media_ids: media_ids.iter().map(|id| id.to_proto_id()).collect(),
+            cover_media_id: self.cover_media_id.map(|id| id.to_proto_id()),
+            visibility: self.visibility.to_i32_visibility(),
+            moderation: self.moderation.to_i32_moderation(),
             created_at: Some(self.created_at.to_proto()),
             updated_at: Some(self.updated_at.to_proto()),
         }
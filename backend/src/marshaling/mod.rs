@@ -16,6 +16,9 @@ pub use configuration_enum_marshaling::*;
 mod configuration_marshaling;
 pub use configuration_marshaling::*;
 
+mod html_marshaling;
+pub use html_marshaling::*;
+
 mod listing_type_marshaling;
 pub use listing_type_marshaling::*;
 
@@ -36,3 +39,9 @@ pub use link_marshaling::*;
 
 mod event_marshaling;
 pub use event_marshaling::*;
+
+mod activitypub_marshaling;
+pub use activitypub_marshaling::*;
+
+mod auth_marshaling;
+pub use auth_marshaling::*;
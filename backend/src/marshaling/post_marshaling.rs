@@ -5,36 +5,44 @@ use itertools::Itertools;
 use tonic::Code;
 use tonic::Status;
 
-use super::{ToI32Moderation, ToI32Visibility, ToLink, ToProtoId, ToProtoTime};
+use super::{ToI32Moderation, ToI32Visibility, ToLink, ToProtoId, ToProtoPermissions, ToProtoTime};
 use crate::db_connection::{PgPooledConnection};
 use crate::models;
 use crate::protos::*;
 use crate::rpcs::validations::PASSING_MODERATIONS;
 use crate::schema::{group_posts, groups, posts};
 
+/// Posts are marshaled with their author's username and permissions (for `Author.bot`),
+/// i.e. `users::username.nullable()` and `users::permissions.nullable()` joined on `posts::user_id`.
 pub trait ToProtoPost {
-    fn to_proto(&self, username: Option<String>) -> Post;
+    fn to_proto(&self, username: Option<String>, author_permissions: Option<serde_json::Value>) -> Post;
     fn to_group_proto(
         &self,
         username: Option<String>,
+        author_permissions: Option<serde_json::Value>,
         group_post: Option<&models::GroupPost>,
     ) -> Post;
-    fn proto_author(&self, username: Option<String>) -> Option<Author>;
+    fn proto_author(
+        &self,
+        username: Option<String>,
+        author_permissions: Option<serde_json::Value>,
+    ) -> Option<Author>;
 }
 
 impl ToProtoPost for models::Post {
-    fn to_proto(&self, username: Option<String>) -> Post {
-        self.to_group_proto(username, None)
+    fn to_proto(&self, username: Option<String>, author_permissions: Option<serde_json::Value>) -> Post {
+        self.to_group_proto(username, author_permissions, None)
     }
     fn to_group_proto(
         &self,
         username: Option<String>,
+        author_permissions: Option<serde_json::Value>,
         group_post: Option<&models::GroupPost>,
     ) -> Post {
         Post {
             id: self.id.to_proto_id(),
             reply_to_post_id: self.parent_post_id.map(|i| i.to_proto_id()),
-            author: self.proto_author(username),
+            author: self.proto_author(username, author_permissions),
 
             title: self.title.to_owned(),
             link: self.link.to_link(),
@@ -64,10 +72,17 @@ impl ToProtoPost for models::Post {
             last_activity_at: Some(self.last_activity_at.to_proto()),
         }
     }
-    fn proto_author(&self, username: Option<String>) -> Option<Author> {
+    fn proto_author(
+        &self,
+        username: Option<String>,
+        author_permissions: Option<serde_json::Value>,
+    ) -> Option<Author> {
         self.user_id.map(|user_id| Author {
             user_id: user_id.to_proto_id(),
             username: username,
+            bot: author_permissions.map_or(false, |permissions| {
+                permissions.to_proto_permissions().contains(&Permission::RunBots)
+            }),
         })
     }
 }
@@ -22,3 +22,24 @@ impl ToDbTime for Timestamp {
         UNIX_EPOCH + std::time::Duration::from_secs(self.seconds as u64)
     }
 }
+
+pub trait ToRfc3339Time {
+    fn to_rfc3339(&self) -> String;
+}
+
+impl ToRfc3339Time for SystemTime {
+    fn to_rfc3339(&self) -> String {
+        chrono::DateTime::<chrono::Utc>::from(*self)
+            .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+    }
+}
+
+pub trait ToRfc2822Time {
+    fn to_rfc2822(&self) -> String;
+}
+
+impl ToRfc2822Time for SystemTime {
+    fn to_rfc2822(&self) -> String {
+        chrono::DateTime::<chrono::Utc>::from(*self).to_rfc2822()
+    }
+}
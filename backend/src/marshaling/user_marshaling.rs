@@ -47,6 +47,7 @@ impl ToProtoUser for models::User {
             email: email,
             phone: phone,
             permissions: self.permissions.to_i32_permissions(),
+            bot: self.permissions.to_proto_permissions().contains(&Permission::RunBots),
             bio: self.bio.to_owned(),
             avatar_media_id: self.avatar_media_id.to_owned().map(|id| id.to_proto_id()),
             visibility: self.visibility.to_proto_visibility().unwrap() as i32,
@@ -0,0 +1,199 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use diesel::*;
+use diesel::pg::PgConnection;
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::db_connection::PgPooledConnection;
+use crate::media_store::MediaStore;
+use crate::models;
+use crate::schema::{media, media_blobs, media_variants};
+
+const HASH_CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+
+/// An upload spooled to a local temp file (deleted on drop), along with its SHA-256.
+pub struct SpooledUpload {
+    pub file: NamedTempFile,
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl SpooledUpload {
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// Opens the spooled data for uploading.
+    pub async fn reader(&self) -> std::io::Result<tokio::fs::File> {
+        tokio::fs::File::open(self.path()).await
+    }
+}
+
+/// A new path to store a blob with the given (hex) SHA-256 at in the `MediaStore`. Each stored
+/// copy gets its own path, so a released blob's object being deleted can never take a newer
+/// blob of the same data with it.
+pub fn blob_path(sha256: &str) -> String {
+    format!("blobs/{}/{}-{}", &sha256[..2], sha256, Uuid::new_v4().simple())
+}
+
+/// Writes `data` to a temp file, hashing it along the way.
+pub async fn spool_upload<R: AsyncRead + Unpin>(data: &mut R) -> std::io::Result<SpooledUpload> {
+    let spooled = NamedTempFile::new()?;
+    let mut file = tokio::fs::File::from_std(spooled.reopen()?);
+    let mut hasher = Sha256::new();
+    let mut size = 0u64;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let read = data.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        file.write_all(&buf[..read]).await?;
+        size += read as u64;
+    }
+    file.flush().await?;
+    Ok(SpooledUpload {
+        file: spooled,
+        sha256: format!("{:x}", hasher.finalize()),
+        size,
+    })
+}
+
+/// Hashes an already-stored object (i.e. a completed multipart upload) in ranged chunks.
+pub async fn hash_object(
+    media_store: &dyn MediaStore,
+    path: &str,
+    size: u64,
+) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut start = 0u64;
+    while start < size {
+        let end = (start + HASH_CHUNK_SIZE).min(size) - 1;
+        hasher.update(media_store.get_range(path, start, end).await?);
+        start = end + 1;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Adds a reference to the stored, still-referenced blob with the given SHA-256, if any, so
+/// uploads of data matching it can skip storing anything. Should be called in the same
+/// transaction that inserts the referencing `Media`. The update locks the blob's row, so it
+/// can't race with `release_blob` deleting it.
+pub fn reference_existing_blob(
+    sha256: &str,
+    conn: &mut PgConnection,
+) -> Result<Option<models::MediaBlob>, diesel::result::Error> {
+    update(
+        media_blobs::table
+            .filter(media_blobs::sha256.eq(sha256))
+            .filter(media_blobs::ref_count.gt(0)),
+    )
+    .set(media_blobs::ref_count.eq(media_blobs::ref_count + 1))
+    .get_result::<models::MediaBlob>(conn)
+    .optional()
+}
+
+/// Adds a reference to the blob with the given SHA-256, creating it (at `minio_path`, where the
+/// data must already be stored) if needed. Should be called in the same transaction that
+/// inserts the referencing `Media`, whose `minio_path` should be taken from the returned blob.
+/// If that isn't `minio_path`, an identical blob already existed, and the caller should delete
+/// its own object once the transaction commits.
+pub fn reference_blob(
+    sha256: &str,
+    minio_path: &str,
+    size: i64,
+    conn: &mut PgConnection,
+) -> Result<models::MediaBlob, diesel::result::Error> {
+    insert_into(media_blobs::table)
+        .values(&models::NewMediaBlob {
+            sha256: sha256.to_string(),
+            minio_path: minio_path.to_string(),
+            size,
+            ref_count: 1,
+        })
+        .on_conflict(media_blobs::sha256)
+        .do_update()
+        .set(media_blobs::ref_count.eq(media_blobs::ref_count + 1))
+        .get_result::<models::MediaBlob>(conn)
+}
+
+/// Drops a reference to a blob. If it was the last one, the blob's row is deleted and
+/// returned, and the caller should delete its object (and anything derived from it).
+pub fn release_blob(
+    blob_id: i64,
+    conn: &mut PgConnection,
+) -> Result<Option<models::MediaBlob>, diesel::result::Error> {
+    let ref_count = update(media_blobs::table.find(blob_id))
+        .set(media_blobs::ref_count.eq(media_blobs::ref_count - 1))
+        .returning(media_blobs::ref_count)
+        .get_result::<i32>(conn)?;
+    if ref_count > 0 {
+        return Ok(None);
+    }
+    delete(
+        media_blobs::table
+            .filter(media_blobs::id.eq(blob_id))
+            .filter(media_blobs::ref_count.le(0)),
+    )
+    .get_result::<models::MediaBlob>(conn)
+    .optional()
+}
+
+/// Processing rewrites a blob's object in place, so media sharing an already-processed blob
+/// must not be processed again. Instead, this copies the results (the variants and thumbnail,
+/// which live alongside the blob) from a processed `Media` sharing the blob. Returns whether
+/// there was one to copy from.
+pub fn adopt_blob_processing(
+    media_id: i64,
+    blob_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<bool, diesel::result::Error> {
+    let processed = media::table
+        .filter(media::blob_id.eq(blob_id))
+        .filter(media::processed.eq(true))
+        .filter(media::id.ne(media_id))
+        .first::<models::Media>(conn)
+        .optional()?;
+    let processed = match processed {
+        Some(processed) => processed,
+        None => return Ok(false),
+    };
+    let variants = media_variants::table
+        .filter(media_variants::media_id.eq(processed.id))
+        .load::<models::MediaVariant>(conn)?
+        .into_iter()
+        .map(|variant| models::NewMediaVariant {
+            media_id,
+            size: variant.size,
+            minio_path: variant.minio_path,
+            content_type: variant.content_type,
+            width: variant.width,
+            height: variant.height,
+        })
+        .collect::<Vec<_>>();
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        delete(media_variants::table.filter(media_variants::media_id.eq(media_id)))
+            .execute(conn)?;
+        insert_into(media_variants::table)
+            .values(&variants)
+            .execute(conn)?;
+        update(media::table.find(media_id))
+            .set((
+                media::processed.eq(true),
+                media::content_type.eq(&processed.content_type),
+                media::size.eq(processed.size),
+                media::thumbnail_minio_path.eq(&processed.thumbnail_minio_path),
+                media::thumbnail_content_type.eq(&processed.thumbnail_content_type),
+                media::updated_at.eq(SystemTime::now()),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })?;
+    Ok(true)
+}
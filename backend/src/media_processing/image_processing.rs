@@ -0,0 +1,120 @@
+use std::io::Cursor;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageOutputFormat};
+
+use super::{MEDIA_SIZES, THUMBNAIL_SIZE};
+
+pub struct EncodedImage {
+    pub data: Vec<u8>,
+    pub content_type: &'static str,
+    pub extension: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct ProcessedImage {
+    /// The original re-encoded with orientation applied and all metadata (EXIF, GPS, etc.) removed.
+    pub original: EncodedImage,
+    /// Named size variants, from `MEDIA_SIZES`.
+    pub variants: Vec<(&'static str, EncodedImage)>,
+    pub thumbnail: EncodedImage,
+}
+
+/// Decodes an uploaded image and produces stripped, resized copies of it.
+/// This is CPU-bound; call it from `spawn_blocking`.
+pub fn process_image(data: &[u8], content_type: &str) -> anyhow::Result<ProcessedImage> {
+    let format = match content_type {
+        "image/jpeg" => ImageFormat::Jpeg,
+        "image/png" => ImageFormat::Png,
+        "image/webp" => ImageFormat::WebP,
+        _ => anyhow::bail!("unsupported content type {}", content_type),
+    };
+    let image = image::load_from_memory_with_format(data, format)?;
+    // Decoding drops metadata, so the orientation has to be baked into the pixels.
+    let image = apply_orientation(image, exif_orientation(data));
+    let has_alpha = image.color().has_alpha();
+
+    // Re-encoding is what actually strips EXIF/GPS data from the original.
+    let original = match format {
+        ImageFormat::Png => encode(&image, ImageOutputFormat::Png)?,
+        ImageFormat::WebP => encode(&image, ImageOutputFormat::WebP)?,
+        _ => encode(&image, ImageOutputFormat::Jpeg(90))?,
+    };
+
+    let (width, height) = image.dimensions();
+    let mut variants = vec![];
+    for (size, max_dimension) in MEDIA_SIZES {
+        if width.max(height) <= max_dimension {
+            continue;
+        }
+        let resized = image.resize(max_dimension, max_dimension, FilterType::Lanczos3);
+        variants.push((size, encode_variant(&resized, has_alpha)?));
+    }
+
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    let thumbnail = encode(&thumbnail, ImageOutputFormat::WebP)?;
+
+    Ok(ProcessedImage {
+        original,
+        variants,
+        thumbnail,
+    })
+}
+
+/// Photos are served as JPEG; images with transparency as (lossless) WebP.
+fn encode_variant(image: &DynamicImage, has_alpha: bool) -> anyhow::Result<EncodedImage> {
+    if has_alpha {
+        encode(image, ImageOutputFormat::WebP)
+    } else {
+        encode(image, ImageOutputFormat::Jpeg(85))
+    }
+}
+
+fn encode(image: &DynamicImage, format: ImageOutputFormat) -> anyhow::Result<EncodedImage> {
+    let (content_type, extension) = match format {
+        ImageOutputFormat::Png => ("image/png", "png"),
+        ImageOutputFormat::WebP => ("image/webp", "webp"),
+        ImageOutputFormat::Jpeg(_) => ("image/jpeg", "jpg"),
+        _ => anyhow::bail!("unsupported output format"),
+    };
+    // JPEG has no alpha channel.
+    let image = match format {
+        ImageOutputFormat::Jpeg(_) => DynamicImage::ImageRgb8(image.to_rgb8()),
+        _ => image.to_owned(),
+    };
+    let mut data = Cursor::new(vec![]);
+    image.write_to(&mut data, format)?;
+    Ok(EncodedImage {
+        data: data.into_inner(),
+        content_type,
+        extension,
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+/// Reads the EXIF orientation (1-8) of an image, defaulting to 1 (upright).
+fn exif_orientation(data: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .unwrap_or(1)
+}
+
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
@@ -0,0 +1,26 @@
+//! Background processing of uploaded images: applying and stripping EXIF metadata,
+//! generating resized variants and a thumbnail, and marking `media.processed`.
+//! Also expires abandoned resumable uploads, and manages the content-addressed blobs
+//! (see `blobs`) that uploaded media is stored in.
+
+mod blobs;
+pub use blobs::*;
+
+mod image_processing;
+pub use image_processing::*;
+
+mod worker;
+pub use worker::*;
+
+mod upload_expiry;
+pub use upload_expiry::*;
+
+/// Size variants generated for processed images, by name and maximum dimension (in pixels).
+/// Variants are only generated when the original is larger.
+pub const MEDIA_SIZES: [(&str, u32); 3] = [("small", 320), ("medium", 800), ("large", 1600)];
+
+/// Maximum dimension of generated thumbnails, served with `?size=thumbnail`.
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+/// Content types the worker knows how to process. Everything else is left unprocessed.
+pub const PROCESSABLE_CONTENT_TYPES: [&str; 3] = ["image/jpeg", "image/png", "image/webp"];
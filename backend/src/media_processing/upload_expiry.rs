@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use diesel::*;
+use tokio::task::JoinHandle;
+
+use crate::db_connection::{PgPool, PgPooledConnection};
+use crate::media_store::MediaStore;
+use crate::models;
+use crate::schema::media_uploads;
+
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Periodically aborts resumable uploads (see `web::media_uploads`) that have expired,
+/// so their parts don't linger in the `MediaStore`.
+pub fn start_media_upload_expiry_worker(
+    pool: Arc<PgPool>,
+    media_store: Arc<dyn MediaStore>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EXPIRY_INTERVAL);
+        loop {
+            interval.tick().await;
+            let mut conn = match pool.get() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    log::warn!("Upload expiry worker failed to get connection: {:?}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = expire_uploads(&mut conn, media_store.as_ref()).await {
+                log::warn!("Upload expiry worker error: {:?}", e);
+            }
+        }
+    })
+}
+
+async fn expire_uploads(
+    conn: &mut PgPooledConnection,
+    media_store: &dyn MediaStore,
+) -> anyhow::Result<()> {
+    let expired = media_uploads::table
+        .filter(media_uploads::expires_at.le(diesel::dsl::now))
+        .load::<models::MediaUpload>(conn)?;
+
+    for upload in expired {
+        // The store may have already dropped it; either way, the session is gone.
+        if let Err(e) = media_store
+            .abort_multipart(&upload.minio_path, &upload.s3_upload_id)
+            .await
+        {
+            log::warn!("Failed to abort expired upload {}: {:?}", upload.id, e);
+        }
+        delete(media_uploads::table.find(upload.id)).execute(conn)?;
+        log::info!("Expired upload {}", upload.id);
+    }
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use diesel::*;
+use diesel::pg::PgConnection;
+use tokio::task::JoinHandle;
+
+use super::*;
+use crate::db_connection::{PgPool, PgPooledConnection};
+use crate::marshaling::*;
+use crate::media_store::MediaStore;
+use crate::models;
+use crate::protos::Moderation;
+use crate::schema::{media, media_variants};
+
+const PROCESSING_INTERVAL: Duration = Duration::from_secs(15);
+const PROCESSING_BATCH_SIZE: i64 = 10;
+/// Failed processing is retried this many times (with exponential backoff from
+/// `PROCESSING_RETRY_BACKOFF`) before the worker gives up on the media.
+const MAX_PROCESSING_ATTEMPTS: i32 = 5;
+const PROCESSING_RETRY_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Periodically picks up unprocessed image uploads and processes them.
+pub fn start_media_processing_worker(
+    pool: Arc<PgPool>,
+    media_store: Arc<dyn MediaStore>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PROCESSING_INTERVAL);
+        loop {
+            interval.tick().await;
+            let mut conn = match pool.get() {
+                Ok(conn) => conn,
+                Err(e) => {
+                    log::warn!("Media processing worker failed to get connection: {:?}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = process_pending(&mut conn, media_store.as_ref()).await {
+                log::warn!("Media processing worker error: {:?}", e);
+            }
+        }
+    })
+}
+
+async fn process_pending(
+    conn: &mut PgPooledConnection,
+    media_store: &dyn MediaStore,
+) -> anyhow::Result<()> {
+    let pending = media::table
+        .filter(media::processed.eq(false))
+        .filter(media::content_type.eq_any(PROCESSABLE_CONTENT_TYPES))
+        .filter(
+            media::next_processing_at
+                .is_null()
+                .or(media::next_processing_at.le(diesel::dsl::now.nullable())),
+        )
+        .order(media::created_at.asc())
+        .limit(PROCESSING_BATCH_SIZE)
+        .load::<models::Media>(conn)?;
+
+    for media in pending {
+        if let Some(blob_id) = media.blob_id {
+            if adopt_blob_processing(media.id, blob_id, conn)? {
+                log::info!("Media {} shares an already-processed blob", media.id);
+                continue;
+            }
+        }
+        match process_media(&media, conn, media_store).await {
+            Ok(_) => log::info!("Processed media {}", media.id),
+            Err(e) => {
+                log::warn!("Failed to process media {}: {:?}", media.id, e);
+                record_processing_failure(&media, &e, conn)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Schedules a retry of failed processing, or after `MAX_PROCESSING_ATTEMPTS`, gives up and
+/// flags the media for moderation. Unprocessed images may still carry EXIF (e.g. GPS)
+/// metadata, so a `PENDING` original is then only served to its owner and admins.
+fn record_processing_failure(
+    media: &models::Media,
+    error: &anyhow::Error,
+    conn: &mut PgPooledConnection,
+) -> anyhow::Result<()> {
+    let attempts = media.processing_attempts + 1;
+    let error = format!("{:?}", error);
+    if attempts >= MAX_PROCESSING_ATTEMPTS {
+        log::error!(
+            "Giving up on processing media {} after {} attempts; flagging it for moderation",
+            media.id,
+            attempts
+        );
+        update(media::table.find(media.id))
+            .set((
+                media::processed.eq(true),
+                media::processing_attempts.eq(attempts),
+                media::processing_error.eq(Some(error)),
+                media::next_processing_at.eq(None::<SystemTime>),
+                media::moderation.eq(Moderation::Pending.to_string_moderation()),
+            ))
+            .execute(conn)?;
+    } else {
+        let backoff = PROCESSING_RETRY_BACKOFF * 2u32.pow(attempts as u32 - 1);
+        update(media::table.find(media.id))
+            .set((
+                media::processing_attempts.eq(attempts),
+                media::processing_error.eq(Some(error)),
+                media::next_processing_at.eq(Some(SystemTime::now() + backoff)),
+            ))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+async fn process_media(
+    media: &models::Media,
+    conn: &mut PgPooledConnection,
+    media_store: &dyn MediaStore,
+) -> anyhow::Result<()> {
+    let data = media_store.get(&media.minio_path).await?;
+    let content_type = media.content_type.to_owned();
+    let processed =
+        tokio::task::spawn_blocking(move || process_image(&data, &content_type)).await??;
+
+    let thumbnail_path = format!("{}.thumbnail.{}", media.minio_path, processed.thumbnail.extension);
+    media_store
+        .put(
+            &thumbnail_path,
+            &processed.thumbnail.data,
+            processed.thumbnail.content_type,
+        )
+        .await?;
+
+    let mut variants = vec![];
+    for (size, variant) in processed.variants.iter() {
+        let path = format!("{}.{}.{}", media.minio_path, size, variant.extension);
+        media_store
+            .put(&path, &variant.data, variant.content_type)
+            .await?;
+        variants.push(models::NewMediaVariant {
+            media_id: media.id,
+            size: size.to_string(),
+            minio_path: path,
+            content_type: variant.content_type.to_string(),
+            width: variant.width as i32,
+            height: variant.height as i32,
+        });
+    }
+
+    // Overwrite the original last, once everything derived from it is stored.
+    media_store
+        .put(
+            &media.minio_path,
+            &processed.original.data,
+            processed.original.content_type,
+        )
+        .await?;
+
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        delete(media_variants::table.filter(media_variants::media_id.eq(media.id)))
+            .execute(conn)?;
+        insert_into(media_variants::table)
+            .values(&variants)
+            .execute(conn)?;
+        update(media::table.find(media.id))
+            .set((
+                media::processed.eq(true),
+                media::processing_error.eq(None::<String>),
+                media::next_processing_at.eq(None::<SystemTime>),
+                media::content_type.eq(processed.original.content_type),
+                media::size.eq(processed.original.data.len() as i64),
+                media::thumbnail_minio_path.eq(Some(thumbnail_path)),
+                media::thumbnail_content_type.eq(Some(processed.thumbnail.content_type)),
+                media::updated_at.eq(SystemTime::now()),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// All stored objects derived from `media` by processing, for cleanup when it is deleted.
+pub fn derived_object_paths(media: &models::Media, conn: &mut PgConnection) -> Vec<String> {
+    let mut paths = media_variants::table
+        .select(media_variants::minio_path)
+        .filter(media_variants::media_id.eq(media.id))
+        .load::<String>(conn)
+        .unwrap_or_default();
+    if let Some(thumbnail) = &media.thumbnail_minio_path {
+        paths.push(thumbnail.to_owned());
+    }
+    paths
+}
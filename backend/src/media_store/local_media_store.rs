@@ -0,0 +1,230 @@
+use std::io::{ErrorKind, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use uuid::Uuid;
+
+use super::MediaStore;
+
+/// Stores media in a directory on the local filesystem. Objects are written to a temp file
+/// and renamed into place, so readers never see partial objects. Multipart uploads keep
+/// their parts under `.multipart/<upload_id>/` until completed.
+pub struct LocalMediaStore {
+    root: PathBuf,
+}
+
+impl LocalMediaStore {
+    /// Uses (and creates, if necessary) the directory `root`.
+    pub async fn create(root: impl Into<PathBuf>) -> anyhow::Result<LocalMediaStore> {
+        let root = root.into();
+        tokio::fs::create_dir_all(root.join(".tmp")).await?;
+        tokio::fs::create_dir_all(root.join(".multipart")).await?;
+        Ok(LocalMediaStore { root })
+    }
+
+    /// The file for `path`, which must be relative and can't escape the root.
+    fn resolve(&self, path: &str) -> anyhow::Result<PathBuf> {
+        let relative = Path::new(path);
+        if path.is_empty()
+            || path.starts_with('.')
+            || !relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            anyhow::bail!("invalid media path {}", path);
+        }
+        Ok(self.root.join(relative))
+    }
+
+    fn multipart_dir(&self, upload_id: &str) -> anyhow::Result<PathBuf> {
+        Uuid::parse_str(upload_id)?;
+        Ok(self.root.join(".multipart").join(upload_id))
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        self.root.join(".tmp").join(Uuid::new_v4().to_string())
+    }
+
+    /// Moves a fully-written temp file to `path`.
+    async fn commit(&self, temp_path: &Path, path: &str) -> anyhow::Result<()> {
+        let target = self.resolve(path)?;
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        if let Err(e) = tokio::fs::rename(temp_path, &target).await {
+            let _ = tokio::fs::remove_file(temp_path).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MediaStore for LocalMediaStore {
+    async fn put(&self, path: &str, data: &[u8], _content_type: &str) -> anyhow::Result<()> {
+        self.resolve(path)?;
+        let temp_path = self.temp_path();
+        tokio::fs::write(&temp_path, data).await?;
+        self.commit(&temp_path, path).await
+    }
+
+    async fn put_stream(
+        &self,
+        path: &str,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+    ) -> anyhow::Result<()> {
+        self.resolve(path)?;
+        let temp_path = self.temp_path();
+        let mut file = tokio::fs::File::create(&temp_path).await?;
+        let copied = tokio::io::copy(reader, &mut file).await;
+        let flushed = file.flush().await;
+        if let Err(e) = copied.map(|_| ()).and(flushed) {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e.into());
+        }
+        self.commit(&temp_path, path).await
+    }
+
+    async fn get(&self, path: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(tokio::fs::read(self.resolve(path)?).await?)
+    }
+
+    async fn get_range(&self, path: &str, start: u64, end: u64) -> anyhow::Result<Vec<u8>> {
+        let mut file = tokio::fs::File::open(self.resolve(path)?).await?;
+        let size = file.metadata().await?.len();
+        if start > end || start >= size {
+            anyhow::bail!("range {}-{} not satisfiable for {}", start, end, path);
+        }
+        file.seek(SeekFrom::Start(start)).await?;
+        let mut data = Vec::with_capacity((end.min(size - 1) - start + 1) as usize);
+        file.take(end - start + 1).read_to_end(&mut data).await?;
+        Ok(data)
+    }
+
+    async fn get_to_writer(
+        &self,
+        path: &str,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> anyhow::Result<()> {
+        let mut file = tokio::fs::File::open(self.resolve(path)?).await?;
+        tokio::io::copy(&mut file, writer).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+
+    async fn size(&self, path: &str) -> anyhow::Result<u64> {
+        Ok(tokio::fs::metadata(self.resolve(path)?).await?.len())
+    }
+
+    async fn delete(&self, path: &str) -> anyhow::Result<()> {
+        match tokio::fs::remove_file(self.resolve(path)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn start_multipart(&self, path: &str, _content_type: &str) -> anyhow::Result<String> {
+        self.resolve(path)?;
+        let upload_id = Uuid::new_v4().to_string();
+        tokio::fs::create_dir_all(self.multipart_dir(&upload_id)?).await?;
+        Ok(upload_id)
+    }
+
+    async fn put_part(
+        &self,
+        _path: &str,
+        upload_id: &str,
+        part_number: u32,
+        data: Vec<u8>,
+        _content_type: &str,
+    ) -> anyhow::Result<String> {
+        let dir = self.multipart_dir(upload_id)?;
+        if tokio::fs::metadata(&dir).await.is_err() {
+            anyhow::bail!("no such upload {}", upload_id);
+        }
+        let temp_path = self.temp_path();
+        tokio::fs::write(&temp_path, &data).await?;
+        tokio::fs::rename(&temp_path, dir.join(part_number.to_string())).await?;
+        Ok(format!("{:x}", Sha256::digest(&data)))
+    }
+
+    async fn complete_multipart(
+        &self,
+        path: &str,
+        upload_id: &str,
+        parts: Vec<(u32, String)>,
+    ) -> anyhow::Result<()> {
+        self.resolve(path)?;
+        let dir = self.multipart_dir(upload_id)?;
+        let temp_path = self.temp_path();
+        let mut file = tokio::fs::File::create(&temp_path).await?;
+        for (part_number, etag) in parts {
+            let data = tokio::fs::read(dir.join(part_number.to_string())).await;
+            let data = match data {
+                Ok(data) if format!("{:x}", Sha256::digest(&data)) == etag => data,
+                _ => {
+                    let _ = tokio::fs::remove_file(&temp_path).await;
+                    anyhow::bail!("invalid part {} of upload {}", part_number, upload_id);
+                }
+            };
+            file.write_all(&data).await?;
+        }
+        file.flush().await?;
+        self.commit(&temp_path, path).await?;
+        tokio::fs::remove_dir_all(dir).await?;
+        Ok(())
+    }
+
+    async fn abort_multipart(&self, _path: &str, upload_id: &str) -> anyhow::Result<()> {
+        match tokio::fs::remove_dir_all(self.multipart_dir(upload_id)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn local_media_store_works() {
+        let root = tempfile::tempdir().unwrap();
+        let store = LocalMediaStore::create(root.path()).await.unwrap();
+
+        store.put("user/1/a.txt", b"hello world", "text/plain").await.unwrap();
+        assert_eq!(store.get("user/1/a.txt").await.unwrap(), b"hello world");
+        assert_eq!(store.size("user/1/a.txt").await.unwrap(), 11);
+        assert_eq!(store.get_range("user/1/a.txt", 6, 100).await.unwrap(), b"world");
+        assert!(store.get_range("user/1/a.txt", 11, 20).await.is_err());
+
+        store.put_stream("b.txt", &mut &b"streamed"[..]).await.unwrap();
+        let mut written = vec![];
+        store.get_to_writer("b.txt", &mut written).await.unwrap();
+        assert_eq!(written, b"streamed");
+
+        store.delete("user/1/a.txt").await.unwrap();
+        store.delete("user/1/a.txt").await.unwrap();
+        assert!(store.get("user/1/a.txt").await.is_err());
+        assert!(store.get("../outside").await.is_err());
+        assert!(store.get(".tmp/x").await.is_err());
+
+        let upload_id = store.start_multipart("c.txt", "text/plain").await.unwrap();
+        let second = store
+            .put_part("c.txt", &upload_id, 2, b"two".to_vec(), "text/plain")
+            .await
+            .unwrap();
+        let first = store
+            .put_part("c.txt", &upload_id, 1, b"one ".to_vec(), "text/plain")
+            .await
+            .unwrap();
+        store
+            .complete_multipart("c.txt", &upload_id, vec![(1, first), (2, second)])
+            .await
+            .unwrap();
+        assert_eq!(store.get("c.txt").await.unwrap(), b"one two");
+        store.abort_multipart("c.txt", &upload_id).await.unwrap();
+    }
+}
@@ -0,0 +1,113 @@
+//! Where media objects (uploads, processed variants, thumbnails and link previews) are stored.
+//! `MEDIA_STORE` selects the backend:
+//! - `s3` (the default) uses the MinIO/S3 bucket configured by the `MINIO_*` env vars.
+//! - `local` uses a directory on the local filesystem, `MEDIA_STORE_PATH` (default `media`).
+//!   Good for small self-hosted instances and tests that shouldn't need MinIO.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::{stream, Stream};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{env_var, minio_connection};
+
+mod local_media_store;
+pub use local_media_store::*;
+
+mod s3_media_store;
+pub use s3_media_store::*;
+
+/// Default for `MEDIA_STORE_PATH`.
+const DEFAULT_MEDIA_STORE_PATH: &str = "media";
+
+/// Storage for media objects, addressed by `/`-separated paths like `blobs/ab/abcd...`.
+/// Implementations must behave identically: putting replaces any existing object, getting
+/// or sizing a missing object is an error, and deleting a missing object is not.
+#[async_trait]
+pub trait MediaStore: Send + Sync {
+    /// Stores `data` at `path`.
+    async fn put(&self, path: &str, data: &[u8], content_type: &str) -> anyhow::Result<()>;
+    /// Stores everything read from `reader` at `path`.
+    async fn put_stream(
+        &self,
+        path: &str,
+        reader: &mut (dyn AsyncRead + Unpin + Send),
+    ) -> anyhow::Result<()>;
+    async fn get(&self, path: &str) -> anyhow::Result<Vec<u8>>;
+    /// Bytes `start` through `end` (inclusive) of the object at `path`.
+    async fn get_range(&self, path: &str, start: u64, end: u64) -> anyhow::Result<Vec<u8>>;
+    /// Writes the object at `path` to `writer`, without buffering all of it.
+    async fn get_to_writer(
+        &self,
+        path: &str,
+        writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> anyhow::Result<()>;
+    /// Size of the object at `path`, in bytes.
+    async fn size(&self, path: &str) -> anyhow::Result<u64>;
+    async fn delete(&self, path: &str) -> anyhow::Result<()>;
+
+    /// Starts a multipart upload to `path`, returning its ID.
+    async fn start_multipart(&self, path: &str, content_type: &str) -> anyhow::Result<String>;
+    /// Stores (or replaces) part `part_number` (from 1) of a multipart upload, returning its ETag.
+    async fn put_part(
+        &self,
+        path: &str,
+        upload_id: &str,
+        part_number: u32,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> anyhow::Result<String>;
+    /// Assembles the given `(part_number, etag)`s, in order, into the object at `path`.
+    async fn complete_multipart(
+        &self,
+        path: &str,
+        upload_id: &str,
+        parts: Vec<(u32, String)>,
+    ) -> anyhow::Result<()>;
+    /// Discards a multipart upload and its parts.
+    async fn abort_multipart(&self, path: &str, upload_id: &str) -> anyhow::Result<()>;
+}
+
+/// Connects to the `MediaStore` selected by `MEDIA_STORE`.
+pub async fn get_media_store() -> anyhow::Result<Arc<dyn MediaStore>> {
+    match env_var("MEDIA_STORE").as_deref().unwrap_or("s3") {
+        "s3" | "minio" => {
+            let bucket = minio_connection::get_and_test_bucket().await?;
+            Ok(Arc::new(S3MediaStore::new(bucket)))
+        }
+        "local" => {
+            let root = env_var("MEDIA_STORE_PATH")
+                .unwrap_or_else(|| DEFAULT_MEDIA_STORE_PATH.to_string());
+            log::info!("Storing media locally in {}", root);
+            Ok(Arc::new(LocalMediaStore::create(root).await?))
+        }
+        other => anyhow::bail!("Unknown MEDIA_STORE: {}", other),
+    }
+}
+
+/// Streams bytes `start` through `end` (inclusive) of the object at `path`, fetching
+/// `chunk_size` bytes at a time.
+pub fn stream_range(
+    store: Arc<dyn MediaStore>,
+    path: String,
+    start: u64,
+    end: u64,
+    chunk_size: u64,
+) -> impl Stream<Item = std::io::Result<bytes::Bytes>> + Send {
+    stream::try_unfold(start, move |offset| {
+        let store = store.clone();
+        let path = path.clone();
+        async move {
+            if offset > end {
+                return Ok(None);
+            }
+            let chunk_end = (offset + chunk_size - 1).min(end);
+            let data = store
+                .get_range(&path, offset, chunk_end)
+                .await
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            Ok::<_, std::io::Error>(Some((bytes::Bytes::from(data), chunk_end + 1)))
+        }
+    })
+}
@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use s3::serde_types::Part;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use super::MediaStore;
+
+/// Stores media in a MinIO/S3 bucket.
+pub struct S3MediaStore {
+    bucket: s3::Bucket,
+}
+
+impl S3MediaStore {
+    pub fn new(bucket: s3::Bucket) -> S3MediaStore {
+        S3MediaStore { bucket }
+    }
+}
+
+fn check_status(status_code: u16, action: &str, path: &str) -> anyhow::Result<()> {
+    match status_code {
+        200..=299 => Ok(()),
+        _ => anyhow::bail!("status {} for {} {}", status_code, action, path),
+    }
+}
+
+#[async_trait]
+impl MediaStore for S3MediaStore {
+    async fn put(&self, path: &str, data: &[u8], content_type: &str) -> anyhow::Result<()> {
+        let response = self
+            .bucket
+            .put_object_with_content_type(path, data, content_type)
+            .await?;
+        check_status(response.status_code(), "PUT", path)
+    }
+
+    async fn put_stream(
+        &self,
+        path: &str,
+        mut reader: &mut (dyn AsyncRead + Unpin + Send),
+    ) -> anyhow::Result<()> {
+        let status_code = self.bucket.put_object_stream(&mut reader, path).await?;
+        check_status(status_code, "PUT", path)
+    }
+
+    async fn get(&self, path: &str) -> anyhow::Result<Vec<u8>> {
+        let response = self.bucket.get_object(path).await?;
+        check_status(response.status_code(), "GET", path)?;
+        Ok(response.bytes().to_vec())
+    }
+
+    async fn get_range(&self, path: &str, start: u64, end: u64) -> anyhow::Result<Vec<u8>> {
+        let response = self.bucket.get_object_range(path, start, Some(end)).await?;
+        check_status(response.status_code(), "GET", path)?;
+        Ok(response.bytes().to_vec())
+    }
+
+    async fn get_to_writer(
+        &self,
+        path: &str,
+        mut writer: &mut (dyn AsyncWrite + Unpin + Send),
+    ) -> anyhow::Result<()> {
+        let status_code = self.bucket.get_object_to_writer(path, &mut writer).await?;
+        check_status(status_code, "GET", path)
+    }
+
+    async fn size(&self, path: &str) -> anyhow::Result<u64> {
+        let (head, status_code) = self.bucket.head_object(path).await?;
+        check_status(status_code, "HEAD", path)?;
+        Ok(head.content_length.unwrap_or(0).max(0) as u64)
+    }
+
+    async fn delete(&self, path: &str) -> anyhow::Result<()> {
+        let response = self.bucket.delete_object(path).await?;
+        check_status(response.status_code(), "DELETE", path)
+    }
+
+    async fn start_multipart(&self, path: &str, content_type: &str) -> anyhow::Result<String> {
+        let multipart = self
+            .bucket
+            .initiate_multipart_upload(path, content_type)
+            .await?;
+        Ok(multipart.upload_id)
+    }
+
+    async fn put_part(
+        &self,
+        path: &str,
+        upload_id: &str,
+        part_number: u32,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> anyhow::Result<String> {
+        let part = self
+            .bucket
+            .put_multipart_chunk(data, path, part_number, upload_id, content_type)
+            .await?;
+        Ok(part.etag)
+    }
+
+    async fn complete_multipart(
+        &self,
+        path: &str,
+        upload_id: &str,
+        parts: Vec<(u32, String)>,
+    ) -> anyhow::Result<()> {
+        let parts = parts
+            .into_iter()
+            .map(|(part_number, etag)| Part { etag, part_number })
+            .collect();
+        let response = self
+            .bucket
+            .complete_multipart_upload(path, upload_id, parts)
+            .await?;
+        check_status(response.status_code(), "complete upload", path)
+    }
+
+    async fn abort_multipart(&self, path: &str, upload_id: &str) -> anyhow::Result<()> {
+        self.bucket.abort_upload(path, upload_id).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use super::{Messenger, OutboundMessage};
+use crate::marshaling::ToRfc3339Time;
+use crate::protos::ContactMethodType;
+
+/// Appends messages to a file as JSON lines, instead of delivering them.
+pub struct FileMessenger {
+    path: PathBuf,
+    /// Serializes appends, so concurrent messages don't interleave.
+    lock: Mutex<()>,
+}
+
+impl FileMessenger {
+    pub fn new(path: impl Into<PathBuf>) -> FileMessenger {
+        FileMessenger {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+#[async_trait]
+impl Messenger for FileMessenger {
+    fn supports(&self, contact_method: ContactMethodType) -> bool {
+        contact_method != ContactMethodType::Unknown
+    }
+
+    async fn send(&self, message: &OutboundMessage) -> anyhow::Result<()> {
+        log::info!(
+            "Message to {} ({}): {}",
+            message.to,
+            message.contact_method.as_str_name(),
+            message.body
+        );
+        let line = serde_json::json!({
+            "contact_method": message.contact_method.as_str_name(),
+            "to": message.to,
+            "subject": message.subject,
+            "body": message.body,
+            "sent_at": SystemTime::now().to_rfc3339(),
+        });
+        let _lock = self.lock.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(format!("{}\n", line).as_bytes()).await?;
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn file_messenger_appends_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("messages.log");
+        let messenger = FileMessenger::new(&path);
+        assert!(messenger.supports(ContactMethodType::Phone));
+        for to in ["a@example.com", "b@example.com"] {
+            messenger
+                .send(&OutboundMessage {
+                    contact_method: ContactMethodType::Email,
+                    to: to.to_string(),
+                    subject: "Hello".to_string(),
+                    body: "Your code is 123456".to_string(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["to"], "b@example.com");
+        assert_eq!(lines[1]["contact_method"], "EMAIL");
+        assert_eq!(lines[1]["body"], "Your code is 123456");
+    }
+}
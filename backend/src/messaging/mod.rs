@@ -0,0 +1,69 @@
+//! Outbound messages to users' contact methods (i.e. verification codes).
+//! `MESSAGING` selects the backend:
+//! - `none` (the default) can't send anything, so no contact methods are `supported_by_server`.
+//! - `smtp` sends email via the server configured by the `SMTP_*` env vars. Phone numbers aren't supported.
+//! - `file` appends messages (as JSON lines) to `MESSAGING_FILE_PATH` (default `messages.log`)
+//!   and logs them. Supports everything; good for development and tests.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::env_var;
+use crate::protos::ContactMethodType;
+
+mod file_messenger;
+pub use file_messenger::*;
+
+mod smtp_messenger;
+pub use smtp_messenger::*;
+
+/// Default for `MESSAGING_FILE_PATH`.
+const DEFAULT_MESSAGING_FILE_PATH: &str = "messages.log";
+
+/// A message to a user's email address or phone number.
+#[derive(Debug, Clone)]
+pub struct OutboundMessage {
+    pub contact_method: ContactMethodType,
+    /// The address or number, without any `mailto:` or `tel:` prefix.
+    pub to: String,
+    /// Only used for email.
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers `OutboundMessage`s.
+#[async_trait]
+pub trait Messenger: Send + Sync {
+    /// Whether messages can be sent to this type of contact method.
+    fn supports(&self, contact_method: ContactMethodType) -> bool;
+    async fn send(&self, message: &OutboundMessage) -> anyhow::Result<()>;
+}
+
+/// Supports nothing; used when `MESSAGING` isn't configured.
+pub struct NoMessenger;
+
+#[async_trait]
+impl Messenger for NoMessenger {
+    fn supports(&self, _contact_method: ContactMethodType) -> bool {
+        false
+    }
+
+    async fn send(&self, message: &OutboundMessage) -> anyhow::Result<()> {
+        anyhow::bail!("messaging is not configured; can't send to {}", message.to)
+    }
+}
+
+pub fn get_messenger() -> anyhow::Result<Arc<dyn Messenger>> {
+    match env_var("MESSAGING").as_deref().unwrap_or("none") {
+        "none" => Ok(Arc::new(NoMessenger)),
+        "smtp" => Ok(Arc::new(SmtpMessenger::from_env()?)),
+        "file" => {
+            let path = env_var("MESSAGING_FILE_PATH")
+                .unwrap_or_else(|| DEFAULT_MESSAGING_FILE_PATH.to_string());
+            log::info!("Writing outbound messages to {}", path);
+            Ok(Arc::new(FileMessenger::new(path)))
+        }
+        other => anyhow::bail!("Unknown MESSAGING: {}", other),
+    }
+}
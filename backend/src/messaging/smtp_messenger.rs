@@ -0,0 +1,63 @@
+use async_trait::async_trait;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use super::{Messenger, OutboundMessage};
+use crate::env_var;
+use crate::protos::ContactMethodType;
+
+/// Sends email through an SMTP server.
+pub struct SmtpMessenger {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMessenger {
+    /// Configured by `SMTP_HOST`, `SMTP_FROM` (i.e. `Jonline <noreply@jonline.io>`), and optionally
+    /// `SMTP_PORT`, `SMTP_USERNAME`/`SMTP_PASSWORD` and `SMTP_TLS` (`starttls`, the default; `tls`; or `none`).
+    pub fn from_env() -> anyhow::Result<SmtpMessenger> {
+        let host = env_var("SMTP_HOST").ok_or_else(|| anyhow::anyhow!("SMTP_HOST is required"))?;
+        let from = env_var("SMTP_FROM")
+            .ok_or_else(|| anyhow::anyhow!("SMTP_FROM is required"))?
+            .parse::<Mailbox>()?;
+        let mut builder = match env_var("SMTP_TLS").as_deref().unwrap_or("starttls") {
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?,
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?,
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+            other => anyhow::bail!("Unknown SMTP_TLS: {}", other),
+        };
+        if let Some(port) = env_var("SMTP_PORT") {
+            builder = builder.port(port.parse()?);
+        }
+        if let (Some(username), Some(password)) = (env_var("SMTP_USERNAME"), env_var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+        log::info!("Sending email via SMTP server {}", host);
+        Ok(SmtpMessenger {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Messenger for SmtpMessenger {
+    fn supports(&self, contact_method: ContactMethodType) -> bool {
+        contact_method == ContactMethodType::Email
+    }
+
+    async fn send(&self, message: &OutboundMessage) -> anyhow::Result<()> {
+        if !self.supports(message.contact_method) {
+            anyhow::bail!("SMTP can't send to {}", message.contact_method.as_str_name());
+        }
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(message.to.parse::<Mailbox>()?)
+            .subject(message.subject.to_owned())
+            .header(ContentType::TEXT_PLAIN)
+            .body(message.body.to_owned())?;
+        self.transport.send(email).await?;
+        Ok(())
+    }
+}
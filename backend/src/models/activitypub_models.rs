@@ -0,0 +1,92 @@
+use std::time::SystemTime;
+
+use diesel::*;
+
+use crate::schema::{
+    activitypub_actor_keys, activitypub_deliveries, activitypub_inbox_activities,
+    activitypub_remote_followers,
+};
+
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+#[diesel(table_name = activitypub_actor_keys)]
+pub struct ActivityPubActorKey {
+    pub id: i64,
+    pub user_id: i64,
+    pub public_key_pem: String,
+    pub private_key_pem: String,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = activitypub_actor_keys)]
+pub struct NewActivityPubActorKey {
+    pub user_id: i64,
+    pub public_key_pem: String,
+    pub private_key_pem: String,
+}
+
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+#[diesel(table_name = activitypub_remote_followers)]
+pub struct ActivityPubRemoteFollower {
+    pub id: i64,
+    pub user_id: i64,
+    pub actor_uri: String,
+    pub inbox_uri: String,
+    pub shared_inbox_uri: Option<String>,
+    pub follow_activity_uri: String,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = activitypub_remote_followers)]
+pub struct NewActivityPubRemoteFollower {
+    pub user_id: i64,
+    pub actor_uri: String,
+    pub inbox_uri: String,
+    pub shared_inbox_uri: Option<String>,
+    pub follow_activity_uri: String,
+}
+
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+#[diesel(table_name = activitypub_inbox_activities)]
+pub struct ActivityPubInboxActivity {
+    pub id: i64,
+    pub activity_uri: String,
+    pub activity_type: String,
+    pub actor_uri: String,
+    pub post_id: Option<i64>,
+    pub activity: serde_json::Value,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = activitypub_inbox_activities)]
+pub struct NewActivityPubInboxActivity {
+    pub activity_uri: String,
+    pub activity_type: String,
+    pub actor_uri: String,
+    pub post_id: Option<i64>,
+    pub activity: serde_json::Value,
+}
+
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+#[diesel(table_name = activitypub_deliveries)]
+pub struct ActivityPubDelivery {
+    pub id: i64,
+    pub user_id: i64,
+    pub inbox_uri: String,
+    pub activity: serde_json::Value,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: SystemTime,
+    pub delivered_at: Option<SystemTime>,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = activitypub_deliveries)]
+pub struct NewActivityPubDelivery {
+    pub user_id: i64,
+    pub inbox_uri: String,
+    pub activity: serde_json::Value,
+}
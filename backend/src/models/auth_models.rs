@@ -0,0 +1,260 @@
+use std::time::SystemTime;
+
+use diesel::*;
+
+use crate::schema::{
+    account_lockouts, contact_method_verifications, invite_codes, linked_identities,
+    oidc_login_completions, oidc_login_states, password_reset_tokens, two_factor_challenges,
+    user_api_tokens, user_devices, user_recovery_codes, user_refresh_tokens, user_totps,
+};
+
+/// A code (see `rpcs::create_invite_code`) required to create an account when the server
+/// has the `INVITE_ONLY` authentication feature.
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+pub struct InviteCode {
+    pub id: i64,
+    pub code: String,
+    pub created_by_user_id: Option<i64>,
+    pub max_uses: Option<i32>,
+    pub use_count: i32,
+    /// A serialized (by name) list of [crate::protos::Permission]s, or `None` for the server's defaults.
+    pub permissions: Option<serde_json::Value>,
+    pub expires_at: Option<SystemTime>,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = invite_codes)]
+pub struct NewInviteCode {
+    pub code: String,
+    pub created_by_user_id: Option<i64>,
+    pub max_uses: Option<i32>,
+    pub permissions: Option<serde_json::Value>,
+    pub expires_at: Option<SystemTime>,
+}
+
+/// A code sent to a user's email or phone (see `rpcs::send_contact_method_verification`).
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+pub struct ContactMethodVerification {
+    pub id: i64,
+    pub user_id: i64,
+    /// A [crate::protos::ContactMethodType] name, `EMAIL` or `PHONE`.
+    pub contact_method: String,
+    pub value: String,
+    pub code_hash: String,
+    pub attempts: i32,
+    pub expires_at: SystemTime,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = contact_method_verifications)]
+pub struct NewContactMethodVerification {
+    pub user_id: i64,
+    pub contact_method: String,
+    pub value: String,
+    pub code_hash: String,
+    pub expires_at: SystemTime,
+}
+
+/// A single-use token for resetting a user's password (see `logic::issue_password_reset`).
+#[derive(Debug, Queryable, Identifiable)]
+pub struct PasswordResetToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub token_hash: String,
+    pub expires_at: SystemTime,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = password_reset_tokens)]
+pub struct NewPasswordResetToken {
+    pub user_id: i64,
+    pub token_hash: String,
+    pub expires_at: SystemTime,
+}
+
+/// A user's TOTP authenticator. Only used for login once `confirmed_at` is set.
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+pub struct UserTotp {
+    pub id: i64,
+    pub user_id: i64,
+    pub secret: String,
+    pub confirmed_at: Option<SystemTime>,
+    pub last_used_step: Option<i64>,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = user_totps)]
+pub struct NewUserTotp {
+    pub user_id: i64,
+    pub secret: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = user_recovery_codes)]
+pub struct NewUserRecoveryCode {
+    pub user_id: i64,
+    pub code_hash: String,
+}
+
+/// A login awaiting its second factor (see `logic::create_two_factor_challenge`).
+#[derive(Debug, Queryable, Identifiable)]
+pub struct TwoFactorChallenge {
+    pub id: i64,
+    pub user_id: i64,
+    pub token_hash: String,
+    pub requested_expires_at: Option<SystemTime>,
+    pub attempts: i32,
+    pub expires_at: SystemTime,
+    pub created_at: SystemTime,
+    pub device_name: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = two_factor_challenges)]
+pub struct NewTwoFactorChallenge {
+    pub user_id: i64,
+    pub token_hash: String,
+    pub requested_expires_at: Option<SystemTime>,
+    pub expires_at: SystemTime,
+    pub device_name: Option<String>,
+}
+
+/// A refresh token, and where its session came from (see `auth::SessionMetadata`).
+/// Tokens rotated from one another share a `family_id` (see `auth::rotate_refresh_token`).
+#[derive(Debug, Queryable, Identifiable)]
+pub struct UserRefreshToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub token: String,
+    /// When the session (i.e. the token family) started.
+    pub created_at: SystemTime,
+    pub expires_at: SystemTime,
+    pub device_id: Option<i64>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_used_at: Option<SystemTime>,
+    pub family_id: i64,
+    pub rotated_at: Option<SystemTime>,
+    /// When the session ends, however often it's rotated.
+    pub session_expires_at: Option<SystemTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = user_refresh_tokens)]
+pub struct NewUserRefreshToken {
+    /// Only set when starting a family, whose `family_id` is its first token's ID.
+    pub id: Option<i64>,
+    pub user_id: i64,
+    pub token: String,
+    pub created_at: SystemTime,
+    pub expires_at: SystemTime,
+    pub device_id: Option<i64>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub last_used_at: Option<SystemTime>,
+    pub family_id: i64,
+    pub session_expires_at: Option<SystemTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = user_devices)]
+pub struct NewUserDevice {
+    pub user_id: i64,
+    pub device_name: String,
+}
+
+/// An external (OpenID Connect) identity linked to a user (see `logic::resolve_oidc_user`).
+#[derive(Debug, Queryable, Identifiable)]
+#[diesel(table_name = linked_identities)]
+pub struct LinkedIdentity {
+    pub id: i64,
+    pub user_id: i64,
+    pub provider_id: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: SystemTime,
+    pub last_login_at: Option<SystemTime>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = linked_identities)]
+pub struct NewLinkedIdentity {
+    pub user_id: i64,
+    pub provider_id: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub last_login_at: Option<SystemTime>,
+}
+
+/// An OIDC login awaiting the provider's callback (see `web::oidc`).
+#[derive(Debug, Queryable, Identifiable)]
+pub struct OidcLoginState {
+    pub id: i64,
+    pub state: String,
+    pub provider_id: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub redirect_path: String,
+    pub link_user_id: Option<i64>,
+    pub expires_at: SystemTime,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = oidc_login_states)]
+pub struct NewOidcLoginState {
+    pub state: String,
+    pub provider_id: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub redirect_path: String,
+    pub link_user_id: Option<i64>,
+    pub expires_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = oidc_login_completions)]
+pub struct NewOidcLoginCompletion {
+    pub user_id: i64,
+    pub code_hash: String,
+    pub expires_at: SystemTime,
+}
+
+/// A user's consecutive failed logins, and any resulting lockout (see `logic::record_failed_login`).
+#[derive(Debug, Queryable, Identifiable)]
+#[diesel(primary_key(user_id))]
+pub struct AccountLockout {
+    pub user_id: i64,
+    pub failed_attempts: i32,
+    pub locked_until: Option<SystemTime>,
+    pub last_failed_at: SystemTime,
+    pub last_failed_ip: Option<String>,
+}
+
+/// A personal API token for bots (see `auth::authenticate_api_token`).
+#[derive(Debug, Queryable, Identifiable)]
+pub struct UserApiToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub token_hash: String,
+    pub label: String,
+    /// A list of [crate::auth::ApiTokenScope]s.
+    pub scopes: serde_json::Value,
+    pub expires_at: Option<SystemTime>,
+    pub last_used_at: Option<SystemTime>,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = user_api_tokens)]
+pub struct NewUserApiToken {
+    pub user_id: i64,
+    pub token_hash: String,
+    pub label: String,
+    pub scopes: serde_json::Value,
+    pub expires_at: Option<SystemTime>,
+}
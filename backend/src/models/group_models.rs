@@ -6,6 +6,11 @@ use diesel::*;
 use crate::db_connection::PgPooledConnection;
 use crate::schema::{groups, memberships};
 
+sql_function! {
+    /// Postgres' `lower`, to match `shortname`s case-insensitively without `ILIKE`'s wildcards.
+    fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text;
+}
+
 pub fn get_group(group_id: i64, conn: &mut PgPooledConnection,) -> Result<Group, Status> {
     groups::table
         .select(groups::all_columns)
@@ -3,7 +3,7 @@ use std::time::SystemTime;
 use tonic::{Status, Code};
 use diesel::*;
 
-use crate::{schema::{media}, db_connection::PgPooledConnection};
+use crate::{schema::{media, media_album_items, media_albums, media_blobs, media_upload_parts, media_uploads, media_variants}, db_connection::PgPooledConnection};
 
 pub fn get_media(media_id: i64, conn: &mut PgPooledConnection,) -> Result<Media, Status> {
     media::table
@@ -42,6 +42,12 @@ pub struct Media {
     pub moderation: String,
     pub created_at: SystemTime,
     pub updated_at: SystemTime,
+    pub size: i64,
+    pub alt_text: Option<String>,
+    pub blob_id: Option<i64>,
+    pub processing_attempts: i32,
+    pub processing_error: Option<String>,
+    pub next_processing_at: Option<SystemTime>,
 }
 
 #[derive(Debug, Insertable)]
@@ -54,4 +60,146 @@ pub struct NewMedia {
     pub description: Option<String>,
     pub generated: bool,
     pub visibility: String,
+    pub size: i64,
+    pub blob_id: Option<i64>,
+}
+
+/// Uploaded data, stored once per distinct SHA-256 and shared by every `Media` referencing it.
+/// `sha256` is of the data as uploaded; processing may rewrite the object in place afterwards.
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+pub struct MediaBlob {
+    pub id: i64,
+    pub sha256: String,
+    pub minio_path: String,
+    pub size: i64,
+    pub ref_count: i32,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = media_blobs)]
+pub struct NewMediaBlob {
+    pub sha256: String,
+    pub minio_path: String,
+    pub size: i64,
+    pub ref_count: i32,
+}
+
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+pub struct MediaVariant {
+    pub id: i64,
+    pub media_id: i64,
+    pub size: String,
+    pub minio_path: String,
+    pub content_type: String,
+    pub width: i32,
+    pub height: i32,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = media_variants)]
+pub struct NewMediaVariant {
+    pub media_id: i64,
+    pub size: String,
+    pub minio_path: String,
+    pub content_type: String,
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+pub struct MediaUpload {
+    pub id: i64,
+    pub user_id: i64,
+    pub minio_path: String,
+    pub s3_upload_id: String,
+    pub content_type: String,
+    pub name: Option<String>,
+    pub created_at: SystemTime,
+    pub expires_at: SystemTime,
+    pub visibility: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = media_uploads)]
+pub struct NewMediaUpload {
+    pub user_id: i64,
+    pub minio_path: String,
+    pub s3_upload_id: String,
+    pub content_type: String,
+    pub name: Option<String>,
+    pub expires_at: SystemTime,
+    pub visibility: String,
+}
+
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+pub struct MediaUploadPart {
+    pub id: i64,
+    pub media_upload_id: i64,
+    pub part_number: i32,
+    pub etag: String,
+    pub sha256: String,
+    pub size: i64,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = media_upload_parts)]
+pub struct NewMediaUploadPart {
+    pub media_upload_id: i64,
+    pub part_number: i32,
+    pub etag: String,
+    pub sha256: String,
+    pub size: i64,
+}
+
+#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+pub struct MediaAlbum {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub cover_media_id: Option<i64>,
+    pub visibility: String,
+    pub moderation: String,
+    pub created_at: SystemTime,
+    pub updated_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = media_albums)]
+pub struct NewMediaAlbum {
+    pub user_id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub cover_media_id: Option<i64>,
+    pub visibility: String,
+    pub moderation: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = media_album_items)]
+pub struct NewMediaAlbumItem {
+    pub media_album_id: i64,
+    pub media_id: i64,
+    pub position: i32,
+}
+
+pub fn get_media_album(album_id: i64, conn: &mut PgPooledConnection) -> Result<MediaAlbum, Status> {
+    media_albums::table
+        .filter(media_albums::id.eq(album_id))
+        .first::<MediaAlbum>(conn)
+        .map_err(|_| Status::new(Code::NotFound, "media_album_not_found"))
+}
+
+/// The media in an album, in album order.
+pub fn get_media_album_media(album_id: i64, conn: &mut PgPooledConnection) -> Result<Vec<Media>, Status> {
+    media_album_items::table
+        .inner_join(media::table)
+        .select(media::all_columns)
+        .filter(media_album_items::media_album_id.eq(album_id))
+        .order((media_album_items::position.asc(), media_album_items::id.asc()))
+        .load::<Media>(conn)
+        .map_err(|_| Status::new(Code::Internal, "data_error"))
 }
@@ -4,6 +4,9 @@ pub use server_configuration_models::*;
 mod user_models;
 pub use user_models::*;
 
+mod auth_models;
+pub use auth_models::*;
+
 mod media_models;
 pub use media_models::*;
 
@@ -18,3 +21,6 @@ pub use event_loaders::*;
 
 mod event_models;
 pub use event_models::*;
+
+mod activitypub_models;
+pub use activitypub_models::*;
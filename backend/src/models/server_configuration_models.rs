@@ -2,7 +2,7 @@ use std::time::SystemTime;
 
 use crate::marshaling::ToJsonPermissions;
 use crate::protos::*;
-use crate::schema::server_configurations;
+use crate::schema::{server_configurations, server_secrets};
 
 #[derive(Debug, Queryable, Identifiable, AsChangeset)]
 pub struct ServerConfiguration {
@@ -28,6 +28,10 @@ pub struct ServerConfiguration {
 
     pub created_at: SystemTime,
     pub updated_at: SystemTime,
+
+    pub disallow_search_indexing: bool,
+    pub media_settings: serde_json::Value,
+    pub authentication_settings: serde_json::Value,
 }
 #[derive(Debug, Insertable)]
 #[diesel(table_name = server_configurations)]
@@ -43,6 +47,9 @@ pub struct NewServerConfiguration {
     pub external_cdn_config: Option<serde_json::Value>,
     pub private_user_strategy: String,
     pub authentication_features: serde_json::Value,
+    pub disallow_search_indexing: bool,
+    pub media_settings: serde_json::Value,
+    pub authentication_settings: serde_json::Value,
 }
 
 pub fn default_server_configuration() -> NewServerConfiguration {
@@ -152,5 +159,48 @@ I will, however, not sell your data to advertisers nor deliberately expose any v
             .collect::<Vec<&str>>(),
         )
         .unwrap(),
+        disallow_search_indexing: false,
+        media_settings: serde_json::to_value(MediaSettings {
+            visible: true,
+            default_moderation: Moderation::Unmoderated as i32,
+            default_visibility: Visibility::GlobalPublic as i32,
+            custom_title: None,
+            limits: vec![
+                MediaLimits {
+                    permission: Permission::Unknown as i32,
+                    max_file_size: Some(50 * 1024 * 1024),
+                    max_total_size: Some(1024 * 1024 * 1024),
+                    allowed_content_types: vec![
+                        "image/*".to_string(),
+                        "video/*".to_string(),
+                        "audio/*".to_string(),
+                    ],
+                },
+                MediaLimits {
+                    permission: Permission::Admin as i32,
+                    max_file_size: None,
+                    max_total_size: None,
+                    allowed_content_types: vec![],
+                },
+            ],
+        })
+        .unwrap(),
+        authentication_settings: serde_json::to_value(AuthenticationSettings::default()).unwrap(),
     };
 }
+
+/// A secret generated by the server (see `logic::get_or_create_server_secret`).
+#[derive(Debug, Queryable, Identifiable)]
+pub struct ServerSecret {
+    pub id: i64,
+    pub name: String,
+    pub value: String,
+    pub created_at: SystemTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = server_secrets)]
+pub struct NewServerSecret {
+    pub name: String,
+    pub value: String,
+}
@@ -14,7 +14,7 @@ pub fn get_user(user_id: i64, conn: &mut PgPooledConnection,) -> Result<User, St
         .map_err(|_| Status::new(Code::NotFound, "user_not_found"))
 }
 
-#[derive(Debug, Queryable, Identifiable, AsChangeset)]
+#[derive(Debug, Clone, Queryable, Identifiable, AsChangeset)]
 pub struct User {
     pub id: i64,
     pub username: String,
@@ -37,6 +37,8 @@ pub struct User {
     pub response_count: i32,
     pub created_at: SystemTime,
     pub updated_at: SystemTime,
+    /// Set by admins (see `rpcs::force_password_reset`); blocks login until the password is reset.
+    pub password_reset_required: bool,
 }
 
 #[derive(Debug, Queryable, Identifiable, AsChangeset)]
@@ -0,0 +1,85 @@
+use anyhow::bail;
+use reqwest::Url;
+use serde::Deserialize;
+
+use super::{oidc_client_secret, OidcProviderMetadata, OIDC_CLIENT};
+use crate::protos::OidcProvider;
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+}
+
+/// The URL to send browsers to at the provider, to log in and return to `redirect_uri` with a code.
+pub fn oidc_authorization_url(
+    provider: &OidcProvider,
+    metadata: &OidcProviderMetadata,
+    redirect_uri: &str,
+    state: &str,
+    nonce: &str,
+    code_challenge: &str,
+) -> anyhow::Result<Url> {
+    let mut scopes = vec!["openid".to_string()];
+    if provider.scopes.is_empty() {
+        scopes.extend(["profile".to_string(), "email".to_string()]);
+    } else {
+        scopes.extend(
+            provider
+                .scopes
+                .iter()
+                .filter(|scope| scope.as_str() != "openid")
+                .cloned(),
+        );
+    }
+    Ok(Url::parse_with_params(
+        &metadata.authorization_endpoint,
+        &[
+            ("response_type", "code"),
+            ("client_id", provider.client_id.as_str()),
+            ("redirect_uri", redirect_uri),
+            ("scope", scopes.join(" ").as_str()),
+            ("state", state),
+            ("nonce", nonce),
+            ("code_challenge", code_challenge),
+            ("code_challenge_method", "S256"),
+        ],
+    )?)
+}
+
+/// Exchanges an authorization code (and its PKCE verifier) at the provider's token endpoint,
+/// returning the (unverified) ID token.
+pub async fn exchange_oidc_code(
+    provider: &OidcProvider,
+    metadata: &OidcProviderMetadata,
+    redirect_uri: &str,
+    code: &str,
+    code_verifier: &str,
+) -> anyhow::Result<String> {
+    let mut request = OIDC_CLIENT.post(&metadata.token_endpoint).form(&[
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", redirect_uri),
+        ("client_id", provider.client_id.as_str()),
+        ("code_verifier", code_verifier),
+    ]);
+    if let Some(secret) = oidc_client_secret(&provider.id) {
+        request = request.basic_auth(&provider.client_id, Some(secret));
+    }
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        match response.json::<TokenErrorResponse>().await {
+            Ok(error) => bail!("token_exchange_failed: {} ({})", error.error, status),
+            Err(_) => bail!("token_exchange_failed: {}", status),
+        }
+    }
+    match response.json::<TokenResponse>().await?.id_token {
+        Some(id_token) => Ok(id_token),
+        None => bail!("token_response_missing_id_token"),
+    }
+}
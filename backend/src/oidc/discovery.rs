@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use serde::Deserialize;
+
+use super::OIDC_CLIENT;
+
+/// How long discovered provider metadata and keys are cached.
+const DISCOVERY_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+/// Minimum time between refetches when an ID token is signed by an unknown key (i.e. after the
+/// provider rotates keys), so bogus tokens can't make us hammer the provider.
+pub const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The parts of a provider's `/.well-known/openid-configuration` we use.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+/// A JSON Web Key Set, as served at a provider's `jwks_uri`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+/// A JSON Web Key. Only RSA and P-256 EC signing keys are usable (see `verify_id_token`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub kid: Option<String>,
+    pub alg: Option<String>,
+    #[serde(rename = "use")]
+    pub key_use: Option<String>,
+    pub n: Option<String>,
+    pub e: Option<String>,
+    pub crv: Option<String>,
+    pub x: Option<String>,
+    pub y: Option<String>,
+}
+
+#[derive(Clone)]
+struct DiscoveredProvider {
+    fetched_at: Instant,
+    metadata: OidcProviderMetadata,
+    jwks: Jwks,
+}
+
+lazy_static! {
+    static ref DISCOVERY_CACHE: Mutex<HashMap<String, DiscoveredProvider>> =
+        Mutex::new(HashMap::new());
+}
+
+/// The issuer's metadata and signing keys, fetched if not cached within `max_age`
+/// (`None` meaning the default TTL).
+pub async fn discover_oidc_provider(
+    issuer: &str,
+    max_age: Option<Duration>,
+) -> anyhow::Result<(OidcProviderMetadata, Jwks)> {
+    let max_age = max_age.unwrap_or(DISCOVERY_CACHE_TTL);
+    if let Some(cached) = DISCOVERY_CACHE.lock().unwrap().get(issuer) {
+        if cached.fetched_at.elapsed() < max_age {
+            return Ok((cached.metadata.clone(), cached.jwks.clone()));
+        }
+    }
+
+    let url = format!("{}/.well-known/openid-configuration", issuer.trim_end_matches('/'));
+    let metadata = OIDC_CLIENT
+        .get(&url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<OidcProviderMetadata>()
+        .await?;
+    // Per OpenID Connect Discovery, the metadata must be for the issuer we asked about.
+    if metadata.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+        bail!("issuer_mismatch: {} != {}", metadata.issuer, issuer);
+    }
+    let jwks = OIDC_CLIENT
+        .get(&metadata.jwks_uri)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<Jwks>()
+        .await?;
+    log::info!("Discovered OIDC provider {} ({} keys)", issuer, jwks.keys.len());
+
+    DISCOVERY_CACHE.lock().unwrap().insert(
+        issuer.to_string(),
+        DiscoveredProvider {
+            fetched_at: Instant::now(),
+            metadata: metadata.clone(),
+            jwks: jwks.clone(),
+        },
+    );
+    Ok((metadata, jwks))
+}
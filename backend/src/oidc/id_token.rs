@@ -0,0 +1,273 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, bail};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine as _};
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde::Deserialize;
+
+use super::{Jwk, Jwks};
+
+/// Allowed clock skew between us and providers when checking `exp` and `iat`.
+const CLOCK_SKEW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+/// The claims of a verified ID token that we use.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    /// A string or an array of strings.
+    pub aud: serde_json::Value,
+    pub exp: u64,
+    pub iat: Option<u64>,
+    pub nonce: Option<String>,
+    pub azp: Option<String>,
+    pub email: Option<String>,
+    /// A boolean, though some providers send `"true"`.
+    pub email_verified: Option<serde_json::Value>,
+    pub preferred_username: Option<String>,
+    pub name: Option<String>,
+}
+
+impl IdTokenClaims {
+    /// The identity's email, if the provider says it's verified.
+    pub fn verified_email(&self) -> Option<&str> {
+        let verified = match &self.email_verified {
+            Some(serde_json::Value::Bool(verified)) => *verified,
+            Some(serde_json::Value::String(verified)) => verified == "true",
+            _ => false,
+        };
+        self.email
+            .as_deref()
+            .map(str::trim)
+            .filter(|email| verified && !email.is_empty())
+    }
+
+    fn audiences(&self) -> Vec<&str> {
+        match &self.aud {
+            serde_json::Value::String(aud) => vec![aud.as_str()],
+            serde_json::Value::Array(auds) => auds.iter().filter_map(|aud| aud.as_str()).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+fn decode_segment(segment: &str) -> anyhow::Result<Vec<u8>> {
+    Ok(BASE64_URL.decode(segment.trim_end_matches('='))?)
+}
+
+/// The `kid` the ID token says it's signed with, if any. Used to refetch keys after rotation.
+pub fn id_token_key_id(id_token: &str) -> Option<String> {
+    let header = id_token.split('.').next()?;
+    serde_json::from_slice::<JwtHeader>(&decode_segment(header).ok()?)
+        .ok()?
+        .kid
+}
+
+/// Whether the key set has a key the ID token could be signed with.
+pub fn jwks_has_key(jwks: &Jwks, kid: Option<&str>) -> bool {
+    match kid {
+        Some(kid) => jwks.keys.iter().any(|key| key.kid.as_deref() == Some(kid)),
+        None => !jwks.keys.is_empty(),
+    }
+}
+
+/// Verifies an ID token's signature (`RS256` or `ES256`) against the provider's keys and checks its
+/// claims: the issuer, that it's for us (`client_id`), that it hasn't expired, and the `nonce` we
+/// sent with the authorization request.
+pub fn verify_id_token(
+    id_token: &str,
+    jwks: &Jwks,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: SystemTime,
+) -> anyhow::Result<IdTokenClaims> {
+    let mut segments = id_token.split('.');
+    let (header, payload, signature) = match (
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+    ) {
+        (Some(header), Some(payload), Some(signature), None) => (header, payload, signature),
+        _ => bail!("malformed_id_token"),
+    };
+    let parsed_header: JwtHeader = serde_json::from_slice(&decode_segment(header)?)?;
+    let signature = decode_segment(signature)?;
+    let message = format!("{}.{}", header, payload);
+
+    let candidates = jwks.keys.iter().filter(|key| {
+        parsed_header
+            .kid
+            .as_ref()
+            .map_or(true, |kid| key.kid.as_ref() == Some(kid))
+            && key.key_use.as_deref().map_or(true, |key_use| key_use == "sig")
+            && key.alg.as_deref().map_or(true, |alg| alg == parsed_header.alg)
+    });
+    let mut verified = false;
+    for key in candidates {
+        if verify_signature(&parsed_header.alg, key, message.as_bytes(), &signature)? {
+            verified = true;
+            break;
+        }
+    }
+    if !verified {
+        bail!("id_token_signature_invalid");
+    }
+
+    let claims: IdTokenClaims = serde_json::from_slice(&decode_segment(payload)?)?;
+    validate_id_token_claims(&claims, issuer, client_id, nonce, now)?;
+    Ok(claims)
+}
+
+/// Whether `signature` is valid for `message` with `key`; an error if the algorithm is unsupported.
+fn verify_signature(alg: &str, key: &Jwk, message: &[u8], signature: &[u8]) -> anyhow::Result<bool> {
+    let field = |value: &Option<String>| -> anyhow::Result<Vec<u8>> {
+        decode_segment(value.as_deref().ok_or_else(|| anyhow!("jwk_missing_field"))?)
+    };
+    match (alg, key.kty.as_str()) {
+        ("RS256", "RSA") => {
+            let n = field(&key.n)?;
+            let e = field(&key.e)?;
+            // ring wants the modulus without leading zeros, which some encoders include.
+            let n = &n[n.iter().position(|b| *b != 0).unwrap_or(n.len())..];
+            let components = RsaPublicKeyComponents { n, e: &e[..] };
+            Ok(components
+                .verify(&signature::RSA_PKCS1_2048_8192_SHA256, message, signature)
+                .is_ok())
+        }
+        ("ES256", "EC") if key.crv.as_deref() == Some("P-256") => {
+            let (x, y) = (field(&key.x)?, field(&key.y)?);
+            if x.len() > 32 || y.len() > 32 {
+                bail!("jwk_invalid_point");
+            }
+            // An uncompressed point: 0x04, then x and y, each left-padded to 32 bytes.
+            let mut point = vec![4u8];
+            point.extend(std::iter::repeat(0).take(32 - x.len()));
+            point.extend(x);
+            point.extend(std::iter::repeat(0).take(32 - y.len()));
+            point.extend(y);
+            Ok(UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                .verify(message, signature)
+                .is_ok())
+        }
+        ("RS256", _) | ("ES256", _) => Ok(false),
+        (alg, _) => bail!("unsupported_id_token_algorithm: {}", alg),
+    }
+}
+
+fn validate_id_token_claims(
+    claims: &IdTokenClaims,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: SystemTime,
+) -> anyhow::Result<()> {
+    let now = now.duration_since(UNIX_EPOCH)?;
+    if claims.iss.trim_end_matches('/') != issuer.trim_end_matches('/') {
+        bail!("id_token_issuer_invalid");
+    }
+    let audiences = claims.audiences();
+    if !audiences.contains(&client_id) {
+        bail!("id_token_audience_invalid");
+    }
+    if audiences.len() > 1 && claims.azp.as_deref().map_or(false, |azp| azp != client_id) {
+        bail!("id_token_authorized_party_invalid");
+    }
+    if Duration::from_secs(claims.exp) + CLOCK_SKEW < now {
+        bail!("id_token_expired");
+    }
+    if claims.iat.map_or(false, |iat| Duration::from_secs(iat) > now + CLOCK_SKEW) {
+        bail!("id_token_issued_in_future");
+    }
+    if claims.nonce.as_deref() != Some(nonce) {
+        bail!("id_token_nonce_invalid");
+    }
+    if claims.sub.is_empty() {
+        bail!("id_token_subject_missing");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+    use serde_json::json;
+
+    use super::*;
+
+    fn sign(key_pair: &EcdsaKeyPair, header: serde_json::Value, claims: serde_json::Value) -> String {
+        let message = format!(
+            "{}.{}",
+            BASE64_URL.encode(header.to_string()),
+            BASE64_URL.encode(claims.to_string())
+        );
+        let signature = key_pair.sign(&SystemRandom::new(), message.as_bytes()).unwrap();
+        format!("{}.{}", message, BASE64_URL.encode(signature.as_ref()))
+    }
+
+    #[test]
+    fn id_tokens_verify() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref()).unwrap();
+        let point = key_pair.public_key().as_ref();
+        let jwks: Jwks = serde_json::from_value(json!({"keys": [{
+            "kty": "EC", "crv": "P-256", "kid": "k1", "use": "sig", "alg": "ES256",
+            "x": BASE64_URL.encode(&point[1..33]),
+            "y": BASE64_URL.encode(&point[33..65]),
+        }]}))
+        .unwrap();
+
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let header = json!({"alg": "ES256", "kid": "k1"});
+        let claims = json!({
+            "iss": "https://sso.example.com", "sub": "123", "aud": "jonline",
+            "exp": 1_700_000_300u64, "iat": 1_700_000_000u64, "nonce": "n0",
+            "email": "a@example.com", "email_verified": "true",
+        });
+        let token = sign(&key_pair, header.clone(), claims.clone());
+        assert_eq!(id_token_key_id(&token).as_deref(), Some("k1"));
+        assert!(jwks_has_key(&jwks, Some("k1")));
+        assert!(!jwks_has_key(&jwks, Some("k2")));
+
+        let verified =
+            verify_id_token(&token, &jwks, "https://sso.example.com/", "jonline", "n0", now).unwrap();
+        assert_eq!(verified.sub, "123");
+        assert_eq!(verified.verified_email(), Some("a@example.com"));
+
+        let verify = |token: &str, nonce: &str, now: SystemTime| {
+            verify_id_token(token, &jwks, "https://sso.example.com", "jonline", nonce, now)
+        };
+        assert!(verify(&token, "other", now).is_err());
+        assert!(verify(&token, "n0", now + Duration::from_secs(3600)).is_err());
+        assert!(verify_id_token(&token, &jwks, "https://evil.example.com", "jonline", "n0", now).is_err());
+        assert!(verify_id_token(&token, &jwks, "https://sso.example.com", "other", "n0", now).is_err());
+
+        // Tampered claims and unsigned tokens are rejected.
+        let mut tampered = claims.clone();
+        tampered["sub"] = json!("456");
+        let (signed, signature) = token.rsplit_once('.').unwrap();
+        let forged = format!(
+            "{}.{}.{}",
+            signed.split('.').next().unwrap(),
+            BASE64_URL.encode(tampered.to_string()),
+            signature
+        );
+        assert!(verify(&forged, "n0", now).is_err());
+        let unsigned = format!(
+            "{}.{}.",
+            BASE64_URL.encode(json!({"alg": "none"}).to_string()),
+            BASE64_URL.encode(claims.to_string())
+        );
+        assert!(verify(&unsigned, "n0", now).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+// OpenID Connect relying party, for logging in with external identity providers (`OidcProvider`s
+// in `ServerConfiguration.authentication_settings`). Rocket routes for the authorization-code flow
+// live in `crate::web::oidc`; linking identities to users in `crate::logic::oidc_logic`.
+
+mod pkce;
+pub use pkce::*;
+
+mod discovery;
+pub use discovery::*;
+
+mod id_token;
+pub use id_token::*;
+
+mod client;
+pub use client::*;
+
+use std::time::Duration;
+
+use crate::env_var;
+use crate::protos::{OidcProvider, ServerConfiguration};
+
+lazy_static! {
+    pub static ref OIDC_CLIENT: reqwest::Client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .user_agent(format!("Jonline/{}", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("Failed to build OIDC HTTP client");
+}
+
+/// The configured provider with the given ID.
+pub fn find_oidc_provider(
+    configuration: &ServerConfiguration,
+    provider_id: &str,
+) -> Option<OidcProvider> {
+    configuration
+        .authentication_settings
+        .as_ref()?
+        .oidc_providers
+        .iter()
+        .find(|provider| provider.id == provider_id)
+        .cloned()
+}
+
+/// Whether `id` is usable as an `OidcProvider.id` (in URLs and env var names).
+pub fn is_valid_oidc_provider_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 32
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// The provider's client secret, from `OIDC_{ID}_CLIENT_SECRET`. Public clients (relying on
+/// PKCE alone) don't have one.
+pub fn oidc_client_secret(provider_id: &str) -> Option<String> {
+    env_var(&format!(
+        "OIDC_{}_CLIENT_SECRET",
+        provider_id.to_uppercase().replace('-', "_")
+    ))
+    .filter(|secret| !secret.is_empty())
+}
+
+/// The callback URL registered with providers: `https://{backend_domain}/auth/oidc/{id}/callback`,
+/// or under `OIDC_REDIRECT_BASE_URL` if set (i.e. `http://localhost:8000` in development).
+pub fn oidc_redirect_uri(backend_domain: &str, provider_id: &str) -> String {
+    let base = env_var("OIDC_REDIRECT_BASE_URL")
+        .map(|base| base.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| format!("https://{}", backend_domain));
+    format!("{}/auth/oidc/{}/callback", base, provider_id)
+}
@@ -0,0 +1,49 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine as _};
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::{Digest, Sha256};
+
+/// A random, URL-safe token with 256 bits of entropy, used for `state`, `nonce` and PKCE
+/// `code_verifier`s (whose 43 characters are the minimum length RFC 7636 allows).
+pub fn generate_oidc_token() -> String {
+    let mut randoms = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut randoms)
+        .expect("Failed to generate OIDC token");
+    BASE64_URL.encode(randoms)
+}
+
+/// The `S256` PKCE `code_challenge` for a `code_verifier`.
+pub fn oidc_code_challenge(code_verifier: &str) -> String {
+    BASE64_URL.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// What the `oidc_state` cookie holds for a login's `state`, binding it to the browser that
+/// started the login (so a callback can't be replayed into another browser).
+pub fn oidc_state_hash(state: &str) -> String {
+    BASE64_URL.encode(Sha256::digest(state.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_challenges_match_rfc_7636() {
+        // RFC 7636, Appendix B.
+        assert_eq!(
+            oidc_code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+        let verifier = generate_oidc_token();
+        assert_eq!(verifier.len(), 43);
+        assert_ne!(verifier, generate_oidc_token());
+    }
+
+    #[test]
+    fn state_hashes_only_match_their_state() {
+        let state = generate_oidc_token();
+        assert_eq!(oidc_state_hash(&state), oidc_state_hash(&state));
+        assert_ne!(oidc_state_hash(&state), oidc_state_hash(&generate_oidc_token()));
+        assert_ne!(oidc_state_hash(&state), state);
+    }
+}
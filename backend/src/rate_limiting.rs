@@ -0,0 +1,202 @@
+// In-memory sliding-window rate limiting, i.e. for login attempts per IP and per username.
+// Limits are per server process. Persistent account lockouts (visible to admins) are in
+// `crate::logic::account_lockout_logic`.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Beyond this many tracked keys, keys without recent events are dropped.
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+/// Allows at most `max_events` per key within any `window`. Past `backoff_after` events in the
+/// window, each further event must also wait `backoff_base` (doubling per event, up to
+/// `max_backoff`) after the last one.
+pub struct RateLimiter {
+    name: &'static str,
+    max_events: usize,
+    window: Duration,
+    backoff_after: Option<usize>,
+    backoff_base: Duration,
+    max_backoff: Duration,
+    events: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    pub fn new(name: &'static str, max_events: usize, window: Duration) -> RateLimiter {
+        RateLimiter {
+            name,
+            max_events,
+            window,
+            backoff_after: None,
+            backoff_base: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            events: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn with_backoff(self, after: usize, base: Duration, max: Duration) -> RateLimiter {
+        RateLimiter {
+            backoff_after: Some(after),
+            backoff_base: base,
+            max_backoff: max,
+            ..self
+        }
+    }
+
+    /// Whether an event for `key` is allowed now, without recording one. The error is how long
+    /// until it would be.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        self.check_at(key, Instant::now())
+    }
+
+    /// Records an event for `key` (i.e. a failed login).
+    pub fn record(&self, key: &str) {
+        self.record_at(key, Instant::now())
+    }
+
+    /// Checks and, if allowed, records an event for `key`.
+    pub fn hit(&self, key: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        self.check_at(key, now)?;
+        self.record_at(key, now);
+        Ok(())
+    }
+
+    /// Forgets `key`'s events (i.e. after a successful login, or an admin unlocking it).
+    pub fn reset(&self, key: &str) {
+        self.events.lock().unwrap().remove(key);
+    }
+
+    fn check_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let mut events = self.events.lock().unwrap();
+        let key_events = match events.get_mut(key) {
+            Some(key_events) => key_events,
+            None => return Ok(()),
+        };
+        while key_events
+            .front()
+            .map_or(false, |event| now.duration_since(*event) >= self.window)
+        {
+            key_events.pop_front();
+        }
+        if key_events.len() >= self.max_events {
+            let retry_after = self.window - now.duration_since(*key_events.front().unwrap());
+            log::warn!("Rate limited {} for {}", key, self.name);
+            return Err(retry_after);
+        }
+        match (self.backoff_after, key_events.back()) {
+            (Some(after), Some(last)) if key_events.len() >= after => {
+                let exponent = (key_events.len() - after).min(16) as u32;
+                let backoff = (self.backoff_base * 2u32.pow(exponent)).min(self.max_backoff);
+                let elapsed = now.duration_since(*last);
+                if elapsed < backoff {
+                    return Err(backoff - elapsed);
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn record_at(&self, key: &str, now: Instant) {
+        let mut events = self.events.lock().unwrap();
+        if events.len() >= MAX_TRACKED_KEYS && !events.contains_key(key) {
+            let window = self.window;
+            events.retain(|_, key_events| {
+                key_events
+                    .back()
+                    .map_or(false, |last| now.duration_since(*last) < window)
+            });
+        }
+        let key_events = events.entry(key.to_string()).or_default();
+        key_events.push_back(now);
+        // Only the window's worth of events matters.
+        while key_events.len() > self.max_events {
+            key_events.pop_front();
+        }
+    }
+}
+
+lazy_static! {
+    /// Failed logins per client IP.
+    pub static ref LOGIN_FAILURES_BY_IP: RateLimiter =
+        RateLimiter::new("login_failures_by_ip", 30, Duration::from_secs(15 * 60))
+            .with_backoff(10, Duration::from_secs(1), Duration::from_secs(60));
+    /// Failed logins per account (see `user_login_failure_key`), or per (lowercased) username
+    /// for usernames without one.
+    pub static ref LOGIN_FAILURES_BY_USERNAME: RateLimiter =
+        RateLimiter::new("login_failures_by_username", 20, Duration::from_secs(15 * 60))
+            .with_backoff(3, Duration::from_secs(1), Duration::from_secs(5 * 60));
+    /// Account creation attempts per client IP.
+    pub static ref ACCOUNT_CREATION_BY_IP: RateLimiter =
+        RateLimiter::new("account_creation_by_ip", 10, Duration::from_secs(60 * 60));
+    /// Media uploads (single-request or multipart) started per user.
+    pub static ref MEDIA_UPLOADS_BY_USER: RateLimiter =
+        RateLimiter::new("media_uploads_by_user", 60, Duration::from_secs(10 * 60));
+}
+
+/// The `LOGIN_FAILURES_BY_USERNAME` key for an existing user, whether logins name them by
+/// username or ID. `UnlockAccount` resets it.
+pub fn user_login_failure_key(user_id: i64) -> String {
+    format!("user:{}", user_id)
+}
+
+/// The `LOGIN_FAILURES_BY_USERNAME` key for logins to a username without an account.
+pub fn username_login_failure_key(username: &str) -> String {
+    format!("username:{}", username.trim().to_lowercase())
+}
+
+/// The error for rate-limited RPCs, with a `retry-after` (see `with_retry_after`).
+pub fn rate_limited_status(retry_after: Duration) -> tonic::Status {
+    with_retry_after(
+        tonic::Status::new(tonic::Code::ResourceExhausted, "rate_limited"),
+        retry_after,
+    )
+}
+
+/// Adds a `retry-after` (in whole seconds, rounded up) to an RPC error's metadata.
+pub fn with_retry_after(mut status: tonic::Status, retry_after: Duration) -> tonic::Status {
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    if let Ok(value) = seconds.to_string().parse() {
+        status.metadata_mut().insert("retry-after", value);
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limiter_slides_and_backs_off() {
+        let second = Duration::from_secs(1);
+        let limiter = RateLimiter::new("test", 3, second * 60);
+        let start = Instant::now();
+        for i in 0..3 {
+            assert_eq!(limiter.check_at("a", start + second * i), Ok(()));
+            limiter.record_at("a", start + second * i);
+        }
+        assert_eq!(limiter.check_at("a", start + second * 3), Err(second * 57));
+        assert_eq!(limiter.check_at("b", start + second * 3), Ok(()));
+        // The first event leaves the window after 60 seconds.
+        assert_eq!(limiter.check_at("a", start + second * 60), Ok(()));
+        limiter.reset("a");
+        assert_eq!(limiter.check_at("a", start + second * 3), Ok(()));
+
+        let limiter = RateLimiter::new("test", 100, second * 3600).with_backoff(2, second, second * 5);
+        for i in 0..2 {
+            limiter.record_at("a", start + second * i);
+        }
+        assert_eq!(limiter.check_at("a", start + second * 1), Err(second));
+        assert_eq!(limiter.check_at("a", start + second * 2), Ok(()));
+        limiter.record_at("a", start + second * 2);
+        assert_eq!(limiter.check_at("a", start + second * 3), Err(second));
+        assert_eq!(limiter.check_at("a", start + second * 4), Ok(()));
+        for i in 4..10 {
+            limiter.record_at("a", start + second * i);
+        }
+        // Capped at `max_backoff`.
+        assert_eq!(limiter.check_at("a", start + second * 10), Err(second * 4));
+    }
+}
@@ -1,5 +1,3 @@
-use std::ops::Add;
-use std::time::Duration;
 use std::time::SystemTime;
 
 use diesel::*;
@@ -7,73 +5,85 @@ use tonic::{Code, Request, Response, Status};
 
 use crate::auth;
 use crate::db_connection::*;
+use crate::logic::*;
+use crate::models;
 use crate::protos::*;
+use crate::rpcs::get_server_configuration;
 use crate::schema::user_refresh_tokens;
 
+/// Rotates the presented refresh token, returning a new one (in the same family) along with an
+/// access token. Presenting a token that was already rotated means it's been leaked (or a client
+/// is misbehaving), so the whole family is revoked. So is the session of a user who must use
+/// two-factor auth (see `requires_two_factor`) but hasn't enrolled, e.g. because it predates
+/// `REQUIRE_PRIVILEGED_TWO_FACTOR` being enabled.
 pub fn access_token(
     request: Request<AccessTokenRequest>,
     conn: &mut PgPooledConnection,
 ) -> Result<Response<AccessTokenResponse>, Status> {
     log::info!("AccessToken called.");
-    let requested_token = &request.into_inner().refresh_token;
-    let token_user_expiry: Result<(i64, i64, Option<SystemTime>), _> = user_refresh_tokens::table
-        .select((
-            user_refresh_tokens::id,
-            user_refresh_tokens::user_id,
-            user_refresh_tokens::expires_at,
-        ))
-        .filter(user_refresh_tokens::token.eq(requested_token))
-        .first::<(i64, i64, Option<SystemTime>)>(conn);
+    let metadata = auth::SessionMetadata::from_request(&request);
+    let requested_token = request.into_inner().refresh_token;
+    let refresh_token = user_refresh_tokens::table
+        .filter(user_refresh_tokens::token.eq(&requested_token))
+        .first::<models::UserRefreshToken>(conn)
+        .optional()
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?
+        .ok_or_else(|| {
+            log::warn!("Refresh token not found.");
+            Status::new(Code::Unauthenticated, "not_authorized")
+        })?;
 
-    const LIFETIME_DAYS: u64 = 7;
-    const RENEWAL_PERIOD_DAYS: u64 = 3;
-    match token_user_expiry {
-        Err(_) => {
-            log::warn!("Auth token {} not found.", requested_token);
-            Err(Status::new(Code::Unauthenticated, "not_authorized"))
+    if refresh_token.rotated_at.is_some() {
+        return Err(revoke_reused_family(&refresh_token, conn));
+    }
+    if refresh_token.expires_at <= SystemTime::now() {
+        log::warn!(
+            "Attempt to use expired refresh token. refresh_token_id={}, user_id={}",
+            refresh_token.id,
+            refresh_token.user_id
+        );
+        return Err(Status::new(Code::Unauthenticated, "not_authorized"));
+    }
+
+    let server_configuration = get_server_configuration(conn)?;
+    let user = models::get_user(refresh_token.user_id, conn)?;
+    if requires_two_factor(&user, &server_configuration)
+        && get_confirmed_totp(user.id, conn)?.is_none()
+    {
+        log::warn!(
+            "Revoking session without required two-factor auth. refresh_token_id={}, user_id={}",
+            refresh_token.id,
+            user.id
+        );
+        if let Err(e) = auth::revoke_token_family(refresh_token.family_id, conn) {
+            log::error!("Error revoking refresh token family: {:?}", e);
         }
-        Ok((refresh_token_id, user_id, expires_at)) => match expires_at {
-            Some(t) if t > SystemTime::now() => {
-                log::warn!("Attempt to use expired refresh token. refresh_token_id={}, user_id={}", refresh_token_id, user_id);
-                Err(Status::new(Code::Unauthenticated, "not_authorized"))
-            }
-            Some(t)
-                if t < SystemTime::now()
-                    && t.add(Duration::from_secs(60 * 60 * 24 * RENEWAL_PERIOD_DAYS))
-                        > SystemTime::now() =>
-            {
-                log::info!(
-                    "Updating refresh token for user_id={}, generating access token...",
-                    user_id
-                );
-                let new_expiry =
-                    SystemTime::now().add(Duration::from_secs(60 * 60 * 24 * LIFETIME_DAYS));
-                let new_token_pair =
-                    auth::generate_refresh_and_access_token(user_id, conn, Some(new_expiry.into()));
-                Ok(Response::new(AccessTokenResponse {
-                    access_token: new_token_pair.access_token,
-                    refresh_token: new_token_pair.refresh_token,
-                }))
-            }
-            None => {
-                log::warn!(
-                    "Non-expiring refresh token for user_id={}, generating access token...",
-                    user_id
-                );
-                let access_token = auth::generate_access_token(refresh_token_id, conn);
-                Ok(Response::new(AccessTokenResponse {
-                    access_token: Some(access_token),
-                    refresh_token: None,
-                }))
-            }
-            _ => {
-                log::debug!("Generating access token for user_id={}", user_id);
-                let access_token = auth::generate_access_token(refresh_token_id, conn);
-                Ok(Response::new(AccessTokenResponse {
-                    access_token: Some(access_token),
-                    refresh_token: None,
-                }))
-            }
+        return Err(Status::new(Code::PermissionDenied, "two_factor_required"));
+    }
+
+    let lifetimes = auth::TokenLifetimes::from_configuration(&server_configuration);
+    match auth::rotate_refresh_token(&refresh_token, conn, &metadata, &lifetimes) {
+        Ok(Some(response)) => Ok(Response::new(response)),
+        // Rotated concurrently, i.e. presented twice.
+        Ok(None) => Err(revoke_reused_family(&refresh_token, conn)),
+        Err(e) => {
+            log::error!("Error rotating refresh token: {:?}", e);
+            Err(Status::new(Code::Internal, "data_error"))
         }
     }
 }
+
+fn revoke_reused_family(
+    refresh_token: &models::UserRefreshToken,
+    conn: &mut PgPooledConnection,
+) -> Status {
+    log::warn!(
+        "Reuse of rotated refresh token; revoking its session. refresh_token_id={}, user_id={}",
+        refresh_token.id,
+        refresh_token.user_id
+    );
+    if let Err(e) = auth::revoke_token_family(refresh_token.family_id, conn) {
+        log::error!("Error revoking refresh token family: {:?}", e);
+    }
+    Status::new(Code::Unauthenticated, "refresh_token_reused")
+}
@@ -0,0 +1,48 @@
+use std::time::SystemTime;
+
+use bcrypt::{hash, verify, DEFAULT_COST};
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::auth;
+use crate::db_connection::PgPooledConnection;
+use crate::models;
+use crate::protos::*;
+use crate::schema::users;
+
+use super::validations::*;
+
+pub fn change_password(
+    request: ChangePasswordRequest,
+    user: models::User,
+    refresh_token_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    validate_password(&request.new_password)?;
+    match verify(&request.current_password, &user.password_salted_hash) {
+        Ok(true) => {}
+        _ => return Err(Status::new(Code::PermissionDenied, "invalid_password")),
+    };
+
+    let hashed_password = hash(&request.new_password, DEFAULT_COST).unwrap();
+    let result = conn.transaction::<usize, diesel::result::Error, _>(|conn| {
+        update(users::table.find(user.id))
+            .set((
+                users::password_salted_hash.eq(hashed_password),
+                users::password_reset_required.eq(false),
+                users::updated_at.eq(SystemTime::now()),
+            ))
+            .execute(conn)?;
+        auth::revoke_user_tokens(user.id, Some(refresh_token_id), conn)
+    });
+    match result {
+        Ok(_) => {
+            log::info!("Changed password for user_id={}", user.id);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Error changing password: {:?}", e);
+            Err(Status::new(Code::Internal, "data_error"))
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use tonic::{Code, Status};
+
+use crate::auth;
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::rpcs::get_server_configuration;
+
+pub fn complete_oidc_login(
+    request: CompleteOidcLoginRequest,
+    metadata: auth::SessionMetadata,
+    conn: &mut PgPooledConnection,
+) -> Result<RefreshTokenResponse, Status> {
+    let metadata = metadata.with_device_name(request.device_name.clone());
+    let user_id = take_oidc_login_completion(&request.code, conn)?;
+    let user = models::get_user(user_id, conn)?;
+    if user.password_reset_required {
+        return Err(Status::new(Code::PermissionDenied, "password_reset_required"));
+    }
+
+    // As with `Login`, admins can always log in.
+    let server_configuration = get_server_configuration(conn)?;
+    if !has_authentication_feature(&server_configuration, AuthenticationFeature::Login)
+        && !user.has_permission(Permission::Admin)
+    {
+        return Err(Status::new(Code::PermissionDenied, "login_disabled"));
+    }
+
+    // Providers' own second factors aren't visible to us, so users with TOTP still need it here.
+    let has_totp = get_confirmed_totp(user.id, conn)?.is_some();
+    if has_totp || requires_two_factor(&user, &server_configuration) {
+        let challenge = create_two_factor_challenge(
+            user.id,
+            request.expires_at.as_ref().map(|expires_at| expires_at.to_db()),
+            metadata.device_name,
+            !has_totp,
+            conn,
+        )?;
+        return Ok(RefreshTokenResponse {
+            refresh_token: None,
+            access_token: None,
+            user: None,
+            two_factor_challenge: Some(challenge),
+        });
+    }
+
+    let tokens = auth::generate_refresh_and_access_token(
+        user.id,
+        conn,
+        request.expires_at,
+        &metadata,
+        &auth::TokenLifetimes::from_configuration(&server_configuration),
+    )?;
+    log::info!("Logged in user {} via OIDC, user_id={}", user.username, user.id);
+    Ok(RefreshTokenResponse {
+        refresh_token: tokens.refresh_token,
+        access_token: tokens.access_token,
+        user: Some(user.to_proto()),
+        two_factor_challenge: None,
+    })
+}
@@ -0,0 +1,92 @@
+use std::time::SystemTime;
+
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::auth;
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::rpcs::get_server_configuration;
+use crate::schema::{two_factor_challenges, user_recovery_codes, user_totps};
+
+pub fn confirm_totp(
+    request: ConfirmTotpRequest,
+    user: Option<models::User>,
+    metadata: auth::SessionMetadata,
+    conn: &mut PgPooledConnection,
+) -> Result<TotpConfirmation, Status> {
+    let (user, challenge) =
+        get_totp_enrollment_user(request.two_factor_token.as_deref(), user, conn)?;
+    let totp = user_totps::table
+        .filter(user_totps::user_id.eq(user.id))
+        .first::<models::UserTotp>(conn)
+        .optional()
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?
+        .ok_or_else(|| Status::new(Code::NotFound, "totp_enrollment_not_found"))?;
+    if let Some(challenge) = &challenge {
+        if !count_two_factor_attempt(challenge, conn)? {
+            return Err(Status::new(Code::PermissionDenied, "two_factor_attempts_exceeded"));
+        }
+    }
+    if !use_totp_code(&totp, &request.code, conn)? {
+        return Err(Status::new(Code::PermissionDenied, "totp_code_invalid"));
+    }
+
+    let recovery_codes = generate_recovery_codes();
+    let result = conn.transaction::<(), diesel::result::Error, _>(|conn| {
+        update(user_totps::table.find(totp.id))
+            .set(user_totps::confirmed_at.eq(SystemTime::now()))
+            .execute(conn)?;
+        delete(user_recovery_codes::table.filter(user_recovery_codes::user_id.eq(user.id)))
+            .execute(conn)?;
+        insert_into(user_recovery_codes::table)
+            .values(
+                recovery_codes
+                    .iter()
+                    .map(|code| models::NewUserRecoveryCode {
+                        user_id: user.id,
+                        code_hash: hash_recovery_code(code),
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .execute(conn)?;
+        if let Some(challenge) = &challenge {
+            delete(two_factor_challenges::table.find(challenge.id)).execute(conn)?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        log::error!("Error confirming TOTP: {:?}", e);
+        return Err(Status::new(Code::Internal, "data_error"));
+    }
+    log::info!("Enabled TOTP for user_id={}", user.id);
+
+    // Enrolling while logging in completes the login.
+    let login = match challenge {
+        Some(challenge) => {
+            let lifetimes =
+                auth::TokenLifetimes::from_configuration(&get_server_configuration(conn)?);
+            let tokens = auth::generate_refresh_and_access_token(
+                user.id,
+                conn,
+                challenge.requested_expires_at.map(|expires_at| expires_at.to_proto()),
+                &metadata.with_device_name(challenge.device_name),
+                &lifetimes,
+            )?;
+            Some(RefreshTokenResponse {
+                refresh_token: tokens.refresh_token,
+                access_token: tokens.access_token,
+                user: Some(user.to_proto()),
+                two_factor_challenge: None,
+            })
+        }
+        None => None,
+    };
+    Ok(TotpConfirmation {
+        recovery_codes,
+        login,
+    })
+}
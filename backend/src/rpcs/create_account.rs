@@ -3,65 +3,120 @@ use diesel::*;
 use tonic::{Code, Status};
 
 use crate::auth;
+use crate::logic::*;
 use crate::marshaling::*;
 use crate::db_connection::PgPooledConnection;
+use crate::messaging::Messenger;
 use crate::models;
-use crate::protos::{RefreshTokenResponse, CreateAccountRequest};
+use crate::protos::{AuthenticationFeature, ContactMethodType, RefreshTokenResponse, CreateAccountRequest};
+use crate::rate_limiting::{self, rate_limited_status};
 use crate::schema::users::dsl::*;
 
 use super::{validations::*, get_server_configuration};
 
 pub fn create_account(
     request: CreateAccountRequest,
+    metadata: auth::SessionMetadata,
+    messenger: &dyn Messenger,
     conn: &mut PgPooledConnection,
 ) -> Result<RefreshTokenResponse, Status> {
+    let server_configuration = get_server_configuration(conn)?;
+    if let Some(ip) = metadata.client_ip(&server_configuration) {
+        rate_limiting::ACCOUNT_CREATION_BY_IP
+            .hit(&ip)
+            .map_err(rate_limited_status)?;
+    }
+    let metadata = metadata.with_device_name(request.device_name.clone());
+    if !has_authentication_feature(&server_configuration, AuthenticationFeature::CreateAccount) {
+        return Err(Status::new(Code::PermissionDenied, "account_creation_disabled"));
+    }
+    let invite_code = match (
+        has_authentication_feature(&server_configuration, AuthenticationFeature::InviteOnly),
+        request.invite_code.as_deref().map(str::trim),
+    ) {
+        (_, Some(code)) if !code.is_empty() => Some(code.to_string()),
+        (true, _) => return Err(Status::new(Code::PermissionDenied, "invite_code_required")),
+        (false, _) => None,
+    };
+
     validate_username(&request.username)?;
     validate_password(&request.password)?;
     match request.email.to_owned() {
         Some(e) => validate_email(&e.value)?,
         None => {}
     }
-    match request.email.to_owned() {
-        Some(e) => validate_phone(&e.value)?,
+    match request.phone.to_owned() {
+        Some(p) => validate_phone(&p.value)?,
         None => {}
     }
 
     let hashed_password = hash(request.password, DEFAULT_COST).unwrap();
 
-    let req_email: Option<serde_json::Value> = match request.email.map(|v| serde_json::to_value(v)) {
-        None => None,
-        Some(Err(_)) => None,
-        Some(Ok(v)) => Some(v),
-    };
-    let req_phone: Option<serde_json::Value> = match request.phone.map(|v| serde_json::to_value(v)) {
-        None => None,
-        Some(Err(_)) => None,
-        Some(Ok(v)) => Some(v),
-    };
-    let server_configuration = get_server_configuration(conn)?;
-    let insert_result: Result<models::User, _> = insert_into(users)
-        .values((
-            username.eq(request.username.to_owned()),
-            password_salted_hash.eq(hashed_password),
-            email.eq(req_email),
-            phone.eq(req_phone),
-            permissions.eq(server_configuration.default_user_permissions.to_json_permissions()),
-            moderation.eq(server_configuration.people_settings.as_ref().unwrap().default_moderation.to_string_moderation()),
-            visibility.eq(server_configuration.people_settings.unwrap().default_visibility.to_string_visibility()),
-        ))
-        .get_result::<models::User>(conn);
+    let req_email = sanitize_contact_method(
+        request.email,
+        None,
+        messenger.supports(ContactMethodType::Email),
+    );
+    let req_phone = sanitize_contact_method(
+        request.phone,
+        None,
+        messenger.supports(ContactMethodType::Phone),
+    );
+    let default_permissions = server_configuration.default_user_permissions.to_json_permissions();
+    let insert_result = conn.transaction::<models::User, diesel::result::Error, _>(|conn| {
+        // Codes are used up atomically, and given back if the account can't be created.
+        let invited_permissions = match &invite_code {
+            Some(code) => use_invite_code(code, conn)?.permissions,
+            None => None,
+        };
+        insert_into(users)
+            .values((
+                username.eq(request.username.to_owned()),
+                password_salted_hash.eq(hashed_password),
+                email.eq(req_email),
+                phone.eq(req_phone),
+                permissions.eq(invited_permissions.unwrap_or(default_permissions)),
+                moderation.eq(server_configuration.people_settings.as_ref().unwrap().default_moderation.to_string_moderation()),
+                visibility.eq(server_configuration.people_settings.as_ref().unwrap().default_visibility.to_string_visibility()),
+            ))
+            .get_result::<models::User>(conn)
+    });
 
     match insert_result {
+        Err(diesel::NotFound) => Err(Status::new(Code::PermissionDenied, "invite_code_invalid")),
         Err(e) => {
-            print!("Username already exists {:?}", e);
+            log::warn!("Username already exists: {:?}", e);
             Err(Status::new(Code::AlreadyExists, "username_already_exists"))
         },
+        // i.e. accounts given ADMIN by their invite code must enroll before they get a session.
+        Ok(user) if requires_two_factor(&user, &server_configuration) => {
+            let challenge = create_two_factor_challenge(
+                user.id,
+                request.expires_at.as_ref().map(|expires_at| expires_at.to_db()),
+                metadata.device_name,
+                true,
+                conn,
+            )?;
+            Ok(RefreshTokenResponse {
+                refresh_token: None,
+                access_token: None,
+                user: None,
+                two_factor_challenge: Some(challenge),
+            })
+        }
         Ok(user) => {
-            let tokens = auth::generate_refresh_and_access_token(user.id, conn, request.expires_at);
+            let tokens = auth::generate_refresh_and_access_token(
+                user.id,
+                conn,
+                request.expires_at,
+                &metadata,
+                &auth::TokenLifetimes::from_configuration(&server_configuration),
+            )?;
             Ok(RefreshTokenResponse {
                 refresh_token: tokens.refresh_token,
                 access_token: tokens.access_token,
                 user: Some(user.to_proto()),
+                two_factor_challenge: None,
             })
         }
     }
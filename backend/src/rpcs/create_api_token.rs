@@ -0,0 +1,75 @@
+use std::time::SystemTime;
+
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::auth::{generate_api_token, hash_api_token, ApiTokenScope, UNSCOPABLE_PERMISSIONS};
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::user_api_tokens;
+
+use super::validations::*;
+
+pub fn create_api_token(
+    request: ApiToken,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<ApiToken, Status> {
+    log::info!("CreateApiToken called for user_id={}", user.id);
+    // Unlike most permissions, not implied by `ADMIN`; tokens only work for users with it.
+    validate_any_permission(&user, vec![Permission::RunBots])?;
+    let label = request.label.trim().to_string();
+    validate_length(&label, "label", 1, 100)?;
+    let expires_at = request.expires_at.as_ref().map(|expires_at| expires_at.to_db());
+    if expires_at.map(|expires_at| expires_at <= SystemTime::now()) == Some(true) {
+        return Err(Status::new(Code::InvalidArgument, "expires_at_must_be_in_the_future"));
+    }
+    if request.scopes.is_empty() {
+        return Err(Status::new(Code::InvalidArgument, "scopes_required"));
+    }
+    let user_permissions = user.permissions.to_proto_permissions();
+    let mut scopes: Vec<ApiTokenScope> = vec![];
+    for scope in &request.scopes {
+        let permission = match Permission::from_i32(scope.permission) {
+            Some(permission) if !UNSCOPABLE_PERMISSIONS.contains(&permission) => permission,
+            _ => return Err(Status::new(Code::InvalidArgument, "scope_permission_not_allowed")),
+        };
+        // Tokens couldn't use it anyway, but make that clear up front.
+        if !user_permissions.contains(&permission) {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                format!("permission_{}_required", permission.as_str_name()),
+            ));
+        }
+        let group_id = match &scope.group_id {
+            Some(group_id) => Some(models::get_group(group_id.to_db_id_or_err("group_id")?, conn)?.id),
+            None => None,
+        };
+        scopes.push(ApiTokenScope {
+            permission: permission.as_str_name().to_string(),
+            group_id,
+        });
+    }
+
+    let token = generate_api_token();
+    let api_token = insert_into(user_api_tokens::table)
+        .values(&models::NewUserApiToken {
+            user_id: user.id,
+            token_hash: hash_api_token(&token),
+            label,
+            scopes: serde_json::to_value(&scopes).unwrap(),
+            expires_at,
+        })
+        .get_result::<models::UserApiToken>(conn)
+        .map_err(|e| {
+            log::error!("Error creating API token: {:?}", e);
+            Status::new(Code::Internal, "data_error")
+        })?;
+    log::info!("Created API token {} for user_id={}", api_token.id, user.id);
+    Ok(ApiToken {
+        token: Some(token),
+        ..api_token.to_proto()
+    })
+}
@@ -27,9 +27,9 @@ pub fn create_event(
         Some(p) => p,
     };
 
+    let mut media_ids = vec![];
     for media_proto_id in &post.media {
-        media_proto_id.to_db_id_or_err("media")?;
-        //TODO further media ID validations?
+        media_ids.push(media_proto_id.to_db_id_or_err("media")?);
     }
     let instances = req.instances;
     if instances.len() == 0 {
@@ -44,7 +44,7 @@ pub fn create_event(
                 validate_max_length(p.link.to_owned(), "instance.post.link", 10000)?;
                 validate_max_length(p.content.to_owned(), "instance.post.content", 10000)?;
                 for media_proto_id in &p.media {
-                    media_proto_id.to_db_id_or_err("instance.media")?;
+                    media_ids.push(media_proto_id.to_db_id_or_err("instance.media")?);
                 }
                 let visibility = match p.visibility() {
                     Visibility::Unknown => Visibility::GlobalPublic,
@@ -69,6 +69,7 @@ pub fn create_event(
             ));
         }
     }
+    validate_media_ownership(&user, &media_ids, conn)?;
 
     validate_max_length(post.link.to_owned(), "post.link", 10000)?;
     validate_max_length(post.content.to_owned(), "post.content", 10000)?;
@@ -19,6 +19,10 @@ pub fn create_group(
 ) -> Result<Group, Status> {
     validate_permission(&user, Permission::CreateGroups)?;
     validate_group(&request)?;
+    let avatar_media_id = request.avatar_media_id.to_db_opt_id_or_err("avatar_media_id")?;
+    if let Some(avatar_media_id) = avatar_media_id {
+        validate_media_ownership(&user, &[avatar_media_id], conn)?;
+    }
 
     let visibility = match request.visibility.to_proto_visibility() {
         Some(Visibility::Unknown) => Visibility::ServerPublic,
@@ -57,7 +61,7 @@ pub fn create_group(
                     name: request.name.to_owned(),
                     shortname: derive_shortname(&request),
                     description: request.description,
-                    avatar_media_id: request.avatar_media_id.map(|id| id.to_db_id().unwrap()),
+                    avatar_media_id,
                     visibility: visibility,
                     default_membership_permissions: default_membership_permissions,
                     default_membership_moderation: default_membership_moderation,
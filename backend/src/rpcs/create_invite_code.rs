@@ -0,0 +1,75 @@
+use std::time::SystemTime;
+
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::invite_codes;
+
+use super::validations::*;
+
+pub fn create_invite_code(
+    request: InviteCode,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<InviteCode, Status> {
+    log::info!("CreateInviteCode called: {:?}", request);
+    validate_permission(&user, Permission::Admin)?;
+
+    let code = match normalize_invite_code(&request.code) {
+        code if code.is_empty() => generate_invite_code(),
+        code if code.len() < 4 || code.len() > 64 => {
+            return Err(Status::new(Code::InvalidArgument, "code_must_be_4_to_64_characters"))
+        }
+        code if !code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') => {
+            return Err(Status::new(Code::InvalidArgument, "code_invalid_characters"))
+        }
+        code => code,
+    };
+    let max_uses = match request.max_uses {
+        Some(0) => return Err(Status::new(Code::InvalidArgument, "max_uses_must_be_positive")),
+        Some(max_uses) if max_uses > i32::MAX as u32 => {
+            return Err(Status::new(Code::InvalidArgument, "max_uses_too_large"))
+        }
+        max_uses => max_uses.map(|max_uses| max_uses as i32),
+    };
+    let expires_at = request.expires_at.as_ref().map(|expires_at| expires_at.to_db());
+    if expires_at.map(|expires_at| expires_at <= SystemTime::now()) == Some(true) {
+        return Err(Status::new(Code::InvalidArgument, "expires_at_must_be_in_the_future"));
+    }
+    let permissions = match request.permissions.is_empty() {
+        true => None,
+        false => {
+            if request.permissions.iter().any(|p| Permission::from_i32(*p).is_none()) {
+                return Err(Status::new(Code::InvalidArgument, "invalid_permission"));
+            }
+            Some(request.permissions.to_json_permissions())
+        }
+    };
+
+    let invite_code = insert_into(invite_codes::table)
+        .values(&models::NewInviteCode {
+            code,
+            created_by_user_id: Some(user.id),
+            max_uses,
+            permissions,
+            expires_at,
+        })
+        .get_result::<models::InviteCode>(conn)
+        .map_err(|e| match e {
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            ) => Status::new(Code::AlreadyExists, "code_already_exists"),
+            e => {
+                log::error!("Error creating invite code: {:?}", e);
+                Status::new(Code::Internal, "data_error")
+            }
+        })?;
+    log::info!("Created invite code {} for user_id={}", invite_code.id, user.id);
+    Ok(invite_code.to_proto())
+}
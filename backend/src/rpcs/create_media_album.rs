@@ -0,0 +1,46 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::media_albums;
+
+use super::update_media_album::{replace_album_media, validate_album_media};
+use super::validations::*;
+
+pub fn create_media_album(
+    request: MediaAlbum,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<MediaAlbum, Status> {
+    log::info!("CreateMediaAlbum called: {:?}", request);
+    validate_permission(&user, Permission::CreateMedia)?;
+    validate_length(&request.name, "name", 1, 255)?;
+    validate_media_fields(None, request.description.to_owned(), request.visibility)?;
+    validate_media_visibility(&user, request.visibility())?;
+    let (media_ids, cover_media_id) = validate_album_media(&request, user.id, conn)?;
+
+    let album = conn.transaction::<models::MediaAlbum, diesel::result::Error, _>(|conn| {
+        let album = insert_into(media_albums::table)
+            .values(&models::NewMediaAlbum {
+                user_id: user.id,
+                name: request.name.to_owned(),
+                description: request.description.to_owned(),
+                cover_media_id,
+                visibility: request.visibility().to_string_visibility(),
+                moderation: Moderation::Unmoderated.to_string_moderation(),
+            })
+            .get_result::<models::MediaAlbum>(conn)?;
+        replace_album_media(album.id, &media_ids, conn)?;
+        Ok(album)
+    });
+    match album {
+        Ok(album) => Ok(album.to_proto(&media_ids)),
+        Err(e) => {
+            log::error!("Error creating media album: {:?}", e);
+            Err(Status::new(Code::Internal, "data_error"))
+        }
+    }
+}
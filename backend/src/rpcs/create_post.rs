@@ -1,6 +1,7 @@
 use diesel::*;
 use tonic::{Code, Request, Response, Status};
 
+use crate::activitypub;
 use crate::db_connection::PgPooledConnection;
 use crate::marshaling::*;
 use crate::models;
@@ -35,9 +36,11 @@ pub fn create_post(
     }
     validate_max_length(req.link.to_owned(), "link", 10000)?;
     validate_max_length(req.content.to_owned(), "content", 10000)?;
+    let mut media_ids = vec![];
     for media_proto_id in &req.media {
-        media_proto_id.to_db_id_or_err("media")?;
+        media_ids.push(media_proto_id.to_db_id_or_err("media")?);
     }
+    validate_media_ownership(&user, &media_ids, conn)?;
 
     // Generate the list of the post's ancestors so we can increment their response_count all at once.
     let mut ancestor_post_ids: Vec<i64> = vec![];
@@ -135,7 +138,10 @@ pub fn create_post(
     match post {
         Ok(post) => {
             log::info!("Post created! PostID:{:?}", post.id);
-            Ok(Response::new(post.to_proto(Some(user.username))))
+            if let Err(e) = activitypub::enqueue_post_create(&post, &user, conn) {
+                log::warn!("Failed to queue ActivityPub delivery for post {}: {:?}", post.id, e);
+            }
+            Ok(Response::new(post.to_proto(Some(user.username), Some(user.permissions))))
         }
         Err(e) => {
             log::error!("Error creating post! {:?}", e);
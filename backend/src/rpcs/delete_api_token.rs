@@ -0,0 +1,33 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::user_api_tokens;
+
+pub fn delete_api_token(
+    request: ApiToken,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    let api_token_id = request.id.to_db_id_or_err("id")?;
+    let deleted = delete(
+        user_api_tokens::table
+            .filter(user_api_tokens::id.eq(api_token_id))
+            .filter(user_api_tokens::user_id.eq(user.id)),
+    )
+    .execute(conn)
+    .map_err(|e| {
+        log::error!("Error deleting API token: {:?}", e);
+        Status::new(Code::Internal, "data_error")
+    })?;
+    match deleted {
+        0 => Err(Status::new(Code::NotFound, "api_token_not_found")),
+        _ => {
+            log::info!("Deleted API token {} for user_id={}", api_token_id, user.id);
+            Ok(())
+        }
+    }
+}
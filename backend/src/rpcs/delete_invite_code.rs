@@ -0,0 +1,30 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::invite_codes;
+
+use super::validations::*;
+
+pub fn delete_invite_code(
+    request: InviteCode,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    validate_permission(&user, Permission::Admin)?;
+    let invite_code_id = request.id.to_db_id_or_err("id")?;
+    match delete(invite_codes::table.find(invite_code_id)).execute(conn) {
+        Ok(0) => Err(Status::new(Code::NotFound, "invite_code_not_found")),
+        Ok(_) => {
+            log::info!("Deleted invite code {}", invite_code_id);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Error deleting invite code: {:?}", e);
+            Err(Status::new(Code::Internal, "data_error"))
+        }
+    }
+}
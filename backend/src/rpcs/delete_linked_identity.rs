@@ -0,0 +1,30 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::linked_identities;
+
+pub fn delete_linked_identity(
+    request: LinkedIdentity,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    let identity_id = request.id.to_db_id_or_err("id")?;
+    let deleted = delete(
+        linked_identities::table
+            .filter(linked_identities::id.eq(identity_id))
+            .filter(linked_identities::user_id.eq(user.id)),
+    )
+    .execute(conn)
+    .map_err(|e| {
+        log::error!("Error deleting linked identity: {:?}", e);
+        Status::new(Code::Internal, "data_error")
+    })?;
+    match deleted {
+        0 => Err(Status::new(Code::NotFound, "linked_identity_not_found")),
+        _ => Ok(()),
+    }
+}
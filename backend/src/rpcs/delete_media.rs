@@ -6,7 +6,7 @@ use crate::db_connection::PgPooledConnection;
 use crate::marshaling::*;
 use crate::models;
 use crate::protos::*;
-use crate::schema::media;
+use crate::schema::{media, media_album_items};
 
 use super::validations::*;
 
@@ -43,9 +43,16 @@ pub fn delete_media(
         return Err(Status::new(Code::PermissionDenied, "not_your_media"));
     }
 
-    let db_result = update(media::table.find(request.id.to_db_id_or_err("id")?))
-        .set(media::user_id.eq(None::<i64>))
-        .execute(conn);
+    // Unowned media (and its reference to its blob) is cleaned up by `delete_unowned_media`,
+    // which only deletes the blob's object once no other media shares it.
+    let media_id = request.id.to_db_id_or_err("id")?;
+    let db_result = conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        delete(media_album_items::table.filter(media_album_items::media_id.eq(media_id)))
+            .execute(conn)?;
+        update(media::table.find(media_id))
+            .set(media::user_id.eq(None::<i64>))
+            .execute(conn)
+    });
 
     let result = match db_result {
         Ok(size) if size == 0 => Err(Status::new(Code::NotFound, "media_not_found")),
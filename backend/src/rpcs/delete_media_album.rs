@@ -0,0 +1,29 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::media_albums;
+
+use super::validations::*;
+
+pub fn delete_media_album(
+    request: MediaAlbum,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    let album = models::get_media_album(request.id.to_db_id_or_err("id")?, conn)?;
+    if album.user_id != user.id {
+        validate_permission(&user, Permission::Admin)?;
+    }
+    // Album items are deleted by cascade; the media itself is untouched.
+    match delete(media_albums::table.find(album.id)).execute(conn) {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            log::error!("Error deleting media album: {:?}", e);
+            Err(Status::new(Code::Internal, "data_error"))
+        }
+    }
+}
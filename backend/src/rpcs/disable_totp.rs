@@ -0,0 +1,47 @@
+use bcrypt::verify;
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::{user_recovery_codes, user_totps};
+
+use super::get_server_configuration;
+
+pub fn disable_totp(
+    request: DisableTotpRequest,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    if requires_two_factor(&user, &get_server_configuration(conn)?) {
+        return Err(Status::new(Code::FailedPrecondition, "two_factor_required"));
+    }
+    match verify(&request.password, &user.password_salted_hash) {
+        Ok(true) => {}
+        _ => return Err(Status::new(Code::PermissionDenied, "invalid_password")),
+    };
+    let totp = get_confirmed_totp(user.id, conn)?
+        .ok_or_else(|| Status::new(Code::FailedPrecondition, "totp_not_enabled"))?;
+    if !use_two_factor_code(&totp, &request.code, conn)? {
+        return Err(Status::new(Code::PermissionDenied, "two_factor_code_invalid"));
+    }
+
+    let result = conn.transaction::<(), diesel::result::Error, _>(|conn| {
+        delete(user_totps::table.filter(user_totps::user_id.eq(user.id))).execute(conn)?;
+        delete(user_recovery_codes::table.filter(user_recovery_codes::user_id.eq(user.id)))
+            .execute(conn)?;
+        Ok(())
+    });
+    match result {
+        Ok(_) => {
+            log::info!("Disabled TOTP for user_id={}", user.id);
+            Ok(())
+        }
+        Err(e) => {
+            log::error!("Error disabling TOTP: {:?}", e);
+            Err(Status::new(Code::Internal, "data_error"))
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::user_totps;
+
+use super::get_server_name;
+
+pub fn enroll_totp(
+    request: EnrollTotpRequest,
+    user: Option<models::User>,
+    conn: &mut PgPooledConnection,
+) -> Result<TotpEnrollment, Status> {
+    let (user, _) = get_totp_enrollment_user(request.two_factor_token.as_deref(), user, conn)?;
+
+    let secret = generate_totp_secret();
+    let result = conn.transaction::<usize, diesel::result::Error, _>(|conn| {
+        // Replaces any unconfirmed enrollment.
+        delete(user_totps::table.filter(user_totps::user_id.eq(user.id))).execute(conn)?;
+        insert_into(user_totps::table)
+            .values(&models::NewUserTotp {
+                user_id: user.id,
+                secret: secret.to_owned(),
+            })
+            .execute(conn)
+    });
+    if let Err(e) = result {
+        log::error!("Error enrolling TOTP: {:?}", e);
+        return Err(Status::new(Code::Internal, "data_error"));
+    }
+
+    let server_name = get_server_name(conn)?;
+    Ok(TotpEnrollment {
+        provisioning_uri: totp_provisioning_uri(&server_name, &user.username, &secret),
+        secret,
+    })
+}
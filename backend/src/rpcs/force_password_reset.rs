@@ -0,0 +1,53 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::auth;
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::marshaling::*;
+use crate::messaging::Messenger;
+use crate::models;
+use crate::protos::*;
+use crate::schema::users;
+
+use super::get_server_name;
+use super::validations::*;
+
+pub fn force_password_reset(
+    request: User,
+    user: models::User,
+    messenger: Arc<dyn Messenger>,
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    validate_permission(&user, Permission::Admin)?;
+    let target = models::get_user(request.id.to_db_id_or_err("id")?, conn)?;
+    // Otherwise the user could never log in again.
+    if !can_issue_password_reset(&target, &messenger) {
+        return Err(Status::new(Code::FailedPrecondition, "verified_email_required"));
+    }
+
+    let result = conn.transaction::<models::User, diesel::result::Error, _>(|conn| {
+        let target = update(users::table.find(target.id))
+            .set((
+                users::password_reset_required.eq(true),
+                users::updated_at.eq(SystemTime::now()),
+            ))
+            .get_result::<models::User>(conn)?;
+        auth::revoke_user_tokens(target.id, None, conn)?;
+        Ok(target)
+    });
+    let target = result.map_err(|e| {
+        log::error!("Error forcing password reset: {:?}", e);
+        Status::new(Code::Internal, "data_error")
+    })?;
+    log::info!("user_id={} forced a password reset for user_id={}", user.id, target.id);
+
+    let server_name = get_server_name(conn)?;
+    if let Err(e) = issue_password_reset(&target, &server_name, messenger, conn) {
+        log::error!("Error issuing password reset for user_id={}: {:?}", target.id, e);
+    }
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::{account_lockouts, users};
+
+use super::validations::*;
+
+/// How many accounts `GetAccountLockouts` returns, most recently failed first.
+const MAX_ACCOUNT_LOCKOUTS: i64 = 200;
+
+pub fn get_account_lockouts(
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<GetAccountLockoutsResponse, Status> {
+    validate_permission(&user, Permission::Admin)?;
+    let lockouts = account_lockouts::table
+        .inner_join(users::table)
+        .select((account_lockouts::all_columns, users::username))
+        .order(account_lockouts::last_failed_at.desc())
+        .limit(MAX_ACCOUNT_LOCKOUTS)
+        .load::<(models::AccountLockout, String)>(conn)
+        .map_err(|e| {
+            log::error!("Error loading account lockouts: {:?}", e);
+            Status::new(Code::Internal, "data_error")
+        })?;
+    Ok(GetAccountLockoutsResponse {
+        account_lockouts: lockouts
+            .into_iter()
+            .map(|(lockout, username)| lockout.to_proto(username))
+            .collect(),
+    })
+}
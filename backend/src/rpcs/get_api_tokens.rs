@@ -0,0 +1,25 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::user_api_tokens;
+
+pub fn get_api_tokens(
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<GetApiTokensResponse, Status> {
+    let api_tokens = user_api_tokens::table
+        .filter(user_api_tokens::user_id.eq(user.id))
+        .order(user_api_tokens::created_at.desc())
+        .load::<models::UserApiToken>(conn)
+        .map_err(|e| {
+            log::error!("Error loading API tokens: {:?}", e);
+            Status::new(Code::Internal, "data_error")
+        })?;
+    Ok(GetApiTokensResponse {
+        api_tokens: api_tokens.iter().map(|api_token| api_token.to_proto()).collect(),
+    })
+}
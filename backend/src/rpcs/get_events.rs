@@ -337,17 +337,17 @@ fn _get_top_posts(user: &Option<models::User>, conn: &mut PgPooledConnection) ->
                 .eq(follows::target_user_id.nullable())
                 .and(follows::user_id.eq(user.as_ref().map(|u| u.id).unwrap_or(0)))),
         )
-        .select((posts::all_columns, users::username.nullable()))
+        .select((posts::all_columns, users::username.nullable(), users::permissions.nullable()))
         // .filter(posts::visibility.eq_any(visibilities))
         .filter(public.or(limited_to_followers))
         .filter(posts::parent_post_id.is_null())
         .filter(posts::context.eq(PostContext::Post.as_str_name()))
         .order(posts::created_at.desc())
         .limit(100)
-        .load::<(models::Post, Option<String>)>(conn)
+        .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
         .unwrap()
         .iter()
-        .map(|(post, username)| post.to_proto(username.to_owned()))
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
         .collect()
 }
 
@@ -362,7 +362,7 @@ fn _get_my_group_posts(user: &models::User, conn: &mut PgPooledConnection) -> Ve
             .inner_join(group_posts::table.on(group_posts::group_id.eq(groups::id)))
             .inner_join(posts::table.on(group_posts::post_id.eq(posts::id)))
             .left_join(users::table.on(posts::user_id.eq(users::id.nullable())))
-            .select((posts::all_columns, users::username.nullable()))
+            .select((posts::all_columns, users::username.nullable(), users::permissions.nullable()))
             .filter(memberships::user_id.eq(user.id))
             .filter(memberships::group_moderation.eq_any(PASSING_MODERATIONS))
             .filter(memberships::user_moderation.eq_any(PASSING_MODERATIONS))
@@ -372,10 +372,10 @@ fn _get_my_group_posts(user: &models::User, conn: &mut PgPooledConnection) -> Ve
             .order(posts::id.desc())
             .distinct_on(posts::id)
             .limit(100)
-            .load::<(models::Post, Option<String>)>(conn)
+            .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
             .unwrap()
             .iter()
-            .map(|(post, username)| post.to_proto(username.to_owned()))
+            .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
             .collect();
     }
     memberships::table
@@ -383,7 +383,7 @@ fn _get_my_group_posts(user: &models::User, conn: &mut PgPooledConnection) -> Ve
         .inner_join(group_posts::table.on(group_posts::group_id.eq(groups::id)))
         .inner_join(posts::table.on(group_posts::post_id.eq(posts::id)))
         .left_join(users::table.on(posts::user_id.eq(users::id.nullable())))
-        .select((posts::all_columns, users::username.nullable()))
+        .select((posts::all_columns, users::username.nullable(), users::permissions.nullable()))
         .filter(memberships::user_id.eq(user.id))
         .filter(
             memberships::permissions.has_any_key(
@@ -398,10 +398,10 @@ fn _get_my_group_posts(user: &models::User, conn: &mut PgPooledConnection) -> Ve
         .order(posts::id.desc())
         .distinct_on(posts::id)
         .limit(100)
-        .load::<(models::Post, Option<String>)>(conn)
+        .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
         .unwrap()
         .iter()
-        .map(|(post, username)| post.to_proto(username.to_owned()))
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
         .collect()
 }
 
@@ -420,6 +420,7 @@ fn _get_group_posts(
             .select((
                 posts::all_columns,
                 users::username.nullable(),
+                users::permissions.nullable(),
                 group_posts::all_columns,
             ))
             .filter(group_posts::group_id.eq(group_id))
@@ -428,11 +429,11 @@ fn _get_group_posts(
             .filter(posts::context.eq(PostContext::Post.as_str_name()))
             .order(posts::created_at.desc())
             .limit(100)
-            .load::<(models::Post, Option<String>, models::GroupPost)>(conn)
+            .load::<(models::Post, Option<String>, Option<serde_json::Value>, models::GroupPost)>(conn)
             .unwrap()
             .iter()
-            .map(|(post, username, group_post)| {
-                post.to_group_proto(username.to_owned(), Some(group_post))
+            .map(|(post, username, permissions, group_post)| {
+                post.to_group_proto(username.to_owned(), permissions.to_owned(), Some(group_post))
             })
             .collect::<Vec<Post>>(),
         (Visibility::GlobalPublic, Some(_)) => group_posts::table
@@ -441,6 +442,7 @@ fn _get_group_posts(
             .select((
                 posts::all_columns,
                 users::username.nullable(),
+                users::permissions.nullable(),
                 group_posts::all_columns,
             ))
             .filter(group_posts::group_id.eq(group_id))
@@ -452,11 +454,11 @@ fn _get_group_posts(
             .filter(posts::context.eq(PostContext::Post.as_str_name()))
             .order(posts::created_at.desc())
             .limit(100)
-            .load::<(models::Post, Option<String>, models::GroupPost)>(conn)
+            .load::<(models::Post, Option<String>, Option<serde_json::Value>, models::GroupPost)>(conn)
             .unwrap()
             .iter()
-            .map(|(post, username, group_post)| {
-                post.to_group_proto(username.to_owned(), Some(group_post))
+            .map(|(post, username, permissions, group_post)| {
+                post.to_group_proto(username.to_owned(), permissions.to_owned(), Some(group_post))
             })
             .collect::<Vec<Post>>(),
         (_, None) => return Err(Status::new(Code::NotFound, "group_not_found")),
@@ -490,17 +492,17 @@ fn _load_group_posts(
     group_posts::table
         .inner_join(posts::table.on(group_posts::post_id.eq(posts::id)))
         .left_join(users::table.on(posts::user_id.eq(users::id.nullable())))
-        .select((posts::all_columns, users::username.nullable()))
+        .select((posts::all_columns, users::username.nullable(), users::permissions.nullable()))
         .filter(group_posts::group_id.eq(group_id))
         .filter(group_posts::group_moderation.eq_any(moderations.to_string_moderations()))
         .filter(posts::visibility.ne(Visibility::Private.as_str_name()))
         .filter(posts::context.eq(PostContext::Post.as_str_name()))
         .order(posts::created_at.desc())
         .limit(100)
-        .load::<(models::Post, Option<String>)>(conn)
+        .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
         .unwrap()
         .iter()
-        .map(|(post, username)| post.to_proto(username.to_owned()))
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
         .collect::<Vec<Post>>()
 }
 
@@ -516,17 +518,17 @@ fn _get_user_posts(
     .to_string_visibilities();
     posts::table
         .left_join(users::table.on(posts::user_id.eq(users::id.nullable())))
-        .select((posts::all_columns, users::username.nullable()))
+        .select((posts::all_columns, users::username.nullable(), users::permissions.nullable()))
         .filter(posts::visibility.eq_any(visibilities))
         // .filter(posts::parent_post_id.is_null())
         .filter(posts::user_id.eq(user_id))
         .filter(posts::context.eq(PostContext::Post.as_str_name()))
         .order(posts::last_activity_at.desc())
         .limit(100)
-        .load::<(models::Post, Option<String>)>(conn)
+        .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
         .unwrap()
         .iter()
-        .map(|(post, username)| post.to_proto(username.to_owned()))
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
         .collect()
 }
 
@@ -534,7 +536,7 @@ fn _get_following_posts(user: &models::User, conn: &mut PgPooledConnection) -> V
     follows::table
         .inner_join(posts::table.on(follows::target_user_id.nullable().eq(posts::user_id)))
         .left_join(users::table.on(posts::user_id.eq(users::id.nullable())))
-        .select((posts::all_columns, users::username.nullable()))
+        .select((posts::all_columns, users::username.nullable(), users::permissions.nullable()))
         .filter(follows::user_id.eq(user.id))
         .filter(follows::target_user_moderation.eq_any(PASSING_MODERATIONS))
         .filter(posts::visibility.eq_any(
@@ -543,10 +545,10 @@ fn _get_following_posts(user: &models::User, conn: &mut PgPooledConnection) -> V
         .filter(posts::context.eq(PostContext::Post.as_str_name()))
         .order(posts::created_at.desc())
         .limit(100)
-        .load::<(models::Post, Option<String>)>(conn)
+        .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
         .unwrap()
         .iter()
-        .map(|(post, username)| post.to_proto(username.to_owned()))
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
         .collect()
 }
 fn _get_replies_to_post_id(
@@ -566,15 +568,15 @@ fn _get_replies_to_post_id(
     };
     let result: Vec<Post> = posts::table
         .left_join(users::table.on(posts::user_id.eq(users::id.nullable())))
-        .select((posts::all_columns, users::username.nullable()))
+        .select((posts::all_columns, users::username.nullable(), users::permissions.nullable()))
         .filter(posts::visibility.eq(Visibility::GlobalPublic.as_str_name()))
         .filter(posts::parent_post_id.eq(post_db_id))
         .order(posts::created_at.desc())
         .limit(100)
-        .load::<(models::Post, Option<String>)>(conn)
+        .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
         .unwrap()
         .iter()
-        .map(|(post, username)| post.to_proto(username.to_owned()))
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
         .collect();
     if reply_depth > 1 {
         let extended_result: Vec<Post> = result
@@ -0,0 +1,44 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::invite_codes;
+
+use super::validations::*;
+
+pub fn get_invite_codes(
+    request: GetInviteCodesRequest,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<GetInviteCodesResponse, Status> {
+    log::info!("GetInviteCodes called: {:?}", request);
+    validate_permission(&user, Permission::Admin)?;
+
+    let mut query = invite_codes::table.into_boxed();
+    if !request.include_inactive {
+        query = query
+            .filter(
+                invite_codes::expires_at
+                    .is_null()
+                    .or(invite_codes::expires_at.gt(diesel::dsl::now.nullable())),
+            )
+            .filter(
+                invite_codes::max_uses
+                    .is_null()
+                    .or(invite_codes::use_count.nullable().lt(invite_codes::max_uses)),
+            );
+    }
+    let invite_codes = query
+        .order(invite_codes::created_at.desc())
+        .load::<models::InviteCode>(conn)
+        .map_err(|e| {
+            log::error!("Error loading invite codes: {:?}", e);
+            Status::new(Code::Internal, "data_error")
+        })?;
+    Ok(GetInviteCodesResponse {
+        invite_codes: invite_codes.iter().map(|code| code.to_proto()).collect(),
+    })
+}
@@ -0,0 +1,25 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::linked_identities;
+
+pub fn get_linked_identities(
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<GetLinkedIdentitiesResponse, Status> {
+    let identities = linked_identities::table
+        .filter(linked_identities::user_id.eq(user.id))
+        .order(linked_identities::created_at.asc())
+        .load::<models::LinkedIdentity>(conn)
+        .map_err(|e| {
+            log::error!("Error loading linked identities: {:?}", e);
+            Status::new(Code::Internal, "data_error")
+        })?;
+    Ok(GetLinkedIdentitiesResponse {
+        linked_identities: identities.iter().map(|identity| identity.to_proto()).collect(),
+    })
+}
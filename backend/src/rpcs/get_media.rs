@@ -1,7 +1,8 @@
 use diesel::*;
-use tonic::Status;
+use tonic::{Code, Status};
 
 use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
 use crate::marshaling::*;
 use crate::models;
 use crate::protos::*;
@@ -13,15 +14,26 @@ pub fn get_media(
     conn: &mut PgPooledConnection,
 ) -> Result<GetMediaResponse, Status> {
     log::info!("GetMedia called: ${:?}", request);
-    let response = match (request.to_owned().media_id, request.to_owned().user_id) {
-        (Some(_), _) => get_by_id(request.to_owned(), user, conn)?,
-        (_, Some(_)) => get_user_media(request.to_owned(), user, conn)?,
+    let mut response = match (
+        request.to_owned().media_id,
+        request.to_owned().album_id,
+        request.to_owned().user_id,
+    ) {
+        (Some(_), _, _) => get_by_id(request.to_owned(), user, conn)?,
+        (_, Some(_), _) => get_album_media(request.to_owned(), user, conn)?,
+        (_, _, Some(_)) => get_user_media(request.to_owned(), user, conn)?,
         _ => {
             return Err(Status::invalid_argument(
-                "media_id or user_id must be provided",
+                "media_id, album_id or user_id must be provided",
             ))
         }
     };
+    if let Some(content_type) = &request.content_type {
+        let allowed = vec![content_type.to_owned()];
+        response
+            .media
+            .retain(|media| content_type_allowed(&media.content_type, &allowed));
+    }
     // log::info!(
     //     "GetMedia::request: {:?}, response: {:?}",
     //     request, response
@@ -60,11 +72,19 @@ fn get_user_media(
     .map(|v| v.as_str_name())
     .collect::<Vec<&str>>();
 
-    let media = media::table
+    let mut query = media::table
         .select(media::all_columns)
         .filter(media::visibility.eq_any(visibilities))
         // .filter(media::name.ilike(format!("{}%", request.media_name.unwrap())))
         .filter(media::user_id.eq(requested_user_id))
+        .into_boxed();
+    if let Some(content_type) = request.content_type {
+        query = match content_type.strip_suffix("/*") {
+            Some(major) => query.filter(media::content_type.like(format!("{}/%", major))),
+            None => query.filter(media::content_type.eq(content_type.to_owned())),
+        };
+    }
+    let media = query
         .order(media::created_at.desc())
         .limit(100)
         .offset((request.page * 100).into())
@@ -79,6 +99,30 @@ fn get_user_media(
     })
 }
 
+fn get_album_media(
+    request: GetMediaRequest,
+    user: Option<models::User>,
+    conn: &mut PgPooledConnection,
+) -> Result<GetMediaResponse, Status> {
+    log::info!("get_album_media: {:?}", request);
+    let album = models::get_media_album(
+        request.album_id.unwrap().to_db_id_or_err("album_id")?,
+        conn,
+    )?;
+    if !can_view_media_album(&album, &user, conn) {
+        return Err(Status::new(Code::NotFound, "media_album_not_found"));
+    }
+    let media = models::get_media_album_media(album.id, conn)?
+        .iter()
+        .filter(|media| can_view_media(media, &user, conn))
+        .map(|media| media.to_proto())
+        .collect();
+    Ok(GetMediaResponse {
+        media,
+        has_next_page: false,
+    })
+}
+
 fn get_by_id(
     request: GetMediaRequest,
     user: Option<models::User>,
@@ -0,0 +1,69 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::media_albums;
+
+const PAGE_SIZE: i64 = 100;
+
+pub fn get_media_albums(
+    request: GetMediaAlbumsRequest,
+    user: Option<models::User>,
+    conn: &mut PgPooledConnection,
+) -> Result<GetMediaAlbumsResponse, Status> {
+    log::info!("GetMediaAlbums called: {:?}", request);
+    let albums = match (request.album_id.to_owned(), request.user_id.to_owned()) {
+        (Some(album_id), _) => vec![models::get_media_album(
+            album_id.to_db_id_or_err("album_id")?,
+            conn,
+        )?],
+        (None, user_id) => {
+            let user_id = match (user_id, user.as_ref()) {
+                (Some(user_id), _) => user_id.to_db_id_or_err("user_id")?,
+                (None, Some(user)) => user.id,
+                (None, None) => {
+                    return Err(Status::new(
+                        Code::InvalidArgument,
+                        "must_be_authenticated_or_provide_user_id",
+                    ))
+                }
+            };
+            media_albums::table
+                .filter(media_albums::user_id.eq(user_id))
+                .order(media_albums::updated_at.desc())
+                .limit(PAGE_SIZE + 1)
+                .offset(request.page as i64 * PAGE_SIZE)
+                .load::<models::MediaAlbum>(conn)
+                .map_err(|_| Status::new(Code::Internal, "data_error"))?
+        }
+    };
+    let has_next_page = albums.len() as i64 > PAGE_SIZE;
+
+    let mut result = vec![];
+    for album in albums.into_iter().take(PAGE_SIZE as usize) {
+        if !can_view_media_album(&album, &user, conn) {
+            continue;
+        }
+        let media_ids = models::get_media_album_media(album.id, conn)?
+            .iter()
+            .filter(|media| can_view_media(media, &user, conn))
+            .map(|media| media.id)
+            .collect::<Vec<i64>>();
+        let mut album = album.to_proto(&media_ids);
+        if !album.media_ids.iter().any(|id| Some(id) == album.cover_media_id.as_ref()) {
+            album.cover_media_id = None;
+        }
+        result.push(album);
+    }
+    if request.album_id.is_some() && result.is_empty() {
+        return Err(Status::new(Code::NotFound, "media_album_not_found"));
+    }
+    Ok(GetMediaAlbumsResponse {
+        albums: result,
+        has_next_page,
+    })
+}
@@ -0,0 +1,27 @@
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::models;
+use crate::protos::*;
+
+use super::get_server_configuration;
+
+pub fn get_media_quota(
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<MediaQuota, Status> {
+    let configuration = get_server_configuration(conn)?;
+    let limits = media_limits(&user, &configuration);
+    let usage = media_usage(user.id, conn).map_err(|e| {
+        log::error!("Error loading media usage: {:?}", e);
+        Status::new(Code::Internal, "data_error")
+    })?;
+    Ok(MediaQuota {
+        used_bytes: usage.bytes,
+        media_count: usage.count,
+        max_total_size: limits.max_total_size,
+        max_file_size: max_media_file_size(&limits),
+        allowed_content_types: limits.allowed_content_types,
+    })
+}
@@ -0,0 +1,49 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::marshaling::*;
+use crate::media_processing::MEDIA_SIZES;
+use crate::models;
+use crate::protos::*;
+
+pub fn get_media_url(
+    request: GetMediaUrlRequest,
+    user: Option<models::User>,
+    conn: &mut PgPooledConnection,
+) -> Result<MediaUrl, Status> {
+    log::info!("GetMediaUrl called: {:?}", request);
+    let media = models::get_media(request.media_id.to_db_id_or_err("media_id")?, conn)?;
+    if !can_view_media(&media, &user, conn) {
+        return Err(Status::new(Code::NotFound, "media_not_found"));
+    }
+
+    let size = match request.size.as_deref() {
+        None | Some("original") => None,
+        Some("thumbnail") => Some("thumbnail"),
+        Some(size) if MEDIA_SIZES.iter().any(|(name, _)| *name == size) => Some(size),
+        Some(_) => return Err(Status::new(Code::InvalidArgument, "size_invalid")),
+    };
+    let ttl = match request.expires_in_seconds {
+        None => DEFAULT_MEDIA_URL_TTL,
+        Some(ttl) if ttl > 0 && ttl as u64 <= MAX_MEDIA_URL_TTL => ttl as u64,
+        Some(_) => {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                format!("expires_in_seconds_must_be_between_1_and_{}", MAX_MEDIA_URL_TTL),
+            ))
+        }
+    };
+
+    let key = media_url_signing_key(conn).map_err(|e| {
+        log::error!("Error loading media URL signing key: {:?}", e);
+        Status::new(Code::Internal, "data_error")
+    })?;
+    let expires = media_url_expiry(SystemTime::now(), ttl);
+    Ok(MediaUrl {
+        url: signed_media_url(&key, media.id, size, expires),
+        expires_at: Some((UNIX_EPOCH + Duration::from_secs(expires)).to_proto()),
+    })
+}
@@ -81,10 +81,11 @@ fn get_by_post_id(
         .select((
             posts::all_columns,
             users::username.nullable(),
+            users::permissions.nullable(),
         ))
         .filter(posts::id.eq(post_db_id))
-        .get_result::<(models::Post, Option<String>)>(conn)
-        .map(|(post, username)| post.to_proto(username.to_owned()));
+        .get_result::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()));
 
     match result {
         Ok(post) => match (post.visibility(), user) {
@@ -111,6 +112,7 @@ fn get_public_and_following_posts(user: &Option<models::User>, conn: &mut PgPool
         .select((
             posts::all_columns,
             users::username.nullable(),
+            users::permissions.nullable(),
         ))
         // .filter(posts::visibility.eq_any(visibilities))
         .filter(public.or(limited_to_followers))
@@ -118,10 +120,10 @@ fn get_public_and_following_posts(user: &Option<models::User>, conn: &mut PgPool
         .filter(posts::context.eq(PostContext::Post.as_str_name()))
         .order(posts::created_at.desc())
         .limit(100)
-        .load::<(models::Post, Option<String>)>(conn)
+        .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
         .unwrap()
         .iter()
-        .map(|(post, username)| post.to_proto(username.to_owned()))
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
         .collect()
 }
 
@@ -139,6 +141,7 @@ fn get_my_group_posts(user: &models::User, conn: &mut PgPooledConnection) -> Vec
             .select((
                 posts::all_columns,
                 users::username.nullable(),
+                users::permissions.nullable(),
             ))
             .filter(memberships::user_id.eq(user.id))
             .filter(memberships::group_moderation.eq_any(PASSING_MODERATIONS))
@@ -149,10 +152,10 @@ fn get_my_group_posts(user: &models::User, conn: &mut PgPooledConnection) -> Vec
             .order(posts::id.desc())
             .distinct_on(posts::id)
             .limit(100)
-            .load::<(models::Post, Option<String>)>(conn)
+            .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
             .unwrap()
             .iter()
-            .map(|(post, username)| post.to_proto(username.to_owned()))
+            .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
             .collect();
     }
     memberships::table
@@ -163,6 +166,7 @@ fn get_my_group_posts(user: &models::User, conn: &mut PgPooledConnection) -> Vec
         .select((
             posts::all_columns,
             users::username.nullable(),
+            users::permissions.nullable(),
         ))
         .filter(memberships::user_id.eq(user.id))
         .filter(
@@ -178,10 +182,10 @@ fn get_my_group_posts(user: &models::User, conn: &mut PgPooledConnection) -> Vec
         .order(posts::id.desc())
         .distinct_on(posts::id)
         .limit(100)
-        .load::<(models::Post, Option<String>)>(conn)
+        .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
         .unwrap()
         .iter()
-        .map(|(post, username)| post.to_proto(username.to_owned()))
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
         .collect()
 }
 
@@ -200,6 +204,7 @@ fn get_group_posts(
             .select((
                 posts::all_columns,
                 users::username.nullable(),
+                users::permissions.nullable(),
                 group_posts::all_columns,
             ))
             .filter(group_posts::group_id.eq(group_id))
@@ -208,11 +213,11 @@ fn get_group_posts(
             .filter(posts::context.eq(PostContext::Post.as_str_name()))
             .order(posts::created_at.desc())
             .limit(100)
-            .load::<(models::Post, Option<String>, models::GroupPost)>(conn)
+            .load::<(models::Post, Option<String>, Option<serde_json::Value>, models::GroupPost)>(conn)
             .unwrap()
             .iter()
-            .map(|(post, username, group_post)| {
-                post.to_group_proto(username.to_owned(), Some(group_post))
+            .map(|(post, username, permissions, group_post)| {
+                post.to_group_proto(username.to_owned(), permissions.to_owned(), Some(group_post))
             })
             .collect::<Vec<Post>>(),
         (Visibility::GlobalPublic, Some(_)) => group_posts::table
@@ -221,6 +226,7 @@ fn get_group_posts(
             .select((
                 posts::all_columns,
                 users::username.nullable(),
+                users::permissions.nullable(),
                 group_posts::all_columns,
             ))
             .filter(group_posts::group_id.eq(group_id))
@@ -229,11 +235,11 @@ fn get_group_posts(
             .filter(posts::context.eq(PostContext::Post.as_str_name()))
             .order(posts::created_at.desc())
             .limit(100)
-            .load::<(models::Post, Option<String>, models::GroupPost)>(conn)
+            .load::<(models::Post, Option<String>, Option<serde_json::Value>, models::GroupPost)>(conn)
             .unwrap()
             .iter()
-            .map(|(post, username, group_post)| {
-                post.to_group_proto(username.to_owned(), Some(group_post))
+            .map(|(post, username, permissions, group_post)| {
+                post.to_group_proto(username.to_owned(), permissions.to_owned(), Some(group_post))
             })
             .collect::<Vec<Post>>(),
         (_, None) => return Err(Status::new(Code::NotFound, "group_not_found")),
@@ -270,6 +276,7 @@ fn load_group_posts(
         .select((
             posts::all_columns,
             users::username.nullable(),
+            users::permissions.nullable(),
         ))
         .filter(group_posts::group_id.eq(group_id))
         .filter(group_posts::group_moderation.eq_any(moderations.to_string_moderations()))
@@ -277,10 +284,10 @@ fn load_group_posts(
         .filter(posts::context.eq(PostContext::Post.as_str_name()))
         .order(posts::created_at.desc())
         .limit(100)
-        .load::<(models::Post, Option<String>)>(conn)
+        .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
         .unwrap()
         .iter()
-        .map(|(post, username)| post.to_proto(username.to_owned()))
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
         .collect::<Vec<Post>>()
 }
 
@@ -299,6 +306,7 @@ fn get_user_posts(
         .select((
             posts::all_columns,
             users::username.nullable(),
+            users::permissions.nullable(),
         ))
         .filter(posts::visibility.eq_any(visibilities))
         // .filter(posts::parent_post_id.is_null())
@@ -306,10 +314,10 @@ fn get_user_posts(
         .filter(posts::context.eq(PostContext::Post.as_str_name()))
         .order(posts::last_activity_at.desc())
         .limit(100)
-        .load::<(models::Post, Option<String>)>(conn)
+        .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
         .unwrap()
         .iter()
-        .map(|(post, username)| post.to_proto(username.to_owned()))
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
         .collect()
 }
 
@@ -320,6 +328,7 @@ fn get_following_posts(user: &models::User, conn: &mut PgPooledConnection) -> Ve
         .select((
             posts::all_columns,
             users::username.nullable(),
+            users::permissions.nullable(),
         ))
         .filter(follows::user_id.eq(user.id))
         .filter(follows::target_user_moderation.eq_any(PASSING_MODERATIONS))
@@ -329,10 +338,10 @@ fn get_following_posts(user: &models::User, conn: &mut PgPooledConnection) -> Ve
         .filter(posts::context.eq(PostContext::Post.as_str_name()))
         .order(posts::created_at.desc())
         .limit(100)
-        .load::<(models::Post, Option<String>)>(conn)
+        .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
         .unwrap()
         .iter()
-        .map(|(post, username)| post.to_proto(username.to_owned()))
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
         .collect()
 }
 fn get_replies_to_post_id(
@@ -355,15 +364,16 @@ fn get_replies_to_post_id(
         .select((
             posts::all_columns,
             users::username.nullable(),
+            users::permissions.nullable(),
         ))
         .filter(posts::visibility.eq(Visibility::GlobalPublic.as_str_name()))
         .filter(posts::parent_post_id.eq(post_db_id))
         .order(posts::created_at.desc())
         .limit(100)
-        .load::<(models::Post, Option<String>)>(conn)
+        .load::<(models::Post, Option<String>, Option<serde_json::Value>)>(conn)
         .unwrap()
         .iter()
-        .map(|(post, username)| post.to_proto(username.to_owned()))
+        .map(|(post, username, permissions)| post.to_proto(username.to_owned(), permissions.to_owned()))
         .collect();
     if reply_depth > 1 {
         let extended_result: Vec<Post> = result
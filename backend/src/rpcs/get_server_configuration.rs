@@ -55,3 +55,11 @@ pub fn create_default_server_configuration(
     log::info!("Generated new default server configuration: {:?}", result);
     Ok(result)
 }
+
+/// The server's name (for messages to users and the like), defaulting to "Jonline".
+pub fn get_server_name(conn: &mut PgPooledConnection) -> Result<String, Status> {
+    Ok(get_server_configuration(conn)?
+        .server_info
+        .and_then(|info| info.name)
+        .unwrap_or_else(|| "Jonline".to_string()))
+}
@@ -0,0 +1,42 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::{user_devices, user_refresh_tokens};
+
+pub fn get_sessions(
+    user: models::User,
+    current_refresh_token_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<GetSessionsResponse, Status> {
+    let sessions = user_refresh_tokens::table
+        .left_join(user_devices::table)
+        .select((
+            user_refresh_tokens::all_columns,
+            user_devices::device_name.nullable(),
+        ))
+        .filter(user_refresh_tokens::user_id.eq(user.id))
+        .filter(user_refresh_tokens::rotated_at.is_null())
+        .filter(user_refresh_tokens::expires_at.gt(diesel::dsl::now))
+        .order((
+            user_refresh_tokens::last_used_at.desc().nulls_last(),
+            user_refresh_tokens::created_at.desc(),
+        ))
+        .load::<(models::UserRefreshToken, Option<String>)>(conn)
+        .map_err(|e| {
+            log::error!("Error loading sessions: {:?}", e);
+            Status::new(Code::Internal, "data_error")
+        })?;
+    Ok(GetSessionsResponse {
+        sessions: sessions
+            .into_iter()
+            .map(|(session, device_name)| {
+                let current = session.id == current_refresh_token_id;
+                session.to_proto(device_name, current)
+            })
+            .collect(),
+    })
+}
@@ -1,12 +1,17 @@
+use std::time::SystemTime;
+
 use bcrypt::verify;
 use diesel::*;
 use tonic::{Code, Request, Response, Status};
 
 use crate::auth;
 use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
 use crate::marshaling::*;
 use crate::models;
 use crate::protos::*;
+use crate::rate_limiting::{self, rate_limited_status, with_retry_after};
+use crate::rpcs::get_server_configuration;
 use crate::rpcs::validations::*;
 use crate::schema::users::dsl::*;
 
@@ -14,7 +19,9 @@ pub fn login(
     request: Request<LoginRequest>,
     conn: &mut PgPooledConnection,
 ) -> Result<Response<RefreshTokenResponse>, Status> {
+    let metadata = auth::SessionMetadata::from_request(&request);
     let req = request.into_inner();
+    let metadata = metadata.with_device_name(req.device_name.clone());
     validate_username(&req.username)?;
     validate_password(&req.password)?;
 
@@ -25,6 +32,15 @@ pub fn login(
             _ => "invalid_username_or_password",
         },
     );
+    // Failures are limited per IP and account before the (deliberately slow) password check.
+    let server_configuration = get_server_configuration(conn)?;
+    let ip_key = metadata.client_ip(&server_configuration);
+    if let Some(ip) = &ip_key {
+        rate_limiting::LOGIN_FAILURES_BY_IP
+            .check(ip)
+            .map_err(rate_limited_status)?;
+    }
+
     let user_result = match &req.user_id {
         Some(user_id) if user_id.len() > 0 => users
             .filter(id.eq(user_id.to_db_id_or_err("user_id")?))
@@ -34,15 +50,79 @@ pub fn login(
             .first::<models::User>(conn),
     };
     let user: models::User = match user_result {
-        Err(_) => return Err(permission_denied),
+        Err(_) => {
+            let username_key = rate_limiting::username_login_failure_key(&req.username);
+            rate_limiting::LOGIN_FAILURES_BY_USERNAME
+                .check(&username_key)
+                .map_err(rate_limited_status)?;
+            record_login_failure(&ip_key, &username_key);
+            return Err(permission_denied);
+        }
         Ok(user) => user,
     };
+    let username_key = rate_limiting::user_login_failure_key(user.id);
+    rate_limiting::LOGIN_FAILURES_BY_USERNAME
+        .check(&username_key)
+        .map_err(rate_limited_status)?;
 
-    let tokens = match verify(req.password, &user.password_salted_hash) {
-        Err(_) => return Err(permission_denied),
-        Ok(false) => return Err(permission_denied),
-        Ok(true) => auth::generate_refresh_and_access_token(user.id, conn, req.expires_at),
+    // Locked accounts fail without checking the password, so guessing can't continue.
+    let data_error = |e: diesel::result::Error| {
+        log::error!("Error checking account lockout: {:?}", e);
+        Status::new(Code::Internal, "data_error")
+    };
+    if let Some(locked_until) = account_locked_until(user.id, conn).map_err(data_error)? {
+        return Err(account_locked_status(locked_until));
+    }
+    match verify(req.password, &user.password_salted_hash) {
+        Ok(true) => {}
+        _ => {
+            record_login_failure(&ip_key, &username_key);
+            return match record_failed_login(user.id, ip_key.to_owned(), conn)
+                .map_err(data_error)?
+            {
+                Some(locked_until) => Err(account_locked_status(locked_until)),
+                None => Err(permission_denied),
+            };
+        }
     };
+    rate_limiting::LOGIN_FAILURES_BY_USERNAME.reset(&username_key);
+    clear_failed_logins(user.id, conn).map_err(data_error)?;
+    if user.password_reset_required {
+        return Err(Status::new(Code::PermissionDenied, "password_reset_required"));
+    }
+
+    // Admins can always log in, so disabling login can't lock them out of reconfiguring the server.
+    if !has_authentication_feature(&server_configuration, AuthenticationFeature::Login)
+        && !user.has_permission(Permission::Admin)
+    {
+        return Err(Status::new(Code::PermissionDenied, "login_disabled"));
+    }
+
+    let has_totp = get_confirmed_totp(user.id, conn)?.is_some();
+    if has_totp || requires_two_factor(&user, &server_configuration) {
+        let challenge = create_two_factor_challenge(
+            user.id,
+            req.expires_at.as_ref().map(|expires_at| expires_at.to_db()),
+            metadata.device_name,
+            !has_totp,
+            conn,
+        )?;
+        log::info!("Awaiting second factor for user {}, user_id={}", &req.username, user.id);
+        return Ok(Response::new(RefreshTokenResponse {
+            refresh_token: None,
+            access_token: None,
+            user: None,
+            two_factor_challenge: Some(challenge),
+        }));
+    }
+
+    let tokens = auth::generate_refresh_and_access_token(
+        user.id,
+        conn,
+        req.expires_at,
+        &metadata,
+        &auth::TokenLifetimes::from_configuration(&server_configuration),
+    )?;
 
     log::info!("Logged in user {}, user_id={}", &req.username, user.id);
 
@@ -50,5 +130,20 @@ pub fn login(
         refresh_token: tokens.refresh_token,
         access_token: tokens.access_token,
         user: Some(user.to_proto()),
+        two_factor_challenge: None,
     }))
 }
+
+fn record_login_failure(ip_key: &Option<String>, username_key: &str) {
+    if let Some(ip) = ip_key {
+        rate_limiting::LOGIN_FAILURES_BY_IP.record(ip);
+    }
+    rate_limiting::LOGIN_FAILURES_BY_USERNAME.record(username_key);
+}
+
+fn account_locked_status(locked_until: SystemTime) -> Status {
+    with_retry_after(
+        Status::new(Code::PermissionDenied, "account_locked"),
+        locked_until.duration_since(SystemTime::now()).unwrap_or_default(),
+    )
+}
@@ -6,6 +6,7 @@ pub use get_service_version::get_service_version;
 
 mod get_server_configuration;
 pub use get_server_configuration::get_server_configuration;
+pub use get_server_configuration::get_server_name;
 pub use get_server_configuration::create_default_server_configuration;
 
 mod configure_server;
@@ -23,12 +24,65 @@ pub use login::login;
 mod access_token;
 pub use access_token::access_token;
 
+mod get_sessions;
+pub use get_sessions::get_sessions;
+mod revoke_session;
+pub use revoke_session::revoke_session;
+mod revoke_other_sessions;
+pub use revoke_other_sessions::revoke_other_sessions;
+mod change_password;
+pub use change_password::change_password;
+mod request_password_reset;
+pub use request_password_reset::request_password_reset;
+mod reset_password;
+pub use reset_password::reset_password;
+mod force_password_reset;
+pub use force_password_reset::force_password_reset;
+mod get_account_lockouts;
+pub use get_account_lockouts::get_account_lockouts;
+mod unlock_account;
+pub use unlock_account::unlock_account;
+
+mod complete_oidc_login;
+pub use complete_oidc_login::complete_oidc_login;
+mod get_linked_identities;
+pub use get_linked_identities::get_linked_identities;
+mod delete_linked_identity;
+pub use delete_linked_identity::delete_linked_identity;
+mod create_api_token;
+pub use create_api_token::create_api_token;
+mod get_api_tokens;
+pub use get_api_tokens::get_api_tokens;
+mod delete_api_token;
+pub use delete_api_token::delete_api_token;
+
+mod verify_two_factor;
+pub use verify_two_factor::verify_two_factor;
+mod enroll_totp;
+pub use enroll_totp::enroll_totp;
+mod confirm_totp;
+pub use confirm_totp::confirm_totp;
+mod disable_totp;
+pub use disable_totp::disable_totp;
+
+mod create_invite_code;
+pub use create_invite_code::create_invite_code;
+mod get_invite_codes;
+pub use get_invite_codes::get_invite_codes;
+mod delete_invite_code;
+pub use delete_invite_code::delete_invite_code;
+
 mod get_current_user;
 pub use get_current_user::get_current_user;
 
 mod update_user;
 pub use update_user::update_user;
 
+mod send_contact_method_verification;
+pub use send_contact_method_verification::send_contact_method_verification;
+mod verify_contact_method;
+pub use verify_contact_method::verify_contact_method;
+
 mod delete_user;
 pub use delete_user::delete_user;
 
@@ -48,6 +102,21 @@ mod get_media;
 pub use get_media::get_media;
 mod delete_media;
 pub use delete_media::delete_media;
+mod update_media;
+pub use update_media::update_media;
+mod get_media_quota;
+pub use get_media_quota::get_media_quota;
+mod get_media_url;
+pub use get_media_url::get_media_url;
+
+mod get_media_albums;
+pub use get_media_albums::get_media_albums;
+mod create_media_album;
+pub use create_media_album::create_media_album;
+mod update_media_album;
+pub use update_media_album::update_media_album;
+mod delete_media_album;
+pub use delete_media_album::delete_media_album;
 
 mod get_groups;
 pub use get_groups::get_groups;
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::messaging::Messenger;
+use crate::models;
+use crate::protos::*;
+use crate::schema::users;
+
+use super::get_server_name;
+use super::validations::*;
+
+pub fn request_password_reset(
+    request: RequestPasswordResetRequest,
+    messenger: Arc<dyn Messenger>,
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    let candidates = match (request.username.as_deref(), request.email.as_deref()) {
+        (Some(username), _) if !username.is_empty() => {
+            validate_username(username)?;
+            users::table
+                .filter(users::username.eq(username))
+                .load::<models::User>(conn)
+        }
+        (_, Some(email)) if !email.trim().is_empty() => {
+            let address = contact_method_address(email).to_string();
+            users::table
+                .filter(
+                    diesel::dsl::sql::<diesel::sql_types::Bool>("(email->>'value') IN (")
+                        .bind::<diesel::sql_types::Text, _>(address.to_owned())
+                        .sql(", ")
+                        .bind::<diesel::sql_types::Text, _>(format!("mailto:{}", address))
+                        .sql(")"),
+                )
+                .load::<models::User>(conn)
+        }
+        _ => return Err(Status::new(Code::InvalidArgument, "username_or_email_required")),
+    }
+    .map_err(|e| {
+        log::error!("Error looking up users for password reset: {:?}", e);
+        Status::new(Code::Internal, "data_error")
+    })?;
+
+    let server_name = get_server_name(conn)?;
+    for user in candidates {
+        // Failures are logged, but the response is the same either way.
+        if let Err(e) = issue_password_reset(&user, &server_name, messenger.clone(), conn) {
+            log::error!("Error issuing password reset for user_id={}: {:?}", user.id, e);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use std::time::SystemTime;
+
+use bcrypt::{hash, DEFAULT_COST};
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::auth;
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::{password_reset_tokens, users};
+
+use super::validations::*;
+
+pub fn reset_password(
+    request: ResetPasswordRequest,
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    validate_password(&request.new_password)?;
+    let invalid = || Status::new(Code::PermissionDenied, "reset_token_invalid");
+    let reset_token = password_reset_tokens::table
+        .filter(password_reset_tokens::token_hash.eq(hash_password_reset_token(&request.token)))
+        .filter(password_reset_tokens::expires_at.gt(diesel::dsl::now))
+        .first::<models::PasswordResetToken>(conn)
+        .optional()
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?
+        .ok_or_else(invalid)?;
+
+    let hashed_password = hash(&request.new_password, DEFAULT_COST).unwrap();
+    let result = conn.transaction::<usize, diesel::result::Error, _>(|conn| {
+        // Using one token invalidates all of the user's outstanding tokens.
+        let deleted = delete(
+            password_reset_tokens::table.filter(password_reset_tokens::user_id.eq(reset_token.user_id)),
+        )
+        .execute(conn)?;
+        if deleted == 0 {
+            // Used concurrently.
+            return Err(diesel::NotFound);
+        }
+        update(users::table.find(reset_token.user_id))
+            .set((
+                users::password_salted_hash.eq(hashed_password),
+                users::password_reset_required.eq(false),
+                users::updated_at.eq(SystemTime::now()),
+            ))
+            .execute(conn)?;
+        // Resetting proves the account is theirs, so it's no longer locked out.
+        clear_failed_logins(reset_token.user_id, conn)?;
+        auth::revoke_user_tokens(reset_token.user_id, None, conn)
+    });
+    match result {
+        Ok(_) => {
+            log::info!("Reset password for user_id={}", reset_token.user_id);
+            Ok(())
+        }
+        Err(diesel::NotFound) => Err(invalid()),
+        Err(e) => {
+            log::error!("Error resetting password: {:?}", e);
+            Err(Status::new(Code::Internal, "data_error"))
+        }
+    }
+}
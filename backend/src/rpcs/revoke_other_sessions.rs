@@ -0,0 +1,18 @@
+use tonic::{Code, Status};
+
+use crate::auth;
+use crate::db_connection::PgPooledConnection;
+use crate::models;
+
+pub fn revoke_other_sessions(
+    user: models::User,
+    current_refresh_token_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    auth::revoke_user_tokens(user.id, Some(current_refresh_token_id), conn)
+        .map(|_| ())
+        .map_err(|e| {
+            log::error!("Error revoking sessions: {:?}", e);
+            Status::new(Code::Internal, "data_error")
+        })
+}
@@ -0,0 +1,30 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::auth;
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::user_refresh_tokens;
+
+pub fn revoke_session(
+    request: Session,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    let session_id = request.id.to_db_id_or_err("id")?;
+    let family_id = user_refresh_tokens::table
+        .filter(user_refresh_tokens::id.eq(session_id))
+        .filter(user_refresh_tokens::user_id.eq(user.id))
+        .select(user_refresh_tokens::family_id)
+        .first::<i64>(conn)
+        .optional()
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?
+        .ok_or_else(|| Status::new(Code::NotFound, "session_not_found"))?;
+    // The session's access tokens are deleted along with it (ON DELETE CASCADE).
+    auth::revoke_token_family(family_id, conn).map(|_| ()).map_err(|e| {
+        log::error!("Error revoking session: {:?}", e);
+        Status::new(Code::Internal, "data_error")
+    })
+}
@@ -0,0 +1,101 @@
+use std::time::SystemTime;
+
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::marshaling::*;
+use crate::messaging::{Messenger, OutboundMessage};
+use crate::models;
+use crate::protos::*;
+use crate::schema::contact_method_verifications;
+
+use super::get_server_name;
+
+pub async fn send_contact_method_verification(
+    request: SendContactMethodVerificationRequest,
+    user: models::User,
+    messenger: &dyn Messenger,
+    conn: &mut PgPooledConnection,
+) -> Result<ContactMethodVerification, Status> {
+    log::info!("SendContactMethodVerification called: {:?}", request);
+    let contact_method_type = match ContactMethodType::from_i32(request.contact_method) {
+        Some(ContactMethodType::Unknown) | None => {
+            return Err(Status::new(Code::InvalidArgument, "invalid_contact_method"))
+        }
+        Some(contact_method_type) => contact_method_type,
+    };
+    let contact_method = get_contact_method(&user, contact_method_type)
+        .ok_or_else(|| Status::new(Code::FailedPrecondition, "contact_method_not_set"))?;
+    let value = contact_method.value.to_owned().unwrap_or_default();
+    if contact_method.verified {
+        return Err(Status::new(Code::FailedPrecondition, "contact_method_already_verified"));
+    }
+    if !messenger.supports(contact_method_type) {
+        return Err(Status::new(Code::FailedPrecondition, "contact_method_not_supported"));
+    }
+
+    let contact_method_name = contact_method_type.as_str_name().to_string();
+    let existing = contact_method_verifications::table
+        .filter(contact_method_verifications::user_id.eq(user.id))
+        .filter(contact_method_verifications::contact_method.eq(&contact_method_name))
+        .first::<models::ContactMethodVerification>(conn)
+        .optional()
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?;
+    if let Some(existing) = existing {
+        if existing.created_at + VERIFICATION_RESEND_INTERVAL > SystemTime::now() {
+            return Err(Status::new(Code::ResourceExhausted, "verification_recently_sent"));
+        }
+    }
+
+    let code = generate_verification_code();
+    let verification = conn
+        .transaction::<models::ContactMethodVerification, diesel::result::Error, _>(|conn| {
+            delete(
+                contact_method_verifications::table
+                    .filter(contact_method_verifications::user_id.eq(user.id))
+                    .filter(contact_method_verifications::contact_method.eq(&contact_method_name)),
+            )
+            .execute(conn)?;
+            insert_into(contact_method_verifications::table)
+                .values(&models::NewContactMethodVerification {
+                    user_id: user.id,
+                    contact_method: contact_method_name.to_owned(),
+                    value: value.to_owned(),
+                    code_hash: hash_verification_code(&code),
+                    expires_at: SystemTime::now() + VERIFICATION_CODE_TTL,
+                })
+                .get_result::<models::ContactMethodVerification>(conn)
+        })
+        .map_err(|e| {
+            log::error!("Error creating contact method verification: {:?}", e);
+            Status::new(Code::Internal, "data_error")
+        })?;
+
+    let server_name = get_server_name(conn)?;
+    let message = OutboundMessage {
+        contact_method: contact_method_type,
+        to: contact_method_address(&value).to_string(),
+        subject: format!("Your {} verification code", server_name),
+        body: format!(
+            "Your {} verification code is {}. It expires in {} minutes.",
+            server_name,
+            code,
+            VERIFICATION_CODE_TTL.as_secs() / 60
+        ),
+    };
+    if let Err(e) = messenger.send(&message).await {
+        log::error!("Error sending verification to user_id={}: {:?}", user.id, e);
+        // Let the user retry right away.
+        let _ = delete(contact_method_verifications::table.find(verification.id)).execute(conn);
+        return Err(Status::new(Code::Unavailable, "verification_send_failed"));
+    }
+    log::info!("Sent {} verification to user_id={}", contact_method_name, user.id);
+
+    Ok(ContactMethodVerification {
+        contact_method: contact_method_type as i32,
+        expires_at: Some(verification.expires_at.to_proto()),
+        attempts_remaining: MAX_VERIFICATION_ATTEMPTS as u32,
+    })
+}
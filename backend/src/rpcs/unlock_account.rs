@@ -0,0 +1,26 @@
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::rate_limiting;
+
+use super::validations::*;
+
+pub fn unlock_account(
+    request: User,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    validate_permission(&user, Permission::Admin)?;
+    let target = models::get_user(request.id.to_db_id_or_err("id")?, conn)?;
+    clear_failed_logins(target.id, conn).map_err(|e| {
+        log::error!("Error unlocking account: {:?}", e);
+        Status::new(Code::Internal, "data_error")
+    })?;
+    rate_limiting::LOGIN_FAILURES_BY_USERNAME.reset(&rate_limiting::user_login_failure_key(target.id));
+    log::info!("user_id={} unlocked user_id={}", user.id, target.id);
+    Ok(())
+}
@@ -43,7 +43,11 @@ pub fn update_group(
 
     group.name = request.name;
     group.description = request.description;
-    group.avatar_media_id = request.avatar_media_id.map(|id| id.to_db_id().unwrap());
+    let avatar_media_id = request.avatar_media_id.to_db_opt_id_or_err("avatar_media_id")?;
+    if let Some(avatar_media_id) = avatar_media_id.filter(|id| group.avatar_media_id != Some(*id)) {
+        validate_media_ownership(&user, &[avatar_media_id], conn)?;
+    }
+    group.avatar_media_id = avatar_media_id;
     group.visibility = request.visibility.to_string_visibility();
     group.default_membership_permissions =
         request.default_membership_permissions.to_json_permissions();
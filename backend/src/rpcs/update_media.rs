@@ -0,0 +1,80 @@
+use std::time::SystemTime;
+
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::media;
+
+use super::validations::*;
+
+pub fn update_media(
+    request: Media,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<Media, Status> {
+    log::info!("UpdateMedia called: {:?}", request);
+    validate_media_fields(
+        request.name.to_owned(),
+        request.description.to_owned(),
+        request.visibility,
+    )?;
+    validate_alt_text(&request.alt_text)?;
+
+    let mut media = media::table
+        .filter(media::id.eq(request.id.to_db_id_or_err("id")?))
+        .first::<models::Media>(conn)
+        .map_err(|_| Status::new(Code::NotFound, "media_not_found"))?;
+
+    let own_media = media.user_id == Some(user.id);
+    let moderator = validate_permission(&user, Permission::ModerateMedia).is_ok();
+    if !own_media && !moderator {
+        return Err(Status::new(Code::PermissionDenied, "not_your_media"));
+    }
+
+    // Read before `request`'s fields are moved out below.
+    let visibility = request.visibility();
+    let moderation = request.moderation();
+    if own_media {
+        if visibility.to_string_visibility() != media.visibility {
+            validate_media_visibility(&user, visibility)?;
+        }
+        media.name = request.name;
+        media.description = request.description;
+        media.alt_text = request.alt_text;
+        media.visibility = visibility.to_string_visibility();
+    }
+
+    if moderation != Moderation::Unknown && moderation.to_string_moderation() != media.moderation {
+        if !moderator {
+            return Err(Status::new(
+                Code::PermissionDenied,
+                "permission_MODERATE_MEDIA_required",
+            ));
+        }
+        media.moderation = moderation.to_string_moderation();
+    }
+    media.updated_at = SystemTime::now();
+
+    // Set explicitly so that fields can be cleared.
+    let result = update(media::table.find(media.id))
+        .set((
+            media::name.eq(&media.name),
+            media::description.eq(&media.description),
+            media::alt_text.eq(&media.alt_text),
+            media::visibility.eq(&media.visibility),
+            media::moderation.eq(&media.moderation),
+            media::updated_at.eq(media.updated_at),
+        ))
+        .get_result::<models::Media>(conn);
+    match result {
+        Ok(media) => Ok(media.to_proto()),
+        Err(e) => {
+            log::error!("Error updating media: {:?}", e);
+            Err(Status::new(Code::Internal, "data_error"))
+        }
+    }
+}
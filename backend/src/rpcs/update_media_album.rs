@@ -0,0 +1,154 @@
+use std::collections::HashSet;
+use std::time::SystemTime;
+
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::{media, media_album_items, media_albums};
+
+use super::validations::*;
+
+const MAX_ALBUM_MEDIA: usize = 1000;
+
+pub fn update_media_album(
+    request: MediaAlbum,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<MediaAlbum, Status> {
+    log::info!("UpdateMediaAlbum called: {:?}", request);
+    validate_length(&request.name, "name", 1, 255)?;
+    validate_media_fields(None, request.description.to_owned(), request.visibility)?;
+
+    let mut album = models::get_media_album(request.id.to_db_id_or_err("id")?, conn)?;
+    let own_album = album.user_id == user.id;
+    let moderator = validate_permission(&user, Permission::ModerateMedia).is_ok();
+    if !own_album && !moderator {
+        return Err(Status::new(Code::PermissionDenied, "not_your_media_album"));
+    }
+
+    let mut media_ids = None;
+    if own_album {
+        let visibility = request.visibility();
+        if visibility.to_string_visibility() != album.visibility {
+            validate_media_visibility(&user, visibility)?;
+        }
+        let (ids, cover_media_id) = validate_album_media(&request, album.user_id, conn)?;
+        album.name = request.name.to_owned();
+        album.description = request.description.to_owned();
+        album.cover_media_id = cover_media_id;
+        album.visibility = visibility.to_string_visibility();
+        media_ids = Some(ids);
+    }
+
+    let moderation = request.moderation();
+    if moderation != Moderation::Unknown && moderation.to_string_moderation() != album.moderation {
+        if !moderator {
+            return Err(Status::new(
+                Code::PermissionDenied,
+                "permission_MODERATE_MEDIA_required",
+            ));
+        }
+        album.moderation = moderation.to_string_moderation();
+    }
+    album.updated_at = SystemTime::now();
+
+    let result = conn.transaction::<models::MediaAlbum, diesel::result::Error, _>(|conn| {
+        if let Some(media_ids) = &media_ids {
+            replace_album_media(album.id, media_ids, conn)?;
+        }
+        // Set explicitly so that fields can be cleared.
+        update(media_albums::table.find(album.id))
+            .set((
+                media_albums::name.eq(&album.name),
+                media_albums::description.eq(&album.description),
+                media_albums::cover_media_id.eq(album.cover_media_id),
+                media_albums::visibility.eq(&album.visibility),
+                media_albums::moderation.eq(&album.moderation),
+                media_albums::updated_at.eq(album.updated_at),
+            ))
+            .get_result::<models::MediaAlbum>(conn)
+    });
+    match result {
+        Ok(album) => {
+            let media_ids = match media_ids {
+                Some(media_ids) => media_ids,
+                None => models::get_media_album_media(album.id, conn)?
+                    .iter()
+                    .map(|media| media.id)
+                    .collect(),
+            };
+            Ok(album.to_proto(&media_ids))
+        }
+        Err(e) => {
+            log::error!("Error updating media album: {:?}", e);
+            Err(Status::new(Code::Internal, "data_error"))
+        }
+    }
+}
+
+/// Validates an album's `media_ids` (which must all belong to `owner_id`, without duplicates)
+/// and `cover_media_id` (which must be one of them). Returns both as DB IDs.
+pub(super) fn validate_album_media(
+    request: &MediaAlbum,
+    owner_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<(Vec<i64>, Option<i64>), Status> {
+    if request.media_ids.len() > MAX_ALBUM_MEDIA {
+        return Err(Status::new(
+            Code::InvalidArgument,
+            format!("media_ids_too_long_max_{}", MAX_ALBUM_MEDIA),
+        ));
+    }
+    let media_ids = request
+        .media_ids
+        .iter()
+        .map(|id| id.to_db_id_or_err("media_ids"))
+        .collect::<Result<Vec<i64>, Status>>()?;
+    if media_ids.iter().collect::<HashSet<_>>().len() != media_ids.len() {
+        return Err(Status::new(Code::InvalidArgument, "duplicate_media_ids"));
+    }
+    let owned_count = media::table
+        .filter(media::id.eq_any(&media_ids))
+        .filter(media::user_id.eq(owner_id))
+        .count()
+        .get_result::<i64>(conn)
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?;
+    if owned_count as usize != media_ids.len() {
+        return Err(Status::new(Code::InvalidArgument, "media_not_found"));
+    }
+    let cover_media_id = request.cover_media_id.to_db_opt_id_or_err("cover_media_id")?;
+    if let Some(cover_media_id) = cover_media_id {
+        if !media_ids.contains(&cover_media_id) {
+            return Err(Status::new(Code::InvalidArgument, "cover_media_not_in_album"));
+        }
+    }
+    Ok((media_ids, cover_media_id))
+}
+
+/// Replaces the media in an album, in the given order.
+pub(super) fn replace_album_media(
+    album_id: i64,
+    media_ids: &[i64],
+    conn: &mut PgPooledConnection,
+) -> Result<(), diesel::result::Error> {
+    delete(media_album_items::table.filter(media_album_items::media_album_id.eq(album_id)))
+        .execute(conn)?;
+    insert_into(media_album_items::table)
+        .values(
+            media_ids
+                .iter()
+                .enumerate()
+                .map(|(position, media_id)| models::NewMediaAlbumItem {
+                    media_album_id: album_id,
+                    media_id: *media_id,
+                    position: position as i32,
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(conn)?;
+    Ok(())
+}
@@ -7,8 +7,10 @@ use diesel::NotFound;
 use diesel::*;
 use tonic::{Code, Status};
 
+use crate::logic::*;
 use crate::marshaling::*;
 use crate::db_connection::PgPooledConnection;
+use crate::messaging::Messenger;
 use crate::models;
 use crate::protos::*;
 use crate::schema::users;
@@ -18,6 +20,7 @@ use super::validations::*;
 pub fn update_user(
     request: User,
     current_user: models::User,
+    messenger: &dyn Messenger,
     conn: &mut PgPooledConnection,
 ) -> Result<User, Status> {
     validate_user(&request)?;
@@ -44,6 +47,13 @@ pub fn update_user(
         self_update, admin, moderator
     );
 
+    let avatar_media_id = request.avatar_media_id.to_db_opt_id_or_err("avatar_media_id")?;
+    if let Some(avatar_media_id) = avatar_media_id {
+        if self_update && current_user.avatar_media_id != Some(avatar_media_id) {
+            validate_media_ownership(&current_user, &[avatar_media_id], conn)?;
+        }
+    }
+
     let transaction_result: Result<models::User, diesel::result::Error> = conn
         .transaction::<models::User, diesel::result::Error, _>(|conn| {
             let mut existing_user = users::table
@@ -53,7 +63,7 @@ pub fn update_user(
             if admin || self_update {
                 existing_user.username = request.username.to_owned();
                 existing_user.bio = request.bio.to_owned();
-                existing_user.avatar_media_id = request.avatar_media_id.to_db_opt_id().unwrap();
+                existing_user.avatar_media_id = avatar_media_id;
                 if request.visibility == Visibility::GlobalPublic as i32
                     && existing_user.visibility.to_proto_visibility().unwrap()
                         != Visibility::GlobalPublic
@@ -63,6 +73,17 @@ pub fn update_user(
                 }
                 existing_user.visibility = request.visibility.to_string_visibility();
                 existing_user.default_follow_moderation = request.default_follow_moderation.to_string_moderation();
+                // Changing an address resets its verification.
+                existing_user.email = sanitize_contact_method(
+                    request.email.to_owned(),
+                    get_contact_method(&existing_user, ContactMethodType::Email),
+                    messenger.supports(ContactMethodType::Email),
+                );
+                existing_user.phone = sanitize_contact_method(
+                    request.phone.to_owned(),
+                    get_contact_method(&existing_user, ContactMethodType::Phone),
+                    messenger.supports(ContactMethodType::Phone),
+                );
             }
             if admin {
                 existing_user.permissions = request.permissions.to_json_permissions();
@@ -14,6 +14,69 @@ pub fn validate_configuration(config: &ServerConfiguration) -> Result<(), Status
         ))
     }
     
+    let media_limits = config
+        .media_settings
+        .as_ref()
+        .map(|settings| settings.limits.to_owned())
+        .unwrap_or_default();
+    for limits in media_limits {
+        if Permission::from_i32(limits.permission).is_none() {
+            return Err(Status::new(Code::InvalidArgument, "invalid_media_limits_permission"));
+        }
+        let invalid_content_type = limits.allowed_content_types.iter().any(|content_type| {
+            match content_type.trim().split_once('/') {
+                Some((major, minor)) => major.is_empty() || minor.is_empty() || major == "*",
+                None => true,
+            }
+        });
+        if invalid_content_type {
+            return Err(Status::new(Code::InvalidArgument, "invalid_media_limits_content_type"));
+        }
+    }
+
+    if let Some(settings) = &config.authentication_settings {
+        let lifetimes = [
+            settings.refresh_token_lifetime_seconds,
+            settings.access_token_lifetime_seconds,
+            settings.session_lifetime_seconds,
+        ];
+        if lifetimes.contains(&Some(0)) {
+            return Err(Status::new(Code::InvalidArgument, "token_lifetimes_must_be_positive"));
+        }
+        let lifetimes = crate::auth::TokenLifetimes::from_configuration(config);
+        if lifetimes.access_token > lifetimes.refresh_token {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                "access_token_lifetime_exceeds_refresh_token_lifetime",
+            ));
+        }
+
+        let mut provider_ids = std::collections::HashSet::new();
+        for provider in &settings.oidc_providers {
+            if !crate::oidc::is_valid_oidc_provider_id(&provider.id) {
+                return Err(Status::new(Code::InvalidArgument, "invalid_oidc_provider_id"));
+            }
+            if !provider_ids.insert(provider.id.as_str()) {
+                return Err(Status::new(Code::InvalidArgument, "duplicate_oidc_provider_id"));
+            }
+            if provider.client_id.trim().is_empty() {
+                return Err(Status::new(Code::InvalidArgument, "oidc_provider_client_id_required"));
+            }
+            // Plain HTTP is only allowed for local issuers, i.e. a mock issuer in development.
+            let issuer_valid = match reqwest::Url::parse(&provider.issuer) {
+                Ok(url) => {
+                    url.scheme() == "https"
+                        || (url.scheme() == "http"
+                            && matches!(url.host_str(), Some("localhost") | Some("127.0.0.1")))
+                }
+                Err(_) => false,
+            };
+            if !issuer_valid {
+                return Err(Status::new(Code::InvalidArgument, "invalid_oidc_provider_issuer"));
+            }
+        }
+    }
+
     let external_edn_config = config.external_cdn_config.to_owned();
     let backend_host = external_edn_config.to_owned().map(|c| c.backend_host);
     let frontend_host = external_edn_config.map(|c| c.frontend_host);
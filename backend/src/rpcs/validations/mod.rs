@@ -22,4 +22,7 @@ mod validate_users;
 pub use validate_users::*;
 
 mod validate_groups;
-pub use validate_groups::*;
\ No newline at end of file
+pub use validate_groups::*;
+
+mod validate_media;
+pub use validate_media::*;
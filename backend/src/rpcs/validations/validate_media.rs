@@ -0,0 +1,66 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::models;
+use crate::protos::*;
+use crate::schema::media;
+
+use super::{validate_length, validate_max_length, validate_permission};
+
+/// Validates the user-editable fields shared by Media and MediaAlbums.
+pub fn validate_media_fields(
+    name: Option<String>,
+    description: Option<String>,
+    visibility: i32,
+) -> Result<(), Status> {
+    validate_max_length(name, "name", 255)?;
+    validate_max_length(description, "description", 10000)?;
+    match Visibility::from_i32(visibility) {
+        None | Some(Visibility::Unknown) => {
+            Err(Status::new(Code::InvalidArgument, "invalid_visibility"))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub fn validate_alt_text(alt_text: &Option<String>) -> Result<(), Status> {
+    match alt_text {
+        Some(alt_text) => validate_length(alt_text, "alt_text", 0, 1500),
+        None => Ok(()),
+    }
+}
+
+/// Publishing media (or albums) requires the same permissions as publishing posts does.
+pub fn validate_media_visibility(user: &models::User, visibility: Visibility) -> Result<(), Status> {
+    match visibility {
+        Visibility::GlobalPublic => validate_permission(user, Permission::PublishMediaGlobally),
+        Visibility::ServerPublic => validate_permission(user, Permission::PublishMediaLocally),
+        _ => Ok(()),
+    }
+}
+
+/// Posts, events and avatars may only reference media their user owns (admins may reference
+/// any), since media becomes viewable through whatever references it.
+pub fn validate_media_ownership(
+    user: &models::User,
+    media_ids: &[i64],
+    conn: &mut PgPooledConnection,
+) -> Result<(), Status> {
+    if media_ids.is_empty() || validate_permission(user, Permission::Admin).is_ok() {
+        return Ok(());
+    }
+    let owned_ids = media::table
+        .select(media::id)
+        .filter(media::id.eq_any(media_ids))
+        .filter(media::user_id.eq(user.id))
+        .load::<i64>(conn)
+        .map_err(|e| {
+            log::error!("Error validating media ownership: {:?}", e);
+            Status::new(Code::Internal, "data_error")
+        })?;
+    match media_ids.iter().all(|id| owned_ids.contains(id)) {
+        true => Ok(()),
+        false => Err(Status::new(Code::PermissionDenied, "media_not_owned")),
+    }
+}
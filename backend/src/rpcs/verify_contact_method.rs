@@ -0,0 +1,98 @@
+use std::time::SystemTime;
+
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::schema::{contact_method_verifications, users};
+
+pub fn verify_contact_method(
+    request: VerifyContactMethodRequest,
+    user: models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<User, Status> {
+    let contact_method_type = match ContactMethodType::from_i32(request.contact_method) {
+        Some(ContactMethodType::Unknown) | None => {
+            return Err(Status::new(Code::InvalidArgument, "invalid_contact_method"))
+        }
+        Some(contact_method_type) => contact_method_type,
+    };
+    let verification = contact_method_verifications::table
+        .filter(contact_method_verifications::user_id.eq(user.id))
+        .filter(contact_method_verifications::contact_method.eq(contact_method_type.as_str_name()))
+        .first::<models::ContactMethodVerification>(conn)
+        .optional()
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?
+        .ok_or_else(|| Status::new(Code::NotFound, "verification_not_found"))?;
+    let discard = |conn: &mut PgPooledConnection| {
+        let _ = delete(contact_method_verifications::table.find(verification.id)).execute(conn);
+    };
+
+    if verification.expires_at <= SystemTime::now() {
+        discard(conn);
+        return Err(Status::new(Code::FailedPrecondition, "verification_expired"));
+    }
+    let contact_method = match get_contact_method(&user, contact_method_type) {
+        Some(contact_method) if contact_method.value.as_deref() == Some(&verification.value) => contact_method,
+        _ => {
+            discard(conn);
+            return Err(Status::new(Code::FailedPrecondition, "contact_method_changed"));
+        }
+    };
+
+    // Count the attempt before checking the code, so concurrent guesses can't exceed the limit.
+    let attempts = update(contact_method_verifications::table.find(verification.id))
+        .filter(contact_method_verifications::attempts.lt(MAX_VERIFICATION_ATTEMPTS))
+        .set(contact_method_verifications::attempts.eq(contact_method_verifications::attempts + 1))
+        .returning(contact_method_verifications::attempts)
+        .get_result::<i32>(conn)
+        .optional()
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?;
+    let attempts = match attempts {
+        Some(attempts) => attempts,
+        None => {
+            discard(conn);
+            return Err(Status::new(Code::PermissionDenied, "verification_attempts_exceeded"));
+        }
+    };
+    if !verification_code_matches(&request.code, &verification.code_hash) {
+        if attempts >= MAX_VERIFICATION_ATTEMPTS {
+            discard(conn);
+            return Err(Status::new(Code::PermissionDenied, "verification_attempts_exceeded"));
+        }
+        return Err(Status::new(Code::PermissionDenied, "verification_code_invalid"));
+    }
+
+    let verified = serde_json::to_value(ContactMethod {
+        supported_by_server: true,
+        verified: true,
+        ..contact_method
+    })
+    .ok();
+    let result = conn.transaction::<models::User, diesel::result::Error, _>(|conn| {
+        delete(contact_method_verifications::table.find(verification.id)).execute(conn)?;
+        let target = users::table.find(user.id);
+        match contact_method_type {
+            ContactMethodType::Email => update(target)
+                .set((users::email.eq(verified), users::updated_at.eq(SystemTime::now())))
+                .get_result::<models::User>(conn),
+            _ => update(target)
+                .set((users::phone.eq(verified), users::updated_at.eq(SystemTime::now())))
+                .get_result::<models::User>(conn),
+        }
+    });
+    match result {
+        Ok(user) => {
+            log::info!("Verified {} for user_id={}", contact_method_type.as_str_name(), user.id);
+            Ok(user.to_proto())
+        }
+        Err(e) => {
+            log::error!("Error verifying contact method: {:?}", e);
+            Err(Status::new(Code::Internal, "data_error"))
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use diesel::*;
+use tonic::{Code, Status};
+
+use crate::auth;
+use crate::db_connection::PgPooledConnection;
+use crate::logic::*;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::*;
+use crate::rpcs::get_server_configuration;
+use crate::schema::two_factor_challenges;
+
+pub fn verify_two_factor(
+    request: VerifyTwoFactorRequest,
+    metadata: auth::SessionMetadata,
+    conn: &mut PgPooledConnection,
+) -> Result<RefreshTokenResponse, Status> {
+    let challenge = get_two_factor_challenge(&request.two_factor_token, conn)?;
+    let totp = get_confirmed_totp(challenge.user_id, conn)?
+        .ok_or_else(|| Status::new(Code::FailedPrecondition, "two_factor_enrollment_required"))?;
+    if !count_two_factor_attempt(&challenge, conn)? {
+        return Err(Status::new(Code::PermissionDenied, "two_factor_attempts_exceeded"));
+    }
+    if !use_two_factor_code(&totp, &request.code, conn)? {
+        return Err(Status::new(Code::PermissionDenied, "two_factor_code_invalid"));
+    }
+
+    // Partial tokens are single-use; a concurrent verification may have used it already.
+    let deleted = delete(two_factor_challenges::table.find(challenge.id))
+        .execute(conn)
+        .map_err(|_| Status::new(Code::Internal, "data_error"))?;
+    if deleted == 0 {
+        return Err(Status::new(Code::Unauthenticated, "two_factor_token_invalid"));
+    }
+    let user = models::get_user(challenge.user_id, conn)?;
+    let lifetimes = auth::TokenLifetimes::from_configuration(&get_server_configuration(conn)?);
+    let tokens = auth::generate_refresh_and_access_token(
+        user.id,
+        conn,
+        challenge.requested_expires_at.map(|expires_at| expires_at.to_proto()),
+        &metadata.with_device_name(challenge.device_name),
+        &lifetimes,
+    )?;
+    log::info!("Logged in user {} with two-factor auth, user_id={}", user.username, user.id);
+    Ok(RefreshTokenResponse {
+        refresh_token: tokens.refresh_token,
+        access_token: tokens.access_token,
+        user: Some(user.to_proto()),
+        two_factor_challenge: None,
+    })
+}
@@ -1,3 +1,74 @@
+table! {
+    account_lockouts (user_id) {
+        user_id -> Int8,
+        failed_attempts -> Int4,
+        locked_until -> Nullable<Timestamp>,
+        last_failed_at -> Timestamp,
+        last_failed_ip -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    activitypub_actor_keys (id) {
+        id -> Int8,
+        user_id -> Int8,
+        public_key_pem -> Text,
+        private_key_pem -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    activitypub_deliveries (id) {
+        id -> Int8,
+        user_id -> Int8,
+        inbox_uri -> Varchar,
+        activity -> Jsonb,
+        attempts -> Int4,
+        last_error -> Nullable<Text>,
+        next_attempt_at -> Timestamp,
+        delivered_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    activitypub_inbox_activities (id) {
+        id -> Int8,
+        activity_uri -> Varchar,
+        activity_type -> Varchar,
+        actor_uri -> Varchar,
+        post_id -> Nullable<Int8>,
+        activity -> Jsonb,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    activitypub_remote_followers (id) {
+        id -> Int8,
+        user_id -> Int8,
+        actor_uri -> Varchar,
+        inbox_uri -> Varchar,
+        shared_inbox_uri -> Nullable<Varchar>,
+        follow_activity_uri -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    contact_method_verifications (id) {
+        id -> Int8,
+        user_id -> Int8,
+        contact_method -> Varchar,
+        value -> Varchar,
+        code_hash -> Varchar,
+        attempts -> Int4,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     event_attendances (id) {
         id -> Int8,
@@ -99,6 +170,31 @@ table! {
     }
 }
 
+table! {
+    invite_codes (id) {
+        id -> Int8,
+        code -> Varchar,
+        created_by_user_id -> Nullable<Int8>,
+        max_uses -> Nullable<Int4>,
+        use_count -> Int4,
+        permissions -> Nullable<Jsonb>,
+        expires_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    linked_identities (id) {
+        id -> Int8,
+        user_id -> Int8,
+        provider_id -> Varchar,
+        subject -> Varchar,
+        email -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        last_login_at -> Nullable<Timestamp>,
+    }
+}
+
 table! {
     media (id) {
         id -> Int8,
@@ -115,6 +211,86 @@ table! {
         moderation -> Varchar,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        size -> Int8,
+        alt_text -> Nullable<Varchar>,
+        blob_id -> Nullable<Int8>,
+        processing_attempts -> Int4,
+        processing_error -> Nullable<Text>,
+        next_processing_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    media_album_items (id) {
+        id -> Int8,
+        media_album_id -> Int8,
+        media_id -> Int8,
+        position -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    media_albums (id) {
+        id -> Int8,
+        user_id -> Int8,
+        name -> Varchar,
+        description -> Nullable<Text>,
+        cover_media_id -> Nullable<Int8>,
+        visibility -> Varchar,
+        moderation -> Varchar,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+table! {
+    media_blobs (id) {
+        id -> Int8,
+        sha256 -> Varchar,
+        minio_path -> Varchar,
+        size -> Int8,
+        ref_count -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    media_upload_parts (id) {
+        id -> Int8,
+        media_upload_id -> Int8,
+        part_number -> Int4,
+        etag -> Varchar,
+        sha256 -> Varchar,
+        size -> Int8,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    media_uploads (id) {
+        id -> Int8,
+        user_id -> Int8,
+        minio_path -> Varchar,
+        s3_upload_id -> Varchar,
+        content_type -> Varchar,
+        name -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+        visibility -> Varchar,
+    }
+}
+
+table! {
+    media_variants (id) {
+        id -> Int8,
+        media_id -> Int8,
+        size -> Varchar,
+        minio_path -> Varchar,
+        content_type -> Varchar,
+        width -> Int4,
+        height -> Int4,
+        created_at -> Timestamp,
     }
 }
 
@@ -131,6 +307,40 @@ table! {
     }
 }
 
+table! {
+    oidc_login_completions (id) {
+        id -> Int8,
+        user_id -> Int8,
+        code_hash -> Varchar,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    oidc_login_states (id) {
+        id -> Int8,
+        state -> Varchar,
+        provider_id -> Varchar,
+        code_verifier -> Varchar,
+        nonce -> Varchar,
+        redirect_path -> Varchar,
+        link_user_id -> Nullable<Int8>,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    password_reset_tokens (id) {
+        id -> Int8,
+        user_id -> Int8,
+        token_hash -> Varchar,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     posts (id) {
         id -> Int8,
@@ -173,6 +383,31 @@ table! {
         authentication_features -> Jsonb,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        disallow_search_indexing -> Bool,
+        media_settings -> Jsonb,
+        authentication_settings -> Jsonb,
+    }
+}
+
+table! {
+    server_secrets (id) {
+        id -> Int8,
+        name -> Varchar,
+        value -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    two_factor_challenges (id) {
+        id -> Int8,
+        user_id -> Int8,
+        token_hash -> Varchar,
+        requested_expires_at -> Nullable<Timestamp>,
+        attempts -> Int4,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+        device_name -> Nullable<Varchar>,
     }
 }
 
@@ -186,6 +421,19 @@ table! {
     }
 }
 
+table! {
+    user_api_tokens (id) {
+        id -> Int8,
+        user_id -> Int8,
+        token_hash -> Varchar,
+        label -> Varchar,
+        scopes -> Jsonb,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     user_devices (id) {
         id -> Int8,
@@ -204,13 +452,40 @@ table! {
     }
 }
 
+table! {
+    user_recovery_codes (id) {
+        id -> Int8,
+        user_id -> Int8,
+        code_hash -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     user_refresh_tokens (id) {
         id -> Int8,
         user_id -> Int8,
         token -> Varchar,
         created_at -> Timestamp,
-        expires_at -> Nullable<Timestamp>,
+        expires_at -> Timestamp,
+        device_id -> Nullable<Int8>,
+        user_agent -> Nullable<Varchar>,
+        ip_address -> Nullable<Varchar>,
+        last_used_at -> Nullable<Timestamp>,
+        family_id -> Int8,
+        rotated_at -> Nullable<Timestamp>,
+        session_expires_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    user_totps (id) {
+        id -> Int8,
+        user_id -> Int8,
+        secret -> Varchar,
+        confirmed_at -> Nullable<Timestamp>,
+        last_used_step -> Nullable<Int8>,
+        created_at -> Timestamp,
     }
 }
 
@@ -236,9 +511,16 @@ table! {
         response_count -> Int4,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        password_reset_required -> Bool,
     }
 }
 
+joinable!(account_lockouts -> users (user_id));
+joinable!(activitypub_actor_keys -> users (user_id));
+joinable!(activitypub_deliveries -> users (user_id));
+joinable!(activitypub_inbox_activities -> posts (post_id));
+joinable!(activitypub_remote_followers -> users (user_id));
+joinable!(contact_method_verifications -> users (user_id));
 joinable!(event_attendances -> event_instances (event_instance_id));
 joinable!(event_instances -> events (event_id));
 joinable!(event_instances -> posts (post_id));
@@ -249,16 +531,40 @@ joinable!(group_posts -> groups (group_id));
 joinable!(group_posts -> posts (post_id));
 joinable!(group_posts -> users (user_id));
 joinable!(groups -> media (avatar_media_id));
+joinable!(invite_codes -> users (created_by_user_id));
+joinable!(linked_identities -> users (user_id));
+joinable!(media -> media_blobs (blob_id));
+joinable!(media_album_items -> media (media_id));
+joinable!(media_album_items -> media_albums (media_album_id));
+joinable!(media_albums -> media (cover_media_id));
+joinable!(media_albums -> users (user_id));
+joinable!(media_upload_parts -> media_uploads (media_upload_id));
+joinable!(media_uploads -> users (user_id));
+joinable!(media_variants -> media (media_id));
 joinable!(memberships -> groups (group_id));
 joinable!(memberships -> users (user_id));
+joinable!(oidc_login_completions -> users (user_id));
+joinable!(oidc_login_states -> users (link_user_id));
+joinable!(password_reset_tokens -> users (user_id));
 joinable!(posts -> users (user_id));
+joinable!(two_factor_challenges -> users (user_id));
 joinable!(user_access_tokens -> user_refresh_tokens (refresh_token_id));
+joinable!(user_api_tokens -> users (user_id));
 joinable!(user_devices -> users (user_id));
 joinable!(user_posts -> posts (post_id));
 joinable!(user_posts -> users (user_id));
+joinable!(user_recovery_codes -> users (user_id));
+joinable!(user_refresh_tokens -> user_devices (device_id));
 joinable!(user_refresh_tokens -> users (user_id));
+joinable!(user_totps -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(
+    account_lockouts,
+    activitypub_actor_keys,
+    activitypub_deliveries,
+    activitypub_inbox_activities,
+    activitypub_remote_followers,
+    contact_method_verifications,
     event_attendances,
     event_instances,
     events,
@@ -267,13 +573,29 @@ allow_tables_to_appear_in_same_query!(
     follows,
     group_posts,
     groups,
+    invite_codes,
+    linked_identities,
     media,
+    media_album_items,
+    media_albums,
+    media_blobs,
+    media_upload_parts,
+    media_uploads,
+    media_variants,
     memberships,
+    oidc_login_completions,
+    oidc_login_states,
+    password_reset_tokens,
     posts,
     server_configurations,
+    server_secrets,
+    two_factor_challenges,
     user_access_tokens,
+    user_api_tokens,
     user_devices,
     user_posts,
+    user_recovery_codes,
     user_refresh_tokens,
+    user_totps,
     users,
 );
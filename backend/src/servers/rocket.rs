@@ -1,6 +1,7 @@
 use std::{fs, sync::Arc};
 
 use crate::{db_connection::PgPool, env_var};
+use crate::media_store::MediaStore;
 use crate::{report_error, web};
 
 use ::log::{info, warn};
@@ -11,8 +12,8 @@ use tokio::task::JoinHandle;
 /// Starts a secure Rocket instance on port 443 in a separate thread.
 pub fn start_rocket_secure(
     pool: Arc<PgPool>,
-    bucket: Arc<s3::Bucket>,
-    tempdir: Arc<tempfile::TempDir>,
+    media_store: Arc<dyn MediaStore>,
+    media_cache: Arc<web::MediaCache>,
 ) -> JoinHandle<()> {
     let cert = env_var("TLS_CERT");
     let key = env_var("TLS_KEY");
@@ -28,7 +29,7 @@ pub fn start_rocket_secure(
                 .merge(("address", "0.0.0.0"))
                 .merge(("tls.certs", ".tls.crt"))
                 .merge(("tls.key", ".tls.key"));
-            Some(create_rocket(figment, pool, bucket, tempdir))
+            Some(create_rocket(figment, pool, media_store, media_cache))
         }
         _ => None,
     };
@@ -51,8 +52,8 @@ pub fn start_rocket_secure(
 pub fn start_rocket_unsecured(
     port: i32,
     pool: Arc<PgPool>,
-    bucket: Arc<s3::Bucket>,
-    tempdir: Arc<tempfile::TempDir>,
+    media_store: Arc<dyn MediaStore>,
+    media_cache: Arc<web::MediaCache>,
 
     secure_server_available: bool,
     uses_external_cdn: bool,
@@ -61,9 +62,9 @@ pub fn start_rocket_unsecured(
         .merge(("port", port))
         .merge(("address", "0.0.0.0"));
     let server_build = if secure_server_available && !uses_external_cdn {
-        create_rocket_https_redirect(figment, pool, bucket, tempdir)
+        create_rocket_https_redirect(figment, pool, media_store, media_cache)
     } else {
-        create_rocket(figment, pool, bucket, tempdir)
+        create_rocket(figment, pool, media_store, media_cache)
     };
 
     rocket::tokio::spawn(async move {
@@ -81,22 +82,28 @@ pub fn start_rocket_unsecured(
 fn create_rocket<T: rocket::figment::Provider>(
     figment: T,
     pool: Arc<PgPool>,
-    bucket: Arc<s3::Bucket>,
-    tempdir: Arc<tempfile::TempDir>,
+    media_store: Arc<dyn MediaStore>,
+    media_cache: Arc<web::MediaCache>,
 ) -> rocket::Rocket<rocket::Build> {
     let mut routes = routes![web::main_index::main_index,];
     routes.append(&mut (*web::EXTERNAL_CDN_PAGES).clone());
     routes.append(&mut (*web::INFORMATIONAL_PAGES).clone());
     routes.append(&mut (*web::SEO_PAGES).clone());
     routes.append(&mut (*web::MEDIA_ENDPOINTS).clone());
+    routes.append(&mut (*web::MEDIA_UPLOAD_ENDPOINTS).clone());
+    routes.append(&mut (*web::ACTIVITYPUB_ENDPOINTS).clone());
+    routes.append(&mut (*web::FEED_PAGES).clone());
+    routes.append(&mut (*web::OEMBED_ENDPOINTS).clone());
+    routes.append(&mut (*web::OIDC_ENDPOINTS).clone());
     routes.append(&mut (*web::FLUTTER_PAGES).clone());
     routes.append(&mut (*web::TAMAGUI_PAGES).clone());
     let server = rocket::custom(figment)
         .attach(web::cors::CORS)
+        .attach(web::EmbedFraming)
         .manage(web::RocketState {
             pool,
-            bucket,
-            tempdir,
+            media_store,
+            media_cache,
         })
         .mount("/", routes)
         .register("/", catchers![web::catchers::not_found]);
@@ -123,15 +130,15 @@ fn create_rocket<T: rocket::figment::Provider>(
 fn create_rocket_https_redirect<T: rocket::figment::Provider>(
     figment: T,
     pool: Arc<PgPool>,
-    bucket: Arc<s3::Bucket>,
-    tempdir: Arc<tempfile::TempDir>,
+    media_store: Arc<dyn MediaStore>,
+    media_cache: Arc<web::MediaCache>,
 ) -> rocket::Rocket<rocket::Build> {
     rocket::custom(figment)
         .attach(web::cors::CORS)
         .manage(web::RocketState {
             pool,
-            bucket,
-            tempdir,
+            media_store,
+            media_cache,
         })
         .mount("/", routes![web::redirect_to_secure,])
         .register("/", catchers![web::catchers::not_found])
@@ -1,6 +1,8 @@
 use std::{env, sync::Arc};
 
-use crate::{db_connection::PgPool, env_var};
+use crate::{auth::AuthInterceptor, db_connection::PgPool, env_var};
+use crate::media_store::MediaStore;
+use crate::messaging::Messenger;
 use crate::jonline::JonLineImpl;
 
 use crate::report_error;
@@ -15,8 +17,13 @@ use ::log::{info, warn};
 
 const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("greeter_descriptor");
 
-pub fn start_tonic_server(pool: Arc<PgPool>, bucket: Arc<s3::Bucket>) -> Result<bool, Box<dyn std::error::Error>> {
-    let jonline = JonLineImpl { pool, bucket };
+pub fn start_tonic_server(
+    pool: Arc<PgPool>,
+    media_store: Arc<dyn MediaStore>,
+    messenger: Arc<dyn Messenger>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let auth_interceptor = AuthInterceptor { pool: pool.clone() };
+    let jonline = JonLineImpl { pool, media_store, messenger };
 
     let reflection_service = tonic_reflection::server::Builder::configure()
         .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
@@ -48,7 +55,7 @@ pub fn start_tonic_server(pool: Arc<PgPool>, bucket: Arc<s3::Bucket>) -> Result<
         .accept_http1(true)
         .layer(CorsLayer::very_permissive())
         .layer(GrpcWebLayer::new())
-        .add_service(JonlineServer::new(jonline))
+        .add_service(JonlineServer::with_interceptor(jonline, auth_interceptor))
         .add_service(reflection_service);
 
     tokio::spawn(async {
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use diesel::*;
+use rocket::data::ToByteUnit;
+use rocket::http::uri::Host;
+use rocket::http::{ContentType, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{routes, Data, Request, Route, State};
+use rocket_cache_response::CacheResponse;
+use serde_json::json;
+
+use super::RocketState;
+use crate::activitypub::*;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::{PostContext, Visibility};
+use crate::schema::{activitypub_remote_followers, posts, users};
+
+const OUTBOX_PAGE_SIZE: i64 = 20;
+
+lazy_static! {
+    pub static ref ACTIVITYPUB_ENDPOINTS: Vec<Route> = routes![
+        webfinger,
+        actor,
+        outbox,
+        followers,
+        note,
+        user_inbox,
+        shared_inbox
+    ];
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SignedRequest {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = req
+            .headers()
+            .iter()
+            .map(|h| (h.name().as_str().to_lowercase(), h.value().to_string()))
+            .collect::<HashMap<String, String>>();
+        let path_and_query = match req.uri().query() {
+            Some(query) => format!("{}?{}", req.uri().path(), query),
+            None => req.uri().path().to_string(),
+        };
+        Outcome::Success(SignedRequest {
+            method: req.method().as_str().to_string(),
+            path_and_query,
+            headers,
+        })
+    }
+}
+
+fn activitypub_domain(host: &Host<'_>) -> String {
+    federation_domain().unwrap_or_else(|| host.domain().to_string())
+}
+
+fn activity_json() -> ContentType {
+    ContentType::new("application", "activity+json")
+}
+
+fn load_public_user(username: &str, state: &State<RocketState>) -> Result<models::User, Status> {
+    users::table
+        .filter(users::username.eq(username))
+        .filter(users::visibility.eq(Visibility::GlobalPublic.to_string_visibility()))
+        .first::<models::User>(&mut state.pool.get().unwrap())
+        .map_err(|_| Status::NotFound)
+}
+
+#[rocket::get("/.well-known/webfinger?<resource>")]
+async fn webfinger(
+    resource: &str,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<(ContentType, String), Status>> {
+    let domain = activitypub_domain(host);
+    let result = match resource
+        .strip_prefix("acct:")
+        .and_then(|acct| acct.rsplit_once('@'))
+    {
+        Some((username, acct_domain)) if acct_domain.eq_ignore_ascii_case(&domain) => {
+            load_public_user(username, state).map(|user| {
+                let jrd = json!({
+                    "subject": format!("acct:{}@{}", user.username, domain),
+                    "aliases": [actor_uri(&domain, &user.username)],
+                    "links": [
+                        {
+                            "rel": "self",
+                            "type": ACTIVITY_JSON,
+                            "href": actor_uri(&domain, &user.username),
+                        },
+                        {
+                            "rel": "http://webfinger.net/rel/profile-page",
+                            "type": "text/html",
+                            "href": format!("https://{}/user/{}", domain, user.id.to_proto_id()),
+                        },
+                    ],
+                });
+                (ContentType::new("application", "jrd+json"), jrd.to_string())
+            })
+        }
+        _ => Err(Status::NotFound),
+    };
+    CacheResponse::Public {
+        responder: result,
+        max_age: 300,
+        must_revalidate: false,
+    }
+}
+
+#[rocket::get("/ap/users/<username>")]
+async fn actor(
+    username: &str,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<(ContentType, String), Status>> {
+    let domain = activitypub_domain(host);
+    let result = match load_public_user(username, state) {
+        Ok(user) => {
+            let mut conn = state.pool.get().unwrap();
+            match get_or_create_actor_key(user.id, &mut conn).await {
+                Ok(key) => Ok((
+                    activity_json(),
+                    user.to_activitypub_actor(&domain, &key.public_key_pem)
+                        .to_string(),
+                )),
+                Err(e) => {
+                    log::error!("Failed to load actor key for {}: {:?}", username, e);
+                    Err(Status::InternalServerError)
+                }
+            }
+        }
+        Err(status) => Err(status),
+    };
+    CacheResponse::Public {
+        responder: result,
+        max_age: 300,
+        must_revalidate: false,
+    }
+}
+
+#[rocket::get("/ap/users/<username>/outbox?<page>")]
+async fn outbox(
+    username: &str,
+    page: Option<i64>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<(ContentType, String), Status>> {
+    let domain = activitypub_domain(host);
+    let result = load_public_user(username, state).map(|user| {
+        let mut conn = state.pool.get().unwrap();
+        let public_posts = posts::table
+            .filter(posts::user_id.eq(user.id))
+            .filter(posts::visibility.eq(Visibility::GlobalPublic.to_string_visibility()))
+            .filter(posts::context.eq(PostContext::Post.as_str_name()));
+        let outbox_uri = format!("{}/outbox", actor_uri(&domain, &user.username));
+        let collection = match page {
+            None => {
+                let total_items = public_posts
+                    .count()
+                    .get_result::<i64>(&mut conn)
+                    .unwrap_or(0);
+                json!({
+                    "@context": ACTIVITYSTREAMS_CONTEXT,
+                    "id": outbox_uri,
+                    "type": "OrderedCollection",
+                    "totalItems": total_items,
+                    "first": format!("{}?page=0", outbox_uri),
+                })
+            }
+            Some(page) => {
+                let page = page.max(0);
+                let items = public_posts
+                    .order(posts::created_at.desc())
+                    .limit(OUTBOX_PAGE_SIZE)
+                    .offset(page * OUTBOX_PAGE_SIZE)
+                    .load::<models::Post>(&mut conn)
+                    .unwrap_or_default();
+                let mut collection_page = json!({
+                    "@context": ACTIVITYSTREAMS_CONTEXT,
+                    "id": format!("{}?page={}", outbox_uri, page),
+                    "type": "OrderedCollectionPage",
+                    "partOf": outbox_uri,
+                    "orderedItems": items
+                        .iter()
+                        .map(|post| post.to_activitypub_create(&domain, &user.username))
+                        .collect::<Vec<serde_json::Value>>(),
+                });
+                if items.len() as i64 == OUTBOX_PAGE_SIZE {
+                    collection_page["next"] = json!(format!("{}?page={}", outbox_uri, page + 1));
+                }
+                collection_page
+            }
+        };
+        (activity_json(), collection.to_string())
+    });
+    CacheResponse::Public {
+        responder: result,
+        max_age: 60,
+        must_revalidate: false,
+    }
+}
+
+#[rocket::get("/ap/users/<username>/followers")]
+async fn followers(
+    username: &str,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<(ContentType, String), Status>> {
+    let domain = activitypub_domain(host);
+    let result = load_public_user(username, state).map(|user| {
+        let remote_followers = activitypub_remote_followers::table
+            .filter(activitypub_remote_followers::user_id.eq(user.id))
+            .count()
+            .get_result::<i64>(&mut state.pool.get().unwrap())
+            .unwrap_or(0);
+        let collection = json!({
+            "@context": ACTIVITYSTREAMS_CONTEXT,
+            "id": format!("{}/followers", actor_uri(&domain, &user.username)),
+            "type": "OrderedCollection",
+            "totalItems": user.follower_count as i64 + remote_followers,
+        });
+        (activity_json(), collection.to_string())
+    });
+    CacheResponse::Public {
+        responder: result,
+        max_age: 300,
+        must_revalidate: false,
+    }
+}
+
+#[rocket::get("/ap/posts/<id>")]
+async fn note(
+    id: &str,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<(ContentType, String), Status>> {
+    let domain = activitypub_domain(host);
+    let result = (|| {
+        let post_id = id.to_string().to_db_id().map_err(|_| Status::NotFound)?;
+        let (post, username) = posts::table
+            .inner_join(users::table.on(posts::user_id.eq(users::id.nullable())))
+            .select((posts::all_columns, users::username))
+            .filter(posts::id.eq(post_id))
+            .filter(posts::visibility.eq(Visibility::GlobalPublic.to_string_visibility()))
+            .filter(users::visibility.eq(Visibility::GlobalPublic.to_string_visibility()))
+            .first::<(models::Post, String)>(&mut state.pool.get().unwrap())
+            .map_err(|_| Status::NotFound)?;
+        let mut note = post.to_activitypub_note(&domain, &username);
+        note["@context"] = json!(ACTIVITYSTREAMS_CONTEXT);
+        Ok((activity_json(), note.to_string()))
+    })();
+    CacheResponse::Public {
+        responder: result,
+        max_age: 300,
+        must_revalidate: false,
+    }
+}
+
+#[rocket::post("/ap/users/<username>/inbox", data = "<body>")]
+async fn user_inbox(
+    username: &str,
+    body: Data<'_>,
+    request: SignedRequest,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> Status {
+    if load_public_user(username, state).is_err() {
+        return Status::NotFound;
+    }
+    receive_activity(body, request, state, host).await
+}
+
+#[rocket::post("/ap/inbox", data = "<body>")]
+async fn shared_inbox(
+    body: Data<'_>,
+    request: SignedRequest,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> Status {
+    receive_activity(body, request, state, host).await
+}
+
+async fn receive_activity(
+    body: Data<'_>,
+    request: SignedRequest,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> Status {
+    let domain = activitypub_domain(host);
+    let body = match body.open(1.mebibytes()).into_bytes().await {
+        Ok(body) if body.is_complete() => body.into_inner(),
+        _ => return Status::PayloadTooLarge,
+    };
+    let activity: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(activity) => activity,
+        Err(_) => return Status::BadRequest,
+    };
+    let remote_actor = match verify_inbox_request(&request, &body, &activity).await {
+        Ok(remote_actor) => remote_actor,
+        Err(e) => {
+            log::warn!("Rejected ActivityPub inbox request: {:?}", e);
+            return Status::Unauthorized;
+        }
+    };
+    match process_inbox_activity(
+        &activity,
+        &remote_actor,
+        &domain,
+        &mut state.pool.get().unwrap(),
+    ) {
+        Ok(_) => Status::Accepted,
+        Err(e) => {
+            log::warn!("Failed to process ActivityPub activity: {:?}", e);
+            Status::BadRequest
+        }
+    }
+}
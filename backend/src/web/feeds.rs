@@ -0,0 +1,444 @@
+use std::time::SystemTime;
+
+use diesel::*;
+use rocket::http::uri::Host;
+use rocket::http::{ContentType, Status};
+use rocket::request::FromParam;
+use rocket::{routes, Route, State};
+use rocket_cache_response::CacheResponse;
+
+use super::media::media_file_url;
+use super::{configured_backend_domain, configured_frontend_domain, RocketState};
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::{PostContext, Visibility};
+use crate::rpcs::get_server_configuration;
+use crate::rpcs::validations::PASSING_MODERATIONS;
+use crate::schema::{group_posts, groups, media, posts, users};
+
+const FEED_SIZE: i64 = 50;
+
+lazy_static! {
+    pub static ref FEED_PAGES: Vec<Route> = routes![public_feed, user_feed, group_feed];
+}
+
+/// `atom.xml` or `rss.xml`, used as the last path segment of feed routes.
+pub enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+impl<'a> FromParam<'a> for FeedFormat {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        match param {
+            "atom.xml" | "atom" => Ok(FeedFormat::Atom),
+            "rss.xml" | "rss" => Ok(FeedFormat::Rss),
+            _ => Err(param),
+        }
+    }
+}
+
+struct Feed {
+    title: String,
+    description: String,
+    /// The Tamagui page this feed mirrors.
+    html_url: String,
+    /// The URL of the feed itself.
+    feed_url: String,
+    /// Where media (i.e. enclosures) is served from.
+    backend_domain: String,
+    entries: Vec<FeedEntry>,
+}
+
+struct FeedEntry {
+    post: models::Post,
+    author: Option<String>,
+    url: String,
+    enclosures: Vec<Enclosure>,
+}
+
+struct Enclosure {
+    media_id: i64,
+    content_type: String,
+}
+
+#[rocket::get("/feeds/public/<format>")]
+async fn public_feed(
+    format: FeedFormat,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<(ContentType, String), Status>> {
+    let domain = configured_frontend_domain(state, host);
+    let backend_domain = configured_backend_domain(state, host);
+    let mut conn = state.pool.get().unwrap();
+    let server_info = get_server_configuration(&mut conn)
+        .ok()
+        .and_then(|c| c.server_info);
+    let posts = posts::table
+        .left_join(users::table.on(posts::user_id.eq(users::id.nullable())))
+        .select((posts::all_columns, users::username.nullable()))
+        .filter(posts::visibility.eq(Visibility::GlobalPublic.to_string_visibility()))
+        .filter(posts::moderation.eq_any(PASSING_MODERATIONS))
+        .filter(posts::context.eq(PostContext::Post.as_str_name()))
+        .order(posts::created_at.desc())
+        .limit(FEED_SIZE)
+        .load::<(models::Post, Option<String>)>(&mut conn)
+        .unwrap_or_default();
+    let feed = Feed {
+        title: server_info
+            .as_ref()
+            .and_then(|i| i.name.to_owned())
+            .unwrap_or(domain.to_owned()),
+        description: server_info
+            .as_ref()
+            .and_then(|i| i.description.to_owned())
+            .unwrap_or_default(),
+        html_url: format!("https://{}/posts", domain),
+        feed_url: format!("https://{}/feeds/public/{}", domain, format.file_name()),
+        backend_domain,
+        entries: to_entries(posts, &mut conn, |post| {
+            format!("https://{}/post/{}", domain, post.id.to_proto_id())
+        }),
+    };
+    feed_response(format, feed)
+}
+
+#[rocket::get("/feeds/users/<username>/<format>")]
+async fn user_feed(
+    username: &str,
+    format: FeedFormat,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<(ContentType, String), Status>> {
+    let domain = configured_frontend_domain(state, host);
+    let backend_domain = configured_backend_domain(state, host);
+    let mut conn = state.pool.get().unwrap();
+    let user = match users::table
+        .filter(users::username.eq(username))
+        .filter(users::visibility.eq(Visibility::GlobalPublic.to_string_visibility()))
+        .first::<models::User>(&mut conn)
+    {
+        Ok(user) => user,
+        Err(_) => return feed_error(Status::NotFound),
+    };
+    let posts = posts::table
+        .left_join(users::table.on(posts::user_id.eq(users::id.nullable())))
+        .select((posts::all_columns, users::username.nullable()))
+        .filter(posts::user_id.eq(user.id))
+        .filter(posts::visibility.eq(Visibility::GlobalPublic.to_string_visibility()))
+        .filter(posts::moderation.eq_any(PASSING_MODERATIONS))
+        .filter(posts::context.eq(PostContext::Post.as_str_name()))
+        .order(posts::created_at.desc())
+        .limit(FEED_SIZE)
+        .load::<(models::Post, Option<String>)>(&mut conn)
+        .unwrap_or_default();
+    let feed = Feed {
+        title: if user.real_name.is_empty() {
+            user.username.to_owned()
+        } else {
+            format!("{} ({})", user.real_name, user.username)
+        },
+        description: user.bio.to_owned(),
+        html_url: format!("https://{}/user/{}", domain, user.id.to_proto_id()),
+        feed_url: format!(
+            "https://{}/feeds/users/{}/{}",
+            domain,
+            user.username,
+            format.file_name()
+        ),
+        backend_domain,
+        entries: to_entries(posts, &mut conn, |post| {
+            format!("https://{}/post/{}", domain, post.id.to_proto_id())
+        }),
+    };
+    feed_response(format, feed)
+}
+
+#[rocket::get("/feeds/groups/<shortname>/<format>")]
+async fn group_feed(
+    shortname: &str,
+    format: FeedFormat,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<(ContentType, String), Status>> {
+    let domain = configured_frontend_domain(state, host);
+    let backend_domain = configured_backend_domain(state, host);
+    let mut conn = state.pool.get().unwrap();
+    let group = match groups::table
+        .filter(models::lower(groups::shortname).eq(models::lower(shortname)))
+        .filter(groups::visibility.eq(Visibility::GlobalPublic.to_string_visibility()))
+        .first::<models::Group>(&mut conn)
+    {
+        Ok(group) => group,
+        Err(_) => return feed_error(Status::NotFound),
+    };
+    let posts = group_posts::table
+        .inner_join(posts::table.on(group_posts::post_id.eq(posts::id)))
+        .left_join(users::table.on(posts::user_id.eq(users::id.nullable())))
+        .select((posts::all_columns, users::username.nullable()))
+        .filter(group_posts::group_id.eq(group.id))
+        .filter(group_posts::group_moderation.eq_any(PASSING_MODERATIONS))
+        .filter(posts::visibility.eq(Visibility::GlobalPublic.to_string_visibility()))
+        .filter(posts::moderation.eq_any(PASSING_MODERATIONS))
+        .filter(posts::context.eq(PostContext::Post.as_str_name()))
+        .order(group_posts::created_at.desc())
+        .limit(FEED_SIZE)
+        .load::<(models::Post, Option<String>)>(&mut conn)
+        .unwrap_or_default();
+    let feed = Feed {
+        title: group.name.to_owned(),
+        description: group.description.to_owned(),
+        html_url: format!("https://{}/g/{}", domain, group.shortname),
+        feed_url: format!(
+            "https://{}/feeds/groups/{}/{}",
+            domain,
+            group.shortname,
+            format.file_name()
+        ),
+        backend_domain,
+        entries: to_entries(posts, &mut conn, |post| {
+            format!(
+                "https://{}/g/{}/p/{}",
+                domain,
+                group.shortname,
+                post.id.to_proto_id()
+            )
+        }),
+    };
+    feed_response(format, feed)
+}
+
+fn to_entries<F: Fn(&models::Post) -> String>(
+    posts: Vec<(models::Post, Option<String>)>,
+    conn: &mut PgPooledConnection,
+    url: F,
+) -> Vec<FeedEntry> {
+    let media_ids = posts
+        .iter()
+        .flat_map(|(post, _)| post.media.to_owned())
+        .collect::<Vec<i64>>();
+    let public_media = media::table
+        .filter(media::id.eq_any(media_ids))
+        .filter(media::visibility.eq(Visibility::GlobalPublic.to_string_visibility()))
+        .filter(media::moderation.eq_any(PASSING_MODERATIONS))
+        .load::<models::Media>(conn)
+        .unwrap_or_default();
+    posts
+        .into_iter()
+        .map(|(post, author)| {
+            let enclosures = post
+                .media
+                .iter()
+                .filter_map(|id| public_media.iter().find(|m| m.id == *id))
+                .map(|m| Enclosure {
+                    media_id: m.id,
+                    content_type: m.content_type.to_owned(),
+                })
+                .collect();
+            FeedEntry {
+                url: url(&post),
+                post,
+                author,
+                enclosures,
+            }
+        })
+        .collect()
+}
+
+impl FeedFormat {
+    fn file_name(&self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "atom.xml",
+            FeedFormat::Rss => "rss.xml",
+        }
+    }
+}
+
+fn feed_error(status: Status) -> CacheResponse<Result<(ContentType, String), Status>> {
+    CacheResponse::Public {
+        responder: Err(status),
+        max_age: 60,
+        must_revalidate: false,
+    }
+}
+
+fn feed_response(
+    format: FeedFormat,
+    feed: Feed,
+) -> CacheResponse<Result<(ContentType, String), Status>> {
+    let body = match format {
+        FeedFormat::Atom => (
+            ContentType::new("application", "atom+xml"),
+            atom_feed(&feed),
+        ),
+        FeedFormat::Rss => (ContentType::new("application", "rss+xml"), rss_feed(&feed)),
+    };
+    CacheResponse::Public {
+        responder: Ok(body),
+        max_age: 300,
+        must_revalidate: false,
+    }
+}
+
+fn entry_title(post: &models::Post) -> String {
+    match post.title.as_ref().filter(|t| !t.is_empty()) {
+        Some(title) => title.to_owned(),
+        None => {
+            let content = post.content.to_owned().unwrap_or_default();
+            let mut title = content.chars().take(80).collect::<String>();
+            if content.chars().count() > 80 {
+                title.push('…');
+            }
+            title
+        }
+    }
+}
+
+fn entry_html(entry: &FeedEntry) -> String {
+    let mut html = entry
+        .post
+        .content
+        .as_deref()
+        .unwrap_or_default()
+        .to_html_paragraphs();
+    if let Some(link) = entry.post.link.as_ref().filter(|l| !l.is_empty()) {
+        html.push_str(&format!(
+            "<p><a href=\"{}\">{}</a></p>",
+            link.to_escaped_html(),
+            link.to_escaped_html()
+        ));
+    }
+    html
+}
+
+fn media_url(feed: &Feed, enclosure: &Enclosure) -> String {
+    media_file_url(&feed.backend_domain, enclosure.media_id)
+}
+
+fn atom_feed(feed: &Feed) -> String {
+    let updated = feed
+        .entries
+        .iter()
+        .map(|e| e.post.updated_at.unwrap_or(e.post.created_at))
+        .max()
+        .unwrap_or(SystemTime::now());
+    let entries = feed
+        .entries
+        .iter()
+        .map(|entry| {
+            let enclosures = entry
+                .enclosures
+                .iter()
+                .map(|m| {
+                    format!(
+                        "\n    <link rel=\"enclosure\" type=\"{}\" href=\"{}\"/>",
+                        m.content_type.to_escaped_html(),
+                        media_url(feed, m).to_escaped_html()
+                    )
+                })
+                .collect::<String>();
+            format!(
+                "
+  <entry>
+    <id>{}</id>
+    <title>{}</title>
+    <link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>{}
+    <author><name>{}</name></author>
+    <published>{}</published>
+    <updated>{}</updated>
+    <content type=\"html\">{}</content>
+  </entry>",
+                entry.url.to_escaped_html(),
+                entry_title(&entry.post).to_escaped_html(),
+                entry.url.to_escaped_html(),
+                enclosures,
+                entry.author.as_deref().unwrap_or("anonymous").to_escaped_html(),
+                entry.post.created_at.to_rfc3339(),
+                entry
+                    .post
+                    .updated_at
+                    .unwrap_or(entry.post.created_at)
+                    .to_rfc3339(),
+                entry_html(entry).to_escaped_html()
+            )
+        })
+        .collect::<String>();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<feed xmlns=\"http://www.w3.org/2005/Atom\">
+  <id>{}</id>
+  <title>{}</title>
+  <subtitle>{}</subtitle>
+  <link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>
+  <link rel=\"self\" type=\"application/atom+xml\" href=\"{}\"/>
+  <updated>{}</updated>{}
+</feed>
+",
+        feed.feed_url.to_escaped_html(),
+        feed.title.to_escaped_html(),
+        feed.description.trim().to_escaped_html(),
+        feed.html_url.to_escaped_html(),
+        feed.feed_url.to_escaped_html(),
+        updated.to_rfc3339(),
+        entries
+    )
+}
+
+fn rss_feed(feed: &Feed) -> String {
+    let items = feed
+        .entries
+        .iter()
+        .map(|entry| {
+            // RSS 2.0 only allows one enclosure per item.
+            let enclosure = entry
+                .enclosures
+                .first()
+                .map(|m| {
+                    format!(
+                        "\n      <enclosure url=\"{}\" type=\"{}\" length=\"0\"/>",
+                        media_url(feed, m).to_escaped_html(),
+                        m.content_type.to_escaped_html()
+                    )
+                })
+                .unwrap_or_default();
+            format!(
+                "
+    <item>
+      <guid isPermaLink=\"true\">{}</guid>
+      <title>{}</title>
+      <link>{}</link>{}
+      <author>{}</author>
+      <pubDate>{}</pubDate>
+      <description>{}</description>
+    </item>",
+                entry.url.to_escaped_html(),
+                entry_title(&entry.post).to_escaped_html(),
+                entry.url.to_escaped_html(),
+                enclosure,
+                entry.author.as_deref().unwrap_or("anonymous").to_escaped_html(),
+                entry.post.created_at.to_rfc2822(),
+                entry_html(entry).to_escaped_html()
+            )
+        })
+        .collect::<String>();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">
+  <channel>
+    <title>{}</title>
+    <description>{}</description>
+    <link>{}</link>
+    <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>{}
+  </channel>
+</rss>
+",
+        feed.title.to_escaped_html(),
+        feed.description.trim().to_escaped_html(),
+        feed.html_url.to_escaped_html(),
+        feed.feed_url.to_escaped_html(),
+        items
+    )
+}
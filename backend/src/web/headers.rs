@@ -73,3 +73,59 @@ impl<'r> FromRequest<'r> for MediaDescriptionHeader<'r> {
         }
     }
 }
+
+pub struct RangeHeader<'a>(pub &'a str);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RangeHeader<'r> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.headers().get_one("Range") {
+            Some(h) => Outcome::Success(RangeHeader(h)),
+            None => Outcome::Failure((rocket::http::Status::NotAcceptable, ())),
+        }
+    }
+}
+
+pub struct IfNoneMatchHeader<'a>(pub &'a str);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatchHeader<'r> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.headers().get_one("If-None-Match") {
+            Some(h) => Outcome::Success(IfNoneMatchHeader(h)),
+            None => Outcome::Failure((rocket::http::Status::NotAcceptable, ())),
+        }
+    }
+}
+
+pub struct UploadChecksumHeader<'a>(pub &'a str);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for UploadChecksumHeader<'r> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.headers().get_one("Upload-Checksum") {
+            Some(h) => Outcome::Success(UploadChecksumHeader(h)),
+            None => Outcome::Failure((rocket::http::Status::NotAcceptable, ())),
+        }
+    }
+}
+
+pub struct MediaVisibilityHeader<'a>(pub &'a str);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for MediaVisibilityHeader<'r> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.headers().get_one("Media-Visibility") {
+            Some(h) => Outcome::Success(MediaVisibilityHeader(h)),
+            None => Outcome::Failure((rocket::http::Status::NotAcceptable, ())),
+        }
+    }
+}
@@ -1,35 +1,34 @@
 use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::db_connection::*;
+use crate::auth::AuthContext;
+use crate::logic::*;
 use crate::marshaling::*;
 use crate::models;
-use crate::protos::Visibility;
+use crate::protos::{MediaLimits, Permission, Visibility};
+use crate::rpcs::get_server_configuration;
+use crate::rpcs::validations::validate_media_visibility;
 use crate::schema;
 use crate::schema::media;
-use crate::schema::user_access_tokens::dsl as user_access_tokens;
-use crate::schema::user_refresh_tokens::dsl as user_refresh_tokens;
-use crate::schema::users::dsl as users;
-use crate::web::headers::{AuthHeader, ContentTypeHeader, FilenameHeader};
-use crate::web::RocketState;
-// use futures::StreamExt;
+use crate::schema::media_variants;
+use crate::media_processing::{
+    adopt_blob_processing, blob_path, reference_blob, reference_existing_blob, spool_upload,
+    MEDIA_SIZES,
+};
+use crate::web::headers::{
+    AuthHeader, ContentTypeHeader, FilenameHeader, IfNoneMatchHeader, MediaVisibilityHeader,
+    RangeHeader,
+};
+use crate::web::secure_media::SecureMediaAccess;
+use crate::web::{etag_matches, parse_range, MediaStream, RocketState};
 use rocket::http::ContentType;
-// use crate::tokio_stream::StreamExt;
-// use crate::bytes::Bytes;
-use log::info;
-use rocket::fs::NamedFile;
-// use rocket::Response;
-// use s3::request::ResponseDataStream;
 
 use diesel::*;
 use rocket::http::MediaType;
-// use rocket::response::stream::ByteStream;
 use rocket::{data::ToByteUnit, http::CookieJar, routes, Data, Route, State};
 
 use rocket::http::Status;
 use rocket_cache_response::CacheResponse;
-// use s3::request::ResponseDataStream;
-// use tokio::io::AsyncWriteExt;
-use uuid::Uuid;
 
 lazy_static! {
     pub static ref MEDIA_ENDPOINTS: Vec<Route> = routes![
@@ -46,47 +45,116 @@ pub async fn create_media_options() -> &'static str {
     return "";
 }
 
+/// Uploads a media item, subject to the user's `MediaLimits` (see `ServerConfiguration.media_settings`).
+/// Its visibility can be set with a `Media-Visibility` header (a `Visibility` name, defaulting to
+/// `GLOBAL_PUBLIC`). Data is stored by content address, so re-uploads of identical data share one blob.
 #[rocket::post("/media", data = "<media>")]
 pub async fn create_media(
-    media: Data<'_>,
+    mut media: Data<'_>,
     cookies: &CookieJar<'_>,
     state: &State<RocketState>,
     auth_header: Option<AuthHeader<'_>>,
     content_type_header: ContentTypeHeader<'_>,
     filename_header: FilenameHeader<'_>,
+    visibility_header: Option<MediaVisibilityHeader<'_>>,
 ) -> Result<String, Status> {
     log::info!("create_media");
-    let user = get_media_user(None, auth_header, cookies, state)?;
-    let uuid = Uuid::new_v4();
-    let minio_path = format!(
-        "user/{}-{}/{}-{}",
-        user.id.to_proto_id(),
-        user.username,
-        uuid,
-        filename_header.0
-    );
+    let user = get_media_upload_user(auth_header, cookies, state)?;
+    let visibility = media_upload_visibility(&user, visibility_header)?;
+    limit_media_upload_rate(&user)?;
+    let (limits, usage) = media_limits_and_usage(&user, state)?;
+    validate_media_upload_start(&user, &limits, &usage).map_err(rejection_status)?;
+
+    let head = media.peek(512).await.to_vec();
+    let content_type = validate_media_content_type(&head, content_type_header.0, &limits)
+        .map_err(rejection_status)?;
 
-    let status_code = state
-        .bucket
-        .put_object_stream(&mut media.open(250.mebibytes()), &minio_path)
+    // Spool the upload locally (reading one byte past the limit, so oversized uploads can be
+    // detected) to hash it, since identical uploads share a single content-addressed blob.
+    let max_file_size = max_media_file_size(&limits);
+    let upload = spool_upload(&mut media.open((max_file_size + 1).bytes()))
         .await
-        .map_err(|_| Status::InternalServerError)?;
+        .map_err(|e| {
+            log::warn!("Failed to spool upload: {:?}", e);
+            Status::InternalServerError
+        })?;
+    validate_media_upload_size(upload.size, &limits, &usage).map_err(rejection_status)?;
 
-    log::info!("create_media status_code: {:?}", status_code);
+    let new_media = |blob: models::MediaBlob| models::NewMedia {
+        user_id: Some(user.id),
+        minio_path: blob.minio_path,
+        content_type: content_type.to_owned(),
+        name: Some(filename_header.0.to_string()),
+        description: None,
+        generated: false,
+        visibility: visibility.to_string_visibility(),
+        size: upload.size as i64,
+        blob_id: Some(blob.id),
+    };
 
-    let media = insert_into(media::table)
-        .values(&models::NewMedia {
-            user_id: Some(user.id),
-            minio_path: minio_path,
-            content_type: content_type_header.0.to_string(),
-            name: Some(filename_header.0.to_string()),
-            description: None,
-            generated: false,
-            visibility: Visibility::GlobalPublic.to_string_visibility(),
+    // Reuse an identical blob if there is one. Otherwise, store the upload as a new one.
+    let reused_media = state
+        .pool
+        .get()
+        .unwrap()
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            match reference_existing_blob(&upload.sha256, conn)? {
+                Some(blob) => insert_into(media::table)
+                    .values(&new_media(blob))
+                    .get_result::<models::Media>(conn)
+                    .map(Some),
+                None => Ok(None),
+            }
         })
-        .get_result::<models::Media>(&mut state.pool.get().unwrap());
+        .map_err(|_| Status::InternalServerError)?;
+    let media = match reused_media {
+        Some(media) => media,
+        None => {
+            let minio_path = blob_path(&upload.sha256);
+            let mut reader = upload.reader().await.map_err(|_| Status::InternalServerError)?;
+            state
+                .media_store
+                .put_stream(&minio_path, &mut reader)
+                .await
+                .map_err(|e| {
+                    log::warn!("Failed to store media {}: {:?}", minio_path, e);
+                    Status::InternalServerError
+                })?;
+            let media = state
+                .pool
+                .get()
+                .unwrap()
+                .transaction::<_, diesel::result::Error, _>(|conn| {
+                    let blob =
+                        reference_blob(&upload.sha256, &minio_path, upload.size as i64, conn)?;
+                    insert_into(media::table)
+                        .values(&new_media(blob))
+                        .get_result::<models::Media>(conn)
+                })
+                .map_err(|_| Status::InternalServerError)?;
+            if media.minio_path == minio_path {
+                return Ok(media.id.to_proto_id());
+            }
+            // An identical upload stored its blob first.
+            if let Err(e) = state.media_store.delete(&minio_path).await {
+                log::warn!("Failed to delete duplicate media {}: {:?}", minio_path, e);
+            }
+            media
+        }
+    };
+    log::info!("create_media reused blob {}", upload.sha256);
+    if let Some(blob_id) = media.blob_id {
+        if let Err(e) = adopt_blob_processing(media.id, blob_id, &mut state.pool.get().unwrap()) {
+            log::warn!("Failed to adopt processing for media {}: {:?}", media.id, e);
+        }
+    }
+
+    return Ok(media.id.to_proto_id());
+}
 
-    return Ok(media.unwrap().id.to_proto_id());
+/// The URL `media_file` serves the media at, on the (configured) backend domain.
+pub fn media_file_url(backend_domain: &str, media_id: i64) -> String {
+    format!("https://{}/media/{}", backend_domain, media_id.to_proto_id())
 }
 
 /// Used to manage CORS for the media download endpoint(s).
@@ -95,16 +163,31 @@ pub async fn media_file_options(_id: &str) -> &'static str {
     return "";
 }
 
-#[rocket::get("/media/<id>?<authorization>")]
+/// Serves media. Processed images can be fetched at a smaller `size`: `thumbnail`,
+/// or one of `media_processing::MEDIA_SIZES`. Unavailable sizes fall back to the original.
+///
+/// Media the viewer can't see may still be served from a signed URL (with `expires` and
+/// `signature`, as issued by `GetMediaUrl`), which is cacheable per URL until it expires.
+///
+/// Supports `Range` requests (for seeking in audio/video) and `If-None-Match`. Media is
+/// streamed from the `MediaStore` until it's been pulled into the local `MediaCache`.
+#[rocket::get("/media/<id>?<authorization>&<size>&<expires>&<signature>")]
 pub async fn media_file<'a>(
     id: &str,
     authorization: Option<String>,
+    size: Option<&str>,
+    expires: Option<u64>,
+    signature: Option<&str>,
     cookies: &CookieJar<'_>,
     state: &State<RocketState>,
     auth_header: Option<AuthHeader<'_>>,
-) -> Result<CacheResponse<(ContentType, NamedFile)>, Status> {
+    range_header: Option<RangeHeader<'_>>,
+    if_none_match: Option<IfNoneMatchHeader<'_>>,
+    _secure_media: SecureMediaAccess,
+) -> Result<CacheResponse<MediaStream>, Status> {
     log::info!("media_file: {:?}", id);
-    let _user = get_media_user(authorization, auth_header, cookies, state).ok();
+    let user = get_media_user(authorization, auth_header, cookies, state).ok();
+    let mut conn = state.pool.get().unwrap();
 
     let media = schema::media::table
         .filter(
@@ -113,94 +196,209 @@ pub async fn media_file<'a>(
                 .to_db_id_or_err("media_id")
                 .map_err(|_| Status::BadRequest)?),
         )
-        .first::<models::Media>(&mut state.pool.get().unwrap())
+        .first::<models::Media>(&mut conn)
         .map_err(|_| Status::NotFound)?;
 
-    // TODO: Validate moderation/visiblity/permissions etc.
-
-    let local_filename = format!(
-        "{}/{}.mediafile",
-        state.tempdir.path().display(),
-        media.minio_path
-    );
-    if !std::path::Path::new(&local_filename).exists() {
-        // Ensure local directory exists.
-        let mut _filevec: Vec<&str> = local_filename.split("/").collect();
-        _filevec.pop();
-        let local_filedir = _filevec.join("/");
-        // info!("local_filedir: {}", local_filedir);
-        std::fs::create_dir_all(local_filedir).map_err(|_| Status::InternalServerError)?;
-
-        // Download file from S3 into a tempfile
-        let temp_filename = format!("{}-download-{}", local_filename, Uuid::new_v4());
-        let mut async_output_file = tokio::fs::File::create(temp_filename.to_owned())
-            .await
-            .map_err(|_| Status::InternalServerError)?;
-        let _status_code = state
-            .bucket
-            .get_object_to_writer(media.minio_path, &mut async_output_file)
-            .await
-            .map_err(|_| Status::InternalServerError)?;
-
-        // Rename tempfile to final filename
-        std::fs::rename(temp_filename, &local_filename).map_err(|_| Status::InternalServerError)?;
+    // Seconds until a valid signed URL expires.
+    let signed_ttl = match (expires, signature) {
+        (Some(expires), Some(signature)) => {
+            let key = media_url_signing_key(&mut conn).map_err(|_| Status::InternalServerError)?;
+            let now = SystemTime::now();
+            if !verify_media_url_signature(&key, media.id, size, expires, signature, now)
+                || !can_view_signed_media(&media)
+            {
+                return Err(Status::Forbidden);
+            }
+            let now = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            Some(expires.saturating_sub(now))
+        }
+        (None, None) => None,
+        _ => return Err(Status::BadRequest),
+    };
+    if signed_ttl.is_none() && !can_view_media(&media, &user, &mut conn) {
+        return Err(Status::NotFound);
     }
+    if !can_serve_media_data(&media, &user) {
+        return Err(Status::ServiceUnavailable);
+    }
+    let anonymously_visible =
+        can_serve_media_data(&media, &None) && can_view_media(&media, &None, &mut conn);
+    // Processing overwrites originals in place, so cached copies are keyed by `updated_at` too.
+    let version = media
+        .updated_at
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (minio_path, content_type) = match size {
+        None | Some("original") => (media.minio_path, media.content_type),
+        Some("thumbnail") => match (media.thumbnail_minio_path, media.thumbnail_content_type) {
+            (Some(path), Some(content_type)) => (path, content_type),
+            _ => (media.minio_path, media.content_type),
+        },
+        Some(size) if MEDIA_SIZES.iter().any(|(name, _)| *name == size) => {
+            match media_variants::table
+                .filter(media_variants::media_id.eq(media.id))
+                .filter(media_variants::size.eq(size))
+                .first::<models::MediaVariant>(&mut conn)
+            {
+                Ok(variant) => (variant.minio_path, variant.content_type),
+                Err(_) => (media.minio_path, media.content_type),
+            }
+        }
+        Some(_) => return Err(Status::BadRequest),
+    };
+    drop(conn);
 
     let media_type = ContentType(
-        MediaType::from_str(&media.content_type).map_err(|_| Status::ExpectationFailed)?,
+        MediaType::from_str(&content_type).map_err(|_| Status::ExpectationFailed)?,
     );
-    info!("media_type: {:?}", media_type);
-    let result = NamedFile::open(local_filename)
-        .await
-        .map_err(|_| Status::ImATeapot)?;
-    Ok(CacheResponse::Public {
-        responder: (media_type, result),
-        max_age: 3600 * 12,
-        must_revalidate: true,
-    })
-
-    // let mut _stream = state
-    //     .bucket
-    //     .get_object_stream(media.minio_path.as_str())
-    //     .await
-    //     .map_err(|_| Status::NotFound)?;
-
-    // Ok(ByteStream::from(async_output_file.st))
+    let cache_key = format!("{}-{}", minio_path, version);
+    let etag = state.media_cache.etag(&cache_key);
+    let stream = if etag_matches(if_none_match.map(|h| h.0), &etag) {
+        MediaStream::not_modified(media_type, etag)
+    } else {
+        let range = range_header.map(|h| h.0);
+        match state.media_cache.get(&cache_key) {
+            Some((path, length)) => {
+                MediaStream::from_file(&path, length, parse_range(range, length), media_type, etag)
+                    .await
+                    .map_err(|_| Status::InternalServerError)?
+            }
+            None => {
+                state.media_cache.clone().populate(
+                    cache_key,
+                    minio_path.to_owned(),
+                    state.media_store.clone(),
+                );
+                MediaStream::from_store(
+                    state.media_store.clone(),
+                    minio_path,
+                    range,
+                    media_type,
+                    etag,
+                )
+                    .await
+                    .map_err(|e| {
+                        log::warn!("Failed to stream media {}: {:?}", id, e);
+                        Status::InternalServerError
+                    })?
+            }
+        }
+    };
+    // Only media anyone can see may be cached by shared caches (i.e. a CDN), along with signed
+    // URLs (whose signatures make them unguessable) until they expire.
+    if anonymously_visible {
+        Ok(CacheResponse::Public {
+            responder: stream,
+            max_age: 3600 * 12,
+            must_revalidate: true,
+        })
+    } else if let Some(ttl) = signed_ttl {
+        Ok(CacheResponse::Public {
+            responder: stream,
+            max_age: ttl.min(3600 * 12) as u32,
+            must_revalidate: true,
+        })
+    } else {
+        Ok(CacheResponse::Private {
+            responder: stream,
+            max_age: 3600,
+        })
+    }
+}
 
-    // This should work.
-    // Ok(ByteStream::from(_stream.bytes()))
+/// The `MediaLimits` that apply to `user`, and their current usage.
+pub(crate) fn media_limits_and_usage(
+    user: &models::User,
+    state: &State<RocketState>,
+) -> Result<(MediaLimits, MediaUsage), Status> {
+    let mut conn = state.pool.get().unwrap();
+    let configuration =
+        get_server_configuration(&mut conn).map_err(|_| Status::InternalServerError)?;
+    let usage = media_usage(user.id, &mut conn).map_err(|_| Status::InternalServerError)?;
+    Ok((media_limits(user, &configuration), usage))
+}
 
-    // Or this.
-    // Ok(ByteStream! {
-    //     let mut stream: &'a ResponseDataStream = &state
-    //         .bucket
-    //         .get_object_stream(media.minio_path.as_str())
-    //         .await
-    //         .map_err(|_| Status::NotFound).unwrap();
-    //     while let Some(bytes) = stream.bytes().next().await {
-    //         yield bytes;
-    //     }
-    // })
+/// Limits how often a user can start uploads (see `rate_limiting::MEDIA_UPLOADS_BY_USER`).
+pub(crate) fn limit_media_upload_rate(user: &models::User) -> Result<(), Status> {
+    crate::rate_limiting::MEDIA_UPLOADS_BY_USER
+        .hit(&user.id.to_string())
+        .map_err(|_| Status::TooManyRequests)
+}
 
-    // We should at least be able to write to an output file and return that... but this doesn't compile either.
-    // Seems the S3 `ResponseDataStream` type is not `Send`.
+pub(crate) fn rejection_status(rejection: MediaUploadRejection) -> Status {
+    match rejection {
+        MediaUploadRejection::PermissionDenied => Status::Forbidden,
+        MediaUploadRejection::FileTooLarge => Status::PayloadTooLarge,
+        MediaUploadRejection::QuotaExceeded => Status::InsufficientStorage,
+        MediaUploadRejection::UnsupportedContentType => Status::UnsupportedMediaType,
+    }
+}
 
-    // let mut async_output_file = tokio::fs::File::create(media.minio_path).await.expect("Unable to create file");
-    // while let Some(chunk) = _stream.bytes.next().await {
-    //     async_output_file.write_all(&chunk).await.map_err(|_| Status::NotFound)?;
-    // }
+/// The visibility for media being uploaded (with `POST /media` or `POST /media/uploads`) from
+/// its `Media-Visibility` header, if the user may upload media with it.
+pub(crate) fn media_upload_visibility(
+    user: &models::User,
+    header: Option<MediaVisibilityHeader<'_>>,
+) -> Result<Visibility, Status> {
+    let visibility = upload_visibility(header.map(|h| h.0))?;
+    validate_media_visibility(user, visibility).map_err(|_| Status::Forbidden)?;
+    Ok(visibility)
+}
 
-    // So far all I can get to work is this...
-    // Ok(ByteStream! { yield bytes::Bytes::from("test")})
+/// Parses a `Media-Visibility` header, defaulting to `GLOBAL_PUBLIC`.
+pub(crate) fn upload_visibility(header: Option<&str>) -> Result<Visibility, Status> {
+    match header.map(|h| Visibility::from_str_name(h.trim())) {
+        None => Ok(Visibility::GlobalPublic),
+        Some(None) | Some(Some(Visibility::Unknown)) => Err(Status::BadRequest),
+        Some(Some(visibility)) => Ok(visibility),
+    }
 }
 
 /// Gets the user from a manual jonline_access_token, auth header, or cookies (in that priority order).
-fn get_media_user(
+/// Includes bots, with their permissions narrowed to their API token's scopes, so it's only for
+/// viewing media.
+pub(crate) fn get_media_user(
+    manual_authorization: Option<String>,
+    auth_header: Option<AuthHeader<'_>>,
+    cookies: &CookieJar<'_>,
+    state: &State<RocketState>,
+) -> Result<models::User, Status> {
+    get_media_auth_context(manual_authorization, auth_header, cookies, state)
+        .map(|context| context.user)
+}
+
+/// As `get_media_user`, but only for logged-in sessions, not bots' API tokens.
+pub(crate) fn get_media_session_user(
     manual_authorization: Option<String>,
     auth_header: Option<AuthHeader<'_>>,
     cookies: &CookieJar<'_>,
     state: &State<RocketState>,
 ) -> Result<models::User, Status> {
+    let context = get_media_auth_context(manual_authorization, auth_header, cookies, state)?;
+    context.validate_session().map_err(|_| Status::Forbidden)?;
+    Ok(context.user)
+}
+
+/// As `get_media_user`, but bots' API tokens need a global `CREATE_MEDIA` scope.
+pub(crate) fn get_media_upload_user(
+    auth_header: Option<AuthHeader<'_>>,
+    cookies: &CookieJar<'_>,
+    state: &State<RocketState>,
+) -> Result<models::User, Status> {
+    let context = get_media_auth_context(None, auth_header, cookies, state)?;
+    context
+        .validate_api_token_scope(&[Permission::CreateMedia], None)
+        .map_err(|_| Status::Forbidden)?;
+    Ok(context.user)
+}
+
+fn get_media_auth_context(
+    manual_authorization: Option<String>,
+    auth_header: Option<AuthHeader<'_>>,
+    cookies: &CookieJar<'_>,
+    state: &State<RocketState>,
+) -> Result<AuthContext, Status> {
     let access_token = match manual_authorization {
         Some(access_token) => access_token,
         _ => match auth_header {
@@ -212,45 +410,7 @@ fn get_media_user(
         },
     };
 
-    let user = get_auth_user(access_token, &mut state.pool.get().unwrap());
-    match user {
-        Ok(user) => Ok(user),
-        Err(status) => Err(status),
-    }
-}
-
-fn get_auth_user(
-    access_token: String,
-    conn: &mut PgPooledConnection,
-) -> Result<models::User, Status> {
-    let user_id = get_auth_user_id(access_token, conn);
-    let user: models::User = match user_id {
-        Err(status) => return Err(status),
-        Ok(user_id) => schema::users::table
-            .filter(users::id.eq(user_id))
-            .first::<models::User>(conn)
-            .unwrap(),
-    };
-    Ok(user)
-}
-
-fn get_auth_user_id(access_token: String, conn: &mut PgPooledConnection) -> Result<i64, Status> {
-    delete(
-        user_access_tokens::user_access_tokens
-            .filter(user_access_tokens::token.eq(access_token.to_owned()))
-            .filter(user_access_tokens::expires_at.lt(diesel::dsl::now)),
-    )
-    .execute(conn)
-    .unwrap_or(0);
-
-    let user_id: Result<i64, _> = schema::user_access_tokens::table
-        .inner_join(schema::user_refresh_tokens::table)
-        .select(user_refresh_tokens::user_id)
-        .filter(user_access_tokens::token.eq(access_token))
-        .first::<i64>(conn);
-
-    match user_id {
-        Ok(user_id) => Ok(user_id),
-        Err(_) => Err(Status::Unauthorized),
-    }
+    let mut conn = state.pool.get().map_err(|_| Status::InternalServerError)?;
+    crate::auth::authenticate_access_token(&access_token, &mut conn)
+        .map_err(|_| Status::Unauthorized)
 }
@@ -0,0 +1,150 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::env_var;
+use crate::media_store::MediaStore;
+
+/// Default for `MEDIA_CACHE_MAX_MB`.
+const DEFAULT_MEDIA_CACHE_MAX_MB: u64 = 1024;
+
+/// A bounded, least-recently-used disk cache of media objects from the `MediaStore`, used by
+/// `GET /media/<id>`. Entries are keyed by object path and version, so replaced objects
+/// (i.e. after processing) are simply cached anew while the stale copy ages out.
+pub struct MediaCache {
+    tempdir: Arc<tempfile::TempDir>,
+    max_bytes: u64,
+    state: Mutex<MediaCacheState>,
+}
+
+#[derive(Default)]
+struct MediaCacheState {
+    entries: HashMap<String, MediaCacheEntry>,
+    total_bytes: u64,
+    clock: u64,
+    /// Keys currently being downloaded, so concurrent misses don't all hit the store.
+    in_flight: HashSet<String>,
+}
+
+struct MediaCacheEntry {
+    size: u64,
+    last_used: u64,
+}
+
+impl MediaCache {
+    /// Creates a cache in `tempdir`, bounded by the `MEDIA_CACHE_MAX_MB` env var (default 1024).
+    pub fn new(tempdir: Arc<tempfile::TempDir>) -> MediaCache {
+        let max_mb = env_var("MEDIA_CACHE_MAX_MB")
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_MEDIA_CACHE_MAX_MB);
+        MediaCache {
+            tempdir,
+            max_bytes: max_mb * 1024 * 1024,
+            state: Mutex::new(MediaCacheState::default()),
+        }
+    }
+
+    /// A strong ETag for the given cache key.
+    pub fn etag(&self, key: &str) -> String {
+        format!("\"{}\"", &hash(key)[..32])
+    }
+
+    /// The cached file and its size, if present. Marks the entry as recently used.
+    pub fn get(&self, key: &str) -> Option<(PathBuf, u64)> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        let entry = state.entries.get_mut(key)?;
+        entry.last_used = clock;
+        Some((self.path(key), entry.size))
+    }
+
+    /// Downloads `minio_path` into the cache in the background, unless that's already happening.
+    pub fn populate(
+        self: Arc<Self>,
+        key: String,
+        minio_path: String,
+        media_store: Arc<dyn MediaStore>,
+    ) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.entries.contains_key(&key) || !state.in_flight.insert(key.to_owned()) {
+                return;
+            }
+        }
+        tokio::spawn(async move {
+            let result = self.download(&key, &minio_path, media_store.as_ref()).await;
+            self.state.lock().unwrap().in_flight.remove(&key);
+            if let Err(e) = result {
+                log::warn!("Failed to cache media {}: {:?}", minio_path, e);
+            }
+        });
+    }
+
+    async fn download(
+        &self,
+        key: &str,
+        minio_path: &str,
+        media_store: &dyn MediaStore,
+    ) -> anyhow::Result<()> {
+        let temp_path = self
+            .tempdir
+            .path()
+            .join(format!("download-{}", Uuid::new_v4()));
+        let mut file = tokio::fs::File::create(&temp_path).await?;
+        if let Err(e) = media_store.get_to_writer(minio_path, &mut file).await {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+        let size = tokio::fs::metadata(&temp_path).await?.len();
+        tokio::fs::rename(&temp_path, self.path(key)).await?;
+        self.insert(key, size);
+        Ok(())
+    }
+
+    fn insert(&self, key: &str, size: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        if let Some(previous) = state.entries.insert(
+            key.to_string(),
+            MediaCacheEntry {
+                size,
+                last_used: clock,
+            },
+        ) {
+            state.total_bytes -= previous.size;
+        }
+        state.total_bytes += size;
+
+        while state.total_bytes > self.max_bytes && state.entries.len() > 1 {
+            let oldest = state
+                .entries
+                .iter()
+                .filter(|(k, _)| k.as_str() != key)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(k, _)| k.to_owned());
+            let oldest = match oldest {
+                Some(oldest) => oldest,
+                None => break,
+            };
+            let entry = state.entries.remove(&oldest).unwrap();
+            state.total_bytes -= entry.size;
+            // Open handles (i.e. responses in progress) keep working after unlinking.
+            if let Err(e) = std::fs::remove_file(self.path(&oldest)) {
+                log::warn!("Failed to evict cached media {}: {:?}", oldest, e);
+            }
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.tempdir.path().join(format!("{}.mediafile", hash(key)))
+    }
+}
+
+fn hash(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
@@ -0,0 +1,239 @@
+use std::io::SeekFrom;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use rocket::http::{ContentType, Status};
+use rocket::response::{self, Responder, Response};
+use rocket::Request;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::StreamReader;
+
+use crate::media_store::{stream_range, MediaStore};
+
+/// Size of each ranged read when streaming straight from the `MediaStore`.
+const STORE_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// The byte range to serve for a request.
+#[derive(Debug, PartialEq)]
+pub enum ByteRange {
+    Full,
+    /// Inclusive start and end offsets.
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header against a representation of `length` bytes. Only single
+/// `bytes=` ranges are supported; anything else is served in full, as RFC 9110 allows.
+pub fn parse_range(header: Option<&str>, length: u64) -> ByteRange {
+    let spec = match header.and_then(|h| h.trim().strip_prefix("bytes=")) {
+        Some(spec) if !spec.contains(',') => spec.trim(),
+        _ => return ByteRange::Full,
+    };
+    let (start, end) = match spec.split_once('-') {
+        Some(parts) => parts,
+        None => return ByteRange::Full,
+    };
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=100-199
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(length.saturating_sub(1))),
+        // bytes=100-
+        (Ok(start), Err(_)) if end.is_empty() => (start, length.saturating_sub(1)),
+        // bytes=-500 (the last 500 bytes)
+        (Err(_), Ok(suffix)) if start.is_empty() && suffix > 0 => {
+            (length.saturating_sub(suffix), length.saturating_sub(1))
+        }
+        _ => return ByteRange::Full,
+    };
+    if length == 0 || range.0 >= length {
+        ByteRange::Unsatisfiable
+    } else {
+        ByteRange::Partial(range.0, range.1)
+    }
+}
+
+/// Whether an `If-None-Match` header matches `etag`.
+pub fn etag_matches(if_none_match: Option<&str>, etag: &str) -> bool {
+    match if_none_match {
+        Some(header) => header
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag),
+        None => false,
+    }
+}
+
+/// A streamed media response supporting `206 Partial Content`, `304 Not Modified`
+/// and `416 Range Not Satisfiable`.
+pub struct MediaStream {
+    status: Status,
+    content_type: ContentType,
+    etag: String,
+    total_length: u64,
+    range: Option<(u64, u64)>,
+    body: Option<Pin<Box<dyn AsyncRead + Send>>>,
+}
+
+impl MediaStream {
+    pub fn not_modified(content_type: ContentType, etag: String) -> MediaStream {
+        MediaStream {
+            status: Status::NotModified,
+            content_type,
+            etag,
+            total_length: 0,
+            range: None,
+            body: None,
+        }
+    }
+
+    /// Streams (part of) a file from the local media cache.
+    pub async fn from_file(
+        path: &Path,
+        total_length: u64,
+        range: ByteRange,
+        content_type: ContentType,
+        etag: String,
+    ) -> std::io::Result<MediaStream> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let (status, range, body) = match range {
+            ByteRange::Full => (
+                Status::Ok,
+                None,
+                Some(Box::pin(file) as Pin<Box<dyn AsyncRead + Send>>),
+            ),
+            ByteRange::Partial(start, end) => {
+                file.seek(SeekFrom::Start(start)).await?;
+                let body = Box::pin(file.take(end - start + 1)) as Pin<Box<dyn AsyncRead + Send>>;
+                (Status::PartialContent, Some((start, end)), Some(body))
+            }
+            ByteRange::Unsatisfiable => (Status::RangeNotSatisfiable, None, None),
+        };
+        Ok(MediaStream {
+            status,
+            content_type,
+            etag,
+            total_length,
+            range,
+            body,
+        })
+    }
+
+    /// Streams (part of) an object directly from the `MediaStore`, in ranged chunks.
+    pub async fn from_store(
+        media_store: Arc<dyn MediaStore>,
+        minio_path: String,
+        range: Option<&str>,
+        content_type: ContentType,
+        etag: String,
+    ) -> anyhow::Result<MediaStream> {
+        let total_length = media_store.size(&minio_path).await?;
+        let (status, (start, end)) = match parse_range(range, total_length) {
+            ByteRange::Full => (Status::Ok, (0, total_length.saturating_sub(1))),
+            ByteRange::Partial(start, end) => (Status::PartialContent, (start, end)),
+            ByteRange::Unsatisfiable => {
+                return Ok(MediaStream {
+                    status: Status::RangeNotSatisfiable,
+                    content_type,
+                    etag,
+                    total_length,
+                    range: None,
+                    body: None,
+                })
+            }
+        };
+        let body = match total_length {
+            0 => Box::pin(tokio::io::empty()) as Pin<Box<dyn AsyncRead + Send>>,
+            _ => Box::pin(StreamReader::new(Box::pin(stream_range(
+                media_store,
+                minio_path,
+                start,
+                end,
+                STORE_CHUNK_SIZE,
+            )))),
+        };
+        Ok(MediaStream {
+            status,
+            content_type,
+            etag,
+            total_length,
+            range: (status == Status::PartialContent).then_some((start, end)),
+            body: Some(body),
+        })
+    }
+}
+
+impl<'r> Responder<'r, 'static> for MediaStream {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .status(self.status)
+            .header(self.content_type)
+            .raw_header("ETag", self.etag)
+            .raw_header("Accept-Ranges", "bytes");
+        // `Status` can't be matched structurally, hence the guards.
+        match self.range {
+            Some((start, end)) if self.status == Status::PartialContent => {
+                response.raw_header(
+                    "Content-Range",
+                    format!("bytes {}-{}/{}", start, end, self.total_length),
+                );
+            }
+            _ if self.status == Status::RangeNotSatisfiable => {
+                response.raw_header("Content-Range", format!("bytes */{}", self.total_length));
+            }
+            _ => {}
+        }
+        if let Some(body) = self.body {
+            response.streamed_body(body);
+        }
+        response.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_parse() {
+        assert_eq!(parse_range(None, 1000), ByteRange::Full);
+        assert_eq!(
+            parse_range(Some("bytes=0-99"), 1000),
+            ByteRange::Partial(0, 99)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=900-"), 1000),
+            ByteRange::Partial(900, 999)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=-100"), 1000),
+            ByteRange::Partial(900, 999)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=-5000"), 1000),
+            ByteRange::Partial(0, 999)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=500-5000"), 1000),
+            ByteRange::Partial(500, 999)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=1000-"), 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse_range(Some("bytes=0-"), 0), ByteRange::Unsatisfiable);
+        // Unsupported or malformed ranges are ignored.
+        assert_eq!(parse_range(Some("bytes=0-1,5-6"), 1000), ByteRange::Full);
+        assert_eq!(parse_range(Some("bytes=5-1"), 1000), ByteRange::Full);
+        assert_eq!(parse_range(Some("items=0-1"), 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn etags_match() {
+        assert!(etag_matches(Some("\"abc\""), "\"abc\""));
+        assert!(etag_matches(Some("\"xyz\", W/\"abc\""), "\"abc\""));
+        assert!(etag_matches(Some("*"), "\"abc\""));
+        assert!(!etag_matches(Some("\"xyz\""), "\"abc\""));
+        assert!(!etag_matches(None, "\"abc\""));
+    }
+}
@@ -0,0 +1,454 @@
+use std::time::{Duration, SystemTime};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use diesel::*;
+use rocket::http::{ContentType, CookieJar, Status};
+use rocket::{data::ToByteUnit, routes, Data, Route, State};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::media::{
+    get_media_upload_user, limit_media_upload_rate, media_limits_and_usage,
+    media_upload_visibility, rejection_status,
+};
+use crate::db_connection::PgPooledConnection;
+use crate::env_var;
+use crate::logic::*;
+use crate::marshaling::*;
+use crate::media_processing::{adopt_blob_processing, hash_object, reference_blob};
+use crate::models;
+use crate::schema::{media, media_upload_parts, media_uploads};
+use crate::web::headers::{
+    AuthHeader, ContentTypeHeader, FilenameHeader, MediaVisibilityHeader, UploadChecksumHeader,
+};
+use crate::web::secure_media::SecureMediaAccess;
+use crate::web::RocketState;
+
+lazy_static! {
+    pub static ref MEDIA_UPLOAD_ENDPOINTS: Vec<Route> = routes![
+        create_media_upload_options,
+        create_media_upload,
+        media_upload_options,
+        media_upload_status,
+        append_media_upload,
+        abort_media_upload,
+        finalize_media_upload_options,
+        finalize_media_upload,
+    ];
+}
+
+/// S3 rejects multipart uploads whose parts (other than the last) are smaller than this.
+pub const MIN_UPLOAD_PART_SIZE: u64 = 5 * 1024 * 1024;
+/// Largest chunk accepted by a single `PUT /media/uploads/<id>?part=<n>`.
+pub const MAX_UPLOAD_PART_SIZE: u64 = 64 * 1024 * 1024;
+/// S3's limit on the number of parts in a multipart upload.
+const MAX_UPLOAD_PARTS: i32 = 10000;
+/// Default for `MEDIA_UPLOAD_EXPIRY_HOURS`.
+const DEFAULT_UPLOAD_EXPIRY_HOURS: u64 = 24;
+/// Status returned when a chunk doesn't match its `Upload-Checksum` (as in tus).
+const CHECKSUM_MISMATCH: Status = Status::new(460);
+
+/// How long an upload session lives after its last chunk. Abandoned sessions are aborted
+/// by `media_processing::start_media_upload_expiry_worker`.
+pub fn media_upload_expiry() -> Duration {
+    let hours = env_var("MEDIA_UPLOAD_EXPIRY_HOURS")
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_UPLOAD_EXPIRY_HOURS);
+    Duration::from_secs(hours * 3600)
+}
+
+/// Used to manage CORS for the resumable upload endpoints.
+#[rocket::options("/media/uploads")]
+pub async fn create_media_upload_options() -> &'static str {
+    return "";
+}
+
+/// Starts a resumable upload, returning its ID. Takes the same `Content-Type`, `Filename` and
+/// `Media-Visibility` headers as `POST /media`, describing the media being uploaded. Chunks are
+/// then uploaded with `PUT /media/uploads/<id>?part=<n>` (numbered from 1; all but the last must be at least 5 MiB),
+/// and the upload is completed with `POST /media/uploads/<id>/finalize`, which returns the Media ID.
+#[rocket::post("/media/uploads")]
+pub async fn create_media_upload(
+    cookies: &CookieJar<'_>,
+    state: &State<RocketState>,
+    auth_header: Option<AuthHeader<'_>>,
+    content_type_header: ContentTypeHeader<'_>,
+    filename_header: FilenameHeader<'_>,
+    visibility_header: Option<MediaVisibilityHeader<'_>>,
+) -> Result<String, Status> {
+    let user = get_media_upload_user(auth_header, cookies, state)?;
+    let visibility = media_upload_visibility(&user, visibility_header)?;
+    limit_media_upload_rate(&user)?;
+    let (limits, usage) = media_limits_and_usage(&user, state)?;
+    validate_media_upload_start(&user, &limits, &usage).map_err(rejection_status)?;
+    let minio_path = format!(
+        "user/{}-{}/{}-{}",
+        user.id.to_proto_id(),
+        user.username,
+        Uuid::new_v4(),
+        filename_header.0
+    );
+    let s3_upload_id = state
+        .media_store
+        .start_multipart(&minio_path, content_type_header.0)
+        .await
+        .map_err(|e| {
+            log::warn!("Failed to initiate multipart upload: {:?}", e);
+            Status::InternalServerError
+        })?;
+
+    let upload = insert_into(media_uploads::table)
+        .values(&models::NewMediaUpload {
+            user_id: user.id,
+            minio_path,
+            s3_upload_id,
+            content_type: content_type_header.0.to_string(),
+            name: Some(filename_header.0.to_string()),
+            expires_at: SystemTime::now() + media_upload_expiry(),
+            visibility: visibility.to_string_visibility(),
+        })
+        .get_result::<models::MediaUpload>(&mut state.pool.get().unwrap())
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(upload.id.to_proto_id())
+}
+
+/// Used to manage CORS for the resumable upload endpoints.
+#[rocket::options("/media/uploads/<_id>")]
+pub async fn media_upload_options(_id: &str) -> &'static str {
+    return "";
+}
+
+/// Describes the parts received so far, so clients can resume after a dropped connection.
+#[rocket::get("/media/uploads/<id>")]
+pub async fn media_upload_status(
+    id: &str,
+    cookies: &CookieJar<'_>,
+    state: &State<RocketState>,
+    auth_header: Option<AuthHeader<'_>>,
+    _secure_media: SecureMediaAccess,
+) -> Result<(ContentType, String), Status> {
+    let user = get_media_upload_user(auth_header, cookies, state)?;
+    let mut conn = state.pool.get().unwrap();
+    let upload = load_upload(id, &user, &mut conn)?;
+    let parts = load_parts(upload.id, &mut conn)?;
+
+    let body = json!({
+        "id": upload.id.to_proto_id(),
+        "contentType": upload.content_type,
+        "name": upload.name,
+        "visibility": upload.visibility,
+        "size": parts.iter().map(|p| p.size).sum::<i64>(),
+        "expiresAt": upload.expires_at.to_rfc3339(),
+        "parts": parts.iter().map(|p| json!({
+            "partNumber": p.part_number,
+            "size": p.size,
+            "sha256": p.sha256,
+        })).collect::<Vec<_>>(),
+    });
+    Ok((ContentType::JSON, body.to_string()))
+}
+
+/// Uploads (or re-uploads) part `part` of an upload. If an `Upload-Checksum: sha256 <base64>`
+/// header is given, a chunk that doesn't match it is rejected with status 460. The content type
+/// (and the user's `MediaLimits`) are checked against part 1, and sizes against every part.
+/// Returns the hex SHA-256 of the stored chunk.
+#[rocket::put("/media/uploads/<id>?<part>", data = "<chunk>")]
+pub async fn append_media_upload(
+    id: &str,
+    part: i32,
+    chunk: Data<'_>,
+    cookies: &CookieJar<'_>,
+    state: &State<RocketState>,
+    auth_header: Option<AuthHeader<'_>>,
+    checksum_header: Option<UploadChecksumHeader<'_>>,
+) -> Result<String, Status> {
+    if part < 1 || part > MAX_UPLOAD_PARTS {
+        return Err(Status::BadRequest);
+    }
+    let user = get_media_upload_user(auth_header, cookies, state)?;
+    let upload = load_upload(id, &user, &mut state.pool.get().unwrap())?;
+
+    let chunk = chunk
+        .open(MAX_UPLOAD_PART_SIZE.bytes())
+        .into_bytes()
+        .await
+        .map_err(|_| Status::InternalServerError)?;
+    if !chunk.is_complete() {
+        return Err(Status::PayloadTooLarge);
+    }
+    let chunk = chunk.into_inner();
+    if chunk.is_empty() {
+        return Err(Status::BadRequest);
+    }
+
+    let digest = Sha256::digest(&chunk);
+    if let Some(checksum) = checksum_header {
+        match checksum.0.trim().split_once(' ') {
+            Some(("sha256", expected)) => {
+                if BASE64.decode(expected.trim()).ok().as_deref() != Some(digest.as_slice()) {
+                    return Err(CHECKSUM_MISMATCH);
+                }
+            }
+            _ => return Err(Status::BadRequest),
+        }
+    }
+    let sha256 = format!("{:x}", digest);
+
+    let (limits, usage) = media_limits_and_usage(&user, state)?;
+    let content_type = match part {
+        1 => validate_media_content_type(&chunk, &upload.content_type, &limits)
+            .map_err(rejection_status)?,
+        _ => upload.content_type.to_owned(),
+    };
+    let other_parts_size: i64 = load_parts(upload.id, &mut state.pool.get().unwrap())?
+        .iter()
+        .filter(|p| p.part_number != part)
+        .map(|p| p.size)
+        .sum();
+    validate_media_upload_size(other_parts_size as u64 + chunk.len() as u64, &limits, &usage)
+        .map_err(rejection_status)?;
+
+    let size = chunk.len() as i64;
+    let etag = state
+        .media_store
+        .put_part(
+            &upload.minio_path,
+            &upload.s3_upload_id,
+            part as u32,
+            chunk,
+            &upload.content_type,
+        )
+        .await
+        .map_err(|e| {
+            log::warn!("Failed to upload part {} of upload {}: {:?}", part, upload.id, e);
+            Status::InternalServerError
+        })?;
+
+    let mut conn = state.pool.get().unwrap();
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        delete(
+            media_upload_parts::table
+                .filter(media_upload_parts::media_upload_id.eq(upload.id))
+                .filter(media_upload_parts::part_number.eq(part)),
+        )
+        .execute(conn)?;
+        insert_into(media_upload_parts::table)
+            .values(&models::NewMediaUploadPart {
+                media_upload_id: upload.id,
+                part_number: part,
+                etag,
+                sha256: sha256.to_owned(),
+                size,
+            })
+            .execute(conn)?;
+        update(media_uploads::table.find(upload.id))
+            .set((
+                media_uploads::content_type.eq(content_type),
+                media_uploads::expires_at.eq(SystemTime::now() + media_upload_expiry()),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })
+    .map_err(|_| Status::InternalServerError)?;
+
+    Ok(sha256)
+}
+
+/// Abandons an upload, discarding any uploaded parts.
+#[rocket::delete("/media/uploads/<id>")]
+pub async fn abort_media_upload(
+    id: &str,
+    cookies: &CookieJar<'_>,
+    state: &State<RocketState>,
+    auth_header: Option<AuthHeader<'_>>,
+) -> Result<(), Status> {
+    let user = get_media_upload_user(auth_header, cookies, state)?;
+    let upload = load_upload(id, &user, &mut state.pool.get().unwrap())?;
+    state
+        .media_store
+        .abort_multipart(&upload.minio_path, &upload.s3_upload_id)
+        .await
+        .map_err(|e| {
+            log::warn!("Failed to abort upload {}: {:?}", upload.id, e);
+            Status::InternalServerError
+        })?;
+    delete(media_uploads::table.find(upload.id))
+        .execute(&mut state.pool.get().unwrap())
+        .map_err(|_| Status::InternalServerError)?;
+    Ok(())
+}
+
+/// Used to manage CORS for the resumable upload endpoints.
+#[rocket::options("/media/uploads/<_id>/finalize")]
+pub async fn finalize_media_upload_options(_id: &str) -> &'static str {
+    return "";
+}
+
+/// Completes an upload and creates its Media, returning the Media ID. Parts must be numbered
+/// contiguously from 1, and all but the last must be at least 5 MiB.
+#[rocket::post("/media/uploads/<id>/finalize")]
+pub async fn finalize_media_upload(
+    id: &str,
+    cookies: &CookieJar<'_>,
+    state: &State<RocketState>,
+    auth_header: Option<AuthHeader<'_>>,
+) -> Result<String, Status> {
+    let user = get_media_upload_user(auth_header, cookies, state)?;
+    let upload = load_upload(id, &user, &mut state.pool.get().unwrap())?;
+    let parts = load_parts(upload.id, &mut state.pool.get().unwrap())?;
+
+    if !parts_are_complete(&parts) {
+        return Err(Status::BadRequest);
+    }
+    let size = parts.iter().map(|p| p.size).sum::<i64>();
+    let (limits, usage) = media_limits_and_usage(&user, state)?;
+    validate_media_upload_size(size as u64, &limits, &usage).map_err(rejection_status)?;
+
+    state
+        .media_store
+        .complete_multipart(
+            &upload.minio_path,
+            &upload.s3_upload_id,
+            parts
+                .iter()
+                .map(|p| (p.part_number as u32, p.etag.to_owned()))
+                .collect(),
+        )
+        .await
+        .map_err(|e| {
+            log::warn!("Failed to complete upload {}: {:?}", upload.id, e);
+            Status::InternalServerError
+        })?;
+
+    // Multipart uploads can't be stored by content address up front, so hash the completed
+    // object and drop it in favor of an identical blob, if there is one (see `reference_blob`).
+    let sha256 = hash_object(state.media_store.as_ref(), &upload.minio_path, size as u64)
+        .await
+        .map_err(|e| {
+            log::warn!("Failed to hash upload {}: {:?}", upload.id, e);
+            Status::InternalServerError
+        })?;
+    let mut conn = state.pool.get().unwrap();
+    let media = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            let blob = reference_blob(&sha256, &upload.minio_path, size, conn)?;
+            let media = insert_into(media::table)
+                .values(&models::NewMedia {
+                    user_id: Some(user.id),
+                    minio_path: blob.minio_path,
+                    content_type: upload.content_type.to_owned(),
+                    name: upload.name.to_owned(),
+                    description: None,
+                    generated: false,
+                    visibility: upload.visibility.to_owned(),
+                    size,
+                    blob_id: Some(blob.id),
+                })
+                .get_result::<models::Media>(conn)?;
+            delete(media_uploads::table.find(upload.id)).execute(conn)?;
+            Ok(media)
+        })
+        .map_err(|_| Status::InternalServerError)?;
+    if media.minio_path != upload.minio_path {
+        // An identical blob already existed, so the upload's own object isn't needed.
+        if let Err(e) = state.media_store.delete(&upload.minio_path).await {
+            log::warn!("Failed to delete duplicate upload {}: {:?}", upload.id, e);
+        }
+        if let Some(blob_id) = media.blob_id {
+            if let Err(e) = adopt_blob_processing(media.id, blob_id, &mut conn) {
+                log::warn!("Failed to adopt processing for media {}: {:?}", media.id, e);
+            }
+        }
+    }
+
+    Ok(media.id.to_proto_id())
+}
+
+/// Whether `parts` (ordered by number) make up a whole upload: numbered contiguously from 1,
+/// with all but the last at least `MIN_UPLOAD_PART_SIZE`.
+fn parts_are_complete(parts: &[models::MediaUploadPart]) -> bool {
+    !parts.is_empty()
+        && parts
+            .iter()
+            .enumerate()
+            .all(|(i, p)| p.part_number == i as i32 + 1)
+        && parts[..parts.len() - 1]
+            .iter()
+            .all(|p| p.size as u64 >= MIN_UPLOAD_PART_SIZE)
+}
+
+/// Loads an unexpired upload belonging to `user`.
+fn load_upload(
+    id: &str,
+    user: &models::User,
+    conn: &mut PgPooledConnection,
+) -> Result<models::MediaUpload, Status> {
+    let id = id
+        .to_string()
+        .to_db_id_or_err("upload_id")
+        .map_err(|_| Status::BadRequest)?;
+    media_uploads::table
+        .filter(media_uploads::id.eq(id))
+        .filter(media_uploads::user_id.eq(user.id))
+        .filter(media_uploads::expires_at.gt(diesel::dsl::now))
+        .first::<models::MediaUpload>(conn)
+        .map_err(|_| Status::NotFound)
+}
+
+fn load_parts(
+    upload_id: i64,
+    conn: &mut PgPooledConnection,
+) -> Result<Vec<models::MediaUploadPart>, Status> {
+    media_upload_parts::table
+        .filter(media_upload_parts::media_upload_id.eq(upload_id))
+        .order(media_upload_parts::part_number.asc())
+        .load::<models::MediaUploadPart>(conn)
+        .map_err(|_| Status::InternalServerError)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::Visibility;
+    use crate::web::media::upload_visibility;
+
+    fn part(part_number: i32, size: u64) -> models::MediaUploadPart {
+        models::MediaUploadPart {
+            id: part_number as i64,
+            media_upload_id: 1,
+            part_number,
+            etag: format!("etag-{}", part_number),
+            sha256: String::new(),
+            size: size as i64,
+            created_at: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn complete_uploads_are_detected() {
+        assert!(parts_are_complete(&[part(1, 10)]));
+        assert!(parts_are_complete(&[
+            part(1, MIN_UPLOAD_PART_SIZE),
+            part(2, MIN_UPLOAD_PART_SIZE),
+            part(3, 1)
+        ]));
+
+        assert!(!parts_are_complete(&[]));
+        // Missing parts.
+        assert!(!parts_are_complete(&[part(2, 10)]));
+        assert!(!parts_are_complete(&[part(1, MIN_UPLOAD_PART_SIZE), part(3, 10)]));
+        // Undersized parts other than the last.
+        assert!(!parts_are_complete(&[part(1, MIN_UPLOAD_PART_SIZE - 1), part(2, 10)]));
+    }
+
+    #[test]
+    fn upload_visibility_comes_from_the_header() {
+        assert_eq!(upload_visibility(None).unwrap(), Visibility::GlobalPublic);
+        assert_eq!(upload_visibility(Some("PRIVATE")).unwrap(), Visibility::Private);
+        assert_eq!(upload_visibility(Some(" LIMITED ")).unwrap(), Visibility::Limited);
+        assert!(upload_visibility(Some("UNKNOWN")).is_err());
+        assert!(upload_visibility(Some("private")).is_err());
+    }
+}
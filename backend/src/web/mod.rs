@@ -12,6 +12,9 @@ pub use catchers::*;
 pub mod flutter_web;
 pub use flutter_web::*;
 
+pub mod open_graph;
+pub use open_graph::*;
+
 pub mod tamagui_web;
 pub use tamagui_web::*;
 
@@ -24,11 +27,34 @@ pub use secure_redirect::*;
 pub mod robots_sitemap;
 pub use robots_sitemap::*;
 
+pub mod secure_media;
+
+pub mod media_cache;
+pub use media_cache::*;
+
+pub mod media_streaming;
+pub use media_streaming::*;
+
 pub mod media;
 pub use media::*;
 
+pub mod media_uploads;
+pub use media_uploads::*;
+
 pub mod server_information;
 pub use server_information::*;
 
 pub mod external_cdn;
 pub use external_cdn::*;
+
+pub mod activitypub;
+pub use activitypub::*;
+
+pub mod feeds;
+pub use feeds::*;
+
+pub mod oembed;
+pub use oembed::*;
+
+pub mod oidc;
+pub use oidc::*;
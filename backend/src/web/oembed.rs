@@ -0,0 +1,334 @@
+use diesel::*;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Host;
+use rocket::http::{ContentType, Header, Status};
+use rocket::response::content::RawHtml;
+use rocket::{routes, Request, Response, Route, State};
+use rocket_cache_response::CacheResponse;
+use serde_json::json;
+
+use super::{
+    anonymous_can, configured_backend_domain, configured_frontend_domain, public_media_url,
+    public_post, RocketState,
+};
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::{Permission, ServerConfiguration, Visibility};
+use crate::rpcs::get_server_configuration;
+use crate::schema::{events, users};
+
+const DEFAULT_EMBED_WIDTH: u32 = 600;
+const DEFAULT_EMBED_HEIGHT: u32 = 400;
+
+lazy_static! {
+    pub static ref OEMBED_ENDPOINTS: Vec<Route> = routes![oembed, embed_post, embed_event];
+}
+
+/// Lets other sites `<iframe>` the `/embed/` cards. Rocket's default `Shield` sends
+/// `X-Frame-Options: SAMEORIGIN` on everything, so this drops it for those routes and allows any
+/// frame ancestor instead. Must be attached after `CORS`, which sets `Content-Security-Policy`.
+pub struct EmbedFraming;
+
+#[rocket::async_trait]
+impl Fairing for EmbedFraming {
+    fn info(&self) -> Info {
+        Info {
+            name: "Allow framing embed cards on other sites",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        if !request.uri().path().starts_with("/embed/") {
+            return;
+        }
+        response.remove_header("X-Frame-Options");
+        response.set_header(Header::new(
+            "Content-Security-Policy",
+            "object-src *; media-src *; frame-ancestors *;",
+        ));
+    }
+}
+
+/// A `GLOBAL_PUBLIC` post (or an event's post) resolved from an embeddable URL.
+struct EmbeddablePost {
+    configuration: ServerConfiguration,
+    post: models::Post,
+    author: Option<models::User>,
+    /// The canonical Tamagui URL for the post or event.
+    url: String,
+    /// The lightweight HTML card for the post or event.
+    embed_url: String,
+}
+
+/// [oEmbed](https://oembed.com) provider for post and event URLs. Only `GLOBAL_PUBLIC`
+/// posts and events anonymous users may view are embeddable; everything else is a 404,
+/// same as a missing entity.
+#[rocket::get("/oembed?<url>&<format>&<maxwidth>&<maxheight>")]
+async fn oembed(
+    url: &str,
+    format: Option<&str>,
+    maxwidth: Option<u32>,
+    maxheight: Option<u32>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<(ContentType, String), Status>> {
+    if format.map(|f| f != "json").unwrap_or(false) {
+        return CacheResponse::NoStore(Err(Status::NotImplemented));
+    }
+    let frontend_domain = configured_frontend_domain(state, host);
+    let backend_domain = configured_backend_domain(state, host);
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(_) => return CacheResponse::NoStore(Err(Status::BadRequest)),
+    };
+    match parsed.host_str() {
+        Some(h) if h == frontend_domain || h == backend_domain => {}
+        _ => return CacheResponse::NoStore(Err(Status::NotFound)),
+    }
+
+    let mut conn = state.pool.get().unwrap();
+    let embeddable = match embeddable_post(parsed.path(), &frontend_domain, &mut conn) {
+        Some(embeddable) => embeddable,
+        None => {
+            return CacheResponse::Public {
+                responder: Err(Status::NotFound),
+                max_age: 60,
+                must_revalidate: false,
+            }
+        }
+    };
+    let provider_name = embeddable
+        .configuration
+        .server_info
+        .as_ref()
+        .and_then(|i| i.name.to_owned())
+        .unwrap_or(frontend_domain.to_owned());
+    let width = maxwidth.unwrap_or(DEFAULT_EMBED_WIDTH).min(DEFAULT_EMBED_WIDTH);
+    let height = maxheight.unwrap_or(DEFAULT_EMBED_HEIGHT).min(DEFAULT_EMBED_HEIGHT);
+    let post = &embeddable.post;
+    let title = post_title(post);
+    let image = public_media_url(post.media.iter().cloned(), &backend_domain, &mut conn);
+
+    // Jonline doesn't store media dimensions, so media-only posts are embedded as cards like
+    // any other (with the image as a thumbnail) rather than as oEmbed photos. The sizes here
+    // are those of the iframe.
+    let mut response = json!({
+        "type": "rich",
+        "html": format!(
+            "<iframe src=\"{}\" width=\"{}\" height=\"{}\" frameborder=\"0\" style=\"border:0;max-width:100%;\" loading=\"lazy\"></iframe>",
+            embeddable.embed_url.to_escaped_html(),
+            width,
+            height
+        ),
+        "width": width,
+        "height": height,
+    });
+    let fields = response.as_object_mut().unwrap();
+    fields.insert("version".to_string(), json!("1.0"));
+    fields.insert("title".to_string(), json!(title));
+    fields.insert("provider_name".to_string(), json!(provider_name));
+    fields.insert("provider_url".to_string(), json!(format!("https://{}", frontend_domain)));
+    fields.insert("cache_age".to_string(), json!(3600));
+    if let Some(author) = &embeddable.author {
+        fields.insert("author_name".to_string(), json!(author.username));
+        fields.insert(
+            "author_url".to_string(),
+            json!(format!("https://{}/{}", frontend_domain, author.username)),
+        );
+    }
+    if let Some(image) = image {
+        fields.insert("thumbnail_url".to_string(), json!(image));
+    }
+
+    CacheResponse::Public {
+        responder: Ok((ContentType::JSON, response.to_string())),
+        max_age: 3600,
+        must_revalidate: false,
+    }
+}
+
+#[rocket::get("/embed/post/<id>")]
+async fn embed_post(
+    id: &str,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    embed_card(&format!("/post/{}", id), state, host)
+}
+
+#[rocket::get("/embed/event/<id>")]
+async fn embed_event(
+    id: &str,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    embed_card(&format!("/event/{}", id), state, host)
+}
+
+/// A self-contained HTML card suitable for an `<iframe>`.
+fn embed_card(
+    path: &str,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    let domain = configured_frontend_domain(state, host);
+    let backend_domain = configured_backend_domain(state, host);
+    let mut conn = state.pool.get().unwrap();
+    let embeddable = match embeddable_post(path, &domain, &mut conn) {
+        Some(embeddable) => embeddable,
+        None => {
+            return CacheResponse::Public {
+                responder: Err(Status::NotFound),
+                max_age: 60,
+                must_revalidate: false,
+            }
+        }
+    };
+    let post = &embeddable.post;
+    let image = public_media_url(post.media.iter().cloned(), &backend_domain, &mut conn)
+        .map(|image| format!("<img src=\"{}\" alt=\"\"/>", image.to_escaped_html()))
+        .unwrap_or_default();
+    let author = embeddable
+        .author
+        .as_ref()
+        .map(|a| format!("<div class=\"author\">{}</div>", a.username.to_escaped_html()))
+        .unwrap_or_default();
+    let html = format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\"/>
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\"/>
+<title>{title}</title>
+<style>
+body {{ margin: 0; font-family: -apple-system, BlinkMacSystemFont, sans-serif; }}
+.card {{ border: 1px solid #ccc; border-radius: 8px; padding: 12px; overflow: hidden; }}
+.author {{ color: #666; font-size: 0.9em; }}
+img {{ max-width: 100%; border-radius: 4px; }}
+a {{ color: inherit; }}
+</style>
+</head>
+<body>
+<div class=\"card\">
+{author}
+<h3><a href=\"{url}\" target=\"_blank\" rel=\"noopener\">{title}</a></h3>
+{image}
+{content}
+<a href=\"{url}\" target=\"_blank\" rel=\"noopener\">View on {domain}</a>
+</div>
+</body>
+</html>
+",
+        title = post_title(post).to_escaped_html(),
+        author = author,
+        url = embeddable.url.to_escaped_html(),
+        image = image,
+        content = post.content.as_deref().unwrap_or_default().to_html_paragraphs(),
+        domain = domain.to_escaped_html(),
+    );
+    CacheResponse::Public {
+        responder: Ok(RawHtml(html)),
+        max_age: 3600,
+        must_revalidate: false,
+    }
+}
+
+/// Resolves a Tamagui path (`/post/<id>`, `/g/<shortname>/p/<id>`, `/event/<id>`,
+/// `/g/<shortname>/e/<id>`, or their `/embed/` equivalents) to a `GLOBAL_PUBLIC` post, if the
+/// server's anonymous permissions allow viewing it.
+fn embeddable_post(
+    path: &str,
+    domain: &str,
+    conn: &mut PgPooledConnection,
+) -> Option<EmbeddablePost> {
+    let segments = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<&str>>();
+    let (is_event, id) = match segments.as_slice() {
+        ["post", id, ..] | ["g", _, "p", id, ..] | ["embed", "post", id] => (false, *id),
+        ["event", id, ..] | ["g", _, "e", id, ..] | ["embed", "event", id] => (true, *id),
+        _ => return None,
+    };
+    let configuration = get_server_configuration(conn).ok()?;
+    anonymous_can(
+        &configuration,
+        if is_event { Permission::ViewEvents } else { Permission::ViewPosts },
+    )?;
+    let db_id = id.to_string().to_db_id().ok()?;
+    let post_id = if is_event {
+        events::table
+            .select(events::post_id)
+            .filter(events::id.eq(db_id))
+            .first::<i64>(conn)
+            .ok()?
+    } else {
+        db_id
+    };
+    let post = public_post(post_id, conn)?;
+    let author = post.user_id.and_then(|user_id| {
+        users::table
+            .filter(users::id.eq(user_id))
+            .filter(users::visibility.eq(Visibility::GlobalPublic.as_str_name()))
+            .first::<models::User>(conn)
+            .ok()
+    });
+    let kind = if is_event { "event" } else { "post" };
+    Some(EmbeddablePost {
+        configuration,
+        post,
+        author,
+        url: format!("https://{}/{}/{}", domain, kind, id),
+        embed_url: format!("https://{}/embed/{}/{}", domain, kind, id),
+    })
+}
+
+fn post_title(post: &models::Post) -> String {
+    post.title
+        .to_owned()
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| {
+            let content = post.content.to_owned().unwrap_or_default();
+            content.chars().take(80).collect()
+        })
+}
+
+#[cfg(test)]
+// Rocket's route attributes emit `use`s of their handlers that the test routes don't need.
+#[allow(unused_imports)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+
+    #[rocket::get("/embed/post/<_id>")]
+    fn embed_stub(_id: &str) -> &'static str {
+        "embed"
+    }
+
+    #[rocket::get("/post/<_id>")]
+    fn page_stub(_id: &str) -> &'static str {
+        "page"
+    }
+
+    #[test]
+    fn embed_cards_can_be_framed_anywhere() {
+        let rocket = rocket::build()
+            .attach(crate::web::cors::CORS)
+            .attach(EmbedFraming)
+            .mount("/", routes![embed_stub, page_stub]);
+        let client = Client::untracked(rocket).unwrap();
+
+        let embed = client.get("/embed/post/1").dispatch();
+        assert_eq!(embed.headers().get_one("X-Frame-Options"), None);
+        assert!(embed
+            .headers()
+            .get_one("Content-Security-Policy")
+            .unwrap()
+            .contains("frame-ancestors *"));
+
+        let page = client.get("/post/1").dispatch();
+        assert_eq!(page.headers().get_one("X-Frame-Options"), Some("SAMEORIGIN"));
+    }
+}
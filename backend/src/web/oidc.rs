@@ -0,0 +1,210 @@
+use std::time::SystemTime;
+
+use rocket::http::uri::Host;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::response::Redirect;
+use rocket::{routes, Route, State};
+
+use super::media::get_media_session_user;
+use super::{configured_backend_domain, RocketState};
+use crate::logic::*;
+use crate::models;
+use crate::oidc::*;
+use crate::protos::OidcProvider;
+use crate::rpcs::get_server_configuration;
+use crate::web::headers::AuthHeader;
+
+lazy_static! {
+    pub static ref OIDC_ENDPOINTS: Vec<Route> = routes![oidc_login, oidc_callback];
+}
+
+/// Holds `oidc_state_hash` of the pending login's `state`.
+const OIDC_STATE_COOKIE: &str = "oidc_state";
+const OIDC_COOKIE_PATH: &str = "/auth/oidc";
+
+fn configured_provider(provider_id: &str, state: &State<RocketState>) -> Result<OidcProvider, Status> {
+    let configuration = get_server_configuration(&mut state.pool.get().unwrap())
+        .map_err(|_| Status::InternalServerError)?;
+    find_oidc_provider(&configuration, provider_id).ok_or(Status::NotFound)
+}
+
+/// Starts logging in with (or, with `link=true`, linking the current user to) an `OidcProvider`,
+/// redirecting to its authorization endpoint.
+#[rocket::get("/auth/oidc/<provider_id>/login?<redirect>&<link>&<authorization>")]
+async fn oidc_login(
+    provider_id: &str,
+    redirect: Option<String>,
+    link: Option<bool>,
+    authorization: Option<String>,
+    auth_header: Option<AuthHeader<'_>>,
+    cookies: &CookieJar<'_>,
+    host: &Host<'_>,
+    state: &State<RocketState>,
+) -> Result<Redirect, Status> {
+    let provider = configured_provider(provider_id, state)?;
+    let redirect_path = sanitize_redirect_path(redirect.as_deref());
+    let link_user_id = match link {
+        Some(true) => Some(get_media_session_user(authorization, auth_header, cookies, state)?.id),
+        _ => None,
+    };
+
+    let metadata = match discover_oidc_provider(&provider.issuer, None).await {
+        Ok((metadata, _)) => metadata,
+        Err(e) => {
+            log::error!("Error discovering OIDC provider {}: {:?}", provider.id, e);
+            return Ok(Redirect::to(with_query_param(
+                &redirect_path,
+                "oidc_error",
+                "provider_unavailable",
+            )));
+        }
+    };
+    let redirect_uri = oidc_redirect_uri(&configured_backend_domain(state, host), &provider.id);
+    let code_verifier = generate_oidc_token();
+    let login_state = models::NewOidcLoginState {
+        state: generate_oidc_token(),
+        provider_id: provider.id.to_owned(),
+        code_verifier: code_verifier.to_owned(),
+        nonce: generate_oidc_token(),
+        redirect_path,
+        link_user_id,
+        expires_at: SystemTime::now() + OIDC_LOGIN_STATE_TTL,
+    };
+    let url = oidc_authorization_url(
+        &provider,
+        &metadata,
+        &redirect_uri,
+        &login_state.state,
+        &login_state.nonce,
+        &oidc_code_challenge(&code_verifier),
+    )
+    .map_err(|e| {
+        log::error!("Invalid OIDC authorization endpoint for {}: {:?}", provider.id, e);
+        Status::InternalServerError
+    })?;
+    let state_hash = oidc_state_hash(&login_state.state);
+    create_oidc_login_state(login_state, &mut state.pool.get().unwrap()).map_err(|e| {
+        log::error!("Error storing OIDC login state: {:?}", e);
+        Status::InternalServerError
+    })?;
+    cookies.add(
+        Cookie::build(OIDC_STATE_COOKIE, state_hash)
+            .path(OIDC_COOKIE_PATH)
+            .http_only(true)
+            .secure(true)
+            .same_site(SameSite::Lax)
+            .max_age(rocket::time::Duration::seconds(OIDC_LOGIN_STATE_TTL.as_secs() as i64))
+            .finish(),
+    );
+    Ok(Redirect::to(url.to_string()))
+}
+
+/// Where providers return users after logging in. Redirects to the login's `redirect` path with an
+/// `oidc_code` for `CompleteOidcLogin` (or `oidc_linked` when linking), or an `oidc_error`.
+/// The `state` must match the `oidc_state` cookie set by `oidc_login` in the same browser.
+#[rocket::get("/auth/oidc/<provider_id>/callback?<code>&<state>&<error>")]
+async fn oidc_callback(
+    provider_id: &str,
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    cookies: &CookieJar<'_>,
+    host: &Host<'_>,
+    rocket_state: &State<RocketState>,
+) -> Result<Redirect, Status> {
+    let state_cookie = cookies.get(OIDC_STATE_COOKIE).map(|c| c.value().to_string());
+    cookies.remove(Cookie::build(OIDC_STATE_COOKIE, "").path(OIDC_COOKIE_PATH).finish());
+    let login_state = match state {
+        Some(state) if state_cookie.as_deref() == Some(oidc_state_hash(&state).as_str()) => {
+            take_oidc_login_state(&state, provider_id, &mut rocket_state.pool.get().unwrap())
+                .map_err(|e| {
+                    log::error!("Error loading OIDC login state: {:?}", e);
+                    Status::InternalServerError
+                })?
+        }
+        Some(_) => {
+            log::warn!("OIDC callback for {} without a matching state cookie", provider_id);
+            None
+        }
+        None => None,
+    }
+    .ok_or(Status::BadRequest)?;
+    let redirect_error = |error: &str| -> Result<Redirect, Status> {
+        Ok(Redirect::to(with_query_param(&login_state.redirect_path, "oidc_error", error)))
+    };
+
+    let code = match (code, error) {
+        (_, Some(error)) => return redirect_error(&error),
+        (Some(code), None) => code,
+        (None, None) => return redirect_error("missing_code"),
+    };
+    let provider = match configured_provider(provider_id, rocket_state) {
+        Ok(provider) => provider,
+        Err(_) => return redirect_error("provider_not_found"),
+    };
+    let redirect_uri = oidc_redirect_uri(&configured_backend_domain(rocket_state, host), &provider.id);
+    let claims = match verify_callback(&provider, &redirect_uri, &code, &login_state).await {
+        Ok(claims) => claims,
+        Err(e) => {
+            log::warn!("OIDC login with {} failed: {:?}", provider.id, e);
+            return redirect_error("oidc_login_failed");
+        }
+    };
+
+    let mut conn = rocket_state.pool.get().unwrap();
+    let configuration =
+        get_server_configuration(&mut conn).map_err(|_| Status::InternalServerError)?;
+    let user = match resolve_oidc_user(
+        &provider,
+        &claims,
+        &configuration,
+        login_state.link_user_id,
+        &mut conn,
+    ) {
+        Ok(user) => user,
+        Err(status) => return redirect_error(status.message()),
+    };
+    if login_state.link_user_id.is_some() {
+        return Ok(Redirect::to(with_query_param(
+            &login_state.redirect_path,
+            "oidc_linked",
+            &provider.id,
+        )));
+    }
+    match create_oidc_login_completion(user.id, &mut conn) {
+        Ok(oidc_code) => Ok(Redirect::to(with_query_param(
+            &login_state.redirect_path,
+            "oidc_code",
+            &oidc_code,
+        ))),
+        Err(e) => {
+            log::error!("Error storing OIDC login completion: {:?}", e);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// Exchanges the callback's code and verifies the resulting ID token, refetching the provider's
+/// keys (at most every `JWKS_REFRESH_INTERVAL`) if it's signed with one we don't know.
+async fn verify_callback(
+    provider: &OidcProvider,
+    redirect_uri: &str,
+    code: &str,
+    login_state: &models::OidcLoginState,
+) -> anyhow::Result<IdTokenClaims> {
+    let (metadata, jwks) = discover_oidc_provider(&provider.issuer, None).await?;
+    let id_token =
+        exchange_oidc_code(provider, &metadata, redirect_uri, code, &login_state.code_verifier).await?;
+    let jwks = match id_token_key_id(&id_token) {
+        kid if jwks_has_key(&jwks, kid.as_deref()) => jwks,
+        _ => discover_oidc_provider(&provider.issuer, Some(JWKS_REFRESH_INTERVAL)).await?.1,
+    };
+    verify_id_token(
+        &id_token,
+        &jwks,
+        &metadata.issuer,
+        &provider.client_id,
+        &login_state.nonce,
+        SystemTime::now(),
+    )
+}
@@ -0,0 +1,267 @@
+use diesel::*;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+use super::media::media_file_url;
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::models;
+use crate::protos::{Permission, ServerConfiguration, Visibility};
+use crate::rpcs::get_server_configuration;
+use crate::rpcs::validations::PASSING_MODERATIONS;
+use crate::schema::{events, groups, media, posts, users};
+
+const DESCRIPTION_LENGTH: usize = 200;
+
+/// Open Graph/Twitter Card metadata for a single entity, injected into the
+/// otherwise-static Tamagui HTML so link previews show something useful.
+pub struct OpenGraphMeta {
+    pub title: String,
+    pub description: String,
+    pub url: String,
+    pub image: Option<String>,
+    pub og_type: &'static str,
+    pub site_name: Option<String>,
+    /// The `/oembed` discovery URL, for posts and events.
+    pub oembed_url: Option<String>,
+}
+
+/// Which entity a Tamagui route points at.
+pub enum OpenGraphTarget {
+    Post(String),
+    Event(String),
+    Group(String),
+    User(String),
+    Username(String),
+}
+
+/// Builds metadata for `target` if it is visible to anonymous users, i.e.
+/// the entity is `GLOBAL_PUBLIC`, passes moderation, and the server's
+/// anonymous permissions allow viewing it. Pages are linked on `domain`,
+/// and images on `backend_domain`, which serves media.
+pub fn open_graph_meta(
+    target: OpenGraphTarget,
+    path: &str,
+    domain: &str,
+    backend_domain: &str,
+    conn: &mut PgPooledConnection,
+) -> Option<OpenGraphMeta> {
+    let configuration = get_server_configuration(conn).ok()?;
+    let url = format!("https://{}{}", domain, path);
+    let site_name = configuration
+        .server_info
+        .as_ref()
+        .and_then(|i| i.name.to_owned());
+    let oembed_url = match target {
+        OpenGraphTarget::Post(_) | OpenGraphTarget::Event(_) => Some(format!(
+            "https://{}/oembed?url={}&format=json",
+            domain,
+            utf8_percent_encode(&url, NON_ALPHANUMERIC)
+        )),
+        _ => None,
+    };
+    let meta = match target {
+        OpenGraphTarget::Post(id) => {
+            anonymous_can(&configuration, Permission::ViewPosts)?;
+            let post = public_post(id.to_db_id().ok()?, conn)?;
+            post_meta(&post, url, backend_domain, conn)
+        }
+        OpenGraphTarget::Event(id) => {
+            anonymous_can(&configuration, Permission::ViewEvents)?;
+            let post_id = events::table
+                .select(events::post_id)
+                .filter(events::id.eq(id.to_db_id().ok()?))
+                .first::<i64>(conn)
+                .ok()?;
+            let post = public_post(post_id, conn)?;
+            post_meta(&post, url, backend_domain, conn)
+        }
+        OpenGraphTarget::Group(shortname) => {
+            anonymous_can(&configuration, Permission::ViewGroups)?;
+            let group = groups::table
+                .filter(models::lower(groups::shortname).eq(models::lower(shortname)))
+                .filter(groups::visibility.eq(Visibility::GlobalPublic.as_str_name()))
+                .filter(groups::moderation.eq_any(PASSING_MODERATIONS))
+                .first::<models::Group>(conn)
+                .ok()?;
+            OpenGraphMeta {
+                title: group.name.to_owned(),
+                description: truncate(&group.description),
+                url,
+                image: public_media_url(group.avatar_media_id.into_iter(), backend_domain, conn),
+                og_type: "website",
+                site_name: None,
+                oembed_url: None,
+            }
+        }
+        OpenGraphTarget::User(id) => {
+            anonymous_can(&configuration, Permission::ViewUsers)?;
+            let user = public_users()
+                .filter(users::id.eq(id.to_db_id().ok()?))
+                .first::<models::User>(conn)
+                .ok()?;
+            user_meta(&user, url, backend_domain, conn)
+        }
+        OpenGraphTarget::Username(username) => {
+            anonymous_can(&configuration, Permission::ViewUsers)?;
+            let user = public_users()
+                .filter(users::username.eq(username))
+                .first::<models::User>(conn)
+                .ok()?;
+            user_meta(&user, url, backend_domain, conn)
+        }
+    };
+    Some(OpenGraphMeta {
+        site_name,
+        oembed_url,
+        ..meta
+    })
+}
+
+/// Inserts the meta tags just before `</head>`.
+pub fn inject_open_graph_meta(html: &str, meta: &OpenGraphMeta) -> String {
+    let mut tags = vec![
+        ("property", "og:type", meta.og_type.to_string()),
+        ("property", "og:title", meta.title.to_owned()),
+        ("property", "og:description", meta.description.to_owned()),
+        ("property", "og:url", meta.url.to_owned()),
+        (
+            "name",
+            "twitter:card",
+            match meta.image {
+                Some(_) => "summary_large_image",
+                None => "summary",
+            }
+            .to_string(),
+        ),
+        ("name", "twitter:title", meta.title.to_owned()),
+        ("name", "twitter:description", meta.description.to_owned()),
+    ];
+    if let Some(site_name) = &meta.site_name {
+        tags.push(("property", "og:site_name", site_name.to_owned()));
+    }
+    if let Some(image) = &meta.image {
+        tags.push(("property", "og:image", image.to_owned()));
+        tags.push(("name", "twitter:image", image.to_owned()));
+    }
+    let tags = tags
+        .iter()
+        .map(|(attribute, key, value)| {
+            format!(
+                "<meta {}=\"{}\" content=\"{}\"/>",
+                attribute,
+                key,
+                value.to_escaped_html()
+            )
+        })
+        .collect::<String>();
+    let tags = match &meta.oembed_url {
+        Some(oembed_url) => format!(
+            "{}<link rel=\"alternate\" type=\"application/json+oembed\" href=\"{}\" title=\"{}\"/>",
+            tags,
+            oembed_url.to_escaped_html(),
+            meta.title.to_escaped_html()
+        ),
+        None => tags,
+    };
+    match html.find("</head>") {
+        Some(index) => format!("{}{}{}", &html[..index], tags, &html[index..]),
+        None => html.to_string(),
+    }
+}
+
+pub fn anonymous_can(configuration: &ServerConfiguration, permission: Permission) -> Option<()> {
+    configuration
+        .anonymous_user_permissions
+        .contains(&(permission as i32))
+        .then_some(())
+}
+
+pub fn public_post(post_id: i64, conn: &mut PgPooledConnection) -> Option<models::Post> {
+    posts::table
+        .filter(posts::id.eq(post_id))
+        .filter(posts::visibility.eq(Visibility::GlobalPublic.as_str_name()))
+        .filter(posts::moderation.eq_any(PASSING_MODERATIONS))
+        .first::<models::Post>(conn)
+        .ok()
+}
+
+fn public_users<'a>() -> users::BoxedQuery<'a, diesel::pg::Pg> {
+    users::table
+        .filter(users::visibility.eq(Visibility::GlobalPublic.as_str_name()))
+        .filter(users::moderation.eq_any(PASSING_MODERATIONS))
+        .into_boxed()
+}
+
+fn post_meta(
+    post: &models::Post,
+    url: String,
+    backend_domain: &str,
+    conn: &mut PgPooledConnection,
+) -> OpenGraphMeta {
+    let content = post.content.to_owned().unwrap_or_default();
+    OpenGraphMeta {
+        title: post
+            .title
+            .to_owned()
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| truncate(&content)),
+        description: truncate(&content),
+        url,
+        // Generated link previews are stored in `post.media`, so this covers them too.
+        image: public_media_url(post.media.iter().cloned(), backend_domain, conn),
+        og_type: "article",
+        site_name: None,
+        oembed_url: None,
+    }
+}
+
+fn user_meta(
+    user: &models::User,
+    url: String,
+    backend_domain: &str,
+    conn: &mut PgPooledConnection,
+) -> OpenGraphMeta {
+    OpenGraphMeta {
+        title: if user.real_name.is_empty() {
+            user.username.to_owned()
+        } else {
+            format!("{} ({})", user.real_name, user.username)
+        },
+        description: truncate(&user.bio),
+        url,
+        image: public_media_url(user.avatar_media_id.into_iter(), backend_domain, conn),
+        og_type: "profile",
+        site_name: None,
+        oembed_url: None,
+    }
+}
+
+/// The URL of the first of `media_ids` that anonymous users can view, on the backend domain.
+pub fn public_media_url<I: Iterator<Item = i64>>(
+    media_ids: I,
+    backend_domain: &str,
+    conn: &mut PgPooledConnection,
+) -> Option<String> {
+    let media_ids = media_ids.collect::<Vec<i64>>();
+    let public_ids = media::table
+        .select(media::id)
+        .filter(media::id.eq_any(&media_ids))
+        .filter(media::visibility.eq(Visibility::GlobalPublic.as_str_name()))
+        .filter(media::moderation.eq_any(PASSING_MODERATIONS))
+        .load::<i64>(conn)
+        .ok()?;
+    media_ids
+        .iter()
+        .find(|id| public_ids.contains(id))
+        .map(|id| media_file_url(backend_domain, *id))
+}
+
+fn truncate(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if text.chars().count() <= DESCRIPTION_LENGTH {
+        return text;
+    }
+    let mut truncated = text.chars().take(DESCRIPTION_LENGTH - 1).collect::<String>();
+    truncated.push('…');
+    truncated
+}
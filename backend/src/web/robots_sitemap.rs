@@ -1,25 +1,48 @@
+use std::time::SystemTime;
+
+use diesel::*;
 use rocket::*;
 use rocket::http::uri::Host;
+use rocket::http::Status;
+use rocket::request::FromParam;
 
-use super::RocketState;
+use super::{configured_frontend_domain, RocketState};
 use rocket::response::content::{RawText, RawXml};
 
 use rocket_cache_response::CacheResponse;
 
+use crate::db_connection::PgPooledConnection;
+use crate::marshaling::*;
+use crate::protos::{PostContext, Visibility};
 use crate::rpcs::get_server_configuration;
+use crate::rpcs::validations::PASSING_MODERATIONS;
+use crate::schema::{events, groups, posts, users};
+
+/// The maximum number of URLs allowed in a single sitemap file.
+const SITEMAP_CHUNK_SIZE: i64 = 50_000;
+
+/// Top-level pages of the Tamagui web client, always listed in `sitemaps/pages.xml`.
+const STATIC_PAGES: [&str; 7] = [
+    "", "posts", "events", "people", "about", "about_jonline", "flutter",
+];
 
 lazy_static! {
-    pub static ref SEO_PAGES: Vec<Route> = routes![robots, sitemap];
+    pub static ref SEO_PAGES: Vec<Route> = routes![robots, sitemap, sitemap_chunk];
 }
 #[rocket::get("/robots.txt")]
 async fn robots(
     state: &State<RocketState>,
     host: &Host<'_>,
 ) -> CacheResponse<RawText<String>> {
-    let domain = host.domain();
+    let domain = configured_frontend_domain(state, host);
     let mut conn = state.pool.get().unwrap();
-    let _configuration = get_server_configuration(&mut conn).unwrap();
-    let response = RawText(
+    let configuration = get_server_configuration(&mut conn).unwrap();
+    let response = RawText(if configuration.disallow_search_indexing {
+        "User-agent: *
+Disallow: /
+"
+        .to_string()
+    } else {
         format!(
             "User-agent: *
 Allow: /
@@ -28,8 +51,7 @@ Sitemap: https://{}/sitemap.xml
 ",
             domain
         )
-        .to_string(),
-    );
+    });
 
     CacheResponse::Public {
         responder: response,
@@ -38,50 +60,268 @@ Sitemap: https://{}/sitemap.xml
     }
 }
 
+/// A sitemap index pointing to the static pages and to every chunk of
+/// `GLOBAL_PUBLIC` posts, events, groups and users.
 #[rocket::get("/sitemap.xml")]
 async fn sitemap(
     state: &State<RocketState>,
     host: &Host<'_>,
-) -> CacheResponse<RawXml<String>> {
-    let domain = host.domain();
+) -> CacheResponse<Result<RawXml<String>, Status>> {
+    let domain = configured_frontend_domain(state, host);
     let mut conn = state.pool.get().unwrap();
-    let _configuration = get_server_configuration(&mut conn).unwrap();
+    let configuration = get_server_configuration(&mut conn).unwrap();
+    if configuration.disallow_search_indexing {
+        return CacheResponse::Public {
+            responder: Err(Status::NotFound),
+            max_age: 3600,
+            must_revalidate: false,
+        };
+    }
 
-    let response = RawXml(
-        format!(
-            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
-<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\"> 
-    <url>
-        <loc>https://{}/</loc>
-    </url>
-    <url>
-        <loc>https://{}/posts</loc> 
-    </url>
-    <url>
-        <loc>https://{}/events</loc> 
-    </url>
-    <url>
-        <loc>https://{}/people</loc> 
-    </url>
-    <url>
-        <loc>https://{}/about</loc> 
-    </url>
+    let mut sitemaps = vec!["pages.xml".to_string()];
+    for kind in [
+        SitemapKind::Posts,
+        SitemapKind::Events,
+        SitemapKind::Groups,
+        SitemapKind::Users,
+    ] {
+        let count = kind.count(&mut conn);
+        let chunks = (count + SITEMAP_CHUNK_SIZE - 1) / SITEMAP_CHUNK_SIZE;
+        for index in 0..chunks {
+            sitemaps.push(format!("{}-{}.xml", kind.name(), index));
+        }
+    }
+    let entries = sitemaps
+        .iter()
+        .map(|name| {
+            format!(
+                "
+    <sitemap>
+        <loc>https://{}/sitemaps/{}</loc>
+    </sitemap>",
+                domain, name
+            )
+        })
+        .collect::<String>();
+
+    let response = RawXml(format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">{}
+</sitemapindex>
+",
+        entries
+    ));
+
+    CacheResponse::Public {
+        responder: Ok(response),
+        max_age: 3600,
+        must_revalidate: false,
+    }
+}
+
+#[rocket::get("/sitemaps/<chunk>")]
+async fn sitemap_chunk(
+    chunk: SitemapChunk,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawXml<String>, Status>> {
+    let domain = configured_frontend_domain(state, host);
+    let mut conn = state.pool.get().unwrap();
+    let configuration = get_server_configuration(&mut conn).unwrap();
+    if configuration.disallow_search_indexing {
+        return CacheResponse::Public {
+            responder: Err(Status::NotFound),
+            max_age: 3600,
+            must_revalidate: false,
+        };
+    }
+
+    let urls: Vec<(String, Option<SystemTime>)> = match chunk {
+        SitemapChunk::Pages => STATIC_PAGES
+            .iter()
+            .map(|page| (format!("https://{}/{}", domain, page), None))
+            .collect(),
+        SitemapChunk::Entities(kind, index) => kind
+            .load(index, &mut conn)
+            .into_iter()
+            .map(|(path, lastmod)| (format!("https://{}/{}", domain, path), Some(lastmod)))
+            .collect(),
+    };
+    if urls.is_empty() {
+        return CacheResponse::Public {
+            responder: Err(Status::NotFound),
+            max_age: 3600,
+            must_revalidate: false,
+        };
+    }
+    let entries = urls
+        .iter()
+        .map(|(loc, lastmod)| match lastmod {
+            Some(lastmod) => format!(
+                "
     <url>
-        <loc>https://{}/about_jonline</loc> 
-    </url>
+        <loc>{}</loc>
+        <lastmod>{}</lastmod>
+    </url>",
+                loc.to_escaped_html(),
+                lastmod.to_rfc3339()
+            ),
+            None => format!(
+                "
     <url>
-        <loc>https://{}/flutter</loc> 
-    </url>
+        <loc>{}</loc>
+    </url>",
+                loc.to_escaped_html()
+            ),
+        })
+        .collect::<String>();
+
+    let response = RawXml(format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">{}
 </urlset>
 ",
-            domain, domain, domain, domain, domain, domain, domain
-        )
-        .to_string(),
-    );
+        entries
+    ));
 
     CacheResponse::Public {
-        responder: response,
+        responder: Ok(response),
         max_age: 3600,
         must_revalidate: false,
     }
 }
+
+#[derive(Clone, Copy)]
+pub enum SitemapKind {
+    Posts,
+    Events,
+    Groups,
+    Users,
+}
+
+/// A single file under `/sitemaps/`, like `pages.xml` or `posts-0.xml`.
+pub enum SitemapChunk {
+    Pages,
+    Entities(SitemapKind, i64),
+}
+
+impl<'a> FromParam<'a> for SitemapChunk {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        if param == "pages.xml" {
+            return Ok(SitemapChunk::Pages);
+        }
+        let (kind, index) = param
+            .strip_suffix(".xml")
+            .and_then(|name| name.rsplit_once('-'))
+            .ok_or(param)?;
+        let kind = match kind {
+            "posts" => SitemapKind::Posts,
+            "events" => SitemapKind::Events,
+            "groups" => SitemapKind::Groups,
+            "users" => SitemapKind::Users,
+            _ => return Err(param),
+        };
+        let index = index.parse::<i64>().map_err(|_| param)?;
+        Ok(SitemapChunk::Entities(kind, index))
+    }
+}
+
+impl SitemapKind {
+    fn name(&self) -> &'static str {
+        match self {
+            SitemapKind::Posts => "posts",
+            SitemapKind::Events => "events",
+            SitemapKind::Groups => "groups",
+            SitemapKind::Users => "users",
+        }
+    }
+
+    fn count(&self, conn: &mut PgPooledConnection) -> i64 {
+        let public = Visibility::GlobalPublic.as_str_name();
+        let result = match self {
+            SitemapKind::Posts => posts::table
+                .filter(posts::visibility.eq(public))
+                .filter(posts::moderation.eq_any(PASSING_MODERATIONS))
+                .filter(posts::context.eq(PostContext::Post.as_str_name()))
+                .count()
+                .get_result::<i64>(conn),
+            SitemapKind::Events => events::table
+                .inner_join(posts::table.on(events::post_id.eq(posts::id)))
+                .filter(posts::visibility.eq(public))
+                .filter(posts::moderation.eq_any(PASSING_MODERATIONS))
+                .count()
+                .get_result::<i64>(conn),
+            SitemapKind::Groups => groups::table
+                .filter(groups::visibility.eq(public))
+                .filter(groups::moderation.eq_any(PASSING_MODERATIONS))
+                .count()
+                .get_result::<i64>(conn),
+            SitemapKind::Users => users::table
+                .filter(users::visibility.eq(public))
+                .filter(users::moderation.eq_any(PASSING_MODERATIONS))
+                .count()
+                .get_result::<i64>(conn),
+        };
+        result.unwrap_or(0)
+    }
+
+    /// Loads the (path, lastmod) pairs for the given chunk, ordered by ID so chunks are stable.
+    fn load(&self, index: i64, conn: &mut PgPooledConnection) -> Vec<(String, SystemTime)> {
+        let public = Visibility::GlobalPublic.as_str_name();
+        let offset = index * SITEMAP_CHUNK_SIZE;
+        match self {
+            SitemapKind::Posts => posts::table
+                .select((posts::id, posts::last_activity_at))
+                .filter(posts::visibility.eq(public))
+                .filter(posts::moderation.eq_any(PASSING_MODERATIONS))
+                .filter(posts::context.eq(PostContext::Post.as_str_name()))
+                .order(posts::id)
+                .offset(offset)
+                .limit(SITEMAP_CHUNK_SIZE)
+                .load::<(i64, SystemTime)>(conn)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(id, lastmod)| (format!("post/{}", id.to_proto_id()), lastmod))
+                .collect(),
+            SitemapKind::Events => events::table
+                .inner_join(posts::table.on(events::post_id.eq(posts::id)))
+                .select((events::id, posts::last_activity_at))
+                .filter(posts::visibility.eq(public))
+                .filter(posts::moderation.eq_any(PASSING_MODERATIONS))
+                .order(events::id)
+                .offset(offset)
+                .limit(SITEMAP_CHUNK_SIZE)
+                .load::<(i64, SystemTime)>(conn)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(id, lastmod)| (format!("event/{}", id.to_proto_id()), lastmod))
+                .collect(),
+            SitemapKind::Groups => groups::table
+                .select((groups::shortname, groups::updated_at))
+                .filter(groups::visibility.eq(public))
+                .filter(groups::moderation.eq_any(PASSING_MODERATIONS))
+                .order(groups::id)
+                .offset(offset)
+                .limit(SITEMAP_CHUNK_SIZE)
+                .load::<(String, SystemTime)>(conn)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(shortname, lastmod)| (format!("g/{}", shortname), lastmod))
+                .collect(),
+            SitemapKind::Users => users::table
+                .select((users::id, users::updated_at))
+                .filter(users::visibility.eq(public))
+                .filter(users::moderation.eq_any(PASSING_MODERATIONS))
+                .order(users::id)
+                .offset(offset)
+                .limit(SITEMAP_CHUNK_SIZE)
+                .load::<(i64, SystemTime)>(conn)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(id, lastmod)| (format!("user/{}", id.to_proto_id()), lastmod))
+                .collect(),
+        }
+    }
+}
@@ -1,8 +1,10 @@
 use std::sync::Arc;
 use crate::db_connection::PgPool;
+use crate::media_store::MediaStore;
+use super::MediaCache;
 
 pub struct RocketState {
   pub pool: Arc<PgPool>,
-  pub bucket: Arc<s3::Bucket>,
-  pub tempdir: Arc<tempfile::TempDir>,
+  pub media_store: Arc<dyn MediaStore>,
+  pub media_cache: Arc<MediaCache>,
 }
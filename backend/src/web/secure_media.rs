@@ -0,0 +1,263 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Request, State};
+
+use super::{external_cdn_config, RocketState};
+
+/// An IPv4 or IPv6 CIDR range, like `173.245.48.0/20` or `2400:cb00::/32`.
+/// A bare address is treated as a single-address range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IpRange {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix_len) = match s.split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (s, None),
+        };
+        let network = IpAddr::from_str(address).map_err(|e| format!("{}: {}", s, e))?;
+        let max_prefix_len = match network {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse::<u8>()
+                .map_err(|e| format!("{}: {}", s, e))?,
+            None => max_prefix_len,
+        };
+        if prefix_len > max_prefix_len {
+            return Err(format!("{}: prefix length too long", s));
+        }
+        Ok(IpRange {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl IpRange {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // Dual-stack listeners report IPv4 clients as IPv4-mapped IPv6 addresses.
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            ip => ip,
+        };
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parses a whitespace- and/or comma-separated list of ranges, as used by
+/// `ExternalCdnConfig.media_ipv4_allowlist` and friends. Invalid entries are logged and skipped.
+pub fn parse_ip_ranges(list: &str) -> Vec<IpRange> {
+    list.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| match IpRange::from_str(s) {
+            Ok(range) => Some(range),
+            Err(e) => {
+                log::warn!("Ignoring invalid IP range {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Determines the IP to check against the allowlists. Unless the connecting peer is a
+/// trusted proxy, that's just the peer. Otherwise, `X-Forwarded-For` is walked from the
+/// right, skipping trusted proxies; `CF-Connecting-IP` is used if there is no `X-Forwarded-For`.
+pub fn effective_client_ip(
+    peer: IpAddr,
+    forwarded_for: Option<&str>,
+    cf_connecting_ip: Option<&str>,
+    trusted_proxies: &[IpRange],
+) -> IpAddr {
+    let trusted = |ip: IpAddr| trusted_proxies.iter().any(|range| range.contains(ip));
+    if !trusted(peer) {
+        return peer;
+    }
+    match forwarded_for {
+        Some(forwarded_for) => {
+            let mut client = peer;
+            for hop in forwarded_for.rsplit(',') {
+                match IpAddr::from_str(hop.trim()) {
+                    Ok(ip) => {
+                        client = ip;
+                        if !trusted(ip) {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+            client
+        }
+        None => cf_connecting_ip
+            .and_then(|ip| IpAddr::from_str(ip.trim()).ok())
+            .unwrap_or(peer),
+    }
+}
+
+/// Request guard implementing `ExternalCdnConfig.secure_media`. On the non-TLS server,
+/// when `secure_media` is set, only clients within `media_ipv4_allowlist`/`media_ipv6_allowlist`
+/// get through; everyone else gets a 403.
+pub struct SecureMediaAccess;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SecureMediaAccess {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        if req.rocket().config().tls_enabled() {
+            return Outcome::Success(SecureMediaAccess);
+        }
+        let state = match req.guard::<&State<RocketState>>().await {
+            Outcome::Success(state) => state,
+            _ => return Outcome::Failure((Status::InternalServerError, ())),
+        };
+        let config = match external_cdn_config(state) {
+            Some(config) if config.secure_media => config,
+            _ => return Outcome::Success(SecureMediaAccess),
+        };
+        let peer = match req.remote() {
+            Some(remote) => remote.ip(),
+            None => return Outcome::Failure((Status::Forbidden, ())),
+        };
+        let trusted_proxies = parse_ip_ranges(config.trusted_proxies.as_deref().unwrap_or(""));
+        let client_ip = effective_client_ip(
+            peer,
+            req.headers().get_one("X-Forwarded-For"),
+            req.headers().get_one("CF-Connecting-IP"),
+            &trusted_proxies,
+        );
+        let allowlist = [
+            config.media_ipv4_allowlist.as_deref().unwrap_or(""),
+            config.media_ipv6_allowlist.as_deref().unwrap_or(""),
+        ]
+        .iter()
+        .flat_map(|list| parse_ip_ranges(list))
+        .collect::<Vec<IpRange>>();
+
+        if allowlist.iter().any(|range| range.contains(client_ip)) {
+            Outcome::Success(SecureMediaAccess)
+        } else {
+            log::info!("Rejecting media request from {} (peer {})", client_ip, peer);
+            Outcome::Failure((Status::Forbidden, ()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        IpAddr::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn ipv4_ranges_work() {
+        let range = IpRange::from_str("173.245.48.0/20").unwrap();
+        assert!(range.contains(ip("173.245.48.1")));
+        assert!(range.contains(ip("173.245.63.255")));
+        assert!(!range.contains(ip("173.245.64.0")));
+        assert!(!range.contains(ip("10.0.0.1")));
+        assert!(range.contains(ip("::ffff:173.245.50.1")));
+        assert!(!range.contains(ip("2400:cb00::1")));
+
+        let single = IpRange::from_str("10.1.2.3").unwrap();
+        assert!(single.contains(ip("10.1.2.3")));
+        assert!(!single.contains(ip("10.1.2.4")));
+
+        let everything = IpRange::from_str("0.0.0.0/0").unwrap();
+        assert!(everything.contains(ip("8.8.8.8")));
+
+        assert!(IpRange::from_str("10.0.0.0/33").is_err());
+        assert!(IpRange::from_str("10.0.0/8").is_err());
+    }
+
+    #[test]
+    fn ipv6_ranges_work() {
+        let range = IpRange::from_str("2400:cb00::/32").unwrap();
+        assert!(range.contains(ip("2400:cb00::1")));
+        assert!(range.contains(ip("2400:cb00:ffff:ffff::1")));
+        assert!(!range.contains(ip("2400:cb01::1")));
+        assert!(!range.contains(ip("173.245.48.1")));
+
+        let single = IpRange::from_str("2606:4700::6810:84e5").unwrap();
+        assert!(single.contains(ip("2606:4700::6810:84e5")));
+        assert!(!single.contains(ip("2606:4700::6810:84e6")));
+
+        let everything = IpRange::from_str("::/0").unwrap();
+        assert!(everything.contains(ip("2001:db8::1")));
+
+        assert!(IpRange::from_str("2400:cb00::/129").is_err());
+    }
+
+    #[test]
+    fn ip_range_lists_parse() {
+        let ranges = parse_ip_ranges(
+            "173.245.48.0/20, 103.21.244.0/22\n2400:cb00::/32\t,,not-an-ip 2606:4700::/32",
+        );
+        assert_eq!(ranges.len(), 4);
+        assert!(ranges.iter().any(|r| r.contains(ip("103.21.245.1"))));
+        assert!(ranges.iter().any(|r| r.contains(ip("2606:4700::1"))));
+        assert!(parse_ip_ranges("").is_empty());
+    }
+
+    #[test]
+    fn trusted_proxies_are_honored() {
+        let trusted = parse_ip_ranges("10.0.0.0/8 fd00::/8");
+        let cloudflare = ip("173.245.48.1");
+
+        // Untrusted peers can't spoof their address.
+        assert_eq!(
+            effective_client_ip(ip("8.8.8.8"), Some("173.245.48.1"), None, &trusted),
+            ip("8.8.8.8")
+        );
+        // Trusted peers pass the rightmost untrusted X-Forwarded-For hop through.
+        assert_eq!(
+            effective_client_ip(
+                ip("10.0.0.5"),
+                Some("1.2.3.4, 173.245.48.1, 10.0.0.9"),
+                None,
+                &trusted
+            ),
+            cloudflare
+        );
+        assert_eq!(
+            effective_client_ip(ip("fd00::1"), Some("2400:cb00::1"), None, &trusted),
+            ip("2400:cb00::1")
+        );
+        // CF-Connecting-IP is only a fallback.
+        assert_eq!(
+            effective_client_ip(ip("10.0.0.5"), None, Some("173.245.48.1"), &trusted),
+            cloudflare
+        );
+        assert_eq!(
+            effective_client_ip(ip("10.0.0.5"), None, None, &trusted),
+            ip("10.0.0.5")
+        );
+    }
+}
@@ -1,8 +1,15 @@
-use rocket::{fs::*, routes, Route};
+use rocket::{fs::*, routes, Route, State};
 use rocket_cache_response::CacheResponse;
 use std::path::*;
 
+use rocket::http::uri::{Host, Origin};
 use rocket::http::Status;
+use rocket::response::content::RawHtml;
+
+use super::{
+    configured_backend_domain, configured_frontend_domain, inject_open_graph_meta, open_graph_meta,
+    OpenGraphTarget, RocketState,
+};
 
 lazy_static! {
     pub static ref TAMAGUI_PAGES: Vec<Route> = routes![
@@ -29,16 +36,38 @@ lazy_static! {
     ];
 }
 
+#[derive(rocket::Responder)]
+enum FileOrHtml {
+    File(NamedFile),
+    Html(RawHtml<String>),
+}
+
 #[rocket::get("/<file..>")]
-async fn tamagui_file_or_username(file: PathBuf) -> CacheResponse<Result<NamedFile, Status>> {
+async fn tamagui_file_or_username(
+    file: PathBuf,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<FileOrHtml, Status>> {
     log::info!("tamagui_file_or_username: {:?}", file);
     let result = match NamedFile::open(Path::new("opt/tamagui_web/").join(file.to_owned())).await {
-        Ok(file) => Ok(file),
+        Ok(file) => Ok(FileOrHtml::File(file)),
         Err(_) => {
-            match NamedFile::open(Path::new("../frontends/tamagui/apps/next/out/").join(file)).await
+            match NamedFile::open(Path::new("../frontends/tamagui/apps/next/out/").join(file.to_owned())).await
             {
-                Ok(file) => Ok(file),
-                Err(_) => return tamagui_path("[username].html").await,
+                Ok(file) => Ok(FileOrHtml::File(file)),
+                Err(_) => {
+                    let username = file.to_string_lossy().to_string();
+                    tamagui_html_with_meta(
+                        "[username].html",
+                        OpenGraphTarget::Username(username),
+                        uri,
+                        state,
+                        host,
+                    )
+                    .await
+                    .map(FileOrHtml::Html)
+                }
 }
 }
     };
@@ -78,24 +107,44 @@ async fn tamagui_about_jonline() -> CacheResponse<Result<NamedFile, Status>> {
     tamagui_path("about_jonline.html").await
 }
 
-#[rocket::get("/post/<_..>")]
-async fn tamagui_post() -> CacheResponse<Result<NamedFile, Status>> {
-    tamagui_path("post/[postId].html").await
+#[rocket::get("/post/<id>/<_..>")]
+async fn tamagui_post(
+    id: String,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    tamagui_html("post/[postId].html", OpenGraphTarget::Post(id), uri, state, host).await
 }
 
-#[rocket::get("/event/<_>")]
-async fn tamagui_event() -> CacheResponse<Result<NamedFile, Status>> {
-    tamagui_path("event/[eventId].html").await
+#[rocket::get("/event/<id>")]
+async fn tamagui_event(
+    id: String,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    tamagui_html("event/[eventId].html", OpenGraphTarget::Event(id), uri, state, host).await
 }
 
-#[rocket::get("/event/<_>/i/<_..>")]
-async fn tamagui_event_instance() -> CacheResponse<Result<NamedFile, Status>> {
-    tamagui_path("event/[eventId]/i/[instanceId].html").await
+#[rocket::get("/event/<id>/i/<_..>")]
+async fn tamagui_event_instance(
+    id: String,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    tamagui_html("event/[eventId]/i/[instanceId].html", OpenGraphTarget::Event(id), uri, state, host).await
 }
 
-#[rocket::get("/user/<_>")]
-async fn tamagui_user() -> CacheResponse<Result<NamedFile, Status>> {
-    tamagui_path("user/[id].html").await
+#[rocket::get("/user/<id>")]
+async fn tamagui_user(
+    id: String,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    tamagui_html("user/[id].html", OpenGraphTarget::User(id), uri, state, host).await
 }
 #[rocket::get("/people")]
 async fn tamagui_people() -> CacheResponse<Result<NamedFile, Status>> {
@@ -106,34 +155,64 @@ async fn tamagui_follow_requests() -> CacheResponse<Result<NamedFile, Status>> {
     tamagui_path("people/follow_requests.html").await
 }
 
-#[rocket::get("/g/<_>")]
-async fn tamagui_group_home() -> CacheResponse<Result<NamedFile, Status>> {
-    tamagui_path("g/[shortname].html").await
+#[rocket::get("/g/<shortname>")]
+async fn tamagui_group_home(
+    shortname: String,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    tamagui_html("g/[shortname].html", OpenGraphTarget::Group(shortname), uri, state, host).await
 }
 
-#[rocket::get("/g/<_>/posts")]
-async fn tamagui_group_posts() -> CacheResponse<Result<NamedFile, Status>> {
-    tamagui_path("g/[shortname]/posts.html").await
+#[rocket::get("/g/<shortname>/posts")]
+async fn tamagui_group_posts(
+    shortname: String,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    tamagui_html("g/[shortname]/posts.html", OpenGraphTarget::Group(shortname), uri, state, host).await
 }
 
-#[rocket::get("/g/<_>/events")]
-async fn tamagui_group_events() -> CacheResponse<Result<NamedFile, Status>> {
-    tamagui_path("g/[shortname]/events.html").await
+#[rocket::get("/g/<shortname>/events")]
+async fn tamagui_group_events(
+    shortname: String,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    tamagui_html("g/[shortname]/events.html", OpenGraphTarget::Group(shortname), uri, state, host).await
 }
 
-#[rocket::get("/g/<_>/p/<_..>")]
-async fn tamagui_group_post() -> CacheResponse<Result<NamedFile, Status>> {
-    tamagui_path("g/[shortname]/p/[postId].html").await
+#[rocket::get("/g/<_>/p/<id>/<_..>")]
+async fn tamagui_group_post(
+    id: String,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    tamagui_html("g/[shortname]/p/[postId].html", OpenGraphTarget::Post(id), uri, state, host).await
 }
 
-#[rocket::get("/g/<_>/e/<_>")]
-async fn tamagui_group_event() -> CacheResponse<Result<NamedFile, Status>> {
-    tamagui_path("g/[shortname]/e/[eventId].html").await
+#[rocket::get("/g/<_>/e/<id>")]
+async fn tamagui_group_event(
+    id: String,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    tamagui_html("g/[shortname]/e/[eventId].html", OpenGraphTarget::Event(id), uri, state, host).await
 }
 
-#[rocket::get("/g/<_>/e/<_>/i/<_..>")]
-async fn tamagui_group_event_instance() -> CacheResponse<Result<NamedFile, Status>> {
-    tamagui_path("g/[shortname]/e/[eventId]/i/[instanceId].html").await
+#[rocket::get("/g/<_>/e/<id>/i/<_..>")]
+async fn tamagui_group_event_instance(
+    id: String,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    tamagui_html("g/[shortname]/e/[eventId]/i/[instanceId].html", OpenGraphTarget::Event(id), uri, state, host).await
 }
 
 #[rocket::get("/server/<_..>")]
@@ -160,3 +239,44 @@ async fn tamagui_path(path: &str) -> CacheResponse<Result<NamedFile, Status>> {
         must_revalidate: false,
     }
 }
+
+async fn tamagui_html(
+    path: &str,
+    target: OpenGraphTarget,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> CacheResponse<Result<RawHtml<String>, Status>> {
+    CacheResponse::Public {
+        responder: tamagui_html_with_meta(path, target, uri, state, host).await,
+        max_age: 60,
+        must_revalidate: false,
+    }
+}
+
+/// Loads a Tamagui page and injects Open Graph/Twitter Card tags for `target`
+/// when it is visible to anonymous users. Otherwise the page is served as-is.
+async fn tamagui_html_with_meta(
+    path: &str,
+    target: OpenGraphTarget,
+    uri: &Origin<'_>,
+    state: &State<RocketState>,
+    host: &Host<'_>,
+) -> Result<RawHtml<String>, Status> {
+    let html = match tokio::fs::read_to_string(format!("opt/tamagui_web/{}", path)).await {
+        Ok(html) => Ok(html),
+        Err(_) => tokio::fs::read_to_string(format!("../frontends/tamagui/apps/next/out/{}", path)).await,
+    };
+    let html = html.map_err(|e| {
+        log::info!("tamagui_html_with_meta: {:?}", e);
+        Status::NotFound
+    })?;
+    let domain = configured_frontend_domain(state, host);
+    let backend_domain = configured_backend_domain(state, host);
+    let mut conn = state.pool.get().unwrap();
+    let meta = open_graph_meta(target, uri.path().as_str(), &domain, &backend_domain, &mut conn);
+    Ok(RawHtml(match meta {
+        Some(meta) => inject_open_graph_meta(&html, &meta),
+        None => html,
+    }))
+}
@@ -147,7 +147,8 @@ class JonlineAccount {
 
   final String id;
   final String server;
-  final String authorizationToken;
+  /// The refresh token. Rotated (and replaced) with each [AccessTokenRequest].
+  String authorizationToken;
   String serviceVersion;
   String accessToken;
   int accessTokenExpiresAt;
@@ -44,11 +44,14 @@ extension JonlineAccountOperations on JonlineAccount {
 
   Future<bool> _updateAccessToken({Function(String)? showMessage}) async {
     ExpirableToken? newAccessToken;
+    ExpirableToken? newRefreshToken;
     try {
       final client = await getClient(showMessage: showMessage);
       final response = await client?.accessToken(
           AccessTokenRequest()..refreshToken = authorizationToken);
       newAccessToken = response?.accessToken;
+      newRefreshToken =
+          response?.hasRefreshToken() == true ? response!.refreshToken : null;
     } catch (e) {
       showMessage?.call(formatServerError(e));
       return false;
@@ -57,6 +60,10 @@ extension JonlineAccountOperations on JonlineAccount {
       showMessage?.call('No access token received.');
       return false;
     }
+    // The old refresh token is now invalid (and reusing it revokes the session).
+    if (newRefreshToken != null && newRefreshToken.token.isNotEmpty) {
+      authorizationToken = newRefreshToken.token;
+    }
     accessToken = newAccessToken.token;
     accessTokenExpiresAt = newAccessToken.expiresAt.seconds.toInt();
     await save();